@@ -0,0 +1,47 @@
+.data
+  array: .word 7, 3, 9, 1, 8, 2, 10, 5, 4, 6
+  space: .asciiz " "
+.text
+.globl main
+main:
+  la $a0, array
+  li $a1, 10
+  jal sort
+sort_done:
+  la $s0, array
+  li $s1, 10
+print:
+  lw $a0, 0($s0)
+  li $v0, 1
+  syscall
+  la $a0, space
+  li $v0, 4
+  syscall
+  addiu $s0, $s0, 4
+  addiu $s1, $s1, -1
+  bgtz $s1, print
+
+  li $v0, 10
+  syscall
+
+# Sorts the $a1 words at $a0 in ascending order, by insertion.
+sort:
+  li $t0, 1
+outer:
+  bge $t0, $a1, sorted
+  sll $t1, $t0, 2
+  addu $t1, $a0, $t1
+  lw $t2, 0($t1)
+shift:
+  beq $t1, $a0, insert
+  lw $t3, -4($t1)
+  ble $t3, $t2, insert
+  sw $t3, 0($t1)
+  addiu $t1, $t1, -4
+  j shift
+insert:
+  sw $t2, 0($t1)
+  addiu $t0, $t0, 1
+  j outer
+sorted:
+  jr $ra
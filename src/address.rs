@@ -1,44 +1,109 @@
 use derive_more::{BitAnd, Eq, From, Into, Shr};
 use std::{
-    fmt::{Debug, Formatter, Result},
-    ops::{Add, AddAssign, Sub},
+    fmt::{self, Debug, Display, Formatter},
+    ops::{Add, AddAssign},
 };
+use thiserror::Error;
 
-#[derive(Clone, Copy, From, Into, Shr, BitAnd, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum AddressError {
+    #[error("{0} + {1} is past the end of the address space")]
+    Overflow(Address, u32),
+    #[error("{0} - {1} is below the start of the address space")]
+    Underflow(Address, u32),
+}
+
+/// A 32-bit byte address. The arithmetic operators wrap around the address
+/// space like the hardware does; the `checked_` methods report it instead.
+#[derive(Clone, Copy, Default, From, Into, Shr, BitAnd, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Address(pub u32);
 
+impl Address {
+    pub fn checked_add(self, offset: u32) -> Result<Address, AddressError> {
+        self.0
+            .checked_add(offset)
+            .map(Address)
+            .ok_or(AddressError::Overflow(self, offset))
+    }
+
+    pub fn checked_sub(self, offset: u32) -> Result<Address, AddressError> {
+        self.0
+            .checked_sub(offset)
+            .map(Address)
+            .ok_or(AddressError::Underflow(self, offset))
+    }
+
+    /// Adds a signed offset, such as a load's displacement.
+    pub fn checked_offset(self, offset: i32) -> Result<Address, AddressError> {
+        match offset < 0 {
+            true => self.checked_sub(offset.unsigned_abs()),
+            false => self.checked_add(offset as u32),
+        }
+    }
+
+    pub fn wrapping_add(self, offset: u32) -> Address {
+        Address(self.0.wrapping_add(offset))
+    }
+
+    pub fn wrapping_sub(self, offset: u32) -> Address {
+        Address(self.0.wrapping_sub(offset))
+    }
+
+    pub fn wrapping_offset(self, offset: i32) -> Address {
+        Address(self.0.wrapping_add_signed(offset))
+    }
+
+    /// Bytes from `base` up to this address, or None if it is below `base`.
+    pub fn offset_from(self, base: Address) -> Option<u32> {
+        self.0.checked_sub(base.0)
+    }
+
+    pub fn is_aligned(self, alignment: u32) -> bool {
+        self.0.is_multiple_of(alignment)
+    }
+
+    pub fn is_word_aligned(self) -> bool {
+        self.is_aligned(4)
+    }
+
+    /// Index of the word containing this address in an array of words
+    /// starting at `base`, or None if it is below `base`.
+    pub fn word_index(self, base: Address) -> Option<usize> {
+        self.offset_from(base).map(|offset| (offset / 4) as usize)
+    }
+}
+
 impl Debug for Address {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_tuple("Address")
             .field(&format_args!("0x{:08X}", self.0))
             .finish()
     }
 }
 
+impl Display for Address {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:08x}", self.0)
+    }
+}
+
 impl Add<usize> for Address {
     type Output = Address;
     fn add(self, offset: usize) -> Address {
-        Address(self.0 + offset as u32)
+        self.wrapping_add(offset as u32)
     }
 }
 
 impl Add<u32> for Address {
     type Output = Address;
     fn add(self, offset: u32) -> Address {
-        Address(self.0 + offset)
-    }
-}
-
-impl Sub for Address {
-    type Output = usize;
-    fn sub(self, other: Address) -> usize {
-        (self.0 - other.0) as usize
+        self.wrapping_add(offset)
     }
 }
 
 impl AddAssign<usize> for Address {
     fn add_assign(&mut self, offset: usize) {
-        self.0 += offset as u32;
+        *self = *self + offset;
     }
 }
 
@@ -48,13 +113,6 @@ impl From<Address> for usize {
     }
 }
 
-impl Sub<Address> for usize {
-    type Output = usize;
-    fn sub(self, rhs: Address) -> Self::Output {
-        self - (rhs.0 as usize)
-    }
-}
-
 impl From<Address> for i32 {
     fn from(value: Address) -> Self {
         value.0 as i32
@@ -1,20 +1,62 @@
-use std::{collections::HashMap, ffi::CString, iter::Peekable, slice::Iter, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    iter::Peekable,
+    ops::Range,
+    path::{Path, PathBuf},
+    slice::Iter,
+    sync::Arc,
+};
 
 use thiserror::Error;
 
 use crate::{
     RuntimeArgs,
     address::Address,
-    lexer::{Directive, Token, TokenizerError, tokenize},
-    registers::{Register, RegisterError},
+    debug_info::{DataKind, DebugInfo},
+    diagnostic::{self, Diagnostic, Severity},
+    encoding::Encoding,
+    expression::{Expression, ExpressionError},
+    heap::HEAP_BASE,
+    intern::{Interner, SymbolId},
+    lexer::{
+        Directive, Line, Span, Token, TokenKind, TokenizerError, classify, lines, read_source,
+        split_words, tokenize_line,
+    },
+    memory::Endianness,
+    plugin::{self, CustomOperands, InstructionPlugin},
+    registers::Register,
+    suggest::{self, did_you_mean},
 };
 
 pub const BASE_TEXT_ADDR: Address = Address(0x0040_0000);
 pub const BASE_DATA_ADDR: Address = Address(0x1001_0000);
 pub const MEMORY_SIZE: usize = 64 * 1024;
+/// How deeply `.include` files may nest before it is taken to be a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Every mnemonic `expand_instruction` accepts, including pseudo-instructions.
+pub const MNEMONICS: &[&str] = &[
+    "abs", "add", "addi", "addiu", "addu", "and", "andi", "beq", "bge", "bgeu", "bgez", "bgezal",
+    "bgt", "bgtu", "bgtz", "ble", "bleu", "blez", "blt", "bltu", "bltz", "bltzal", "bne", "dadd",
+    "daddi", "daddiu", "daddu", "div", "divu", "dsll", "dsll32", "dsra", "dsra32", "dsrl",
+    "dsrl32", "dsub", "dsubu", "eret", "j", "jal", "jalr", "jr", "la", "lb", "lbu", "ld", "lh",
+    "lhu", "li", "lui", "lw", "lwl", "lwr", "mfc0", "mfhi", "mflo", "move", "mtc0", "mthi", "mtlo",
+    "mul", "mult", "multu", "neg", "nop", "nor", "not", "or", "ori", "rem", "sb", "sd", "seq",
+    "sge", "sgeu", "sgt", "sgtu", "sh", "sle", "sleu", "sll", "sllv", "slt", "sltu", "sne", "sra",
+    "srav", "srl", "srlv", "sub", "subu", "sw", "swl", "swr", "syscall", "teq", "teqi", "tge",
+    "tgei", "tgeiu", "tgeu", "tlbp", "tlbr", "tlbwi", "tlbwr", "tlt", "tlti", "tltiu", "tltu",
+    "tne", "tnei", "xor", "xori",
+];
+
+/// Mnemonics only accepted in MIPS64 mode.
+pub const MIPS64_MNEMONICS: &[&str] = &[
+    "dadd", "daddi", "daddiu", "daddu", "dsll", "dsll32", "dsra", "dsra32", "dsrl", "dsrl32",
+    "dsub", "dsubu", "ld", "sd",
+];
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
-enum Segment {
+pub enum Segment {
     Text,
     Data,
 }
@@ -29,8 +71,10 @@ pub enum AssemblerError {
     EntrypointMissing,
     #[error("Invalid instruction")]
     InvalidInstruction,
-    #[error("Invalid register: {0}")]
-    InvalidRegister(#[from] RegisterError),
+    #[error("Unknown instruction '{0}'{suggestion}", suggestion = did_you_mean(.1))]
+    UnknownInstruction(String, Option<&'static str>),
+    #[error("Invalid floating-point value '{0}'")]
+    InvalidFloat(String),
     #[error("Invalid label: {0}")]
     InvalidLabel(String),
     #[error("Invalid string")]
@@ -39,22 +83,174 @@ pub enum AssemblerError {
     InvalidByteValue,
     #[error("Tokenization failed: {0}")]
     TokenizationFailed(#[from] TokenizerError),
+    #[error("Cannot include '{0}'")]
+    IncludeNotFound(String),
+    #[error("Cannot read binary file '{0}'")]
+    IncbinNotFound(String),
+    #[error("Reading '{0}' from disk is not allowed here")]
+    FileAccessDenied(String),
+    #[error("Includes nested too deeply at '{0}'; do two files include each other?")]
+    IncludeTooDeep(String),
+    #[error("'{0}' is not allowed in this assignment (line {1})")]
+    Forbidden(String, usize),
+    #[error("'{0}' is a 64-bit instruction; assemble in MIPS64 mode to use it")]
+    Mips64Only(String),
+    #[error("Invalid operands for '{0}': {1}")]
+    InvalidOperands(String, String),
+    #[error("Invalid expression '{0}': {1}")]
+    InvalidExpression(String, ExpressionError),
+    #[error("Undefined constant '{0}'")]
+    UndefinedConstant(String),
+    #[error("'{0}' is {1}, which does not fit in 32 bits")]
+    ConstantOverflow(String, i64),
+    #[error(".space of {0} bytes does not fit in the data segment")]
+    InvalidSpace(i64),
+    #[error("{0} more bytes do not fit in the data segment")]
+    DataSegmentFull(usize),
+    #[error("Label '{0}' is never defined")]
+    UndefinedLabel(String),
+    #[error("'{0}' is too far away to branch to")]
+    BranchOutOfRange(String),
+    #[error("'{0}' is outside the 256 MB region the jump can reach")]
+    JumpOutOfRange(String),
 }
 
+type TokenIter<'t, 'a> = Peekable<Iter<'t, Token<'a>>>;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Symbol {
     address: Address,
     segment: Segment,
 }
 
+/// A branch, jump or data address naming a label that was not defined yet
+/// when it was assembled, patched once the whole program has been read.
+struct Fixup {
+    /// Index of the branch or jump, or of the `lui` loading the upper half
+    /// of the address, in the text segment.
+    index: usize,
+    label: String,
+    /// Where the label is used, for reporting it if it is never defined.
+    site: Diagnostic,
+}
+
+/// Which files on disk `.include` and `.incbin` may read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FileAccess {
+    /// Any file the process can read.
+    #[default]
+    Any,
+    /// Only files inside this directory, which paths are resolved against.
+    Within(PathBuf),
+    /// None; only buffers added with [`Assembler::add_buffer`] can be
+    /// included.
+    Nothing,
+}
+
 pub struct Assembler {
-    symbols: HashMap<String, Symbol>,
+    interner: Interner,
+    symbols: HashMap<SymbolId, Symbol>,
+    /// Values of the `.eqv` constants defined so far.
+    constants: HashMap<SymbolId, i64>,
     data_addr: Address,
     text_addr: Address,
-    entry_point: Option<String>,
+    entry_point: Option<SymbolId>,
     memory: Vec<u8>,
     text_lines: Vec<Instruction>,
+    /// Byte order to lay out data in.
+    endianness: Endianness,
+    /// Labels the current line uses before defining them, with the index
+    /// of the instruction to patch and the label's span.
+    forward: Vec<(usize, String, Option<Span>)>,
+    fixups: Vec<Fixup>,
+    /// The text each source line expanded to, for `-i` to print once the
+    /// fixups are resolved.
+    listing: Vec<Range<usize>>,
+    debug_info: DebugInfo,
     current_segment: Segment,
+    /// Directory `.include` paths are relative to.
+    include_dir: PathBuf,
+    /// Contents to use instead of the file on disk, e.g. unsaved editor buffers.
+    buffers: HashMap<PathBuf, String>,
+    file_access: FileAccess,
+    /// Line, column and span of the outermost `.include` being assembled.
+    /// Everything the included files contain is attributed to it.
+    include_site: Option<(usize, usize, Span)>,
+    /// Names of the files being included, innermost last.
+    include_files: Vec<String>,
+    /// Errors and warnings so far, located in the main source.
+    diagnostics: Vec<Diagnostic>,
+    /// Span of the token that caused the error being reported, if known.
+    error_span: Option<Span>,
+    /// Set once an error is reported, so enclosing includes don't repeat it.
+    failed: bool,
+    /// Reported at the end if the `.globl` label is never defined.
+    entry_warning: Option<Diagnostic>,
+}
+
+/// The trimmed text of `line`, for errors without a more precise location.
+fn line_span(line: &Line) -> Span {
+    let start = line.offset + (line.text.len() - line.text.trim_start().len());
+    Span {
+        start,
+        end: (line.offset + line.text.trim_end().len()).max(start),
+    }
+}
+
+/// The first word on `line` the tokenizer rejects.
+fn rejected_word(line: &Line) -> Option<Span> {
+    let mut first = true;
+    split_words(line.text).find_map(|(offset, word)| match classify(word, first) {
+        Ok(TokenKind::Label { decl: true, .. }) => None,
+        Ok(_) => {
+            first = false;
+            None
+        }
+        Err(_) => Some(Span {
+            start: line.offset + offset,
+            end: line.offset + offset + word.len(),
+        }),
+    })
+}
+
+/// The boundary a data directive's values are aligned to, as MARS does.
+fn alignment(kind: Directive) -> usize {
+    match kind {
+        Directive::Word | Directive::Float => 4,
+        Directive::Dword | Directive::Double => 8,
+        _ => 1,
+    }
+}
+
+/// Drops `.` components so the same file is spelled the same way.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// The output of a successful assembly, ready to be moved into a simulator.
+#[derive(Debug, Clone)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    memory: Vec<u8>,
+    entry: Address,
+    debug_info: DebugInfo,
+    endianness: Endianness,
+}
+
+impl Program {
+    pub fn debug_info(&self) -> &DebugInfo {
+        &self.debug_info
+    }
+
+    /// The byte order the data segment was laid out in.
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Splits the program into its text segment, data segment and entry point.
+    pub fn into_parts(self) -> (Vec<Instruction>, Vec<u8>, Address) {
+        (self.instructions, self.memory, self.entry)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -69,6 +265,30 @@ pub enum Instruction {
         reg: Register,
         ret: Register,
     },
+    /// `add`: like `addu`, but signed overflow raises an error.
+    Add {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `addiu`: like `addi`, but wraps on overflow.
+    AddImmediateUnsigned {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `sub`: `reg - ret`, where signed overflow raises an error.
+    Subtract {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `subu`: `reg - ret`, wrapping on overflow.
+    SubtractUnsigned {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
     LoadUpperImmediate {
         res: Register,
         imm: i32,
@@ -79,147 +299,3015 @@ pub enum Instruction {
         imm: i32,
     },
     SystemCall,
-}
-
-impl Assembler {
-    pub fn new() -> Self {
-        Self {
-            symbols: HashMap::new(),
-            data_addr: BASE_DATA_ADDR,
-            text_addr: BASE_TEXT_ADDR,
-            entry_point: None,
-            memory: vec![0; MEMORY_SIZE],
-            text_lines: Vec::new(),
-            current_segment: Segment::Text,
-        }
-    }
-
-    // TODO: Add support for forward references
-    pub fn assemble(&mut self, args: &RuntimeArgs) -> Result<(), AssemblerError> {
-        let tokenized = tokenize(&args.file)?;
-
-        for line_tokens in tokenized {
-            if args.tokens {
-                println!("{:?}", line_tokens);
-            }
-
-            let mut tokens = line_tokens.iter().peekable();
-
-            if let Some(Token::Label { name, decl: true }) = tokens.peek() {
-                let addr = match self.current_segment {
-                    Segment::Data => self.data_addr,
-                    Segment::Text => self.text_addr,
-                };
-                self.symbols.insert(
-                    name.clone(),
-                    Symbol {
-                        address: addr,
-                        segment: self.current_segment,
-                    },
-                );
-                tokens.next();
-            }
-
-            match tokens.next() {
-                Some(Token::Directive { kind }) => self.handle_directive(kind, &mut tokens)?,
-                Some(token) if matches!(token, Token::Operator { .. }) => {
-                    let expanded = self.expand_instruction(line_tokens)?;
-                    self.text_lines.extend(&expanded);
-                    if args.instructions {
-                        println!("{:?}", expanded);
+    /// `beq`: branches `offset` words past the next instruction when `reg`
+    /// equals `ret`.
+    BranchEqual {
+        reg: Register,
+        ret: Register,
+        offset: i32,
+    },
+    BranchNotEqual {
+        reg: Register,
+        ret: Register,
+        offset: i32,
+    },
+    /// `bltz`: branches `offset` words past the next instruction when `reg`
+    /// is less than zero.
+    BranchLessThanZero {
+        reg: Register,
+        offset: i32,
+    },
+    /// `bgez`: branches `offset` words past the next instruction when `reg`
+    /// is greater than or equal to zero.
+    BranchGreaterEqualZero {
+        reg: Register,
+        offset: i32,
+    },
+    /// `blez`: branches `offset` words past the next instruction when `reg`
+    /// is less than or equal to zero.
+    BranchLessEqualZero {
+        reg: Register,
+        offset: i32,
+    },
+    /// `bgtz`: branches `offset` words past the next instruction when `reg`
+    /// is greater than zero.
+    BranchGreaterThanZero {
+        reg: Register,
+        offset: i32,
+    },
+    /// `bltzal`: like `bltz`, and puts the return address in `$ra` whether
+    /// or not the branch is taken.
+    BranchLessThanZeroAndLink {
+        reg: Register,
+        offset: i32,
+    },
+    /// `bgezal`: like `bgez`, and puts the return address in `$ra` whether
+    /// or not the branch is taken.
+    BranchGreaterEqualZeroAndLink {
+        reg: Register,
+        offset: i32,
+    },
+    /// `teq`: raises a trap exception if `reg` equals `ret`.
+    TrapEqual {
+        reg: Register,
+        ret: Register,
+    },
+    /// `tne`: raises a trap exception if `reg` differs from `ret`.
+    TrapNotEqual {
+        reg: Register,
+        ret: Register,
+    },
+    /// `tge`: raises a trap exception if `reg >= ret`, signed.
+    TrapGreaterEqual {
+        reg: Register,
+        ret: Register,
+    },
+    /// `tgeu`: like `tge`, comparing unsigned.
+    TrapGreaterEqualUnsigned {
+        reg: Register,
+        ret: Register,
+    },
+    /// `tlt`: raises a trap exception if `reg < ret`, signed.
+    TrapLessThan {
+        reg: Register,
+        ret: Register,
+    },
+    /// `tltu`: like `tlt`, comparing unsigned.
+    TrapLessThanUnsigned {
+        reg: Register,
+        ret: Register,
+    },
+    /// `teqi`: like `teq` with a sign-extended immediate.
+    TrapEqualImmediate {
+        reg: Register,
+        imm: i32,
+    },
+    /// `tnei`: like `tne` with a sign-extended immediate.
+    TrapNotEqualImmediate {
+        reg: Register,
+        imm: i32,
+    },
+    /// `tgei`: like `tge` with a sign-extended immediate.
+    TrapGreaterEqualImmediate {
+        reg: Register,
+        imm: i32,
+    },
+    /// `tgeiu`: like `tgeu`, sign-extending `imm` first.
+    TrapGreaterEqualImmediateUnsigned {
+        reg: Register,
+        imm: i32,
+    },
+    /// `tlti`: like `tlt` with a sign-extended immediate.
+    TrapLessThanImmediate {
+        reg: Register,
+        imm: i32,
+    },
+    /// `tltiu`: like `tltu`, sign-extending `imm` first.
+    TrapLessThanImmediateUnsigned {
+        reg: Register,
+        imm: i32,
+    },
+    /// `j`: jumps to `target` words into the 256 MB region holding the
+    /// next instruction.
+    Jump {
+        target: u32,
+    },
+    /// `jal`: jumps like `j`, leaving the return address in `$ra`.
+    JumpAndLink {
+        target: u32,
+    },
+    /// `jr`: jumps to the address in `reg`.
+    JumpRegister {
+        reg: Register,
+    },
+    /// `jalr`: jumps to the address in `reg`, leaving the return address
+    /// in `res`.
+    JumpAndLinkRegister {
+        res: Register,
+        reg: Register,
+    },
+    /// `and`: bitwise AND of `reg` and `ret`.
+    And {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `or`: bitwise OR of `reg` and `ret`.
+    Or {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `xor`: bitwise exclusive OR of `reg` and `ret`.
+    Xor {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `nor`: bitwise NOT of `reg` OR `ret`.
+    Nor {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `andi`: bitwise AND of `reg` and the zero-extended `imm`.
+    AndImmediate {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `xori`: bitwise exclusive OR of `reg` and the zero-extended `imm`.
+    XorImmediate {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `sll`: shifts `reg` left by `shamt` bits.
+    ShiftLeftLogical {
+        res: Register,
+        reg: Register,
+        shamt: u32,
+    },
+    /// `srl`: shifts `reg` right by `shamt` bits, filling with zeros.
+    ShiftRightLogical {
+        res: Register,
+        reg: Register,
+        shamt: u32,
+    },
+    /// `sra`: shifts `reg` right by `shamt` bits, filling with copies of
+    /// the sign bit.
+    ShiftRightArithmetic {
+        res: Register,
+        reg: Register,
+        shamt: u32,
+    },
+    /// `sllv`: shifts `reg` left by the low five bits of `ret`.
+    ShiftLeftLogicalVariable {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `srlv`: shifts `reg` right by the low five bits of `ret`, filling
+    /// with zeros.
+    ShiftRightLogicalVariable {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `srav`: shifts `reg` right by the low five bits of `ret`, filling
+    /// with copies of the sign bit.
+    ShiftRightArithmeticVariable {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `mult`: the signed 64-bit product of `reg` and `ret`, high word in HI
+    /// and low word in LO.
+    Multiply {
+        reg: Register,
+        ret: Register,
+    },
+    /// `multu`: like `mult`, treating both operands as unsigned.
+    MultiplyUnsigned {
+        reg: Register,
+        ret: Register,
+    },
+    /// `div`: the signed quotient of `reg` by `ret` in LO and the remainder in
+    /// HI. Dividing by zero leaves both unchanged.
+    Divide {
+        reg: Register,
+        ret: Register,
+    },
+    /// `divu`: like `div`, treating both operands as unsigned.
+    DivideUnsigned {
+        reg: Register,
+        ret: Register,
+    },
+    /// `mfhi`: copies HI into `res`.
+    MoveFromHi {
+        res: Register,
+    },
+    /// `mflo`: copies LO into `res`.
+    MoveFromLo {
+        res: Register,
+    },
+    /// `mthi`: copies `reg` into HI.
+    MoveToHi {
+        reg: Register,
+    },
+    /// `mtlo`: copies `reg` into LO.
+    MoveToLo {
+        reg: Register,
+    },
+    /// `slt`: 1 if `reg` is less than `ret` as signed numbers, else 0.
+    SetLessThan {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `sltu`: 1 if `reg` is less than `ret` as unsigned numbers, else 0.
+    SetLessThanUnsigned {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    /// `lw`: loads the word at `imm` bytes past the address in `reg`.
+    LoadWord {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `lb`: loads the byte at `imm` bytes past the address in `reg`,
+    /// sign-extended.
+    LoadByte {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `lbu`: loads the byte at `imm` bytes past the address in `reg`,
+    /// zero-extended.
+    LoadByteUnsigned {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `lh`: loads the halfword at `imm` bytes past the address in `reg`,
+    /// sign-extended.
+    LoadHalf {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `lhu`: loads the halfword at `imm` bytes past the address in `reg`,
+    /// zero-extended.
+    LoadHalfUnsigned {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `sw`: stores `ret` at `imm` bytes past the address in `reg`.
+    StoreWord {
+        ret: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `ld`: loads the doubleword at `imm` bytes past the address in `reg`.
+    /// MIPS64 only.
+    LoadDouble {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `sd`: stores all 64 bits of `ret` at `imm` bytes past the address in
+    /// `reg`. MIPS64 only.
+    StoreDouble {
+        ret: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `sb`: stores the low byte of `ret` at `imm` bytes past the address
+    /// in `reg`.
+    StoreByte {
+        ret: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `sh`: stores the low halfword of `ret` at `imm` bytes past the
+    /// address in `reg`.
+    StoreHalf {
+        ret: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `lwl`: loads the bytes from the address `imm` bytes past `reg` to the
+    /// most significant end of its word into the top of `res`.
+    LoadWordLeft {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `lwr`: loads the bytes from the address `imm` bytes past `reg` to the
+    /// least significant end of its word into the bottom of `res`.
+    LoadWordRight {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `swl`: stores the top of `ret` from the address `imm` bytes past
+    /// `reg` to the most significant end of its word.
+    StoreWordLeft {
+        ret: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `swr`: stores the bottom of `ret` from the address `imm` bytes past
+    /// `reg` to the least significant end of its word.
+    StoreWordRight {
+        ret: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `mfc0`: copies coprocessor 0 register `cp0` into `res`.
+    MoveFromCoprocessor0 {
+        res: Register,
+        cp0: u32,
+    },
+    /// `mtc0`: copies `ret` into coprocessor 0 register `cp0`.
+    MoveToCoprocessor0 {
+        ret: Register,
+        cp0: u32,
+    },
+    TlbRead,
+    TlbWriteIndexed,
+    TlbWriteRandom,
+    TlbProbe,
+    ExceptionReturn,
+    /// `dadd`: 64-bit add that traps on signed overflow.
+    DoubleAdd {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    DoubleAddUnsigned {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    DoubleSubtract {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    DoubleSubtractUnsigned {
+        res: Register,
+        reg: Register,
+        ret: Register,
+    },
+    DoubleAddImmediate {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    DoubleAddImmediateUnsigned {
+        res: Register,
+        reg: Register,
+        imm: i32,
+    },
+    /// `dsll`, or `dsll32` for shifts of 32 or more.
+    DoubleShiftLeftLogical {
+        res: Register,
+        reg: Register,
+        shamt: u32,
+    },
+    DoubleShiftRightLogical {
+        res: Register,
+        reg: Register,
+        shamt: u32,
+    },
+    DoubleShiftRightArithmetic {
+        res: Register,
+        reg: Register,
+        shamt: u32,
+    },
+    /// An instruction from the [`plugin`] registered with index `plugin`.
+    Custom {
+        plugin: u16,
+        operands: CustomOperands,
+    },
+}
+
+/// Broad instruction categories, for timing models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionClass {
+    Alu,
+    Load,
+    Store,
+    Branch,
+    Jump,
+    Syscall,
+}
+
+impl InstructionClass {
+    pub const ALL: [InstructionClass; 6] = [
+        InstructionClass::Alu,
+        InstructionClass::Load,
+        InstructionClass::Store,
+        InstructionClass::Branch,
+        InstructionClass::Jump,
+        InstructionClass::Syscall,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            InstructionClass::Alu => "alu",
+            InstructionClass::Load => "load",
+            InstructionClass::Store => "store",
+            InstructionClass::Branch => "branch",
+            InstructionClass::Jump => "jump",
+            InstructionClass::Syscall => "syscall",
+        }
+    }
+}
+
+impl Instruction {
+    pub fn class(&self) -> InstructionClass {
+        match self {
+            Instruction::AddImmediate { .. }
+            | Instruction::AddUnsigned { .. }
+            | Instruction::Add { .. }
+            | Instruction::AddImmediateUnsigned { .. }
+            | Instruction::Subtract { .. }
+            | Instruction::SubtractUnsigned { .. }
+            | Instruction::LoadUpperImmediate { .. }
+            | Instruction::OrImmediate { .. }
+            | Instruction::TrapEqual { .. }
+            | Instruction::TrapNotEqual { .. }
+            | Instruction::TrapGreaterEqual { .. }
+            | Instruction::TrapGreaterEqualUnsigned { .. }
+            | Instruction::TrapLessThan { .. }
+            | Instruction::TrapLessThanUnsigned { .. }
+            | Instruction::TrapEqualImmediate { .. }
+            | Instruction::TrapNotEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediateUnsigned { .. }
+            | Instruction::TrapLessThanImmediate { .. }
+            | Instruction::TrapLessThanImmediateUnsigned { .. }
+            | Instruction::SetLessThan { .. }
+            | Instruction::SetLessThanUnsigned { .. }
+            | Instruction::Multiply { .. }
+            | Instruction::MultiplyUnsigned { .. }
+            | Instruction::Divide { .. }
+            | Instruction::DivideUnsigned { .. }
+            | Instruction::MoveFromHi { .. }
+            | Instruction::MoveFromLo { .. }
+            | Instruction::MoveToHi { .. }
+            | Instruction::MoveToLo { .. }
+            | Instruction::ShiftLeftLogical { .. }
+            | Instruction::ShiftRightLogical { .. }
+            | Instruction::ShiftRightArithmetic { .. }
+            | Instruction::ShiftLeftLogicalVariable { .. }
+            | Instruction::ShiftRightLogicalVariable { .. }
+            | Instruction::ShiftRightArithmeticVariable { .. }
+            | Instruction::And { .. }
+            | Instruction::Or { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Nor { .. }
+            | Instruction::AndImmediate { .. }
+            | Instruction::XorImmediate { .. }
+            | Instruction::MoveFromCoprocessor0 { .. }
+            | Instruction::MoveToCoprocessor0 { .. }
+            | Instruction::TlbRead
+            | Instruction::TlbWriteIndexed
+            | Instruction::TlbWriteRandom
+            | Instruction::TlbProbe
+            | Instruction::DoubleAdd { .. }
+            | Instruction::DoubleAddUnsigned { .. }
+            | Instruction::DoubleSubtract { .. }
+            | Instruction::DoubleSubtractUnsigned { .. }
+            | Instruction::DoubleAddImmediate { .. }
+            | Instruction::DoubleAddImmediateUnsigned { .. }
+            | Instruction::DoubleShiftLeftLogical { .. }
+            | Instruction::DoubleShiftRightLogical { .. }
+            | Instruction::DoubleShiftRightArithmetic { .. } => InstructionClass::Alu,
+            Instruction::LoadWord { .. }
+            | Instruction::LoadDouble { .. }
+            | Instruction::LoadByte { .. }
+            | Instruction::LoadByteUnsigned { .. }
+            | Instruction::LoadHalf { .. }
+            | Instruction::LoadHalfUnsigned { .. }
+            | Instruction::LoadWordLeft { .. }
+            | Instruction::LoadWordRight { .. } => InstructionClass::Load,
+            Instruction::StoreWord { .. }
+            | Instruction::StoreDouble { .. }
+            | Instruction::StoreByte { .. }
+            | Instruction::StoreHalf { .. }
+            | Instruction::StoreWordLeft { .. }
+            | Instruction::StoreWordRight { .. } => InstructionClass::Store,
+            Instruction::SystemCall => InstructionClass::Syscall,
+            Instruction::BranchEqual { .. }
+            | Instruction::BranchNotEqual { .. }
+            | Instruction::BranchLessThanZero { .. }
+            | Instruction::BranchGreaterEqualZero { .. }
+            | Instruction::BranchLessEqualZero { .. }
+            | Instruction::BranchGreaterThanZero { .. }
+            | Instruction::BranchLessThanZeroAndLink { .. }
+            | Instruction::BranchGreaterEqualZeroAndLink { .. } => InstructionClass::Branch,
+            Instruction::Jump { .. }
+            | Instruction::JumpAndLink { .. }
+            | Instruction::JumpRegister { .. }
+            | Instruction::JumpAndLinkRegister { .. }
+            | Instruction::ExceptionReturn => InstructionClass::Jump,
+            Instruction::Custom { plugin, .. } => custom(*plugin).class(),
+        }
+    }
+
+    /// General-purpose registers the instruction reads. A syscall is taken
+    /// to read its service number and first argument.
+    pub fn sources(&self) -> [Option<Register>; 2] {
+        match *self {
+            Instruction::AddImmediate { reg, .. }
+            | Instruction::AddImmediateUnsigned { reg, .. }
+            | Instruction::OrImmediate { reg, .. }
+            | Instruction::TrapEqualImmediate { reg, .. }
+            | Instruction::TrapNotEqualImmediate { reg, .. }
+            | Instruction::TrapGreaterEqualImmediate { reg, .. }
+            | Instruction::TrapGreaterEqualImmediateUnsigned { reg, .. }
+            | Instruction::TrapLessThanImmediate { reg, .. }
+            | Instruction::TrapLessThanImmediateUnsigned { reg, .. }
+            | Instruction::BranchLessThanZero { reg, .. }
+            | Instruction::BranchGreaterEqualZero { reg, .. }
+            | Instruction::BranchLessEqualZero { reg, .. }
+            | Instruction::BranchGreaterThanZero { reg, .. }
+            | Instruction::BranchLessThanZeroAndLink { reg, .. }
+            | Instruction::BranchGreaterEqualZeroAndLink { reg, .. }
+            | Instruction::MoveToHi { reg, .. }
+            | Instruction::MoveToLo { reg, .. }
+            | Instruction::ShiftLeftLogical { reg, .. }
+            | Instruction::ShiftRightLogical { reg, .. }
+            | Instruction::ShiftRightArithmetic { reg, .. }
+            | Instruction::AndImmediate { reg, .. }
+            | Instruction::XorImmediate { reg, .. }
+            | Instruction::LoadWord { reg, .. }
+            | Instruction::LoadDouble { reg, .. }
+            | Instruction::LoadByte { reg, .. }
+            | Instruction::LoadByteUnsigned { reg, .. }
+            | Instruction::LoadHalf { reg, .. }
+            | Instruction::LoadHalfUnsigned { reg, .. } => [Some(reg), None],
+            Instruction::StoreWord { ret, reg, .. }
+            | Instruction::StoreDouble { ret, reg, .. }
+            | Instruction::StoreByte { ret, reg, .. }
+            | Instruction::StoreHalf { ret, reg, .. }
+            | Instruction::StoreWordLeft { ret, reg, .. }
+            | Instruction::StoreWordRight { ret, reg, .. } => [Some(reg), Some(ret)],
+            // Only some bytes are loaded; the rest come from the register.
+            Instruction::LoadWordLeft { res, reg, .. }
+            | Instruction::LoadWordRight { res, reg, .. } => [Some(reg), Some(res)],
+            Instruction::AddUnsigned { reg, ret, .. }
+            | Instruction::Add { reg, ret, .. }
+            | Instruction::TrapEqual { reg, ret, .. }
+            | Instruction::TrapNotEqual { reg, ret, .. }
+            | Instruction::TrapGreaterEqual { reg, ret, .. }
+            | Instruction::TrapGreaterEqualUnsigned { reg, ret, .. }
+            | Instruction::TrapLessThan { reg, ret, .. }
+            | Instruction::TrapLessThanUnsigned { reg, ret, .. }
+            | Instruction::SetLessThan { reg, ret, .. }
+            | Instruction::SetLessThanUnsigned { reg, ret, .. }
+            | Instruction::Multiply { reg, ret, .. }
+            | Instruction::MultiplyUnsigned { reg, ret, .. }
+            | Instruction::Divide { reg, ret, .. }
+            | Instruction::DivideUnsigned { reg, ret, .. }
+            | Instruction::ShiftLeftLogicalVariable { reg, ret, .. }
+            | Instruction::ShiftRightLogicalVariable { reg, ret, .. }
+            | Instruction::ShiftRightArithmeticVariable { reg, ret, .. }
+            | Instruction::And { reg, ret, .. }
+            | Instruction::Or { reg, ret, .. }
+            | Instruction::Xor { reg, ret, .. }
+            | Instruction::Nor { reg, ret, .. }
+            | Instruction::Subtract { reg, ret, .. }
+            | Instruction::SubtractUnsigned { reg, ret, .. }
+            | Instruction::BranchEqual { reg, ret, .. }
+            | Instruction::BranchNotEqual { reg, ret, .. }
+            | Instruction::DoubleAdd { reg, ret, .. }
+            | Instruction::DoubleAddUnsigned { reg, ret, .. }
+            | Instruction::DoubleSubtract { reg, ret, .. }
+            | Instruction::DoubleSubtractUnsigned { reg, ret, .. } => [Some(reg), Some(ret)],
+            Instruction::DoubleAddImmediate { reg, .. }
+            | Instruction::DoubleAddImmediateUnsigned { reg, .. }
+            | Instruction::DoubleShiftLeftLogical { reg, .. }
+            | Instruction::DoubleShiftRightLogical { reg, .. }
+            | Instruction::DoubleShiftRightArithmetic { reg, .. } => [Some(reg), None],
+            Instruction::MoveToCoprocessor0 { ret, .. } => [Some(ret), None],
+            Instruction::JumpRegister { reg } | Instruction::JumpAndLinkRegister { reg, .. } => {
+                [Some(reg), None]
+            }
+            Instruction::LoadUpperImmediate { .. }
+            | Instruction::Jump { .. }
+            | Instruction::JumpAndLink { .. }
+            | Instruction::MoveFromCoprocessor0 { .. }
+            | Instruction::MoveFromHi { .. }
+            | Instruction::MoveFromLo { .. }
+            | Instruction::TlbRead
+            | Instruction::TlbWriteIndexed
+            | Instruction::TlbWriteRandom
+            | Instruction::TlbProbe
+            | Instruction::ExceptionReturn => [None, None],
+            Instruction::SystemCall => [Some(Register::V0), Some(Register::A0)],
+            Instruction::Custom { plugin, operands } => custom(plugin).sources(&operands),
+        }
+    }
+
+    /// The general-purpose register the instruction writes, if any.
+    pub fn destination(&self) -> Option<Register> {
+        match *self {
+            Instruction::AddImmediate { res, .. }
+            | Instruction::AddUnsigned { res, .. }
+            | Instruction::Add { res, .. }
+            | Instruction::AddImmediateUnsigned { res, .. }
+            | Instruction::Subtract { res, .. }
+            | Instruction::SubtractUnsigned { res, .. }
+            | Instruction::LoadUpperImmediate { res, .. }
+            | Instruction::OrImmediate { res, .. }
+            | Instruction::SetLessThan { res, .. }
+            | Instruction::SetLessThanUnsigned { res, .. }
+            | Instruction::LoadWordLeft { res, .. }
+            | Instruction::LoadWordRight { res, .. }
+            | Instruction::MoveFromHi { res, .. }
+            | Instruction::MoveFromLo { res, .. }
+            | Instruction::ShiftLeftLogical { res, .. }
+            | Instruction::ShiftRightLogical { res, .. }
+            | Instruction::ShiftRightArithmetic { res, .. }
+            | Instruction::ShiftLeftLogicalVariable { res, .. }
+            | Instruction::ShiftRightLogicalVariable { res, .. }
+            | Instruction::ShiftRightArithmeticVariable { res, .. }
+            | Instruction::And { res, .. }
+            | Instruction::Or { res, .. }
+            | Instruction::Xor { res, .. }
+            | Instruction::Nor { res, .. }
+            | Instruction::AndImmediate { res, .. }
+            | Instruction::XorImmediate { res, .. }
+            | Instruction::LoadWord { res, .. }
+            | Instruction::LoadDouble { res, .. }
+            | Instruction::LoadByte { res, .. }
+            | Instruction::LoadByteUnsigned { res, .. }
+            | Instruction::LoadHalf { res, .. }
+            | Instruction::LoadHalfUnsigned { res, .. }
+            | Instruction::MoveFromCoprocessor0 { res, .. }
+            | Instruction::DoubleAdd { res, .. }
+            | Instruction::DoubleAddUnsigned { res, .. }
+            | Instruction::DoubleSubtract { res, .. }
+            | Instruction::DoubleSubtractUnsigned { res, .. }
+            | Instruction::DoubleAddImmediate { res, .. }
+            | Instruction::DoubleAddImmediateUnsigned { res, .. }
+            | Instruction::DoubleShiftLeftLogical { res, .. }
+            | Instruction::DoubleShiftRightLogical { res, .. }
+            | Instruction::DoubleShiftRightArithmetic { res, .. }
+            | Instruction::JumpAndLinkRegister { res, .. } => Some(res),
+            Instruction::JumpAndLink { .. }
+            | Instruction::BranchLessThanZeroAndLink { .. }
+            | Instruction::BranchGreaterEqualZeroAndLink { .. } => Some(Register::RA),
+            Instruction::SystemCall
+            | Instruction::BranchEqual { .. }
+            | Instruction::BranchNotEqual { .. }
+            | Instruction::Jump { .. }
+            | Instruction::JumpRegister { .. }
+            | Instruction::StoreWord { .. }
+            | Instruction::StoreDouble { .. }
+            | Instruction::StoreByte { .. }
+            | Instruction::StoreHalf { .. }
+            | Instruction::MoveToCoprocessor0 { .. }
+            | Instruction::TrapEqual { .. }
+            | Instruction::TrapNotEqual { .. }
+            | Instruction::TrapGreaterEqual { .. }
+            | Instruction::TrapGreaterEqualUnsigned { .. }
+            | Instruction::TrapLessThan { .. }
+            | Instruction::TrapLessThanUnsigned { .. }
+            | Instruction::TrapEqualImmediate { .. }
+            | Instruction::TrapNotEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediateUnsigned { .. }
+            | Instruction::TrapLessThanImmediate { .. }
+            | Instruction::TrapLessThanImmediateUnsigned { .. }
+            | Instruction::BranchLessThanZero { .. }
+            | Instruction::BranchGreaterEqualZero { .. }
+            | Instruction::BranchLessEqualZero { .. }
+            | Instruction::BranchGreaterThanZero { .. }
+            | Instruction::StoreWordLeft { .. }
+            | Instruction::StoreWordRight { .. }
+            | Instruction::Multiply { .. }
+            | Instruction::MultiplyUnsigned { .. }
+            | Instruction::Divide { .. }
+            | Instruction::DivideUnsigned { .. }
+            | Instruction::MoveToHi { .. }
+            | Instruction::MoveToLo { .. }
+            | Instruction::TlbRead
+            | Instruction::TlbWriteIndexed
+            | Instruction::TlbWriteRandom
+            | Instruction::TlbProbe
+            | Instruction::ExceptionReturn => None,
+            Instruction::Custom { plugin, operands } => custom(plugin).destination(&operands),
+        }
+    }
+
+    /// Whether the instruction calls a subroutine, leaving a return address
+    /// in a register. `bltzal` and `bgezal` only call when they branch, so
+    /// step-over and step-out ask [`Simulator::called`] instead.
+    ///
+    /// [`Simulator::called`]: crate::simulator::Simulator::called
+    pub fn is_call(&self) -> bool {
+        match self {
+            Instruction::JumpAndLink { .. }
+            | Instruction::JumpAndLinkRegister { .. }
+            | Instruction::BranchLessThanZeroAndLink { .. }
+            | Instruction::BranchGreaterEqualZeroAndLink { .. } => true,
+            Instruction::AddImmediate { .. }
+            | Instruction::AddUnsigned { .. }
+            | Instruction::Add { .. }
+            | Instruction::AddImmediateUnsigned { .. }
+            | Instruction::Subtract { .. }
+            | Instruction::SubtractUnsigned { .. }
+            | Instruction::LoadUpperImmediate { .. }
+            | Instruction::OrImmediate { .. }
+            | Instruction::TrapEqual { .. }
+            | Instruction::TrapNotEqual { .. }
+            | Instruction::TrapGreaterEqual { .. }
+            | Instruction::TrapGreaterEqualUnsigned { .. }
+            | Instruction::TrapLessThan { .. }
+            | Instruction::TrapLessThanUnsigned { .. }
+            | Instruction::TrapEqualImmediate { .. }
+            | Instruction::TrapNotEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediateUnsigned { .. }
+            | Instruction::TrapLessThanImmediate { .. }
+            | Instruction::TrapLessThanImmediateUnsigned { .. }
+            | Instruction::SetLessThan { .. }
+            | Instruction::SetLessThanUnsigned { .. }
+            | Instruction::Multiply { .. }
+            | Instruction::MultiplyUnsigned { .. }
+            | Instruction::Divide { .. }
+            | Instruction::DivideUnsigned { .. }
+            | Instruction::MoveFromHi { .. }
+            | Instruction::MoveFromLo { .. }
+            | Instruction::MoveToHi { .. }
+            | Instruction::MoveToLo { .. }
+            | Instruction::ShiftLeftLogical { .. }
+            | Instruction::ShiftRightLogical { .. }
+            | Instruction::ShiftRightArithmetic { .. }
+            | Instruction::ShiftLeftLogicalVariable { .. }
+            | Instruction::ShiftRightLogicalVariable { .. }
+            | Instruction::ShiftRightArithmeticVariable { .. }
+            | Instruction::And { .. }
+            | Instruction::Or { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Nor { .. }
+            | Instruction::AndImmediate { .. }
+            | Instruction::XorImmediate { .. }
+            | Instruction::LoadWord { .. }
+            | Instruction::LoadDouble { .. }
+            | Instruction::LoadByte { .. }
+            | Instruction::LoadByteUnsigned { .. }
+            | Instruction::LoadHalf { .. }
+            | Instruction::LoadHalfUnsigned { .. }
+            | Instruction::StoreWord { .. }
+            | Instruction::StoreDouble { .. }
+            | Instruction::StoreByte { .. }
+            | Instruction::StoreHalf { .. }
+            | Instruction::SystemCall
+            | Instruction::BranchLessThanZero { .. }
+            | Instruction::BranchGreaterEqualZero { .. }
+            | Instruction::BranchLessEqualZero { .. }
+            | Instruction::BranchGreaterThanZero { .. }
+            | Instruction::LoadWordLeft { .. }
+            | Instruction::LoadWordRight { .. }
+            | Instruction::StoreWordLeft { .. }
+            | Instruction::StoreWordRight { .. }
+            | Instruction::BranchEqual { .. }
+            | Instruction::BranchNotEqual { .. }
+            | Instruction::Jump { .. }
+            | Instruction::JumpRegister { .. }
+            | Instruction::MoveFromCoprocessor0 { .. }
+            | Instruction::MoveToCoprocessor0 { .. }
+            | Instruction::TlbRead
+            | Instruction::TlbWriteIndexed
+            | Instruction::TlbWriteRandom
+            | Instruction::TlbProbe
+            | Instruction::ExceptionReturn
+            | Instruction::DoubleAdd { .. }
+            | Instruction::DoubleAddUnsigned { .. }
+            | Instruction::DoubleSubtract { .. }
+            | Instruction::DoubleSubtractUnsigned { .. }
+            | Instruction::DoubleAddImmediate { .. }
+            | Instruction::DoubleAddImmediateUnsigned { .. }
+            | Instruction::DoubleShiftLeftLogical { .. }
+            | Instruction::DoubleShiftRightLogical { .. }
+            | Instruction::DoubleShiftRightArithmetic { .. }
+            | Instruction::Custom { .. } => false,
+        }
+    }
+
+    /// Whether the instruction returns from a subroutine. Only `jr $ra`
+    /// counts; a jump through any other register is taken to be a jump
+    /// table or similar.
+    pub fn is_return(&self) -> bool {
+        match self {
+            Instruction::JumpRegister { reg } => *reg == Register::RA,
+            Instruction::AddImmediate { .. }
+            | Instruction::AddUnsigned { .. }
+            | Instruction::Add { .. }
+            | Instruction::AddImmediateUnsigned { .. }
+            | Instruction::Subtract { .. }
+            | Instruction::SubtractUnsigned { .. }
+            | Instruction::LoadUpperImmediate { .. }
+            | Instruction::OrImmediate { .. }
+            | Instruction::TrapEqual { .. }
+            | Instruction::TrapNotEqual { .. }
+            | Instruction::TrapGreaterEqual { .. }
+            | Instruction::TrapGreaterEqualUnsigned { .. }
+            | Instruction::TrapLessThan { .. }
+            | Instruction::TrapLessThanUnsigned { .. }
+            | Instruction::TrapEqualImmediate { .. }
+            | Instruction::TrapNotEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediate { .. }
+            | Instruction::TrapGreaterEqualImmediateUnsigned { .. }
+            | Instruction::TrapLessThanImmediate { .. }
+            | Instruction::TrapLessThanImmediateUnsigned { .. }
+            | Instruction::SetLessThan { .. }
+            | Instruction::SetLessThanUnsigned { .. }
+            | Instruction::Multiply { .. }
+            | Instruction::MultiplyUnsigned { .. }
+            | Instruction::Divide { .. }
+            | Instruction::DivideUnsigned { .. }
+            | Instruction::MoveFromHi { .. }
+            | Instruction::MoveFromLo { .. }
+            | Instruction::MoveToHi { .. }
+            | Instruction::MoveToLo { .. }
+            | Instruction::ShiftLeftLogical { .. }
+            | Instruction::ShiftRightLogical { .. }
+            | Instruction::ShiftRightArithmetic { .. }
+            | Instruction::ShiftLeftLogicalVariable { .. }
+            | Instruction::ShiftRightLogicalVariable { .. }
+            | Instruction::ShiftRightArithmeticVariable { .. }
+            | Instruction::And { .. }
+            | Instruction::Or { .. }
+            | Instruction::Xor { .. }
+            | Instruction::Nor { .. }
+            | Instruction::AndImmediate { .. }
+            | Instruction::XorImmediate { .. }
+            | Instruction::LoadWord { .. }
+            | Instruction::LoadDouble { .. }
+            | Instruction::LoadByte { .. }
+            | Instruction::LoadByteUnsigned { .. }
+            | Instruction::LoadHalf { .. }
+            | Instruction::LoadHalfUnsigned { .. }
+            | Instruction::StoreWord { .. }
+            | Instruction::StoreDouble { .. }
+            | Instruction::StoreByte { .. }
+            | Instruction::StoreHalf { .. }
+            | Instruction::SystemCall
+            | Instruction::BranchLessThanZero { .. }
+            | Instruction::BranchGreaterEqualZero { .. }
+            | Instruction::BranchLessEqualZero { .. }
+            | Instruction::BranchGreaterThanZero { .. }
+            | Instruction::BranchLessThanZeroAndLink { .. }
+            | Instruction::BranchGreaterEqualZeroAndLink { .. }
+            | Instruction::LoadWordLeft { .. }
+            | Instruction::LoadWordRight { .. }
+            | Instruction::StoreWordLeft { .. }
+            | Instruction::StoreWordRight { .. }
+            | Instruction::BranchEqual { .. }
+            | Instruction::BranchNotEqual { .. }
+            | Instruction::Jump { .. }
+            | Instruction::JumpAndLink { .. }
+            | Instruction::JumpAndLinkRegister { .. }
+            | Instruction::MoveFromCoprocessor0 { .. }
+            | Instruction::MoveToCoprocessor0 { .. }
+            | Instruction::TlbRead
+            | Instruction::TlbWriteIndexed
+            | Instruction::TlbWriteRandom
+            | Instruction::TlbProbe
+            | Instruction::ExceptionReturn
+            | Instruction::DoubleAdd { .. }
+            | Instruction::DoubleAddUnsigned { .. }
+            | Instruction::DoubleSubtract { .. }
+            | Instruction::DoubleSubtractUnsigned { .. }
+            | Instruction::DoubleAddImmediate { .. }
+            | Instruction::DoubleAddImmediateUnsigned { .. }
+            | Instruction::DoubleShiftLeftLogical { .. }
+            | Instruction::DoubleShiftRightLogical { .. }
+            | Instruction::DoubleShiftRightArithmetic { .. }
+            | Instruction::Custom { .. } => false,
+        }
+    }
+
+    /// Whether the instruction reads or writes coprocessor 0, including
+    /// the TLB. Custom instructions may do anything, so they count.
+    pub fn uses_cp0(&self) -> bool {
+        matches!(
+            self,
+            Instruction::MoveFromCoprocessor0 { .. }
+                | Instruction::Custom { .. }
+                | Instruction::MoveToCoprocessor0 { .. }
+                | Instruction::TlbRead
+                | Instruction::TlbWriteIndexed
+                | Instruction::TlbWriteRandom
+                | Instruction::TlbProbe
+                | Instruction::ExceptionReturn
+        )
+    }
+
+    /// Whether the instruction only exists in MIPS64 mode.
+    pub fn is_mips64(&self) -> bool {
+        matches!(
+            self,
+            Instruction::DoubleAdd { .. }
+                | Instruction::DoubleAddUnsigned { .. }
+                | Instruction::DoubleSubtract { .. }
+                | Instruction::DoubleSubtractUnsigned { .. }
+                | Instruction::DoubleAddImmediate { .. }
+                | Instruction::DoubleAddImmediateUnsigned { .. }
+                | Instruction::DoubleShiftLeftLogical { .. }
+                | Instruction::DoubleShiftRightLogical { .. }
+                | Instruction::DoubleShiftRightArithmetic { .. }
+                | Instruction::LoadDouble { .. }
+                | Instruction::StoreDouble { .. }
+        )
+    }
+
+    /// Splits the instruction's machine word into the fields of its format.
+    pub fn encoding(&self) -> Encoding {
+        let imm = |imm: i32| imm as u32 & 0xffff;
+        match *self {
+            Instruction::AddImmediate {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x08,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::AddUnsigned { res, reg, ret } => Encoding::Register {
+                opcode: 0,
+                rs: reg as u32,
+                rt: ret as u32,
+                rd: res as u32,
+                shamt: 0,
+                funct: 0x21,
+            },
+            Instruction::Add { res, reg, ret } => special(reg, ret, res, 0, 0x20),
+            Instruction::AddImmediateUnsigned {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x09,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::Subtract { res, reg, ret } => special(reg, ret, res, 0, 0x22),
+            Instruction::SubtractUnsigned { res, reg, ret } => special(reg, ret, res, 0, 0x23),
+            Instruction::LoadUpperImmediate { res, imm: value } => Encoding::Immediate {
+                opcode: 0x0f,
+                rs: 0,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::OrImmediate {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x0d,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::And { res, reg, ret } => special(reg, ret, res, 0, 0x24),
+            Instruction::Or { res, reg, ret } => special(reg, ret, res, 0, 0x25),
+            Instruction::Xor { res, reg, ret } => special(reg, ret, res, 0, 0x26),
+            Instruction::Nor { res, reg, ret } => special(reg, ret, res, 0, 0x27),
+            Instruction::AndImmediate {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x0c,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::XorImmediate {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x0e,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::ShiftLeftLogical { res, reg, shamt } => {
+                special(Register::ZERO, reg, res, shamt, 0x00)
+            }
+            Instruction::ShiftRightLogical { res, reg, shamt } => {
+                special(Register::ZERO, reg, res, shamt, 0x02)
+            }
+            Instruction::ShiftRightArithmetic { res, reg, shamt } => {
+                special(Register::ZERO, reg, res, shamt, 0x03)
+            }
+            // The amount goes in rs and the value in rt.
+            Instruction::ShiftLeftLogicalVariable { res, reg, ret } => {
+                special(ret, reg, res, 0, 0x04)
+            }
+            Instruction::ShiftRightLogicalVariable { res, reg, ret } => {
+                special(ret, reg, res, 0, 0x06)
+            }
+            Instruction::ShiftRightArithmeticVariable { res, reg, ret } => {
+                special(ret, reg, res, 0, 0x07)
+            }
+            Instruction::Multiply { reg, ret } => special(reg, ret, Register::ZERO, 0, 0x18),
+            Instruction::MultiplyUnsigned { reg, ret } => {
+                special(reg, ret, Register::ZERO, 0, 0x19)
+            }
+            Instruction::Divide { reg, ret } => special(reg, ret, Register::ZERO, 0, 0x1a),
+            Instruction::DivideUnsigned { reg, ret } => special(reg, ret, Register::ZERO, 0, 0x1b),
+            Instruction::MoveFromHi { res } => {
+                special(Register::ZERO, Register::ZERO, res, 0, 0x10)
+            }
+            Instruction::MoveToHi { reg } => special(reg, Register::ZERO, Register::ZERO, 0, 0x11),
+            Instruction::MoveFromLo { res } => {
+                special(Register::ZERO, Register::ZERO, res, 0, 0x12)
+            }
+            Instruction::MoveToLo { reg } => special(reg, Register::ZERO, Register::ZERO, 0, 0x13),
+            Instruction::SetLessThan { res, reg, ret } => special(reg, ret, res, 0, 0x2a),
+            Instruction::SetLessThanUnsigned { res, reg, ret } => special(reg, ret, res, 0, 0x2b),
+            Instruction::SystemCall => Encoding::Register {
+                opcode: 0,
+                rs: 0,
+                rt: 0,
+                rd: 0,
+                shamt: 0,
+                funct: 0x0c,
+            },
+            Instruction::BranchEqual { reg, ret, offset } => Encoding::Immediate {
+                opcode: 0x04,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(offset),
+            },
+            Instruction::BranchNotEqual { reg, ret, offset } => Encoding::Immediate {
+                opcode: 0x05,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(offset),
+            },
+            Instruction::BranchLessThanZero { reg, offset } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x00,
+                imm: imm(offset),
+            },
+            Instruction::BranchGreaterEqualZero { reg, offset } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x01,
+                imm: imm(offset),
+            },
+            Instruction::BranchLessEqualZero { reg, offset } => Encoding::Immediate {
+                opcode: 0x06,
+                rs: reg as u32,
+                rt: 0x00,
+                imm: imm(offset),
+            },
+            Instruction::BranchGreaterThanZero { reg, offset } => Encoding::Immediate {
+                opcode: 0x07,
+                rs: reg as u32,
+                rt: 0x00,
+                imm: imm(offset),
+            },
+            Instruction::BranchLessThanZeroAndLink { reg, offset } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x10,
+                imm: imm(offset),
+            },
+            Instruction::BranchGreaterEqualZeroAndLink { reg, offset } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x11,
+                imm: imm(offset),
+            },
+            Instruction::TrapEqual { reg, ret } => special(reg, ret, Register::ZERO, 0, 0x34),
+            Instruction::TrapNotEqual { reg, ret } => special(reg, ret, Register::ZERO, 0, 0x36),
+            Instruction::TrapGreaterEqual { reg, ret } => {
+                special(reg, ret, Register::ZERO, 0, 0x30)
+            }
+            Instruction::TrapGreaterEqualUnsigned { reg, ret } => {
+                special(reg, ret, Register::ZERO, 0, 0x31)
+            }
+            Instruction::TrapLessThan { reg, ret } => special(reg, ret, Register::ZERO, 0, 0x32),
+            Instruction::TrapLessThanUnsigned { reg, ret } => {
+                special(reg, ret, Register::ZERO, 0, 0x33)
+            }
+            Instruction::TrapEqualImmediate { reg, imm: value } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x0c,
+                imm: imm(value),
+            },
+            Instruction::TrapNotEqualImmediate { reg, imm: value } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x0e,
+                imm: imm(value),
+            },
+            Instruction::TrapGreaterEqualImmediate { reg, imm: value } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x08,
+                imm: imm(value),
+            },
+            Instruction::TrapGreaterEqualImmediateUnsigned { reg, imm: value } => {
+                Encoding::Immediate {
+                    opcode: 0x01,
+                    rs: reg as u32,
+                    rt: 0x09,
+                    imm: imm(value),
+                }
+            }
+            Instruction::TrapLessThanImmediate { reg, imm: value } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x0a,
+                imm: imm(value),
+            },
+            Instruction::TrapLessThanImmediateUnsigned { reg, imm: value } => Encoding::Immediate {
+                opcode: 0x01,
+                rs: reg as u32,
+                rt: 0x0b,
+                imm: imm(value),
+            },
+            Instruction::Jump { target } => Encoding::Jump {
+                opcode: 0x02,
+                target,
+            },
+            Instruction::JumpAndLink { target } => Encoding::Jump {
+                opcode: 0x03,
+                target,
+            },
+            Instruction::JumpRegister { reg } => {
+                special(reg, Register::ZERO, Register::ZERO, 0, 0x08)
+            }
+            Instruction::JumpAndLinkRegister { res, reg } => {
+                special(reg, Register::ZERO, res, 0, 0x09)
+            }
+            Instruction::LoadWord {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x23,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::StoreWord {
+                ret,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x2b,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadDouble {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x37,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::StoreDouble {
+                ret,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x3f,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadByte {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x20,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadByteUnsigned {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x24,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::StoreByte {
+                ret,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x28,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadHalf {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x21,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadHalfUnsigned {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x25,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::StoreHalf {
+                ret,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x29,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadWordLeft {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x22,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::LoadWordRight {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x26,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::StoreWordLeft {
+                ret,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x2a,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(value),
+            },
+            Instruction::StoreWordRight {
+                ret,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x2e,
+                rs: reg as u32,
+                rt: ret as u32,
+                imm: imm(value),
+            },
+            Instruction::MoveFromCoprocessor0 { res, cp0 } => Encoding::Register {
+                opcode: 0x10,
+                rs: 0x00,
+                rt: res as u32,
+                rd: cp0,
+                shamt: 0,
+                funct: 0,
+            },
+            Instruction::MoveToCoprocessor0 { ret, cp0 } => Encoding::Register {
+                opcode: 0x10,
+                rs: 0x04,
+                rt: ret as u32,
+                rd: cp0,
+                shamt: 0,
+                funct: 0,
+            },
+            Instruction::TlbRead => coprocessor0(0x01),
+            Instruction::TlbWriteIndexed => coprocessor0(0x02),
+            Instruction::TlbWriteRandom => coprocessor0(0x06),
+            Instruction::TlbProbe => coprocessor0(0x08),
+            Instruction::ExceptionReturn => coprocessor0(0x18),
+            Instruction::DoubleAdd { res, reg, ret } => special(reg, ret, res, 0, 0x2c),
+            Instruction::DoubleAddUnsigned { res, reg, ret } => special(reg, ret, res, 0, 0x2d),
+            Instruction::DoubleSubtract { res, reg, ret } => special(reg, ret, res, 0, 0x2e),
+            Instruction::DoubleSubtractUnsigned { res, reg, ret } => {
+                special(reg, ret, res, 0, 0x2f)
+            }
+            Instruction::DoubleAddImmediate {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x18,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            Instruction::DoubleAddImmediateUnsigned {
+                res,
+                reg,
+                imm: value,
+            } => Encoding::Immediate {
+                opcode: 0x19,
+                rs: reg as u32,
+                rt: res as u32,
+                imm: imm(value),
+            },
+            // Shifts of 32 or more use the `32` variant, 4 past the funct.
+            Instruction::DoubleShiftLeftLogical { res, reg, shamt } => {
+                special(Register::ZERO, reg, res, shamt % 32, 0x38 + shamt / 32 * 4)
+            }
+            Instruction::DoubleShiftRightLogical { res, reg, shamt } => {
+                special(Register::ZERO, reg, res, shamt % 32, 0x3a + shamt / 32 * 4)
+            }
+            Instruction::DoubleShiftRightArithmetic { res, reg, shamt } => {
+                special(Register::ZERO, reg, res, shamt % 32, 0x3b + shamt / 32 * 4)
+            }
+            Instruction::Custom { plugin, operands } => custom(plugin).encode(&operands),
+        }
+    }
+
+    /// The 32-bit machine word for the instruction.
+    pub fn encode(&self) -> u32 {
+        self.encoding().word()
+    }
+
+    /// The instruction a machine word encodes, if the simulator supports it.
+    pub fn decode(word: u32) -> Option<Instruction> {
+        let opcode = word >> 26;
+        let rs = Register::ALL[(word >> 21) as usize & 0x1f];
+        let rt = Register::ALL[(word >> 16) as usize & 0x1f];
+        let rd = Register::ALL[(word >> 11) as usize & 0x1f];
+        let shamt = (word >> 6) & 0x1f;
+        let funct = word & 0x3f;
+        let imm = word & 0xffff;
+        let format = (word >> 21) & 0x1f;
+        let cp0 = (word >> 11) & 0x1f;
+        let instruction = match (opcode, funct) {
+            (0x10, 0x00) if format == 0x00 && word & 0x7ff == 0 => {
+                Instruction::MoveFromCoprocessor0 { res: rt, cp0 }
+            }
+            (0x10, 0x00) if format == 0x04 && word & 0x7ff == 0 => {
+                Instruction::MoveToCoprocessor0 { ret: rt, cp0 }
+            }
+            (0x10, _) if word & 0x03ff_ffc0 != 0x0200_0000 => return None,
+            (0x10, 0x01) => Instruction::TlbRead,
+            (0x10, 0x02) => Instruction::TlbWriteIndexed,
+            (0x10, 0x06) => Instruction::TlbWriteRandom,
+            (0x10, 0x08) => Instruction::TlbProbe,
+            (0x10, 0x18) => Instruction::ExceptionReturn,
+            (0x00, 0x21) if shamt == 0 => Instruction::AddUnsigned {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x20) if shamt == 0 => Instruction::Add {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x22) if shamt == 0 => Instruction::Subtract {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x23) if shamt == 0 => Instruction::SubtractUnsigned {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x24) if shamt == 0 => Instruction::And {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x25) if shamt == 0 => Instruction::Or {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x26) if shamt == 0 => Instruction::Xor {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x27) if shamt == 0 => Instruction::Nor {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x00) if format == 0 => Instruction::ShiftLeftLogical {
+                res: rd,
+                reg: rt,
+                shamt,
+            },
+            (0x00, 0x02) if format == 0 => Instruction::ShiftRightLogical {
+                res: rd,
+                reg: rt,
+                shamt,
+            },
+            (0x00, 0x03) if format == 0 => Instruction::ShiftRightArithmetic {
+                res: rd,
+                reg: rt,
+                shamt,
+            },
+            (0x00, 0x04) if shamt == 0 => Instruction::ShiftLeftLogicalVariable {
+                res: rd,
+                reg: rt,
+                ret: rs,
+            },
+            (0x00, 0x06) if shamt == 0 => Instruction::ShiftRightLogicalVariable {
+                res: rd,
+                reg: rt,
+                ret: rs,
+            },
+            (0x00, 0x07) if shamt == 0 => Instruction::ShiftRightArithmeticVariable {
+                res: rd,
+                reg: rt,
+                ret: rs,
+            },
+            (0x00, 0x10) if word & 0x03ff_07c0 == 0 => Instruction::MoveFromHi { res: rd },
+            (0x00, 0x11) if word & 0x001f_ffc0 == 0 => Instruction::MoveToHi { reg: rs },
+            (0x00, 0x12) if word & 0x03ff_07c0 == 0 => Instruction::MoveFromLo { res: rd },
+            (0x00, 0x13) if word & 0x001f_ffc0 == 0 => Instruction::MoveToLo { reg: rs },
+            (0x00, 0x18) if word & 0xffc0 == 0 => Instruction::Multiply { reg: rs, ret: rt },
+            (0x00, 0x19) if word & 0xffc0 == 0 => {
+                Instruction::MultiplyUnsigned { reg: rs, ret: rt }
+            }
+            (0x00, 0x1a) if word & 0xffc0 == 0 => Instruction::Divide { reg: rs, ret: rt },
+            (0x00, 0x1b) if word & 0xffc0 == 0 => Instruction::DivideUnsigned { reg: rs, ret: rt },
+            (0x00, 0x2a) if shamt == 0 => Instruction::SetLessThan {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x2b) if shamt == 0 => Instruction::SetLessThanUnsigned {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x0c) => Instruction::SystemCall,
+            (0x00, 0x08) if word & 0x001f_ffc0 == 0 => Instruction::JumpRegister { reg: rs },
+            (0x00, 0x09) if word & 0x001f_07c0 == 0 => {
+                Instruction::JumpAndLinkRegister { res: rd, reg: rs }
+            }
+            (0x00, 0x2c) if shamt == 0 => Instruction::DoubleAdd {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x2d) if shamt == 0 => Instruction::DoubleAddUnsigned {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x2e) if shamt == 0 => Instruction::DoubleSubtract {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x2f) if shamt == 0 => Instruction::DoubleSubtractUnsigned {
+                res: rd,
+                reg: rs,
+                ret: rt,
+            },
+            (0x00, 0x38 | 0x3c) if format == 0 => Instruction::DoubleShiftLeftLogical {
+                res: rd,
+                reg: rt,
+                shamt: shamt + (funct - 0x38) * 8,
+            },
+            (0x00, 0x3a | 0x3e) if format == 0 => Instruction::DoubleShiftRightLogical {
+                res: rd,
+                reg: rt,
+                shamt: shamt + (funct - 0x3a) * 8,
+            },
+            (0x00, 0x3b | 0x3f) if format == 0 => Instruction::DoubleShiftRightArithmetic {
+                res: rd,
+                reg: rt,
+                shamt: shamt + (funct - 0x3b) * 8,
+            },
+            (0x18, _) => Instruction::DoubleAddImmediate {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x19, _) => Instruction::DoubleAddImmediateUnsigned {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x02, _) => Instruction::Jump {
+                target: word & 0x03ff_ffff,
+            },
+            (0x03, _) => Instruction::JumpAndLink {
+                target: word & 0x03ff_ffff,
+            },
+            (0x01, _) if rt as u32 == 0x00 => Instruction::BranchLessThanZero {
+                reg: rs,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x01 => Instruction::BranchGreaterEqualZero {
+                reg: rs,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x06, _) if rt as u32 == 0x00 => Instruction::BranchLessEqualZero {
+                reg: rs,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x07, _) if rt as u32 == 0x00 => Instruction::BranchGreaterThanZero {
+                reg: rs,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x10 => Instruction::BranchLessThanZeroAndLink {
+                reg: rs,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x11 => Instruction::BranchGreaterEqualZeroAndLink {
+                reg: rs,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x00, 0x34) => Instruction::TrapEqual { reg: rs, ret: rt },
+            (0x00, 0x36) => Instruction::TrapNotEqual { reg: rs, ret: rt },
+            (0x00, 0x30) => Instruction::TrapGreaterEqual { reg: rs, ret: rt },
+            (0x00, 0x31) => Instruction::TrapGreaterEqualUnsigned { reg: rs, ret: rt },
+            (0x00, 0x32) => Instruction::TrapLessThan { reg: rs, ret: rt },
+            (0x00, 0x33) => Instruction::TrapLessThanUnsigned { reg: rs, ret: rt },
+            (0x01, _) if rt as u32 == 0x0c => Instruction::TrapEqualImmediate {
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x0e => Instruction::TrapNotEqualImmediate {
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x08 => Instruction::TrapGreaterEqualImmediate {
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x09 => Instruction::TrapGreaterEqualImmediateUnsigned {
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x0a => Instruction::TrapLessThanImmediate {
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x01, _) if rt as u32 == 0x0b => Instruction::TrapLessThanImmediateUnsigned {
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x04, _) => Instruction::BranchEqual {
+                reg: rs,
+                ret: rt,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x05, _) => Instruction::BranchNotEqual {
+                reg: rs,
+                ret: rt,
+                offset: imm as u16 as i16 as i32,
+            },
+            (0x08, _) => Instruction::AddImmediate {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x09, _) => Instruction::AddImmediateUnsigned {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x0f, _) => Instruction::LoadUpperImmediate {
+                res: rt,
+                imm: imm as i32,
+            },
+            (0x23, _) => Instruction::LoadWord {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x2b, _) => Instruction::StoreWord {
+                ret: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x37, _) => Instruction::LoadDouble {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x3f, _) => Instruction::StoreDouble {
+                ret: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x20, _) => Instruction::LoadByte {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x24, _) => Instruction::LoadByteUnsigned {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x28, _) => Instruction::StoreByte {
+                ret: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x21, _) => Instruction::LoadHalf {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x25, _) => Instruction::LoadHalfUnsigned {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x29, _) => Instruction::StoreHalf {
+                ret: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x0c, _) => Instruction::AndImmediate {
+                res: rt,
+                reg: rs,
+                imm: imm as i32,
+            },
+            (0x0e, _) => Instruction::XorImmediate {
+                res: rt,
+                reg: rs,
+                imm: imm as i32,
+            },
+            (0x22, _) => Instruction::LoadWordLeft {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x26, _) => Instruction::LoadWordRight {
+                res: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x2a, _) => Instruction::StoreWordLeft {
+                ret: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x2e, _) => Instruction::StoreWordRight {
+                ret: rt,
+                reg: rs,
+                imm: imm as u16 as i16 as i32,
+            },
+            (0x0d, _) => Instruction::OrImmediate {
+                res: rt,
+                reg: rs,
+                imm: imm as i32,
+            },
+            _ => {
+                let (plugin, operands) = plugin::decode(word)?;
+                Instruction::Custom { plugin, operands }
+            }
+        };
+        Some(instruction)
+    }
+}
+
+/// The plugin behind a custom instruction. Plugins are never removed, so
+/// one that made an instruction is always there.
+fn custom(index: u16) -> Arc<dyn InstructionPlugin> {
+    plugin::get(index).expect("custom instructions come from registered plugins")
+}
+
+/// A SPECIAL-opcode register instruction.
+fn special(rs: Register, rt: Register, rd: Register, shamt: u32, funct: u32) -> Encoding {
+    Encoding::Register {
+        opcode: 0,
+        rs: rs as u32,
+        rt: rt as u32,
+        rd: rd as u32,
+        shamt,
+        funct,
+    }
+}
+
+/// A coprocessor 0 operation, selected by `funct`.
+fn coprocessor0(funct: u32) -> Encoding {
+    Encoding::Register {
+        opcode: 0x10,
+        rs: 0x10,
+        rt: 0,
+        rd: 0,
+        shamt: 0,
+        funct,
+    }
+}
+
+/// The shortest sequence that loads `imm` into `res`. It uses `addiu`
+/// rather than `addi`, so building a constant never traps on overflow.
+fn load_constant(res: Register, imm: i32) -> Vec<Instruction> {
+    if (-32768..=32767).contains(&imm) {
+        vec![Instruction::AddImmediateUnsigned {
+            res,
+            reg: Register::ZERO,
+            imm,
+        }]
+    } else if (imm & 0xFFFF) == 0 {
+        vec![Instruction::LoadUpperImmediate {
+            res,
+            imm: (imm >> 16),
+        }]
+    } else {
+        // The low half is sign-extended, so round the upper half up when it
+        // is negative.
+        let high = (imm >> 16) + if (imm & 0x8000) != 0 { 1 } else { 0 };
+        let low = imm as u16 as i16 as i32;
+        vec![
+            Instruction::LoadUpperImmediate { res, imm: high },
+            Instruction::AddImmediateUnsigned {
+                res,
+                reg: res,
+                imm: low,
+            },
+        ]
+    }
+}
+
+/// The offset a branch at text index `index` encodes to reach `target`:
+/// the number of words from the instruction after it.
+fn branch_offset(index: usize, target: Address, label: &str) -> Result<i32, AssemblerError> {
+    let next = i64::from(BASE_TEXT_ADDR.0) + index as i64 * 4 + 4;
+    let distance = i64::from(target.0) - next;
+    let offset = distance / 4;
+    if distance % 4 != 0 || !(-32768..=32767).contains(&offset) {
+        return Err(AssemblerError::BranchOutOfRange(label.to_string()));
+    }
+    Ok(offset as i32)
+}
+
+/// The target field a jump at text index `index` encodes to reach
+/// `target`, which must be in the same 256 MB region as the instruction
+/// after it.
+fn jump_target(index: usize, target: Address, label: &str) -> Result<u32, AssemblerError> {
+    let next = BASE_TEXT_ADDR.wrapping_add(index as u32 * 4 + 4);
+    if (target.0 ^ next.0) & 0xf000_0000 != 0 || !target.0.is_multiple_of(4) {
+        return Err(AssemblerError::JumpOutOfRange(label.to_string()));
+    }
+    Ok(target.0 >> 2 & 0x03ff_ffff)
+}
+
+/// Points the branch or jump at text index `index` to `target`.
+fn retarget(
+    text: &mut [Instruction],
+    index: usize,
+    target: Address,
+    segment: Segment,
+    label: &str,
+) -> Result<(), AssemblerError> {
+    let [instruction, rest @ ..] = &mut text[index..] else {
+        return Ok(());
+    };
+    match instruction {
+        Instruction::BranchEqual { offset, .. }
+        | Instruction::BranchNotEqual { offset, .. }
+        | Instruction::BranchLessThanZero { offset, .. }
+        | Instruction::BranchGreaterEqualZero { offset, .. }
+        | Instruction::BranchLessEqualZero { offset, .. }
+        | Instruction::BranchGreaterThanZero { offset, .. }
+        | Instruction::BranchLessThanZeroAndLink { offset, .. }
+        | Instruction::BranchGreaterEqualZeroAndLink { offset, .. } => {
+            *offset = branch_offset(index, target, label)?;
+        }
+        Instruction::Jump { target: field } | Instruction::JumpAndLink { target: field } => {
+            *field = jump_target(index, target, label)?;
+        }
+        // `la`, or a load or store naming the label, which like
+        // `Assembler::parse_address` rounds the upper half up when the
+        // sign-extended offset is negative.
+        Instruction::LoadUpperImmediate { imm: high, .. } => {
+            if segment != Segment::Data {
+                return Err(AssemblerError::InvalidLabel(label.to_string()));
+            }
+            match rest.first_mut() {
+                Some(Instruction::OrImmediate { imm: low, .. }) => {
+                    *high = (target.0 >> 16) as i32;
+                    *low = (target.0 & 0xffff) as i32;
+                }
+                Some(
+                    Instruction::LoadWord { imm: low, .. }
+                    | Instruction::LoadDouble { imm: low, .. }
+                    | Instruction::LoadByte { imm: low, .. }
+                    | Instruction::LoadByteUnsigned { imm: low, .. }
+                    | Instruction::LoadHalf { imm: low, .. }
+                    | Instruction::LoadHalfUnsigned { imm: low, .. }
+                    | Instruction::LoadWordLeft { imm: low, .. }
+                    | Instruction::LoadWordRight { imm: low, .. }
+                    | Instruction::StoreWord { imm: low, .. }
+                    | Instruction::StoreDouble { imm: low, .. }
+                    | Instruction::StoreByte { imm: low, .. }
+                    | Instruction::StoreHalf { imm: low, .. }
+                    | Instruction::StoreWordLeft { imm: low, .. }
+                    | Instruction::StoreWordRight { imm: low, .. },
+                ) => {
+                    *high = (target.0.wrapping_add(0x8000) >> 16) as i32;
+                    *low = target.0 as u16 as i16 as i32;
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Writes a doubleword shift as the `32` variant when the amount needs it,
+/// with the amount that variant encodes.
+fn write_double_shift(
+    f: &mut fmt::Formatter<'_>,
+    mnemonic: &str,
+    res: Register,
+    reg: Register,
+    shamt: u32,
+) -> fmt::Result {
+    match shamt >= 32 {
+        true => write!(
+            f,
+            "{}32 {}, {}, {}",
+            mnemonic,
+            res.name(),
+            reg.name(),
+            shamt - 32
+        ),
+        false => write!(f, "{} {}, {}, {}", mnemonic, res.name(), reg.name(), shamt),
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Formats the instruction as basic (non-pseudo) assembly.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::AddImmediate { res, reg, imm } => {
+                write!(f, "addi {}, {}, {}", res.name(), reg.name(), imm)
+            }
+            Instruction::AddUnsigned { res, reg, ret } => {
+                write!(f, "addu {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::Add { res, reg, ret } => {
+                write!(f, "add {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::AddImmediateUnsigned { res, reg, imm } => {
+                write!(f, "addiu {}, {}, {}", res.name(), reg.name(), imm)
+            }
+            Instruction::Subtract { res, reg, ret } => {
+                write!(f, "sub {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::SubtractUnsigned { res, reg, ret } => {
+                write!(f, "subu {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::LoadUpperImmediate { res, imm } => {
+                write!(f, "lui {}, 0x{:04x}", res.name(), imm as u32 & 0xffff)
+            }
+            Instruction::OrImmediate { res, reg, imm } => {
+                write!(
+                    f,
+                    "ori {}, {}, 0x{:04x}",
+                    res.name(),
+                    reg.name(),
+                    imm as u32 & 0xffff
+                )
+            }
+            Instruction::And { res, reg, ret } => {
+                write!(f, "and {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::Or { res, reg, ret } => {
+                write!(f, "or {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::Xor { res, reg, ret } => {
+                write!(f, "xor {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::Nor { res, reg, ret } => {
+                write!(f, "nor {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::AndImmediate { res, reg, imm } => {
+                write!(
+                    f,
+                    "andi {}, {}, 0x{:04x}",
+                    res.name(),
+                    reg.name(),
+                    imm as u32 & 0xffff
+                )
+            }
+            Instruction::XorImmediate { res, reg, imm } => {
+                write!(
+                    f,
+                    "xori {}, {}, 0x{:04x}",
+                    res.name(),
+                    reg.name(),
+                    imm as u32 & 0xffff
+                )
+            }
+            Instruction::ShiftLeftLogical { res, reg, shamt } => {
+                write!(f, "sll {}, {}, {}", res.name(), reg.name(), shamt)
+            }
+            Instruction::ShiftRightLogical { res, reg, shamt } => {
+                write!(f, "srl {}, {}, {}", res.name(), reg.name(), shamt)
+            }
+            Instruction::ShiftRightArithmetic { res, reg, shamt } => {
+                write!(f, "sra {}, {}, {}", res.name(), reg.name(), shamt)
+            }
+            Instruction::ShiftLeftLogicalVariable { res, reg, ret } => {
+                write!(f, "sllv {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::ShiftRightLogicalVariable { res, reg, ret } => {
+                write!(f, "srlv {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::ShiftRightArithmeticVariable { res, reg, ret } => {
+                write!(f, "srav {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::Multiply { reg, ret } => write!(f, "mult {}, {}", reg.name(), ret.name()),
+            Instruction::MultiplyUnsigned { reg, ret } => {
+                write!(f, "multu {}, {}", reg.name(), ret.name())
+            }
+            Instruction::Divide { reg, ret } => write!(f, "div {}, {}", reg.name(), ret.name()),
+            Instruction::DivideUnsigned { reg, ret } => {
+                write!(f, "divu {}, {}", reg.name(), ret.name())
+            }
+            Instruction::MoveFromHi { res } => write!(f, "mfhi {}", res.name()),
+            Instruction::MoveFromLo { res } => write!(f, "mflo {}", res.name()),
+            Instruction::MoveToHi { reg } => write!(f, "mthi {}", reg.name()),
+            Instruction::MoveToLo { reg } => write!(f, "mtlo {}", reg.name()),
+            Instruction::SetLessThan { res, reg, ret } => {
+                write!(f, "slt {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::SetLessThanUnsigned { res, reg, ret } => {
+                write!(f, "sltu {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::SystemCall => write!(f, "syscall"),
+            Instruction::BranchEqual { reg, ret, offset } => {
+                write!(f, "beq {}, {}, {}", reg.name(), ret.name(), offset)
+            }
+            Instruction::BranchNotEqual { reg, ret, offset } => {
+                write!(f, "bne {}, {}, {}", reg.name(), ret.name(), offset)
+            }
+            Instruction::BranchLessThanZero { reg, offset } => {
+                write!(f, "bltz {}, {}", reg.name(), offset)
+            }
+            Instruction::BranchGreaterEqualZero { reg, offset } => {
+                write!(f, "bgez {}, {}", reg.name(), offset)
+            }
+            Instruction::BranchLessEqualZero { reg, offset } => {
+                write!(f, "blez {}, {}", reg.name(), offset)
+            }
+            Instruction::BranchGreaterThanZero { reg, offset } => {
+                write!(f, "bgtz {}, {}", reg.name(), offset)
+            }
+            Instruction::BranchLessThanZeroAndLink { reg, offset } => {
+                write!(f, "bltzal {}, {}", reg.name(), offset)
+            }
+            Instruction::BranchGreaterEqualZeroAndLink { reg, offset } => {
+                write!(f, "bgezal {}, {}", reg.name(), offset)
+            }
+            Instruction::TrapEqual { reg, ret } => write!(f, "teq {}, {}", reg.name(), ret.name()),
+            Instruction::TrapNotEqual { reg, ret } => {
+                write!(f, "tne {}, {}", reg.name(), ret.name())
+            }
+            Instruction::TrapGreaterEqual { reg, ret } => {
+                write!(f, "tge {}, {}", reg.name(), ret.name())
+            }
+            Instruction::TrapGreaterEqualUnsigned { reg, ret } => {
+                write!(f, "tgeu {}, {}", reg.name(), ret.name())
+            }
+            Instruction::TrapLessThan { reg, ret } => {
+                write!(f, "tlt {}, {}", reg.name(), ret.name())
+            }
+            Instruction::TrapLessThanUnsigned { reg, ret } => {
+                write!(f, "tltu {}, {}", reg.name(), ret.name())
+            }
+            Instruction::TrapEqualImmediate { reg, imm } => {
+                write!(f, "teqi {}, {}", reg.name(), imm)
+            }
+            Instruction::TrapNotEqualImmediate { reg, imm } => {
+                write!(f, "tnei {}, {}", reg.name(), imm)
+            }
+            Instruction::TrapGreaterEqualImmediate { reg, imm } => {
+                write!(f, "tgei {}, {}", reg.name(), imm)
+            }
+            Instruction::TrapGreaterEqualImmediateUnsigned { reg, imm } => {
+                write!(f, "tgeiu {}, {}", reg.name(), imm)
+            }
+            Instruction::TrapLessThanImmediate { reg, imm } => {
+                write!(f, "tlti {}, {}", reg.name(), imm)
+            }
+            Instruction::TrapLessThanImmediateUnsigned { reg, imm } => {
+                write!(f, "tltiu {}, {}", reg.name(), imm)
+            }
+            Instruction::LoadWord { res, reg, imm } => {
+                write!(f, "lw {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::StoreWord { ret, reg, imm } => {
+                write!(f, "sw {}, {}({})", ret.name(), imm, reg.name())
+            }
+            Instruction::LoadDouble { res, reg, imm } => {
+                write!(f, "ld {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::StoreDouble { ret, reg, imm } => {
+                write!(f, "sd {}, {}({})", ret.name(), imm, reg.name())
+            }
+            Instruction::LoadByte { res, reg, imm } => {
+                write!(f, "lb {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::LoadByteUnsigned { res, reg, imm } => {
+                write!(f, "lbu {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::StoreByte { ret, reg, imm } => {
+                write!(f, "sb {}, {}({})", ret.name(), imm, reg.name())
+            }
+            Instruction::LoadHalf { res, reg, imm } => {
+                write!(f, "lh {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::LoadHalfUnsigned { res, reg, imm } => {
+                write!(f, "lhu {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::StoreHalf { ret, reg, imm } => {
+                write!(f, "sh {}, {}({})", ret.name(), imm, reg.name())
+            }
+            Instruction::LoadWordLeft { res, reg, imm } => {
+                write!(f, "lwl {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::LoadWordRight { res, reg, imm } => {
+                write!(f, "lwr {}, {}({})", res.name(), imm, reg.name())
+            }
+            Instruction::StoreWordLeft { ret, reg, imm } => {
+                write!(f, "swl {}, {}({})", ret.name(), imm, reg.name())
+            }
+            Instruction::StoreWordRight { ret, reg, imm } => {
+                write!(f, "swr {}, {}({})", ret.name(), imm, reg.name())
+            }
+            // Shown as the address the target reaches from the text segment.
+            Instruction::Jump { target } => write!(f, "j 0x{:08x}", target << 2),
+            Instruction::JumpAndLink { target } => write!(f, "jal 0x{:08x}", target << 2),
+            Instruction::JumpRegister { reg } => write!(f, "jr {}", reg.name()),
+            Instruction::JumpAndLinkRegister { res, reg } => {
+                write!(f, "jalr {}, {}", res.name(), reg.name())
+            }
+            Instruction::MoveFromCoprocessor0 { res, cp0 } => {
+                write!(f, "mfc0 {}, ${}", res.name(), cp0)
+            }
+            Instruction::MoveToCoprocessor0 { ret, cp0 } => {
+                write!(f, "mtc0 {}, ${}", ret.name(), cp0)
+            }
+            Instruction::TlbRead => write!(f, "tlbr"),
+            Instruction::TlbWriteIndexed => write!(f, "tlbwi"),
+            Instruction::TlbWriteRandom => write!(f, "tlbwr"),
+            Instruction::TlbProbe => write!(f, "tlbp"),
+            Instruction::ExceptionReturn => write!(f, "eret"),
+            Instruction::DoubleAdd { res, reg, ret } => {
+                write!(f, "dadd {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::DoubleAddUnsigned { res, reg, ret } => {
+                write!(f, "daddu {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::DoubleSubtract { res, reg, ret } => {
+                write!(f, "dsub {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::DoubleSubtractUnsigned { res, reg, ret } => {
+                write!(f, "dsubu {}, {}, {}", res.name(), reg.name(), ret.name())
+            }
+            Instruction::DoubleAddImmediate { res, reg, imm } => {
+                write!(f, "daddi {}, {}, {}", res.name(), reg.name(), imm)
+            }
+            Instruction::DoubleAddImmediateUnsigned { res, reg, imm } => {
+                write!(f, "daddiu {}, {}, {}", res.name(), reg.name(), imm)
+            }
+            Instruction::DoubleShiftLeftLogical { res, reg, shamt } => {
+                write_double_shift(f, "dsll", res, reg, shamt)
+            }
+            Instruction::DoubleShiftRightLogical { res, reg, shamt } => {
+                write_double_shift(f, "dsrl", res, reg, shamt)
+            }
+            Instruction::DoubleShiftRightArithmetic { res, reg, shamt } => {
+                write_double_shift(f, "dsra", res, reg, shamt)
+            }
+            Instruction::Custom { plugin, operands } => {
+                write!(f, "{}", custom(plugin).display(&operands))
+            }
+        }
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self {
+            interner: Interner::new(),
+            symbols: HashMap::new(),
+            constants: HashMap::new(),
+            data_addr: BASE_DATA_ADDR,
+            text_addr: BASE_TEXT_ADDR,
+            entry_point: None,
+            memory: vec![0; MEMORY_SIZE],
+            text_lines: Vec::new(),
+            endianness: Endianness::default(),
+            forward: Vec::new(),
+            fixups: Vec::new(),
+            listing: Vec::new(),
+            debug_info: DebugInfo::default(),
+            current_segment: Segment::Text,
+            include_dir: PathBuf::from("."),
+            buffers: HashMap::new(),
+            file_access: FileAccess::Any,
+            include_site: None,
+            include_files: Vec::new(),
+            diagnostics: Vec::new(),
+            error_span: None,
+            failed: false,
+            entry_warning: None,
+        }
+    }
+
+    /// Errors and warnings from assembling so far. An error that stopped
+    /// assembly is the last entry.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Records a diagnostic for `line`, pointing at `span` or the whole
+    /// line. Inside an included file it points at the `.include` instead.
+    fn diagnose(
+        &mut self,
+        severity: Severity,
+        message: String,
+        source: &str,
+        line: &Line,
+        span: Option<Span>,
+    ) -> Diagnostic {
+        match (self.include_site, self.include_files.last()) {
+            (Some((number, column, site)), Some(file)) => Diagnostic {
+                severity,
+                message,
+                line: number,
+                column,
+                span: site,
+                note: Some(format!("in '{}' line {}", file, line.number)),
+            },
+            _ => Diagnostic::new(
+                severity,
+                message,
+                source,
+                line.number,
+                span.unwrap_or_else(|| line_span(line)),
+            ),
+        }
+    }
+
+    /// Sets the directory `.include` paths are resolved against, which is
+    /// the current directory unless set.
+    pub fn set_include_dir(&mut self, directory: impl Into<PathBuf>) {
+        self.include_dir = directory.into();
+    }
+
+    /// Limits the files `.include` and `.incbin` read, e.g. for source from
+    /// an untrusted client.
+    pub fn set_file_access(&mut self, access: FileAccess) {
+        if let FileAccess::Within(directory) = &access {
+            self.include_dir = directory.clone();
+        }
+        self.file_access = access;
+    }
+
+    /// Whether [`Assembler::set_file_access`] lets `path` be read.
+    fn may_read(&self, path: &Path) -> bool {
+        match &self.file_access {
+            FileAccess::Any => true,
+            FileAccess::Nothing => false,
+            // Resolving links and `..` first, so neither can escape.
+            FileAccess::Within(directory) => {
+                match (path.canonicalize(), directory.canonicalize()) {
+                    (Ok(path), Ok(directory)) => path.starts_with(directory),
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// Makes `.include` of `path` read `contents` instead of the file.
+    pub fn add_buffer(&mut self, path: &Path, contents: String) {
+        self.buffers.insert(normalize(path), contents);
+    }
+
+    pub fn assemble(&mut self, args: &RuntimeArgs) -> Result<(), AssemblerError> {
+        let source = read_source(&args.file)?;
+        if let Some(directory) = Path::new(&args.file).parent() {
+            self.set_include_dir(directory);
+        }
+        self.assemble_source(&source, args)
+    }
+
+    /// Assembles an included file in place, as if its text replaced the
+    /// `.include` line.
+    fn include(
+        &mut self,
+        name: &str,
+        source: &str,
+        line: &Line,
+        args: &RuntimeArgs,
+    ) -> Result<(), AssemblerError> {
+        if self.include_files.len() == MAX_INCLUDE_DEPTH {
+            return Err(AssemblerError::IncludeTooDeep(name.to_string()));
+        }
+        let path = normalize(&self.include_dir.join(name));
+        let contents = match self.buffers.get(&path) {
+            Some(contents) => contents.clone(),
+            None if !self.may_read(&path) => {
+                return Err(AssemblerError::FileAccessDenied(name.to_string()));
+            }
+            None => read_source(&path.to_string_lossy())
+                .map_err(|_| AssemblerError::IncludeNotFound(name.to_string()))?,
+        };
+
+        let outer = self.include_site;
+        if outer.is_none() {
+            let span = line_span(line);
+            self.include_site = Some((line.number, diagnostic::column(source, span), span));
+        }
+        self.include_files.push(name.to_string());
+        let result = self.assemble_included(&contents, args);
+        self.include_files.pop();
+        self.include_site = outer;
+        result
+    }
+
+    /// Assembles source text directly, without reading `args.file`.
+    pub fn assemble_source(
+        &mut self,
+        source: &str,
+        args: &RuntimeArgs,
+    ) -> Result<(), AssemblerError> {
+        self.endianness = args.endianness;
+        self.assemble_included(source, args)?;
+        self.resolve_fixups()?;
+        for range in std::mem::take(&mut self.listing) {
+            println!("{:?}", &self.text_lines[range]);
+        }
+        if let Some(warning) = self.entry_warning.take()
+            && self
+                .entry_point
+                .is_some_and(|entry| !self.symbols.contains_key(&entry))
+        {
+            self.diagnostics.push(warning);
+        }
+        Ok(())
+    }
+
+    /// Points every branch, jump and address to a label defined after it at
+    /// the label.
+    fn resolve_fixups(&mut self) -> Result<(), AssemblerError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            let result = match self.lookup(&fixup.label) {
+                Some(&Symbol { address, segment }) => retarget(
+                    &mut self.text_lines,
+                    fixup.index,
+                    address,
+                    segment,
+                    &fixup.label,
+                ),
+                None => Err(AssemblerError::UndefinedLabel(fixup.label.clone())),
+            };
+            if let Err(err) = result {
+                self.failed = true;
+                self.diagnostics.push(Diagnostic {
+                    message: err.to_string(),
+                    ..fixup.site
+                });
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Assembles the main source or an included file.
+    fn assemble_included(
+        &mut self,
+        source: &str,
+        args: &RuntimeArgs,
+    ) -> Result<(), AssemblerError> {
+        let mut line_tokens = Vec::new();
+
+        for line in lines(source) {
+            self.error_span = None;
+            let result = match tokenize_line(&line, &mut line_tokens) {
+                Ok(()) => self.assemble_line(source, &line, &line_tokens, args),
+                Err(err) => {
+                    self.error_span = rejected_word(&line);
+                    Err(err.into())
+                }
+            };
+
+            if let Err(err) = result {
+                if !self.failed {
+                    self.failed = true;
+                    let span = self.error_span.take();
+                    let diagnostic =
+                        self.diagnose(Severity::Error, err.to_string(), source, &line, span);
+                    self.diagnostics.push(diagnostic);
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn assemble_line(
+        &mut self,
+        source: &str,
+        line: &Line,
+        line_tokens: &[Token],
+        args: &RuntimeArgs,
+    ) -> Result<(), AssemblerError> {
+        if args.tokens {
+            let kinds: Vec<_> = line_tokens.iter().map(|token| &token.kind).collect();
+            println!("{:?}", kinds);
+        }
+
+        let mut body = line_tokens;
+
+        let number = self.include_site.map_or(line.number, |(number, ..)| number);
+        // Align before declaring a label on the same line, so it names the
+        // aligned data.
+        if let Some(TokenKind::Directive { kind }) = body
+            .iter()
+            .map(|token| &token.kind)
+            .find(|kind| !matches!(kind, TokenKind::Label { decl: true, .. }))
+        {
+            let padding = self.data_len().next_multiple_of(alignment(*kind)) - self.data_len();
+            self.write_data(&vec![0; padding])?;
+        }
+        if let Some(token) = body.first()
+            && let TokenKind::Label { name, decl: true } = token.kind
+        {
+            let addr = match self.current_segment {
+                Segment::Data => self.data_addr,
+                Segment::Text => self.text_addr,
+            };
+            let previous = self.symbols.insert(
+                self.interner.intern(name),
+                Symbol {
+                    address: addr,
+                    segment: self.current_segment,
+                },
+            );
+            if previous.is_some() {
+                let warning = self.diagnose(
+                    Severity::Warning,
+                    format!("Label '{}' is defined again; this definition wins", name),
+                    source,
+                    line,
+                    Some(token.span),
+                );
+                self.diagnostics.push(warning);
+            }
+            self.debug_info
+                .push_symbol(name, addr, self.current_segment, number);
+            body = &body[1..];
+        }
+
+        let mut tokens = body.iter().peekable();
+        match tokens.next().map(|token| &token.kind) {
+            Some(TokenKind::Directive {
+                kind: Directive::Include,
+            }) => {
+                let Some(TokenKind::Text { value }) = tokens.next().map(|t| &t.kind) else {
+                    return Err(AssemblerError::InvalidToken);
+                };
+                self.include(value, source, line, args)?;
+            }
+            Some(TokenKind::Directive { kind }) => {
+                self.handle_directive(kind, &mut tokens)?;
+                if *kind == Directive::Global
+                    && let Some(label) = body.get(1)
+                {
+                    let warning = self.diagnose(
+                        Severity::Warning,
+                        "The entry point label is never defined; execution starts at the top of .text".to_string(),
+                        source,
+                        line,
+                        Some(label.span),
+                    );
+                    self.entry_warning = Some(warning);
+                }
+            }
+            Some(TokenKind::Operator { value }) => {
+                if !args.isa.allows(value) {
+                    self.error_span = body.first().map(|token| token.span);
+                    return Err(AssemblerError::Forbidden(value.to_string(), number));
+                }
+                if !args.mips64 && MIPS64_MNEMONICS.contains(value) {
+                    self.error_span = body.first().map(|token| token.span);
+                    return Err(AssemblerError::Mips64Only(value.to_string()));
+                }
+                let mut expanded = self.expand_instruction(body)?;
+                for (index, label, span) in std::mem::take(&mut self.forward) {
+                    let site = self.diagnose(Severity::Error, String::new(), source, line, span);
+                    self.fixups.push(Fixup { index, label, site });
+                }
+                // `move` has to copy the whole register in 64-bit code.
+                if args.mips64
+                    && *value == "move"
+                    && let [Instruction::AddUnsigned { res, reg, ret }] = expanded[..]
+                {
+                    expanded = vec![Instruction::DoubleAddUnsigned { res, reg, ret }];
+                }
+                if args.instructions {
+                    let start = self.text_lines.len();
+                    self.listing.push(start..start + expanded.len());
+                }
+                self.text_lines.extend(&expanded);
+                for _ in &expanded {
+                    self.debug_info.push(number);
+                }
+                self.text_addr += expanded.len() * 4;
+            }
+            Some(_) => {
+                self.error_span = body.first().map(|token| token.span);
+                return Err(AssemblerError::InvalidToken);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub fn expand_instruction(
+        &mut self,
+        tokens: &[Token],
+    ) -> Result<Vec<Instruction>, AssemblerError> {
+        let mut iter = tokens.iter().peekable();
+        if let Some(TokenKind::Operator { value }) = iter.next().map(|token| &token.kind) {
+            match *value {
+                "syscall" => return Ok(vec![Instruction::SystemCall]),
+                "tlbr" => return Ok(vec![Instruction::TlbRead]),
+                "tlbwi" => return Ok(vec![Instruction::TlbWriteIndexed]),
+                "tlbwr" => return Ok(vec![Instruction::TlbWriteRandom]),
+                "tlbp" => return Ok(vec![Instruction::TlbProbe]),
+                "eret" => return Ok(vec![Instruction::ExceptionReturn]),
+                "dadd" | "daddu" | "dsub" | "dsubu" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "dadd" => Instruction::DoubleAdd { res, reg, ret },
+                        "daddu" => Instruction::DoubleAddUnsigned { res, reg, ret },
+                        "dsub" => Instruction::DoubleSubtract { res, reg, ret },
+                        _ => Instruction::DoubleSubtractUnsigned { res, reg, ret },
+                    }]);
+                }
+                "daddi" | "daddiu" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let imm = self.parse_immediate(&mut iter)?;
+                    if !(-32768..=32767).contains(&imm) {
+                        return Err(AssemblerError::InvalidInstruction);
+                    }
+                    return Ok(vec![match *value {
+                        "daddi" => Instruction::DoubleAddImmediate { res, reg, imm },
+                        _ => Instruction::DoubleAddImmediateUnsigned { res, reg, imm },
+                    }]);
+                }
+                "sll" | "srl" | "sra" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let amount = self.parse_immediate(&mut iter)?;
+                    if !(0..32).contains(&amount) {
+                        return Err(AssemblerError::InvalidOperands(
+                            value.to_string(),
+                            format!("shift amount {} is not between 0 and 31", amount),
+                        ));
                     }
+                    let shamt = amount as u32;
+                    return Ok(vec![match *value {
+                        "sll" => Instruction::ShiftLeftLogical { res, reg, shamt },
+                        "srl" => Instruction::ShiftRightLogical { res, reg, shamt },
+                        _ => Instruction::ShiftRightArithmetic { res, reg, shamt },
+                    }]);
+                }
+                "mult" | "multu" | "div" | "divu" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "mult" => Instruction::Multiply { reg, ret },
+                        "multu" => Instruction::MultiplyUnsigned { reg, ret },
+                        "div" => Instruction::Divide { reg, ret },
+                        _ => Instruction::DivideUnsigned { reg, ret },
+                    }]);
+                }
+                "mfhi" | "mflo" => {
+                    let res = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "mfhi" => Instruction::MoveFromHi { res },
+                        _ => Instruction::MoveFromLo { res },
+                    }]);
+                }
+                "mthi" | "mtlo" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "mthi" => Instruction::MoveToHi { reg },
+                        _ => Instruction::MoveToLo { reg },
+                    }]);
+                }
+                "sllv" | "srlv" | "srav" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "sllv" => Instruction::ShiftLeftLogicalVariable { res, reg, ret },
+                        "srlv" => Instruction::ShiftRightLogicalVariable { res, reg, ret },
+                        _ => Instruction::ShiftRightArithmeticVariable { res, reg, ret },
+                    }]);
+                }
+                "dsll" | "dsrl" | "dsra" | "dsll32" | "dsrl32" | "dsra32" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let amount = self.parse_immediate(&mut iter)?;
+                    // The plain forms take any amount below 64, choosing the
+                    // `32` encoding themselves.
+                    let (base, limit) = match value.strip_suffix("32") {
+                        Some(base) => (base, 32),
+                        None => (*value, 64),
+                    };
+                    if !(0..limit).contains(&amount) {
+                        return Err(AssemblerError::InvalidInstruction);
+                    }
+                    let shamt = (amount + 64 - limit) as u32;
+                    return Ok(vec![match base {
+                        "dsll" => Instruction::DoubleShiftLeftLogical { res, reg, shamt },
+                        "dsrl" => Instruction::DoubleShiftRightLogical { res, reg, shamt },
+                        _ => Instruction::DoubleShiftRightArithmetic { res, reg, shamt },
+                    }]);
+                }
+                "beq" | "bne" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    let ret = self.parse_register(&mut iter)?;
+                    let offset = self.parse_branch_target(&mut iter, 0)?;
+                    return Ok(vec![match *value {
+                        "beq" => Instruction::BranchEqual { reg, ret, offset },
+                        _ => Instruction::BranchNotEqual { reg, ret, offset },
+                    }]);
+                }
+                "bltz" | "bgez" | "blez" | "bgtz" | "bltzal" | "bgezal" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    let offset = self.parse_branch_target(&mut iter, 0)?;
+                    return Ok(vec![match *value {
+                        "bltz" => Instruction::BranchLessThanZero { reg, offset },
+                        "bgez" => Instruction::BranchGreaterEqualZero { reg, offset },
+                        "blez" => Instruction::BranchLessEqualZero { reg, offset },
+                        "bgtz" => Instruction::BranchGreaterThanZero { reg, offset },
+                        "bltzal" => Instruction::BranchLessThanZeroAndLink { reg, offset },
+                        _ => Instruction::BranchGreaterEqualZeroAndLink { reg, offset },
+                    }]);
+                }
+                "teq" | "tne" | "tge" | "tgeu" | "tlt" | "tltu" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "teq" => Instruction::TrapEqual { reg, ret },
+                        "tne" => Instruction::TrapNotEqual { reg, ret },
+                        "tge" => Instruction::TrapGreaterEqual { reg, ret },
+                        "tgeu" => Instruction::TrapGreaterEqualUnsigned { reg, ret },
+                        "tlt" => Instruction::TrapLessThan { reg, ret },
+                        _ => Instruction::TrapLessThanUnsigned { reg, ret },
+                    }]);
+                }
+                "teqi" | "tnei" | "tgei" | "tgeiu" | "tlti" | "tltiu" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    let imm = self.parse_immediate(&mut iter)?;
+                    if !(-32768..=32767).contains(&imm) {
+                        return Err(AssemblerError::InvalidOperands(
+                            value.to_string(),
+                            format!("{} does not fit in 16 signed bits", imm),
+                        ));
+                    }
+                    return Ok(vec![match *value {
+                        "teqi" => Instruction::TrapEqualImmediate { reg, imm },
+                        "tnei" => Instruction::TrapNotEqualImmediate { reg, imm },
+                        "tgei" => Instruction::TrapGreaterEqualImmediate { reg, imm },
+                        "tgeiu" => Instruction::TrapGreaterEqualImmediateUnsigned { reg, imm },
+                        "tlti" => Instruction::TrapLessThanImmediate { reg, imm },
+                        _ => Instruction::TrapLessThanImmediateUnsigned { reg, imm },
+                    }]);
+                }
+                "j" | "jal" => {
+                    let target = self.parse_jump_target(&mut iter, 0)?;
+                    return Ok(vec![match *value {
+                        "j" => Instruction::Jump { target },
+                        _ => Instruction::JumpAndLink { target },
+                    }]);
+                }
+                "jr" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    return Ok(vec![Instruction::JumpRegister { reg }]);
+                }
+                // `jalr $rs` links in `$ra`; `jalr $rd, $rs` names the register.
+                "jalr" => {
+                    let first = self.parse_register(&mut iter)?;
+                    let (res, reg) = match iter.peek() {
+                        Some(_) => (first, self.parse_register(&mut iter)?),
+                        None => (Register::RA, first),
+                    };
+                    return Ok(vec![Instruction::JumpAndLinkRegister { res, reg }]);
+                }
+                "lb" | "lbu" | "ld" | "lh" | "lhu" | "lw" | "lwl" | "lwr" | "sb" | "sd" | "sh"
+                | "sw" | "swl" | "swr" => {
+                    let target = self.parse_register(&mut iter)?;
+                    let (reg, imm, mut expanded) = self.parse_address(&mut iter)?;
+                    expanded.push(match *value {
+                        "ld" => Instruction::LoadDouble {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "sd" => Instruction::StoreDouble {
+                            ret: target,
+                            reg,
+                            imm,
+                        },
+                        "lb" => Instruction::LoadByte {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "lbu" => Instruction::LoadByteUnsigned {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "lh" => Instruction::LoadHalf {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "lhu" => Instruction::LoadHalfUnsigned {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "lw" => Instruction::LoadWord {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "lwl" => Instruction::LoadWordLeft {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "lwr" => Instruction::LoadWordRight {
+                            res: target,
+                            reg,
+                            imm,
+                        },
+                        "swl" => Instruction::StoreWordLeft {
+                            ret: target,
+                            reg,
+                            imm,
+                        },
+                        "swr" => Instruction::StoreWordRight {
+                            ret: target,
+                            reg,
+                            imm,
+                        },
+                        "sb" => Instruction::StoreByte {
+                            ret: target,
+                            reg,
+                            imm,
+                        },
+                        "sh" => Instruction::StoreHalf {
+                            ret: target,
+                            reg,
+                            imm,
+                        },
+                        _ => Instruction::StoreWord {
+                            ret: target,
+                            reg,
+                            imm,
+                        },
+                    });
+                    return Ok(expanded);
+                }
+                "mfc0" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let cp0 = self.parse_register(&mut iter)? as u32;
+                    return Ok(vec![Instruction::MoveFromCoprocessor0 { res, cp0 }]);
+                }
+                "mtc0" => {
+                    let ret = self.parse_register(&mut iter)?;
+                    let cp0 = self.parse_register(&mut iter)? as u32;
+                    return Ok(vec![Instruction::MoveToCoprocessor0 { ret, cp0 }]);
                 }
-                None => continue,
-                _ => return Err(AssemblerError::InvalidToken),
-            }
-        }
-
-        Ok(())
-    }
-
-    pub fn expand_instruction(
-        &mut self,
-        tokens: Vec<Token>,
-    ) -> Result<Vec<Instruction>, AssemblerError> {
-        let mut iter = tokens.iter().peekable();
-        if let Some(Token::Operator { value }) = iter.next() {
-            let value_str = value.as_str();
-            match value_str {
-                "syscall" => return Ok(vec![Instruction::SystemCall]),
                 "addi" => {
                     let res = self.parse_register(&mut iter)?;
                     let reg = self.parse_register(&mut iter)?;
                     let imm = self.parse_immediate(&mut iter)?;
                     return Ok(vec![Instruction::AddImmediate { res, reg, imm }]);
                 }
-                "addu" => {
+                "addiu" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let imm = self.parse_immediate(&mut iter)?;
+                    return Ok(vec![Instruction::AddImmediateUnsigned { res, reg, imm }]);
+                }
+                "add" | "addu" | "sub" | "subu" => {
                     let res = self.parse_register(&mut iter)?;
                     let reg = self.parse_register(&mut iter)?;
                     let ret = self.parse_register(&mut iter)?;
-                    return Ok(vec![Instruction::AddUnsigned { res, reg, ret }]);
+                    return Ok(vec![match *value {
+                        "add" => Instruction::Add { res, reg, ret },
+                        "addu" => Instruction::AddUnsigned { res, reg, ret },
+                        "sub" => Instruction::Subtract { res, reg, ret },
+                        _ => Instruction::SubtractUnsigned { res, reg, ret },
+                    }]);
                 }
                 "lui" => {
                     let res = self.parse_register(&mut iter)?;
                     let imm = self.parse_immediate(&mut iter)?;
                     return Ok(vec![Instruction::LoadUpperImmediate { res, imm }]);
                 }
-                "ori" => {
+                "andi" | "ori" | "xori" => {
                     let res = self.parse_register(&mut iter)?;
                     let reg = self.parse_register(&mut iter)?;
                     let imm = self.parse_immediate(&mut iter)?;
-                    return Ok(vec![Instruction::OrImmediate { res, reg, imm }]);
+                    // The immediate is zero-extended, so a negative one
+                    // would not mean what it says.
+                    if !(0..=0xffff).contains(&imm) {
+                        return Err(AssemblerError::InvalidOperands(
+                            value.to_string(),
+                            format!("{} does not fit in 16 unsigned bits", imm),
+                        ));
+                    }
+                    return Ok(vec![match *value {
+                        "andi" => Instruction::AndImmediate { res, reg, imm },
+                        "ori" => Instruction::OrImmediate { res, reg, imm },
+                        _ => Instruction::XorImmediate { res, reg, imm },
+                    }]);
                 }
-                "move" => {
+                "slt" | "sltu" => {
                     let res = self.parse_register(&mut iter)?;
                     let reg = self.parse_register(&mut iter)?;
-                    return Ok(vec![Instruction::AddUnsigned {
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "slt" => Instruction::SetLessThan { res, reg, ret },
+                        _ => Instruction::SetLessThanUnsigned { res, reg, ret },
+                    }]);
+                }
+                // Compared through `$at` the way MARS expands them: `blt`
+                // and `bge` test `rs < rt`, `bgt` and `ble` test `rt < rs`.
+                "blt" | "bltu" | "bge" | "bgeu" | "bgt" | "bgtu" | "ble" | "bleu" => {
+                    let reg = self.parse_register(&mut iter)?;
+                    let (ret, mut expanded) = self.parse_register_or_constant(&mut iter)?;
+                    let (base, unsigned) = match value.strip_suffix('u') {
+                        Some(base) => (base, true),
+                        None => (*value, false),
+                    };
+                    let (reg, ret) = match base {
+                        "blt" | "bge" => (reg, ret),
+                        _ => (ret, reg),
+                    };
+                    expanded.push(match unsigned {
+                        false => Instruction::SetLessThan {
+                            res: Register::AT,
+                            reg,
+                            ret,
+                        },
+                        true => Instruction::SetLessThanUnsigned {
+                            res: Register::AT,
+                            reg,
+                            ret,
+                        },
+                    });
+                    let offset = self.parse_branch_target(&mut iter, expanded.len())?;
+                    expanded.push(match base {
+                        "blt" | "bgt" => Instruction::BranchNotEqual {
+                            reg: Register::AT,
+                            ret: Register::ZERO,
+                            offset,
+                        },
+                        _ => Instruction::BranchEqual {
+                            reg: Register::AT,
+                            ret: Register::ZERO,
+                            offset,
+                        },
+                    });
+                    return Ok(expanded);
+                }
+                "nop" => {
+                    return Ok(vec![Instruction::ShiftLeftLogical {
+                        res: Register::ZERO,
+                        reg: Register::ZERO,
+                        shamt: 0,
+                    }]);
+                }
+                "not" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    return Ok(vec![Instruction::Nor {
                         res,
                         reg,
                         ret: Register::ZERO,
                     }]);
                 }
-                "li" => {
+                "neg" => {
                     let res = self.parse_register(&mut iter)?;
-                    let imm = self.parse_immediate(&mut iter)?;
-
-                    if imm >= -32768 && imm <= 32767 {
-                        return Ok(vec![Instruction::AddImmediate {
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![Instruction::Subtract {
+                        res,
+                        reg: Register::ZERO,
+                        ret,
+                    }]);
+                }
+                // `$at` holds all ones for a negative value and zero
+                // otherwise, so the xor and subtract negate only those.
+                "abs" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    return Ok(vec![
+                        Instruction::ShiftRightArithmetic {
+                            res: Register::AT,
+                            reg,
+                            shamt: 31,
+                        },
+                        Instruction::Xor {
                             res,
-                            reg: Register::ZERO,
-                            imm,
-                        }]);
-                    } else if (imm & 0xFFFF) == 0 {
-                        return Ok(vec![Instruction::LoadUpperImmediate {
+                            reg: Register::AT,
+                            ret: reg,
+                        },
+                        Instruction::SubtractUnsigned {
                             res,
-                            imm: (imm >> 16),
-                        }]);
-                    } else {
-                        let high = (imm >> 16) + if (imm & 0x8000) != 0 { 1 } else { 0 };
-                        let low = imm & 0xFFFF;
-                        return Ok(vec![
-                            Instruction::LoadUpperImmediate { res, imm: high },
-                            Instruction::AddImmediate {
+                            reg: res,
+                            ret: Register::AT,
+                        },
+                    ]);
+                }
+                "mul" | "rem" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let (ret, mut expanded) = self.parse_register_or_constant(&mut iter)?;
+                    expanded.extend(match *value {
+                        "mul" => [
+                            Instruction::Multiply { reg, ret },
+                            Instruction::MoveFromLo { res },
+                        ],
+                        _ => [
+                            Instruction::Divide { reg, ret },
+                            Instruction::MoveFromHi { res },
+                        ],
+                    });
+                    return Ok(expanded);
+                }
+                // Expanded as MARS does; the ones that negate a comparison
+                // subtract it from 1, kept in `$at`.
+                "seq" | "sne" | "sge" | "sgeu" | "sgt" | "sgtu" | "sle" | "sleu" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let (ret, mut expanded) = self.parse_register_or_constant(&mut iter)?;
+                    let one = Instruction::OrImmediate {
+                        res: Register::AT,
+                        reg: Register::ZERO,
+                        imm: 1,
+                    };
+                    let not = Instruction::SubtractUnsigned {
+                        res,
+                        reg: Register::AT,
+                        ret: res,
+                    };
+                    let less = |res, reg, ret| match value.ends_with('u') {
+                        true => Instruction::SetLessThanUnsigned { res, reg, ret },
+                        false => Instruction::SetLessThan { res, reg, ret },
+                    };
+                    let difference = Instruction::SubtractUnsigned { res, reg, ret };
+                    expanded.extend(match *value {
+                        "seq" => vec![
+                            difference,
+                            one,
+                            Instruction::SetLessThanUnsigned {
                                 res,
                                 reg: res,
-                                imm: low,
+                                ret: Register::AT,
                             },
-                        ]);
-                    }
+                        ],
+                        "sne" => vec![
+                            difference,
+                            Instruction::SetLessThanUnsigned {
+                                res,
+                                reg: Register::ZERO,
+                                ret: res,
+                            },
+                        ],
+                        "sge" | "sgeu" => vec![less(res, reg, ret), one, not],
+                        "sgt" | "sgtu" => vec![less(res, ret, reg)],
+                        _ => vec![less(res, ret, reg), one, not],
+                    });
+                    return Ok(expanded);
+                }
+                "and" | "nor" | "or" | "xor" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    let ret = self.parse_register(&mut iter)?;
+                    return Ok(vec![match *value {
+                        "and" => Instruction::And { res, reg, ret },
+                        "nor" => Instruction::Nor { res, reg, ret },
+                        "or" => Instruction::Or { res, reg, ret },
+                        _ => Instruction::Xor { res, reg, ret },
+                    }]);
+                }
+                "move" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let reg = self.parse_register(&mut iter)?;
+                    return Ok(vec![Instruction::AddUnsigned {
+                        res,
+                        reg,
+                        ret: Register::ZERO,
+                    }]);
+                }
+                "li" => {
+                    let res = self.parse_register(&mut iter)?;
+                    let imm = self.parse_immediate(&mut iter)?;
+                    return Ok(load_constant(res, imm));
                 }
                 "la" => {
                     let res = self.parse_register(&mut iter)?;
                     let label = self.parse_label(&mut iter)?;
-                    let symbol = self
-                        .symbols
-                        .get(&label)
-                        .ok_or(AssemblerError::InvalidLabel(label.clone()))?;
-
-                    if symbol.segment != Segment::Data {
-                        return Err(AssemblerError::InvalidLabel(label.clone()));
-                    }
-
-                    let high = (*symbol).address >> 16;
-                    let low = (*symbol).address & 0xffff.into();
+                    let (high, low) = match self.lookup(label) {
+                        Some(symbol) if symbol.segment == Segment::Data => {
+                            (symbol.address >> 16, symbol.address & 0xffff.into())
+                        }
+                        Some(_) => return Err(AssemblerError::InvalidLabel(label.to_string())),
+                        // Patched by `resolve_fixups` once the label is defined.
+                        None => {
+                            self.defer(label, 0);
+                            (Address(0), Address(0))
+                        }
+                    };
 
                     return Ok(vec![
                         Instruction::LoadUpperImmediate {
@@ -233,13 +3321,31 @@ impl Assembler {
                         },
                     ]);
                 }
-                _ => {}
+                _ => {
+                    if let Some((plugin, custom)) = plugin::lookup(value) {
+                        let operands: Vec<TokenKind> =
+                            iter.map(|token| token.kind.clone()).collect();
+                        let operands = custom.parse(&operands).map_err(|err| {
+                            AssemblerError::InvalidOperands(value.to_string(), err)
+                        })?;
+                        return Ok(vec![Instruction::Custom { plugin, operands }]);
+                    }
+                    self.error_span = tokens.first().map(|token| token.span);
+                    return Err(AssemblerError::UnknownInstruction(
+                        value.to_string(),
+                        suggest::closest(value, MNEMONICS.iter().copied()),
+                    ));
+                }
             }
         }
         Err(AssemblerError::InvalidInstruction)
     }
 
-    pub fn get_entry_point(&self) -> Address {
+    fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(&self.interner.get(name)?)
+    }
+
+    fn get_entry_point(&self) -> Address {
         match &self.entry_point {
             Some(entry) => match self.symbols.get(entry) {
                 Some(symbol) => symbol.address,
@@ -249,118 +3355,550 @@ impl Assembler {
         }
     }
 
-    pub fn take_memory(&self) -> Vec<u8> {
-        self.memory.clone()
-    }
-
-    pub fn get_instructions(&self) -> HashMap<Address, Instruction> {
-        self.text_lines
-            .clone()
-            .into_iter()
-            .enumerate()
-            .map(|(i, inst)| {
-                let addr = BASE_TEXT_ADDR + i * 4;
-                (addr, inst)
-            })
-            .collect()
+    /// Consumes the assembler, moving the assembled segments into a [`Program`].
+    pub fn finish(mut self) -> Program {
+        let entry = self.get_entry_point();
+        self.debug_info.finish_data(self.data_addr);
+        Program {
+            instructions: self.text_lines,
+            memory: self.memory,
+            entry,
+            debug_info: self.debug_info,
+            endianness: self.endianness,
+        }
     }
 
     fn handle_directive(
         &mut self,
         kind: &Directive,
-        tokens: &mut Peekable<Iter<Token>>,
+        tokens: &mut TokenIter,
     ) -> Result<(), AssemblerError> {
         match kind {
-            Directive::DataDirective => {
+            Directive::Data => {
                 self.current_segment = Segment::Data;
                 Ok(())
             }
-            Directive::TextDirective => {
+            Directive::Text => {
                 self.current_segment = Segment::Text;
                 Ok(())
             }
-            Directive::GlobalDirective => {
-                if let Some(Token::Label { name, decl: false }) = tokens.next() {
-                    self.entry_point = Some(name.clone());
+            Directive::Global => {
+                if let Some(TokenKind::Label { name, decl: false }) = tokens.next().map(|t| &t.kind)
+                {
+                    self.entry_point = Some(self.interner.intern(name));
                     Ok(())
                 } else {
                     Err(AssemblerError::EntrypointMissing)
                 }
             }
-            Directive::AsciizDirective => {
-                if let Some(Token::Text { value }) = tokens.next() {
-                    let bytes = CString::from_str(&value)
-                        .map_err(|_| AssemblerError::InvalidString)?
-                        .into_bytes_with_nul();
-                    let start_offset = self.data_addr - BASE_DATA_ADDR;
-                    let end_offset = start_offset + bytes.len();
-                    self.memory
-                        .resize(std::cmp::max(self.memory.len(), end_offset), 0);
-                    self.memory[start_offset..end_offset].copy_from_slice(&bytes);
-                    self.data_addr += bytes.len();
-                    Ok(())
-                } else {
-                    Err(AssemblerError::InvalidToken)
-                }
-            }
-            Directive::AsciiDirective => {
-                if let Some(Token::Text { value }) = tokens.next() {
-                    let bytes = CString::from_str(&value)
-                        .map_err(|_| AssemblerError::InvalidString)?
-                        .into_bytes();
-                    let start_offset = self.data_addr - BASE_DATA_ADDR;
-                    let end_offset = start_offset + bytes.len();
-                    self.memory
-                        .resize(std::cmp::max(self.memory.len(), end_offset), 0);
-                    self.memory[start_offset..end_offset].copy_from_slice(&bytes);
-                    self.data_addr += bytes.len();
-                    Ok(())
-                } else {
-                    Err(AssemblerError::InvalidToken)
+            Directive::Asciiz | Directive::Ascii => {
+                let Some(TokenKind::Text { value }) = tokens.next().map(|t| &t.kind) else {
+                    return Err(AssemblerError::InvalidToken);
+                };
+                if value.contains('\0') {
+                    return Err(AssemblerError::InvalidString);
+                }
+                self.debug_info
+                    .set_data_kind(self.data_addr, DataKind::String);
+
+                self.write_data(value.as_bytes())?;
+                if *kind == Directive::Asciiz {
+                    self.write_data(&[0])?;
+                }
+                Ok(())
+            }
+            Directive::Incbin => {
+                let Some(TokenKind::Text { value }) = tokens.next().map(|t| &t.kind) else {
+                    return Err(AssemblerError::InvalidToken);
+                };
+                let path = normalize(&self.include_dir.join(value.as_ref()));
+                if !self.may_read(&path) {
+                    return Err(AssemblerError::FileAccessDenied(value.to_string()));
                 }
+                let bytes = fs::read(&path)
+                    .map_err(|_| AssemblerError::IncbinNotFound(value.to_string()))?;
+                self.debug_info
+                    .set_data_kind(self.data_addr, DataKind::Bytes);
+                self.write_data(&bytes)?;
+                Ok(())
             }
-            Directive::ByteDirective => {
-                while let Some(Token::Number { value }) = tokens.next() {
-                    if *value < -128 || *value > 255 {
+            Directive::Byte => {
+                self.debug_info
+                    .set_data_kind(self.data_addr, DataKind::Bytes);
+                for token in tokens.by_ref() {
+                    let Some(value) = self.constant(&token.kind)? else {
+                        break;
+                    };
+                    if !(-128..=255).contains(&value) {
                         return Err(AssemblerError::InvalidByteValue);
                     }
-
-                    let byte_val = *value as u8;
-                    let offset: usize = (self.data_addr - BASE_DATA_ADDR).into();
-
-                    if offset >= self.memory.len() {
-                        self.memory.resize(offset + 1, 0);
+                    self.write_data(&[value as u8])?;
+                }
+                Ok(())
+            }
+            Directive::Word | Directive::Dword => {
+                self.debug_info
+                    .set_data_kind(self.data_addr, DataKind::Words);
+                for token in tokens.by_ref() {
+                    // A label defined above stands for its address.
+                    let value = match &token.kind {
+                        TokenKind::Label { name, decl: false } => match self.lookup(name) {
+                            Some(symbol) => Some(i64::from(symbol.address.0)),
+                            None => self.constant(&token.kind)?,
+                        },
+                        kind => self.constant(kind)?,
+                    };
+                    let Some(value) = value else {
+                        break;
+                    };
+                    match kind {
+                        Directive::Word => {
+                            self.write_data(&self.endianness.arrange((value as u32).to_le_bytes()))?
+                        }
+                        _ => self.write_data(&self.endianness.arrange(value.to_le_bytes()))?,
                     }
-
-                    self.memory[offset] = byte_val;
-                    self.data_addr += 1;
                 }
                 Ok(())
             }
+            Directive::Float | Directive::Double => {
+                self.debug_info
+                    .set_data_kind(self.data_addr, DataKind::Words);
+                for token in tokens.by_ref() {
+                    let text = match &token.kind {
+                        TokenKind::Number { value } => value.to_string(),
+                        TokenKind::Label { name, decl: false } => name.to_string(),
+                        TokenKind::Expression { text } => text.to_string(),
+                        _ => break,
+                    };
+                    let value: f64 = text
+                        .parse()
+                        .map_err(|_| AssemblerError::InvalidFloat(text.clone()))?;
+                    match kind {
+                        Directive::Float => {
+                            self.write_data(&self.endianness.arrange((value as f32).to_le_bytes()))?
+                        }
+                        _ => self.write_data(&self.endianness.arrange(value.to_le_bytes()))?,
+                    }
+                }
+                Ok(())
+            }
+            Directive::Eqv => {
+                let Some(TokenKind::Label { name, decl: false }) = tokens.next().map(|t| &t.kind)
+                else {
+                    return Err(AssemblerError::InvalidToken);
+                };
+                let value = match tokens.next() {
+                    Some(token) => self.constant(&token.kind)?,
+                    None => None,
+                };
+                let value = value.ok_or(AssemblerError::InvalidToken)?;
+                self.constants.insert(self.interner.intern(name), value);
+                Ok(())
+            }
+            Directive::Space => {
+                let value = match tokens.next() {
+                    Some(token) => self.constant(&token.kind)?,
+                    None => None,
+                };
+                let size = value.ok_or(AssemblerError::InvalidToken)?;
+                let room = i64::from(HEAP_BASE.0) - i64::from(self.data_addr.0);
+                if !(0..=room).contains(&size) {
+                    return Err(AssemblerError::InvalidSpace(size));
+                }
+                self.write_data(&vec![0; size as usize])?;
+                Ok(())
+            }
             _ => Err(AssemblerError::UnknownDirective),
         }
     }
 
-    fn parse_register(&self, iter: &mut Peekable<Iter<Token>>) -> Result<Register, AssemblerError> {
-        match iter.next() {
-            Some(Token::Register { value }) => value
-                .parse::<Register>()
-                .map_err(|e| AssemblerError::InvalidRegister(e)),
+    /// Bytes of data laid out so far. The data address only ever grows
+    /// from [`BASE_DATA_ADDR`].
+    fn data_len(&self) -> usize {
+        self.data_addr.offset_from(BASE_DATA_ADDR).unwrap_or(0) as usize
+    }
+
+    /// Appends bytes at the current data address, growing memory if needed.
+    /// Fails rather than letting the data segment run into the heap.
+    fn write_data(&mut self, bytes: &[u8]) -> Result<(), AssemblerError> {
+        let start_offset = self.data_len();
+        let end_offset = start_offset + bytes.len();
+        if end_offset > HEAP_BASE.offset_from(BASE_DATA_ADDR).unwrap_or(0) as usize {
+            return Err(AssemblerError::DataSegmentFull(bytes.len()));
+        }
+        if end_offset > self.memory.len() {
+            self.memory.resize(end_offset, 0);
+        }
+        self.memory[start_offset..end_offset].copy_from_slice(bytes);
+        self.data_addr += bytes.len();
+        Ok(())
+    }
+
+    /// Takes the next operand, remembering where it is in case it turns
+    /// out to be the cause of an error.
+    fn operand<'t, 'a>(&mut self, iter: &mut TokenIter<'t, 'a>) -> Option<&'t TokenKind<'a>> {
+        let token = iter.next();
+        self.error_span = token.map(|token| token.span);
+        token.map(|token| &token.kind)
+    }
+
+    fn parse_register(&mut self, iter: &mut TokenIter) -> Result<Register, AssemblerError> {
+        match self.operand(iter) {
+            Some(TokenKind::Register { value }) => Ok(*value),
             _ => Err(AssemblerError::InvalidInstruction),
         }
     }
 
-    fn parse_immediate(&self, iter: &mut Peekable<Iter<Token>>) -> Result<i32, AssemblerError> {
-        match iter.next() {
-            Some(Token::Number { value }) => Ok(*value),
+    fn parse_immediate(&mut self, iter: &mut TokenIter) -> Result<i32, AssemblerError> {
+        match self.operand(iter) {
+            Some(kind) => match self.constant(kind)? {
+                // Values up to u32::MAX keep their bits.
+                Some(value) => Ok(value as i32),
+                None => Err(AssemblerError::InvalidInstruction),
+            },
+            None => Err(AssemblerError::InvalidInstruction),
+        }
+    }
+
+    /// The value of a number, a `.eqv` constant or a constant expression
+    /// over them, or None if the token is none of those. Values must fit in
+    /// 32 bits, signed or unsigned.
+    fn constant(&self, kind: &TokenKind) -> Result<Option<i64>, AssemblerError> {
+        let text = match kind {
+            TokenKind::Number { value } => return Ok(Some(i64::from(*value))),
+            TokenKind::Label { name, decl: false } => *name,
+            TokenKind::Expression { text } => *text,
+            _ => return Ok(None),
+        };
+        let invalid = |err| AssemblerError::InvalidExpression(text.to_string(), err);
+        let expression: Expression = text.parse().map_err(invalid)?;
+        let value = expression
+            .evaluate_constant(&|name| {
+                let id = self.interner.get(name)?;
+                self.constants.get(&id).copied()
+            })
+            .map_err(|err| match err {
+                ExpressionError::UndefinedConstant(name) => AssemblerError::UndefinedConstant(name),
+                err => invalid(err),
+            })?;
+        if !(i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(&value) {
+            return Err(AssemblerError::ConstantOverflow(text.to_string(), value));
+        }
+        Ok(Some(value))
+    }
+
+    /// Reads an operand that may be a register or a constant, as the
+    /// register to use and the instructions that must come first to load a
+    /// constant into `$at`.
+    fn parse_register_or_constant(
+        &mut self,
+        iter: &mut TokenIter,
+    ) -> Result<(Register, Vec<Instruction>), AssemblerError> {
+        match iter.peek().map(|token| &token.kind) {
+            Some(TokenKind::Register { .. }) => Ok((self.parse_register(iter)?, Vec::new())),
+            _ => {
+                let imm = self.parse_immediate(iter)?;
+                Ok((Register::AT, load_constant(Register::AT, imm)))
+            }
+        }
+    }
+
+    /// Reads a memory operand as the base register and offset to use, and
+    /// the instructions that must come first to set up the base. Besides
+    /// `offset($base)`, a data label can be named directly, which loads its
+    /// upper half into `$at`.
+    fn parse_address(
+        &mut self,
+        iter: &mut TokenIter,
+    ) -> Result<(Register, i32, Vec<Instruction>), AssemblerError> {
+        match self.operand(iter) {
+            Some(TokenKind::Address { offset, base }) => {
+                let offset = match *offset {
+                    "" => 0,
+                    text => self
+                        .constant(&classify(text, false)?)?
+                        .ok_or(AssemblerError::InvalidInstruction)?,
+                };
+                if !(-32768..=32767).contains(&offset) {
+                    return Err(AssemblerError::InvalidInstruction);
+                }
+                Ok((*base, offset as i32, Vec::new()))
+            }
+            Some(TokenKind::Label { name, decl: false }) => {
+                let address = match self.lookup(name) {
+                    Some(symbol) if symbol.segment == Segment::Data => symbol.address.0,
+                    Some(_) => return Err(AssemblerError::InvalidLabel(name.to_string())),
+                    // Patched by `resolve_fixups` once the label is defined.
+                    None => {
+                        self.defer(name, 0);
+                        0
+                    }
+                };
+                // The offset is sign-extended, so round the upper half up
+                // when the lower half is negative.
+                let high = (address.wrapping_add(0x8000) >> 16) as i32;
+                let low = address as u16 as i16 as i32;
+                Ok((
+                    Register::AT,
+                    low,
+                    vec![Instruction::LoadUpperImmediate {
+                        res: Register::AT,
+                        imm: high,
+                    }],
+                ))
+            }
             _ => Err(AssemblerError::InvalidInstruction),
         }
     }
 
-    fn parse_label(&self, iter: &mut Peekable<Iter<Token>>) -> Result<String, AssemblerError> {
-        match iter.next() {
-            Some(Token::Label { name, decl: false }) => Ok(name.clone()),
+    /// Reads the label a branch or jump goes to, with the text index of
+    /// the instruction `position` instructions into the expansion and the
+    /// label's address. A label not defined yet has no address until
+    /// [`Assembler::resolve_fixups`] patches the instruction.
+    fn parse_target<'a>(
+        &mut self,
+        iter: &mut TokenIter<'_, 'a>,
+        position: usize,
+    ) -> Result<(usize, &'a str, Option<Address>), AssemblerError> {
+        let label = self.parse_label(iter)?;
+        let index = self.text_lines.len() + position;
+        let address = self.lookup(label).map(|symbol| symbol.address);
+        if address.is_none() {
+            self.defer(label, position);
+        }
+        Ok((index, label, address))
+    }
+
+    /// Notes that the instruction `position` instructions into the
+    /// expansion uses `label` before it is defined, for
+    /// [`Assembler::resolve_fixups`] to patch.
+    fn defer(&mut self, label: &str, position: usize) {
+        let index = self.text_lines.len() + position;
+        self.forward
+            .push((index, label.to_string(), self.error_span));
+    }
+
+    /// The offset of a branch `position` instructions into the expansion,
+    /// or 0 for a label defined later.
+    fn parse_branch_target(
+        &mut self,
+        iter: &mut TokenIter,
+        position: usize,
+    ) -> Result<i32, AssemblerError> {
+        match self.parse_target(iter, position)? {
+            (index, label, Some(address)) => branch_offset(index, address, label),
+            (_, _, None) => Ok(0),
+        }
+    }
+
+    /// The target field of a jump `position` instructions into the
+    /// expansion, or 0 for a label defined later.
+    fn parse_jump_target(
+        &mut self,
+        iter: &mut TokenIter,
+        position: usize,
+    ) -> Result<u32, AssemblerError> {
+        match self.parse_target(iter, position)? {
+            (index, label, Some(address)) => jump_target(index, address, label),
+            (_, _, None) => Ok(0),
+        }
+    }
+
+    fn parse_label<'a>(&mut self, iter: &mut TokenIter<'_, 'a>) -> Result<&'a str, AssemblerError> {
+        match self.operand(iter) {
+            Some(TokenKind::Label { name, decl: false }) => Ok(*name),
             _ => Err(AssemblerError::InvalidLabel("Not a label".to_string())),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(source: &str, args: &RuntimeArgs) -> Result<Program, AssemblerError> {
+        let mut assembler = Assembler::new();
+        assembler.assemble_source(source, args)?;
+        Ok(assembler.finish())
+    }
+
+    fn text(source: &str) -> Vec<Instruction> {
+        assemble(source, &RuntimeArgs::default())
+            .expect("test program assembles")
+            .into_parts()
+            .0
+    }
+
+    #[test]
+    fn forward_branches_and_jumps_are_patched() {
+        let text = text(
+            ".text
+main:
+  beq $t0, $t1, later
+  j later
+  nop
+later:
+  jal main
+",
+        );
+        assert!(matches!(
+            text[0],
+            Instruction::BranchEqual { offset: 2, .. }
+        ));
+        let later = (BASE_TEXT_ADDR.0 + 12) >> 2;
+        assert!(matches!(text[1], Instruction::Jump { target } if target == later));
+        let main = BASE_TEXT_ADDR.0 >> 2;
+        assert!(matches!(text[3], Instruction::JumpAndLink { target } if target == main));
+    }
+
+    #[test]
+    fn forward_data_labels_are_patched() {
+        let text = text(
+            ".text
+main:
+  la $t0, low
+  lw $t1, high
+.data
+low: .word 1
+.space 0x7ffc
+high: .word 2
+",
+        );
+        // `low` is the first data word, `high` lies 0x8000 bytes in, where
+        // the sign-extended offset of a load needs the upper half rounded up.
+        assert!(matches!(
+            text[0],
+            Instruction::LoadUpperImmediate { imm: 0x1001, .. }
+        ));
+        assert!(matches!(text[1], Instruction::OrImmediate { imm: 0, .. }));
+        assert!(matches!(
+            text[2],
+            Instruction::LoadUpperImmediate { imm: 0x1002, .. }
+        ));
+        assert!(matches!(
+            text[3],
+            Instruction::LoadWord { imm: -0x8000, .. }
+        ));
+    }
+
+    #[test]
+    fn unresolved_labels_are_errors() {
+        let args = RuntimeArgs::default();
+        assert!(matches!(
+            assemble(".text\nmain:\n  beq $t0, $t1, nowhere\n", &args),
+            Err(AssemblerError::UndefinedLabel(label)) if label == "nowhere"
+        ));
+        assert!(matches!(
+            assemble(".text\nmain:\n  la $t0, later\nlater:\n  nop\n", &args),
+            Err(AssemblerError::InvalidLabel(label)) if label == "later"
+        ));
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let source = ".data
+value: .word 0
+.text
+main:
+  add $t0, $t1, $t2
+  addu $t0, $t1, $t2
+  addi $t0, $t1, -5
+  addiu $t0, $t1, 0x7fff
+  sub $s0, $s1, $s2
+  subu $s0, $s1, $s2
+  and $a0, $a1, $a2
+  or $a0, $a1, $a2
+  xor $a0, $a1, $a2
+  nor $a0, $a1, $a2
+  andi $v0, $v1, 0xffff
+  ori $v0, $v1, 0x8000
+  xori $v0, $v1, 1
+  lui $t3, 0xabcd
+  slt $t4, $t5, $t6
+  sltu $t4, $t5, $t6
+  sll $t7, $t8, 31
+  srl $t7, $t8, 1
+  sra $t7, $t8, 16
+  sllv $t7, $t8, $t9
+  srlv $t7, $t8, $t9
+  srav $t7, $t8, $t9
+  mult $t0, $t1
+  multu $t0, $t1
+  div $t0, $t1
+  divu $t0, $t1
+  mfhi $t2
+  mflo $t2
+  mthi $t2
+  mtlo $t2
+  lw $t0, -4($sp)
+  lb $t0, 1($sp)
+  lbu $t0, 2($sp)
+  lh $t0, 2($sp)
+  lhu $t0, 6($sp)
+  lwl $t0, 3($sp)
+  lwr $t0, 0($sp)
+  sw $t0, 8($gp)
+  sb $t0, 9($gp)
+  sh $t0, 10($gp)
+  swl $t0, 11($gp)
+  swr $t0, 8($gp)
+  ld $t0, 16($sp)
+  sd $t0, -16($sp)
+  beq $t0, $t1, main
+  bne $t0, $t1, main
+  bltz $t0, main
+  bgez $t0, main
+  blez $t0, main
+  bgtz $t0, main
+  bltzal $t0, main
+  bgezal $t0, main
+  teq $t0, $t1
+  tne $t0, $t1
+  tge $t0, $t1
+  tgeu $t0, $t1
+  tlt $t0, $t1
+  tltu $t0, $t1
+  teqi $t0, -1
+  tnei $t0, 2
+  tgei $t0, 3
+  tgeiu $t0, 4
+  tlti $t0, 5
+  tltiu $t0, 6
+  j main
+  jal main
+  jr $ra
+  jalr $t9
+  jalr $k0, $ra
+  dadd $t0, $t1, $t2
+  daddu $t0, $t1, $t2
+  dsub $t0, $t1, $t2
+  dsubu $t0, $t1, $t2
+  daddi $t0, $t1, -1
+  daddiu $t0, $t1, 1
+  dsll $t0, $t1, 4
+  dsrl $t0, $t1, 40
+  dsra $t0, $t1, 63
+  tlbr
+  tlbwi
+  tlbwr
+  tlbp
+  mfc0 $k0, $14
+  mtc0 $k1, $12
+  eret
+  syscall
+";
+        let args = RuntimeArgs {
+            mips64: true,
+            ..RuntimeArgs::default()
+        };
+        let (text, _, _) = assemble(source, &args)
+            .expect("test program assembles")
+            .into_parts();
+        for instruction in text {
+            let word = instruction.encode();
+            let decoded = Instruction::decode(word)
+                .unwrap_or_else(|| panic!("{} ({:#010x}) does not decode", instruction, word));
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", instruction));
+        }
+    }
+}
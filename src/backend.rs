@@ -0,0 +1,415 @@
+use std::{str::FromStr, sync::Arc};
+
+use thiserror::Error;
+
+use crate::{
+    address::Address,
+    assembler::{BASE_TEXT_ADDR, Instruction},
+    registers::Register,
+    simulator::{Simulator, SimulatorError},
+};
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+    #[error("Unknown backend '{0}', expected 'interpreter' or 'threaded'")]
+    UnknownBackend(String),
+}
+
+/// Selects how the simulator dispatches instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Decodes the `Instruction` enum on every step. This is the reference
+    /// implementation every other backend must agree with.
+    #[default]
+    Interpreter,
+    /// Pre-compiles the text segment into a table of specialised closures
+    /// indexed by word offset, skipping the per-step `match`.
+    Threaded,
+}
+
+impl FromStr for Backend {
+    type Err = BackendError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interpreter" | "interp" => Ok(Backend::Interpreter),
+            "threaded" => Ok(Backend::Threaded),
+            other => Err(BackendError::UnknownBackend(other.to_string())),
+        }
+    }
+}
+
+pub type Handler = Box<dyn Fn(&mut Simulator) -> Result<(), SimulatorError> + Send + Sync>;
+
+/// A text segment compiled into one handler per instruction word.
+pub type ThreadedCode = Arc<[Handler]>;
+
+pub fn compile(instructions: &[Instruction]) -> ThreadedCode {
+    instructions
+        .iter()
+        .enumerate()
+        .map(|(index, &inst)| compile_one(inst, BASE_TEXT_ADDR.wrapping_add(index as u32 * 4)))
+        .collect()
+}
+
+/// Where a branch at `pc` goes: `offset` words past the instruction after
+/// it, worked out once instead of on every step.
+fn branch_target(pc: Address, offset: i32) -> Address {
+    pc.wrapping_add(4).wrapping_add((offset as u32) << 2)
+}
+
+/// A handler that jumps to `target` when `taken` holds for the register.
+fn branch(reg: Register, target: Address, taken: fn(i64) -> bool) -> Handler {
+    Box::new(move |sim| {
+        if taken(sim.registers.get64(reg) as i64) {
+            sim.jump = Some(target);
+        }
+        Ok(())
+    })
+}
+
+/// Compiles the instruction at `pc`. Each handler must do exactly what
+/// `Simulator::execute_instruction` does for it.
+fn compile_one(instruction: Instruction, pc: Address) -> Handler {
+    match instruction {
+        Instruction::AddImmediate { res, reg, imm } => Box::new(move |sim| {
+            match (sim.registers.get(reg) as i32).checked_add(imm) {
+                Some(value) => sim.registers.set(res, value as u32),
+                None => sim.overflow()?,
+            }
+            Ok(())
+        }),
+        Instruction::AddImmediateUnsigned { res, reg, imm } => Box::new(move |sim| {
+            let value = sim.registers.get(reg).wrapping_add(imm as u32);
+            sim.registers.set(res, value);
+            Ok(())
+        }),
+        Instruction::AddUnsigned { res, reg, ret } => Box::new(move |sim| {
+            let value = sim.registers.get(reg).wrapping_add(sim.registers.get(ret));
+            sim.registers.set(res, value);
+            Ok(())
+        }),
+        Instruction::Add { res, reg, ret } => Box::new(move |sim| {
+            match (sim.registers.get(reg) as i32).checked_add(sim.registers.get(ret) as i32) {
+                Some(value) => sim.registers.set(res, value as u32),
+                None => sim.overflow()?,
+            }
+            Ok(())
+        }),
+        Instruction::Subtract { res, reg, ret } => Box::new(move |sim| {
+            match (sim.registers.get(reg) as i32).checked_sub(sim.registers.get(ret) as i32) {
+                Some(value) => sim.registers.set(res, value as u32),
+                None => sim.overflow()?,
+            }
+            Ok(())
+        }),
+        Instruction::SubtractUnsigned { res, reg, ret } => Box::new(move |sim| {
+            let value = sim.registers.get(reg).wrapping_sub(sim.registers.get(ret));
+            sim.registers.set(res, value);
+            Ok(())
+        }),
+        Instruction::And { res, reg, ret } => Box::new(move |sim| {
+            let value = sim.registers.get64(reg) & sim.registers.get64(ret);
+            sim.registers.set64(res, value);
+            Ok(())
+        }),
+        Instruction::Or { res, reg, ret } => Box::new(move |sim| {
+            let value = sim.registers.get64(reg) | sim.registers.get64(ret);
+            sim.registers.set64(res, value);
+            Ok(())
+        }),
+        Instruction::Xor { res, reg, ret } => Box::new(move |sim| {
+            let value = sim.registers.get64(reg) ^ sim.registers.get64(ret);
+            sim.registers.set64(res, value);
+            Ok(())
+        }),
+        Instruction::Nor { res, reg, ret } => Box::new(move |sim| {
+            let value = !(sim.registers.get64(reg) | sim.registers.get64(ret));
+            sim.registers.set64(res, value);
+            Ok(())
+        }),
+        Instruction::AndImmediate { res, reg, imm } => {
+            let mask = imm as u32 as u64 & 0xffff;
+            Box::new(move |sim| {
+                sim.registers.set64(res, sim.registers.get64(reg) & mask);
+                Ok(())
+            })
+        }
+        Instruction::XorImmediate { res, reg, imm } => {
+            let mask = imm as u32 as u64 & 0xffff;
+            Box::new(move |sim| {
+                sim.registers.set64(res, sim.registers.get64(reg) ^ mask);
+                Ok(())
+            })
+        }
+        Instruction::SetLessThan { res, reg, ret } => Box::new(move |sim| {
+            let less = (sim.registers.get64(reg) as i64) < (sim.registers.get64(ret) as i64);
+            sim.registers.set(res, less as u32);
+            Ok(())
+        }),
+        Instruction::SetLessThanUnsigned { res, reg, ret } => Box::new(move |sim| {
+            let less = sim.registers.get64(reg) < sim.registers.get64(ret);
+            sim.registers.set(res, less as u32);
+            Ok(())
+        }),
+        Instruction::ShiftLeftLogical { res, reg, shamt } => Box::new(move |sim| {
+            sim.registers.set(res, sim.registers.get(reg) << shamt);
+            Ok(())
+        }),
+        Instruction::ShiftRightLogical { res, reg, shamt } => Box::new(move |sim| {
+            sim.registers.set(res, sim.registers.get(reg) >> shamt);
+            Ok(())
+        }),
+        Instruction::ShiftRightArithmetic { res, reg, shamt } => Box::new(move |sim| {
+            let value = sim.registers.get(reg) as i32 >> shamt;
+            sim.registers.set(res, value as u32);
+            Ok(())
+        }),
+        Instruction::ShiftLeftLogicalVariable { res, reg, ret } => Box::new(move |sim| {
+            let amount = sim.registers.get(ret) & 0x1f;
+            sim.registers.set(res, sim.registers.get(reg) << amount);
+            Ok(())
+        }),
+        Instruction::ShiftRightLogicalVariable { res, reg, ret } => Box::new(move |sim| {
+            let amount = sim.registers.get(ret) & 0x1f;
+            sim.registers.set(res, sim.registers.get(reg) >> amount);
+            Ok(())
+        }),
+        Instruction::ShiftRightArithmeticVariable { res, reg, ret } => Box::new(move |sim| {
+            let amount = sim.registers.get(ret) & 0x1f;
+            let value = sim.registers.get(reg) as i32 >> amount;
+            sim.registers.set(res, value as u32);
+            Ok(())
+        }),
+        Instruction::BranchEqual { reg, ret, offset } => {
+            let target = branch_target(pc, offset);
+            Box::new(move |sim| {
+                if sim.registers.get64(reg) == sim.registers.get64(ret) {
+                    sim.jump = Some(target);
+                }
+                Ok(())
+            })
+        }
+        Instruction::BranchNotEqual { reg, ret, offset } => {
+            let target = branch_target(pc, offset);
+            Box::new(move |sim| {
+                if sim.registers.get64(reg) != sim.registers.get64(ret) {
+                    sim.jump = Some(target);
+                }
+                Ok(())
+            })
+        }
+        Instruction::BranchLessThanZero { reg, offset } => {
+            branch(reg, branch_target(pc, offset), |value| value < 0)
+        }
+        Instruction::BranchGreaterEqualZero { reg, offset } => {
+            branch(reg, branch_target(pc, offset), |value| value >= 0)
+        }
+        Instruction::BranchLessEqualZero { reg, offset } => {
+            branch(reg, branch_target(pc, offset), |value| value <= 0)
+        }
+        Instruction::BranchGreaterThanZero { reg, offset } => {
+            branch(reg, branch_target(pc, offset), |value| value > 0)
+        }
+        Instruction::Jump { target } => {
+            let target = Address(pc.wrapping_add(4).0 & 0xf000_0000 | target << 2);
+            Box::new(move |sim| {
+                sim.jump = Some(target);
+                Ok(())
+            })
+        }
+        Instruction::JumpAndLink { target } => {
+            let target = Address(pc.wrapping_add(4).0 & 0xf000_0000 | target << 2);
+            Box::new(move |sim| {
+                sim.jump = Some(target);
+                sim.registers.set(Register::RA, sim.return_address().0);
+                Ok(())
+            })
+        }
+        Instruction::JumpRegister { reg } => Box::new(move |sim| {
+            sim.jump = Some(Address(sim.registers.get(reg)));
+            Ok(())
+        }),
+        Instruction::JumpAndLinkRegister { res, reg } => Box::new(move |sim| {
+            // Read before linking, in case both are the same register.
+            sim.jump = Some(Address(sim.registers.get(reg)));
+            sim.registers.set(res, sim.return_address().0);
+            Ok(())
+        }),
+        Instruction::LoadWord { res, reg, imm } => Box::new(move |sim| {
+            let address = sim.effective_address(reg, imm);
+            if let Some(value) = sim.load(address, 4) {
+                sim.load_word(res, value);
+            }
+            Ok(())
+        }),
+        Instruction::StoreWord { ret, reg, imm } => Box::new(move |sim| {
+            let address = sim.effective_address(reg, imm);
+            sim.store(address, 4, sim.registers.get(ret));
+            Ok(())
+        }),
+        Instruction::LoadUpperImmediate { res, imm } => {
+            let value = (imm as u32) << 16;
+            Box::new(move |sim| {
+                sim.registers.set(res, value);
+                Ok(())
+            })
+        }
+        Instruction::OrImmediate { res, reg, imm } => Box::new(move |sim| {
+            // Keeps the upper half under `--mips64`, as the interpreter does.
+            let value = sim.registers.get64(reg) | (imm as u32 as u64);
+            match sim.mips64() {
+                true => sim.registers.set64(res, value),
+                false => sim.registers.set(res, value as u32),
+            }
+            Ok(())
+        }),
+        // Anything without a specialised handler falls back to the interpreter.
+        other => Box::new(move |sim| sim.execute_instruction(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RuntimeArgs,
+        assembler::Assembler,
+        memory::INITIAL_SP,
+        simulator::{DelaySlots, Stopped},
+    };
+
+    /// Uses every instruction with a specialised handler, with a `nop` in
+    /// the delay slots so it computes the same in every delay-slot mode.
+    const PROGRAM: &str = ".data
+source: .word 3, -1, 0x7fffffff, 0x80000000, 12345, -678, 0, 1
+destination: .space 32
+.text
+main:
+  li $a0, 10
+  jal fib
+  nop
+  move $s0, $v0
+  la $t0, source
+  la $t1, destination
+  li $t2, 8
+copy:
+  lw $t3, 0($t0)
+  sll $t4, $t3, 3
+  srl $t5, $t3, 7
+  sra $t6, $t3, 2
+  sllv $t7, $t3, $t2
+  srlv $t8, $t3, $t2
+  srav $t9, $t3, $t2
+  xor $t3, $t3, $t4
+  nor $t3, $t3, $t5
+  and $t3, $t3, $t6
+  or $t3, $t3, $t7
+  subu $t3, $t3, $t8
+  addu $t3, $t3, $t9
+  andi $s1, $t3, 0xff0f
+  xori $s1, $s1, 0x1234
+  sltu $s2, $t3, $s1
+  slt $s3, $t3, $s1
+  addu $s4, $s4, $s2
+  addu $s4, $s4, $s3
+  sw $t3, 0($t1)
+  addiu $t0, $t0, 4
+  addiu $t1, $t1, 4
+  addiu $t2, $t2, -1
+  bgtz $t2, copy
+  nop
+  bltz $t2, done
+  nop
+  blez $t2, skip
+  nop
+  addi $s5, $s5, 100
+skip:
+  bgez $t2, done
+  nop
+  addi $s5, $s5, 1000
+done:
+  li $t0, 0x7fffff00
+  add $s6, $t0, $s0
+  sub $s7, $s6, $s0
+  j exit
+  nop
+fib:
+  li $t1, 2
+  slt $t0, $a0, $t1
+  beq $t0, $zero, recurse
+  nop
+  move $v0, $a0
+  jr $ra
+  nop
+recurse:
+  addiu $sp, $sp, -12
+  sw $ra, 8($sp)
+  sw $a0, 4($sp)
+  addiu $a0, $a0, -1
+  jal fib
+  nop
+  sw $v0, 0($sp)
+  lw $a0, 4($sp)
+  nop
+  addiu $a0, $a0, -2
+  jal fib
+  nop
+  lw $t0, 0($sp)
+  nop
+  addu $v0, $v0, $t0
+  lw $ra, 8($sp)
+  addiu $sp, $sp, 12
+  bne $sp, $zero, return
+  nop
+return:
+  jalr $k0, $ra
+  nop
+exit:
+  li $v0, 10
+  syscall
+";
+
+    #[test]
+    fn threaded_matches_the_interpreter() {
+        let mut assembler = Assembler::new();
+        assembler
+            .assemble_source(PROGRAM, &RuntimeArgs::default())
+            .unwrap();
+        let program = assembler.finish();
+
+        for (branches, loads) in [(false, false), (true, false), (true, true)] {
+            let delay_slots = DelaySlots { branches, loads };
+            let run = |backend| {
+                let mut simulator = Simulator::new(program.clone());
+                simulator.set_delay_slots(delay_slots);
+                simulator.set_backend(backend);
+                let stop = simulator.run();
+                assert!(
+                    matches!(stop, Stopped::Halted(SimulatorError::Exit(0))),
+                    "{:?} on {:?}",
+                    stop,
+                    backend
+                );
+                simulator
+            };
+            let (interpreter, threaded) = (run(Backend::Interpreter), run(Backend::Threaded));
+
+            assert_eq!(interpreter.registers().get(Register::S0), 55);
+            assert_eq!(
+                interpreter.registers(),
+                threaded.registers(),
+                "{:?}",
+                delay_slots
+            );
+            assert_eq!(interpreter.steps(), threaded.steps(), "{:?}", delay_slots);
+            assert_eq!(interpreter.memory().data(), threaded.memory().data());
+            for offset in (0..64).step_by(4) {
+                let address = Address(INITIAL_SP.0 - offset);
+                assert_eq!(
+                    interpreter.memory().read_word(address),
+                    threaded.memory().read_word(address)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    RuntimeArgs,
+    assembler::Assembler,
+    scheduler::{self, Deadline},
+    simulator::{Simulator, SimulatorError},
+    syscall_io::SharedIo,
+};
+
+/// Wall-clock time a program may run for, unless configured otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Instructions a program may run, unless configured otherwise.
+pub const DEFAULT_STEP_LIMIT: u64 = 100_000_000;
+
+/// When to give up on a program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub steps: u64,
+    pub timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            steps: DEFAULT_STEP_LIMIT,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// How a program's run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program exited with this code.
+    Exited(u32),
+    /// `main` returned, which counts as exiting with 0.
+    Returned,
+    /// The program ran past its last instruction, which counts as exiting
+    /// with 0.
+    DroppedOffBottom,
+    AssemblerError(String),
+    RuntimeError(String),
+    /// The program read more input than it was given.
+    InputExhausted,
+    StepLimit,
+    Timeout,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Exited(_) => "exited",
+            Outcome::Returned => "returned_from_main",
+            Outcome::DroppedOffBottom => "dropped_off_bottom",
+            Outcome::AssemblerError(_) => "assembler_error",
+            Outcome::RuntimeError(_) => "runtime_error",
+            Outcome::InputExhausted => "input_exhausted",
+            Outcome::StepLimit => "step_limit",
+            Outcome::Timeout => "timeout",
+        }
+    }
+}
+
+/// The result of running one program.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub program: PathBuf,
+    pub outcome: Outcome,
+    pub steps: u64,
+    pub elapsed: Duration,
+    /// Bytes the program printed.
+    pub output_bytes: usize,
+}
+
+impl Report {
+    /// The exit code, counting a return from `main` or running off the end
+    /// as 0.
+    pub fn exit_code(&self) -> Option<u32> {
+        match self.outcome {
+            Outcome::Exited(code) => Some(code),
+            Outcome::Returned | Outcome::DroppedOffBottom => Some(0),
+            _ => None,
+        }
+    }
+
+    /// Whether the program failed to run to an exit, whatever its code.
+    pub fn failed(&self) -> bool {
+        self.exit_code().is_none()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let error = match &self.outcome {
+            Outcome::AssemblerError(err) | Outcome::RuntimeError(err) => Some(err.as_str()),
+            _ => None,
+        };
+        json!({
+            "program": self.program.display().to_string(),
+            "outcome": self.outcome.name(),
+            "exit_code": self.exit_code(),
+            "error": error,
+            "steps": self.steps,
+            "elapsed_us": self.elapsed.as_micros() as u64,
+            "output_bytes": self.output_bytes,
+        })
+    }
+}
+
+/// Assembles and runs `program` within `limits`, with the file of the same
+/// name and an `.in` extension as its console input if there is one.
+pub fn run(program: &Path, limits: Limits) -> Report {
+    let start = Instant::now();
+    let mut report = Report {
+        program: program.to_path_buf(),
+        outcome: Outcome::Exited(0),
+        steps: 0,
+        elapsed: Duration::ZERO,
+        output_bytes: 0,
+    };
+    let args = RuntimeArgs {
+        file: program.to_string_lossy().into_owned(),
+        ..RuntimeArgs::default()
+    };
+    let mut assembler = Assembler::new();
+    if let Err(err) = assembler.assemble(&args) {
+        let message = assembler
+            .diagnostics()
+            .last()
+            .map_or_else(|| err.to_string(), ToString::to_string);
+        report.outcome = Outcome::AssemblerError(message);
+        report.elapsed = start.elapsed();
+        return report;
+    }
+
+    let mut simulator = Simulator::new(assembler.finish());
+    let io = SharedIo::new();
+    if let Ok(input) = fs::read_to_string(program.with_extension("in")) {
+        io.lock().input.extend(input.lines().map(str::to_string));
+    }
+    simulator.set_io(Box::new(io.clone()));
+    let deadline = Deadline::new(limits.timeout);
+    report.outcome = match scheduler::run_within(&mut simulator, limits.steps, Some(deadline)) {
+        Ok(()) => Outcome::StepLimit,
+        Err(SimulatorError::Exit(code)) => Outcome::Exited(code),
+        Err(SimulatorError::NoMoreInstructions) => Outcome::DroppedOffBottom,
+        Err(SimulatorError::ReturnedFromMain) => Outcome::Returned,
+        Err(SimulatorError::InputPending) => Outcome::InputExhausted,
+        Err(SimulatorError::Timeout(_)) => Outcome::Timeout,
+        Err(err) => Outcome::RuntimeError(err.to_string()),
+    };
+    report.steps = simulator.steps();
+    report.elapsed = start.elapsed();
+    report.output_bytes = io.lock().output.len();
+    report
+}
+
+/// Runs every `.asm` and `.s` program in `directory`, by name.
+pub fn run_all(directory: &Path, limits: Limits) -> io::Result<Vec<Report>> {
+    let programs = crate::golden::programs(directory)?;
+    Ok(programs
+        .iter()
+        .map(|program| run(program, limits))
+        .collect())
+}
+
+/// The reports and a summary of them as one JSON document.
+pub fn to_json(reports: &[Report]) -> Value {
+    let failed = reports.iter().filter(|report| report.failed()).count();
+    json!({
+        "programs": reports.iter().map(Report::to_json).collect::<Vec<_>>(),
+        "summary": {
+            "total": reports.len(),
+            "failed": failed,
+            "steps": reports.iter().map(|report| report.steps).sum::<u64>(),
+        },
+    })
+}
+
+/// Prints one row per program and a summary line.
+pub fn print_table(reports: &[Report]) {
+    let names: Vec<String> = reports
+        .iter()
+        .map(|report| report.program.display().to_string())
+        .collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(7);
+    println!(
+        "{:<width$}  {:<18}  {:>4}  {:>12}  {:>10}",
+        "program", "outcome", "exit", "steps", "time (ms)"
+    );
+    for (report, name) in reports.iter().zip(&names) {
+        let exit = report
+            .exit_code()
+            .map_or_else(|| "-".to_string(), |code| code.to_string());
+        println!(
+            "{:<width$}  {:<18}  {:>4}  {:>12}  {:>10.1}",
+            name,
+            report.outcome.name(),
+            exit,
+            report.steps,
+            report.elapsed.as_secs_f64() * 1000.0
+        );
+        if let Outcome::AssemblerError(err) | Outcome::RuntimeError(err) = &report.outcome {
+            println!("{:<width$}    {}", "", err);
+        }
+    }
+    let failed = reports.iter().filter(|report| report.failed()).count();
+    println!("\n{} programs, {} failed", reports.len(), failed);
+}
@@ -0,0 +1,289 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    RuntimeArgs,
+    address::Address,
+    assembler::{Assembler, AssemblerError, BASE_DATA_ADDR, Program},
+    backend::Backend,
+    cache::{Cache, CacheConfig},
+    memory::INITIAL_SP,
+    simulator::{Simulator, SimulatorError, Stopped},
+    tlb::TlbEntry,
+};
+
+const BACKENDS: [Backend; 2] = [Backend::Interpreter, Backend::Threaded];
+
+/// How data accesses reach memory while a workload runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryModel {
+    /// Addresses go straight to memory.
+    Flat,
+    /// Addresses go through a TLB preloaded with identity mappings for the
+    /// data and stack pages, so no workload takes a miss.
+    Virtual,
+    /// Every access also goes through the default data cache model, which
+    /// keeps execution on the interpreter.
+    Cached,
+}
+
+const MEMORY_MODELS: [MemoryModel; 3] =
+    [MemoryModel::Flat, MemoryModel::Virtual, MemoryModel::Cached];
+
+/// Pages mapped from the start of the data segment, and below the initial
+/// stack pointer, under [`MemoryModel::Virtual`].
+const MAPPED_PAGES: u32 = 4;
+const PAGE_SIZE: u32 = 0x1000;
+
+/// A synthetic program used to measure simulator throughput.
+pub struct Workload {
+    pub name: &'static str,
+    pub description: &'static str,
+    generate: fn() -> String,
+}
+
+pub const WORKLOADS: &[Workload] = &[
+    Workload {
+        name: "alu",
+        description: "unrolled arithmetic and logic loop body",
+        generate: alu_workload,
+    },
+    Workload {
+        name: "loop",
+        description: "counted loop of arithmetic and a branch",
+        generate: loop_workload,
+    },
+    Workload {
+        name: "memcpy",
+        description: "word-by-word copies between two 4 KiB buffers",
+        generate: memcpy_workload,
+    },
+    Workload {
+        name: "recursion",
+        description: "naive recursive Fibonacci, saving frames on the stack",
+        generate: recursion_workload,
+    },
+    Workload {
+        name: "syscalls",
+        description: "back-to-back time syscalls",
+        generate: syscall_workload,
+    },
+];
+
+const UNROLL: usize = 25_000;
+
+fn program(body: &str) -> String {
+    format!(
+        ".text\n.globl main\nmain:\n{}  li $v0, 10\n  syscall\n",
+        body
+    )
+}
+
+fn alu_workload() -> String {
+    let mut body = String::from("  li $t0, 1\n");
+    for _ in 0..UNROLL {
+        body.push_str("  addi $t0, $t0, 1\n");
+        body.push_str("  addu $t1, $t1, $t0\n");
+        body.push_str("  ori $t2, $t1, 0x0f0f\n");
+        body.push_str("  lui $t3, 0x1234\n");
+    }
+    program(&body)
+}
+
+fn loop_workload() -> String {
+    program(
+        "  li $t0, 100000
+loop:
+  addiu $t1, $t1, 3
+  xor $t2, $t2, $t1
+  sll $t3, $t2, 2
+  addiu $t0, $t0, -1
+  bne $t0, $zero, loop
+",
+    )
+}
+
+fn memcpy_workload() -> String {
+    format!(
+        ".data
+source: .space 4096
+destination: .space 4096
+{}",
+        program(
+            "  li $t4, 25
+copy:
+  la $t0, source
+  la $t1, destination
+  li $t2, 1024
+word:
+  lw $t3, 0($t0)
+  sw $t3, 0($t1)
+  addiu $t0, $t0, 4
+  addiu $t1, $t1, 4
+  addiu $t2, $t2, -1
+  bne $t2, $zero, word
+  addiu $t4, $t4, -1
+  bne $t4, $zero, copy
+"
+        )
+    )
+}
+
+fn recursion_workload() -> String {
+    format!(
+        "{}fib:
+  li $t1, 2
+  slt $t0, $a0, $t1
+  beq $t0, $zero, recurse
+  move $v0, $a0
+  jr $ra
+recurse:
+  addiu $sp, $sp, -12
+  sw $ra, 8($sp)
+  sw $a0, 4($sp)
+  addiu $a0, $a0, -1
+  jal fib
+  sw $v0, 0($sp)
+  lw $a0, 4($sp)
+  addiu $a0, $a0, -2
+  jal fib
+  lw $t0, 0($sp)
+  addu $v0, $v0, $t0
+  lw $ra, 8($sp)
+  addiu $sp, $sp, 12
+  jr $ra
+",
+        program("  li $a0, 20\n  jal fib\n")
+    )
+}
+
+fn syscall_workload() -> String {
+    let mut body = String::new();
+    for _ in 0..UNROLL {
+        body.push_str("  li $v0, 30\n");
+        body.push_str("  syscall\n");
+    }
+    program(&body)
+}
+
+pub struct BenchResult {
+    pub workload: &'static str,
+    pub backend: Backend,
+    pub memory: MemoryModel,
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+fn assemble(workload: &Workload) -> Result<Program, AssemblerError> {
+    let mut assembler = Assembler::new();
+    assembler.assemble_source(&(workload.generate)(), &RuntimeArgs::default())?;
+    Ok(assembler.finish())
+}
+
+/// Maps `pages` pages from `first` to themselves, valid, writable and global.
+fn identity_map(simulator: &mut Simulator, first: Address, pages: u32, index: &mut usize) {
+    for page in 0..pages {
+        let page = (first.0 & !(PAGE_SIZE - 1)) + page * PAGE_SIZE;
+        let entry = TlbEntry {
+            entry_hi: page,
+            entry_lo: page | 0x700,
+        };
+        simulator.cp0_mut().tlb_mut().set(*index, entry);
+        *index += 1;
+    }
+}
+
+fn configure(simulator: &mut Simulator, memory: MemoryModel) {
+    match memory {
+        MemoryModel::Flat => {}
+        MemoryModel::Virtual => {
+            simulator.set_virtual_memory(true);
+            let stack = Address(INITIAL_SP.0 - (MAPPED_PAGES - 1) * PAGE_SIZE);
+            let mut index = 0;
+            identity_map(simulator, BASE_DATA_ADDR, MAPPED_PAGES, &mut index);
+            identity_map(simulator, stack, MAPPED_PAGES, &mut index);
+        }
+        MemoryModel::Cached => {
+            let cache = Cache::new(CacheConfig::default()).expect("default cache is valid");
+            simulator.set_cache(Some(cache));
+        }
+    }
+}
+
+/// Runs `program` to completion `iterations` times on `backend` under
+/// `memory`, timing only execution.
+fn measure(
+    program: &Program,
+    backend: Backend,
+    memory: MemoryModel,
+    iterations: u32,
+) -> (u64, Duration) {
+    let mut instructions = 0;
+    let mut elapsed = Duration::ZERO;
+
+    for _ in 0..iterations {
+        let mut simulator = Simulator::new(program.clone());
+        simulator.set_backend(backend);
+        configure(&mut simulator, memory);
+
+        let start = Instant::now();
+        let stop = simulator.run();
+        elapsed += start.elapsed();
+
+        if !matches!(stop, Stopped::Halted(SimulatorError::Exit(_))) {
+            println!("warning: workload stopped with {:?}", stop);
+        }
+        instructions += simulator.steps();
+    }
+
+    (instructions, elapsed)
+}
+
+pub fn run_benchmarks(iterations: u32) -> Result<Vec<BenchResult>, AssemblerError> {
+    let mut results = Vec::new();
+
+    for workload in WORKLOADS {
+        let program = assemble(workload)?;
+        for backend in BACKENDS {
+            for memory in MEMORY_MODELS {
+                let (instructions, elapsed) = measure(&program, backend, memory, iterations);
+                results.push(BenchResult {
+                    workload: workload.name,
+                    backend,
+                    memory,
+                    instructions,
+                    elapsed,
+                });
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+pub fn print_report(results: &[BenchResult]) {
+    for workload in WORKLOADS {
+        println!("{:<10} {}", workload.name, workload.description);
+    }
+    println!();
+    println!(
+        "{:<10} {:<12} {:<8} {:>12} {:>10} {:>14}",
+        "workload", "backend", "memory", "instructions", "time", "instr/s"
+    );
+    for result in results {
+        println!(
+            "{:<10} {:<12} {:<8} {:>12} {:>8.1}ms {:>12.2}M",
+            result.workload,
+            format!("{:?}", result.backend).to_lowercase(),
+            format!("{:?}", result.memory).to_lowercase(),
+            result.instructions,
+            result.elapsed.as_secs_f64() * 1000.0,
+            result.instructions_per_second() / 1_000_000.0,
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+
+use crate::{address::Address, debug_info::DebugInfo};
+
+/// Pattern table entries, as a power of two, unless configured otherwise.
+pub const DEFAULT_INDEX_BITS: u32 = 10;
+
+/// How a [`BranchPredictor`] guesses whether a conditional branch is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredictorKind {
+    /// Static: every branch is predicted taken.
+    AlwaysTaken,
+    /// One bit per table entry: predict what the branch did last time.
+    OneBit,
+    /// A two-bit saturating counter per table entry, so one surprise does
+    /// not flip the prediction.
+    TwoBit,
+    /// Two-bit counters indexed by the PC XORed with the global history of
+    /// recent outcomes.
+    Gshare,
+}
+
+impl PredictorKind {
+    pub const ALL: [PredictorKind; 4] = [
+        PredictorKind::AlwaysTaken,
+        PredictorKind::OneBit,
+        PredictorKind::TwoBit,
+        PredictorKind::Gshare,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PredictorKind::AlwaysTaken => "always-taken",
+            PredictorKind::OneBit => "1-bit",
+            PredictorKind::TwoBit => "2-bit",
+            PredictorKind::Gshare => "gshare",
+        }
+    }
+}
+
+impl std::str::FromStr for PredictorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PredictorKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown branch predictor '{}', expected always-taken, 1-bit, 2-bit or gshare",
+                    s
+                )
+            })
+    }
+}
+
+/// How one branch instruction behaved and was predicted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SiteStats {
+    pub executed: u64,
+    pub taken: u64,
+    pub correct: u64,
+}
+
+impl SiteStats {
+    pub fn accuracy(&self) -> f64 {
+        match self.executed {
+            0 => 0.0,
+            executed => self.correct as f64 / executed as f64,
+        }
+    }
+}
+
+/// Predicts every conditional branch the program executes before its
+/// outcome is known, then learns from the outcome, keeping accuracy
+/// statistics overall and per branch.
+#[derive(Debug, Clone)]
+pub struct BranchPredictor {
+    kind: PredictorKind,
+    /// Counters for the table-based predictors.
+    table: Vec<u8>,
+    /// Recent outcomes for gshare, newest in bit 0.
+    history: u32,
+    sites: BTreeMap<Address, SiteStats>,
+}
+
+impl BranchPredictor {
+    /// A predictor with a pattern table of `2^index_bits` entries.
+    pub fn new(kind: PredictorKind, index_bits: u32) -> Self {
+        // Tables start out predicting not taken, two-bit counters weakly.
+        let initial = match kind {
+            PredictorKind::TwoBit | PredictorKind::Gshare => 1,
+            _ => 0,
+        };
+        Self {
+            kind,
+            table: vec![initial; 1 << index_bits],
+            history: 0,
+            sites: BTreeMap::new(),
+        }
+    }
+
+    pub fn kind(&self) -> PredictorKind {
+        self.kind
+    }
+
+    fn index(&self, address: Address) -> usize {
+        let pc = (address.0 >> 2) as usize;
+        let index = match self.kind {
+            PredictorKind::Gshare => pc ^ self.history as usize,
+            _ => pc,
+        };
+        index & (self.table.len() - 1)
+    }
+
+    /// Whether the branch at `address` is predicted taken.
+    pub fn predict(&self, address: Address) -> bool {
+        let counter = self.table[self.index(address)];
+        match self.kind {
+            PredictorKind::AlwaysTaken => true,
+            PredictorKind::OneBit => counter == 1,
+            PredictorKind::TwoBit | PredictorKind::Gshare => counter >= 2,
+        }
+    }
+
+    /// Predicts the branch at `address`, then updates the tables with what
+    /// it actually did. Returns whether the prediction was right.
+    pub(crate) fn record(&mut self, address: Address, taken: bool) -> bool {
+        let correct = self.predict(address) == taken;
+        let index = self.index(address);
+        let counter = &mut self.table[index];
+        match self.kind {
+            PredictorKind::AlwaysTaken => {}
+            PredictorKind::OneBit => *counter = taken as u8,
+            PredictorKind::TwoBit | PredictorKind::Gshare => {
+                *counter = match taken {
+                    true => (*counter + 1).min(3),
+                    false => counter.saturating_sub(1),
+                }
+            }
+        }
+        if self.kind == PredictorKind::Gshare {
+            self.history = (self.history << 1 | taken as u32) & (self.table.len() as u32 - 1);
+        }
+
+        let site = self.sites.entry(address).or_default();
+        site.executed += 1;
+        site.taken += taken as u64;
+        site.correct += correct as u64;
+        correct
+    }
+
+    /// Statistics for every branch executed so far, by address.
+    pub fn sites(&self) -> &BTreeMap<Address, SiteStats> {
+        &self.sites
+    }
+
+    pub fn predictions(&self) -> u64 {
+        self.sites.values().map(|site| site.executed).sum()
+    }
+
+    pub fn correct(&self) -> u64 {
+        self.sites.values().map(|site| site.correct).sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        match self.predictions() {
+            0 => 0.0,
+            predictions => self.correct() as f64 / predictions as f64,
+        }
+    }
+
+    /// Prints the overall accuracy, then each branch's, worst first.
+    pub fn print_summary(&self, debug_info: &DebugInfo) {
+        println!(
+            "Branch predictor ({}): {}/{} correct ({:.1}%)",
+            self.kind.name(),
+            self.correct(),
+            self.predictions(),
+            self.accuracy() * 100.0
+        );
+        let mut sites: Vec<_> = self.sites.iter().collect();
+        sites.sort_by(|a, b| a.1.accuracy().total_cmp(&b.1.accuracy()).then(a.0.cmp(b.0)));
+        for (address, site) in sites {
+            let line = debug_info
+                .line_of(*address)
+                .map_or_else(String::new, |line| format!(" (line {})", line));
+            println!(
+                "  0x{:08x}{:<12} {:>8} executed {:>6.1}% taken {:>6.1}% correct",
+                address.0,
+                line,
+                site.executed,
+                site.taken as f64 * 100.0 / site.executed as f64,
+                site.accuracy() * 100.0
+            );
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+
+use thiserror::Error;
+
+use crate::address::Address;
+
+/// Accesses kept for display.
+const HISTORY: usize = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CacheError {
+    #[error("Block size {0} is not a power of two of at least 4 bytes")]
+    BlockSize(u32),
+    #[error("Associativity {0} is not a power of two")]
+    Associativity(u32),
+    #[error(
+        "Cache size {size} does not hold a power-of-two number of {associativity}-way sets of {block_size}-byte blocks"
+    )]
+    Size {
+        size: u32,
+        block_size: u32,
+        associativity: u32,
+    },
+}
+
+/// Which block of a full set makes room for a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    LeastRecentlyUsed,
+    FirstInFirstOut,
+    Random,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 3] = [
+        Policy::LeastRecentlyUsed,
+        Policy::FirstInFirstOut,
+        Policy::Random,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Policy::LeastRecentlyUsed => "LRU",
+            Policy::FirstInFirstOut => "FIFO",
+            Policy::Random => "Random",
+        }
+    }
+}
+
+impl std::str::FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Policy::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown replacement policy '{}', expected lru, fifo or random",
+                    s
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Total data capacity in bytes.
+    pub size: u32,
+    pub block_size: u32,
+    /// Blocks per set; equal to the block count for a fully associative cache.
+    pub associativity: u32,
+    pub policy: Policy,
+}
+
+impl Default for CacheConfig {
+    /// A small direct-mapped cache, as in the MARS data cache tool.
+    fn default() -> Self {
+        Self {
+            size: 128,
+            block_size: 16,
+            associativity: 1,
+            policy: Policy::LeastRecentlyUsed,
+        }
+    }
+}
+
+impl std::str::FromStr for CacheConfig {
+    type Err = String;
+
+    /// Reads `<size>:<block size>:<associativity>[:<policy>]`, sizes in
+    /// bytes, e.g. `1024:16:2:lru`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let number = |index: usize| -> Result<u32, String> {
+            fields[index]
+                .parse()
+                .map_err(|_| format!("Invalid number '{}' in cache '{}'", fields[index], s))
+        };
+        if !(3..=4).contains(&fields.len()) {
+            return Err(format!(
+                "Invalid cache '{}', expected <size>:<block size>:<associativity>[:<policy>]",
+                s
+            ));
+        }
+        let config = CacheConfig {
+            size: number(0)?,
+            block_size: number(1)?,
+            associativity: number(2)?,
+            policy: match fields.get(3) {
+                Some(policy) => policy.parse()?,
+                None => Policy::LeastRecentlyUsed,
+            },
+        };
+        config.validate().map_err(|err| err.to_string())?;
+        Ok(config)
+    }
+}
+
+impl CacheConfig {
+    pub fn blocks(&self) -> u32 {
+        self.size / self.block_size
+    }
+
+    pub fn sets(&self) -> u32 {
+        self.blocks() / self.associativity
+    }
+
+    fn validate(&self) -> Result<(), CacheError> {
+        if self.block_size < 4 || !self.block_size.is_power_of_two() {
+            return Err(CacheError::BlockSize(self.block_size));
+        }
+        if !self.associativity.is_power_of_two() {
+            return Err(CacheError::Associativity(self.associativity));
+        }
+        let blocks = self.size / self.block_size;
+        if !self.size.is_multiple_of(self.block_size)
+            || blocks < self.associativity
+            || !(blocks / self.associativity).is_power_of_two()
+        {
+            return Err(CacheError::Size {
+                size: self.size,
+                block_size: self.block_size,
+                associativity: self.associativity,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Fetch,
+    Load,
+    Store,
+}
+
+/// One way of a set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Block {
+    pub valid: bool,
+    pub tag: u32,
+    /// Access count when the block was last used, for LRU.
+    pub used: u64,
+    /// Access count when the block was filled, for FIFO.
+    pub filled: u64,
+}
+
+/// The outcome of one access.
+#[derive(Debug, Clone, Copy)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub address: Address,
+    pub set: u32,
+    pub way: u32,
+    pub tag: u32,
+    pub hit: bool,
+    /// Tag of the valid block replaced on a miss.
+    pub evicted: Option<u32>,
+}
+
+/// A single-level cache model. It tracks only which blocks are resident,
+/// not their contents, so it never changes what the program sees.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    config: CacheConfig,
+    /// `sets * associativity` blocks, set by set.
+    blocks: Vec<Block>,
+    accesses: u64,
+    hits: u64,
+    history: VecDeque<Access>,
+    /// xorshift state for the random policy.
+    seed: u32,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Result<Self, CacheError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            blocks: vec![Block::default(); config.blocks() as usize],
+            accesses: 0,
+            hits: 0,
+            history: VecDeque::with_capacity(HISTORY),
+            seed: 0x2545_f491,
+        })
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// The blocks of `set`, one per way.
+    pub fn set(&self, set: u32) -> &[Block] {
+        let ways = self.config.associativity as usize;
+        let start = set as usize * ways;
+        &self.blocks[start..start + ways]
+    }
+
+    fn random(&mut self) -> u32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+
+    /// Looks up the block holding `address`, filling it on a miss.
+    pub fn access(&mut self, kind: AccessKind, address: Address) -> Access {
+        let block = address.0 / self.config.block_size;
+        let set = block % self.config.sets();
+        let tag = block / self.config.sets();
+        self.accesses += 1;
+        let now = self.accesses;
+
+        let ways = self.config.associativity;
+        let start = (set * ways) as usize;
+        let resident = self.blocks[start..start + ways as usize]
+            .iter()
+            .position(|block| block.valid && block.tag == tag);
+
+        let (way, evicted) = match resident {
+            Some(way) => {
+                self.hits += 1;
+                (way, None)
+            }
+            None => {
+                let empty = self.blocks[start..start + ways as usize]
+                    .iter()
+                    .position(|block| !block.valid);
+                let way = empty.unwrap_or_else(|| {
+                    let blocks = &self.blocks[start..start + ways as usize];
+                    match self.config.policy {
+                        Policy::LeastRecentlyUsed => oldest(blocks, |block| block.used),
+                        Policy::FirstInFirstOut => oldest(blocks, |block| block.filled),
+                        Policy::Random => (self.random() % ways) as usize,
+                    }
+                });
+                let victim = self.blocks[start + way];
+                self.blocks[start + way] = Block {
+                    valid: true,
+                    tag,
+                    used: now,
+                    filled: now,
+                };
+                (way, victim.valid.then_some(victim.tag))
+            }
+        };
+        self.blocks[start + way].used = now;
+
+        let access = Access {
+            kind,
+            address,
+            set,
+            way: way as u32,
+            tag,
+            hit: resident.is_some(),
+            evicted,
+        };
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(access);
+        access
+    }
+
+    pub fn accesses(&self) -> u64 {
+        self.accesses
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.accesses - self.hits
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        if self.accesses == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / self.accesses as f64
+    }
+
+    /// The most recent accesses, oldest first.
+    pub fn history(&self) -> &VecDeque<Access> {
+        &self.history
+    }
+}
+
+/// The way whose `age` stamp is smallest.
+fn oldest(blocks: &[Block], age: impl Fn(&Block) -> u64) -> usize {
+    blocks
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, block)| age(block))
+        .map_or(0, |(way, _)| way)
+}
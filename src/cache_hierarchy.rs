@@ -0,0 +1,126 @@
+use crate::{
+    address::Address,
+    cache::{AccessKind, Cache, CacheConfig, CacheError},
+};
+
+/// Cycles an L1 miss waits for the L2, unless configured otherwise.
+pub const DEFAULT_L2_LATENCY: u32 = 10;
+/// Cycles a miss in the last level waits for main memory.
+pub const DEFAULT_MEMORY_LATENCY: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HierarchyConfig {
+    pub l1i: CacheConfig,
+    pub l1d: CacheConfig,
+    /// A unified second level behind both L1s.
+    pub l2: Option<CacheConfig>,
+    pub l2_latency: u32,
+    pub memory_latency: u32,
+}
+
+impl Default for HierarchyConfig {
+    fn default() -> Self {
+        Self {
+            l1i: CacheConfig::default(),
+            l1d: CacheConfig::default(),
+            l2: None,
+            l2_latency: DEFAULT_L2_LATENCY,
+            memory_latency: DEFAULT_MEMORY_LATENCY,
+        }
+    }
+}
+
+/// Split instruction and data L1 caches with an optional unified L2, fed
+/// by the program's fetches, loads and stores. Like [`Cache`] it only
+/// tracks residency. An L1 hit costs nothing extra; each miss stalls for
+/// the latency of the level that serves it.
+#[derive(Debug, Clone)]
+pub struct CacheHierarchy {
+    config: HierarchyConfig,
+    l1i: Cache,
+    l1d: Cache,
+    l2: Option<Cache>,
+    stall_cycles: u64,
+}
+
+impl CacheHierarchy {
+    pub fn new(config: HierarchyConfig) -> Result<Self, CacheError> {
+        Ok(Self {
+            config,
+            l1i: Cache::new(config.l1i)?,
+            l1d: Cache::new(config.l1d)?,
+            l2: config.l2.map(Cache::new).transpose()?,
+            stall_cycles: 0,
+        })
+    }
+
+    pub fn config(&self) -> &HierarchyConfig {
+        &self.config
+    }
+
+    pub(crate) fn access(&mut self, kind: AccessKind, address: Address) {
+        let l1 = match kind {
+            AccessKind::Fetch => &mut self.l1i,
+            AccessKind::Load | AccessKind::Store => &mut self.l1d,
+        };
+        if l1.access(kind, address).hit {
+            return;
+        }
+        let memory = match &mut self.l2 {
+            Some(l2) => {
+                self.stall_cycles += self.config.l2_latency as u64;
+                !l2.access(kind, address).hit
+            }
+            None => true,
+        };
+        if memory {
+            self.stall_cycles += self.config.memory_latency as u64;
+        }
+    }
+
+    pub fn l1i(&self) -> &Cache {
+        &self.l1i
+    }
+
+    pub fn l1d(&self) -> &Cache {
+        &self.l1d
+    }
+
+    pub fn l2(&self) -> Option<&Cache> {
+        self.l2.as_ref()
+    }
+
+    /// Cycles spent waiting on misses so far.
+    pub fn stall_cycles(&self) -> u64 {
+        self.stall_cycles
+    }
+
+    /// Prints each level's configuration and hit statistics, then the
+    /// stall estimate.
+    pub fn print_summary(&self) {
+        println!("Cache hierarchy:");
+        let levels = [
+            ("L1I", Some(&self.l1i)),
+            ("L1D", Some(&self.l1d)),
+            ("L2", self.l2.as_ref()),
+        ];
+        for (name, cache) in levels {
+            let Some(cache) = cache else {
+                continue;
+            };
+            let config = cache.config();
+            println!(
+                "  {:<3} {:>7} B, {:>3} B blocks, {:>2}-way {:<6} {:>10} accesses {:>10} misses {:>6.1}% hits",
+                name,
+                config.size,
+                config.block_size,
+                config.associativity,
+                config.policy.name(),
+                cache.accesses(),
+                cache.misses(),
+                cache.hit_rate() * 100.0
+            );
+        }
+        println!("  Memory stall cycles: {}", self.stall_cycles);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{address::Address, debug_info::DebugInfo};
+
+/// A subroutine call still waiting to return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// The call instruction.
+    pub call_site: Address,
+    /// The first instruction of the called subroutine.
+    pub entry: Address,
+    /// Where the matching return goes.
+    pub return_address: Address,
+    /// `$sp` when the call was made.
+    pub sp: u32,
+}
+
+/// Calls made and not yet returned from, reconstructed by watching call and
+/// return instructions as they execute. Returns that skip frames, e.g. to an
+/// outer caller's return address, pop every frame down to the one they
+/// match.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    /// Frames from the outermost call to the innermost.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub(crate) fn call(&mut self, frame: Frame) {
+        self.frames.push(frame);
+    }
+
+    /// Records a return that jumped to `target`.
+    pub(crate) fn ret(&mut self, target: Address) {
+        match self
+            .frames
+            .iter()
+            .rposition(|frame| frame.return_address == target)
+        {
+            Some(index) => self.frames.truncate(index),
+            None => {
+                self.frames.pop();
+            }
+        }
+    }
+}
+
+/// Names `address` after the nearest text label at or before it, e.g.
+/// `fact+8`, falling back to the bare address.
+pub fn symbolize(debug_info: &DebugInfo, address: Address) -> String {
+    let label = debug_info
+        .text_label_before(address)
+        .and_then(|(name, start)| Some((name, address.offset_from(start)?)));
+    match label {
+        Some((name, 0)) => name.to_string(),
+        Some((name, offset)) => format!("{}+{}", name, offset),
+        None => format!("0x{:08x}", address.0),
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{debug_info::DebugInfo, profile::Profile};
+
+/// Which source lines with code have executed, from a [`Profile`]. Code
+/// from `.include`d files counts towards the line of the `.include`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// Executions of every line that produced instructions.
+    lines: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    pub fn new(profile: &Profile, debug_info: &DebugInfo) -> Self {
+        let executed = profile.by_line(debug_info);
+        let lines = debug_info
+            .code_lines()
+            .into_iter()
+            .map(|line| (line, executed.get(&line).copied().unwrap_or(0)))
+            .collect();
+        Self { lines }
+    }
+
+    /// Lines with code.
+    pub fn total(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Lines with code that executed at least once.
+    pub fn covered(&self) -> usize {
+        self.lines.values().filter(|&&count| count > 0).count()
+    }
+
+    pub fn percent(&self) -> f64 {
+        match self.total() {
+            0 => 100.0,
+            total => self.covered() as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// Lines with code that never executed, ascending.
+    pub fn uncovered(&self) -> impl Iterator<Item = usize> + '_ {
+        self.lines
+            .iter()
+            .filter(|&(_, &count)| count == 0)
+            .map(|(&line, _)| line)
+    }
+
+    /// Writes an lcov tracefile naming `source` as the file covered, which
+    /// tools such as genhtml can render.
+    pub fn write_lcov(&self, out: &mut impl Write, source: &str) -> io::Result<()> {
+        writeln!(out, "TN:")?;
+        writeln!(out, "SF:{}", source)?;
+        for (line, count) in &self.lines {
+            writeln!(out, "DA:{},{}", line, count)?;
+        }
+        writeln!(out, "LF:{}", self.total())?;
+        writeln!(out, "LH:{}", self.covered())?;
+        writeln!(out, "end_of_record")
+    }
+
+    /// Like [`Coverage::write_lcov`], to a new file at `path`.
+    pub fn save_lcov(&self, path: &Path, source: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_lcov(&mut out, source)?;
+        out.flush()
+    }
+}
+
+/// Collapses ascending line numbers into ranges, e.g. `3, 7-9, 12`.
+fn ranges(lines: impl Iterator<Item = usize>) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(start, end)| match start == end {
+            true => start.to_string(),
+            false => format!("{}-{}", start, end),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prints how many lines executed and which did not.
+pub fn print_summary(coverage: &Coverage) {
+    println!(
+        "Coverage: {}/{} lines ({:.1}%)",
+        coverage.covered(),
+        coverage.total(),
+        coverage.percent()
+    );
+    if coverage.covered() < coverage.total() {
+        println!("Not executed: {}", ranges(coverage.uncovered()));
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::{
+    address::Address,
+    tlb::{TLB_ENTRIES, Tlb, TlbEntry, TlbFault, WIRED_ENTRIES},
+};
+
+/// Coprocessor 0 register numbers, as `mfc0` and `mtc0` name them.
+pub const INDEX: u32 = 0;
+pub const RANDOM: u32 = 1;
+pub const ENTRY_LO: u32 = 2;
+pub const BAD_VADDR: u32 = 8;
+pub const ENTRY_HI: u32 = 10;
+pub const STATUS: u32 = 12;
+pub const CAUSE: u32 = 13;
+pub const EPC: u32 = 14;
+
+/// Set in Index by a `tlbp` that found no matching entry.
+const PROBE_FAILED: u32 = 1 << 31;
+/// Status bit set while an exception is being handled.
+const EXCEPTION_LEVEL: u32 = 1 << 1;
+/// Cause bit set when the faulting instruction was in a delay slot.
+const BRANCH_DELAY: u32 = 1 << 31;
+/// Cause bits a program may write: the two software interrupts.
+const CAUSE_WRITABLE: u32 = 0x300;
+
+/// The exceptions the simulator raises, by their Cause ExcCode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionCode {
+    TlbModified = 1,
+    TlbLoad = 2,
+    TlbStore = 3,
+    /// A load from an unaligned or unmapped address.
+    AddressErrorLoad = 4,
+    /// A store to an unaligned or unmapped address.
+    AddressErrorStore = 5,
+    /// A signed `add`, `addi`, `sub` or doubleword form that overflowed.
+    Overflow = 12,
+    /// A trap instruction whose condition held.
+    Trap = 13,
+}
+
+impl ExceptionCode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ExceptionCode::TlbModified => "TLB modification",
+            ExceptionCode::TlbLoad => "TLB miss on load",
+            ExceptionCode::TlbStore => "TLB miss on store",
+            ExceptionCode::AddressErrorLoad => "Address error on load",
+            ExceptionCode::AddressErrorStore => "Address error on store",
+            ExceptionCode::Overflow => "Arithmetic overflow",
+            ExceptionCode::Trap => "Trap",
+        }
+    }
+}
+
+/// An exception and the virtual address that caused it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exception {
+    pub code: ExceptionCode,
+    pub address: Address,
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at 0x{:08x}", self.code.name(), self.address.0)
+    }
+}
+
+/// The system control coprocessor: the registers a kernel uses to manage
+/// the TLB and handle exceptions. Index and Random hold an entry number in
+/// bits 13..8, as on the R3000.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coprocessor0 {
+    pub index: u32,
+    pub random: u32,
+    pub entry_lo: u32,
+    pub entry_hi: u32,
+    pub bad_vaddr: u32,
+    pub status: u32,
+    pub cause: u32,
+    pub epc: u32,
+    tlb: Tlb,
+}
+
+impl Default for Coprocessor0 {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            random: ((TLB_ENTRIES - 1) as u32) << 8,
+            entry_lo: 0,
+            entry_hi: 0,
+            bad_vaddr: 0,
+            status: 0,
+            cause: 0,
+            epc: 0,
+            tlb: Tlb::default(),
+        }
+    }
+}
+
+impl Coprocessor0 {
+    pub fn tlb(&self) -> &Tlb {
+        &self.tlb
+    }
+
+    pub fn tlb_mut(&mut self) -> &mut Tlb {
+        &mut self.tlb
+    }
+
+    /// The value `mfc0` reads from register `number`; unimplemented
+    /// registers read as zero.
+    pub fn read(&self, number: u32) -> u32 {
+        match number {
+            INDEX => self.index,
+            RANDOM => self.random,
+            ENTRY_LO => self.entry_lo,
+            BAD_VADDR => self.bad_vaddr,
+            ENTRY_HI => self.entry_hi,
+            STATUS => self.status,
+            CAUSE => self.cause,
+            EPC => self.epc,
+            _ => 0,
+        }
+    }
+
+    /// Writes register `number` as `mtc0` does. Read-only registers and
+    /// fields keep their value.
+    pub fn write(&mut self, number: u32, value: u32) {
+        match number {
+            INDEX => self.index = self.index & PROBE_FAILED | value & 0x3f00,
+            ENTRY_LO => self.entry_lo = value & 0xffff_ff00,
+            ENTRY_HI => self.entry_hi = value & 0xffff_ffc0,
+            STATUS => self.status = value,
+            CAUSE => self.cause = self.cause & !CAUSE_WRITABLE | value & CAUSE_WRITABLE,
+            EPC => self.epc = value,
+            _ => {}
+        }
+    }
+
+    fn selected(register: u32) -> usize {
+        (register >> 8) as usize & 0x3f
+    }
+
+    /// `tlbr`: loads EntryHi and EntryLo from the entry Index selects.
+    pub(crate) fn tlb_read(&mut self) {
+        let entry = self.tlb.get(Self::selected(self.index));
+        self.entry_hi = entry.entry_hi;
+        self.entry_lo = entry.entry_lo;
+    }
+
+    fn current_entry(&self) -> TlbEntry {
+        TlbEntry {
+            entry_hi: self.entry_hi,
+            entry_lo: self.entry_lo,
+        }
+    }
+
+    /// `tlbwi`: stores EntryHi and EntryLo in the entry Index selects.
+    pub(crate) fn tlb_write_indexed(&mut self) {
+        self.tlb
+            .set(Self::selected(self.index), self.current_entry());
+    }
+
+    /// `tlbwr`: stores EntryHi and EntryLo in the entry Random selects.
+    /// Random then counts down, wrapping past the wired entries.
+    pub(crate) fn tlb_write_random(&mut self) {
+        let random = Self::selected(self.random);
+        self.tlb.set(random, self.current_entry());
+        let next = match random <= WIRED_ENTRIES {
+            true => TLB_ENTRIES - 1,
+            false => random - 1,
+        };
+        self.random = (next as u32) << 8;
+    }
+
+    /// `tlbp`: sets Index to the entry matching EntryHi, or sets its probe
+    /// failure bit.
+    pub(crate) fn tlb_probe(&mut self) {
+        self.index = match self.tlb.probe(self.entry_hi) {
+            Some(index) => (index as u32) << 8,
+            None => PROBE_FAILED,
+        };
+    }
+
+    /// Translates `address` through the TLB for the ASID in EntryHi.
+    pub fn translate(&self, address: Address, write: bool) -> Result<Address, Exception> {
+        let asid = (self.entry_hi >> 6) & 0x3f;
+        self.tlb
+            .translate(address, asid, write)
+            .map_err(|fault| Exception {
+                code: match (fault, write) {
+                    (TlbFault::Modified, _) => ExceptionCode::TlbModified,
+                    (TlbFault::Miss, false) => ExceptionCode::TlbLoad,
+                    (TlbFault::Miss, true) => ExceptionCode::TlbStore,
+                },
+                address,
+            })
+    }
+
+    /// Records `exception` for the handler. `epc` is where to resume: the
+    /// faulting instruction, or the branch before it if it was in a delay
+    /// slot. For a bad address, EntryHi is set to the faulting page, ready
+    /// for `tlbwr`.
+    pub(crate) fn enter(&mut self, exception: Exception, epc: Address, in_delay_slot: bool) {
+        self.cause = self.cause & CAUSE_WRITABLE
+            | (exception.code as u32) << 2
+            | if in_delay_slot { BRANCH_DELAY } else { 0 };
+        self.epc = epc.0;
+        if !matches!(
+            exception.code,
+            ExceptionCode::Trap | ExceptionCode::Overflow
+        ) {
+            self.bad_vaddr = exception.address.0;
+            self.entry_hi = exception.address.0 & 0xffff_f000 | self.entry_hi & 0xfc0;
+        }
+        self.status |= EXCEPTION_LEVEL;
+    }
+
+    /// `eret`: leaves the exception level, returning where to resume.
+    pub(crate) fn exception_return(&mut self) -> Address {
+        self.status &= !EXCEPTION_LEVEL;
+        Address(self.epc)
+    }
+}
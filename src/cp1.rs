@@ -0,0 +1,156 @@
+/// One of the five IEEE exception conditions, in FCSR bit order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    Inexact,
+    Underflow,
+    Overflow,
+    DivideByZero,
+    Invalid,
+}
+
+impl Exception {
+    pub const ALL: [Exception; 5] = [
+        Exception::Inexact,
+        Exception::Underflow,
+        Exception::Overflow,
+        Exception::DivideByZero,
+        Exception::Invalid,
+    ];
+
+    /// The single-letter name MIPS documentation uses, e.g. `V` for invalid.
+    pub fn letter(self) -> char {
+        match self {
+            Exception::Inexact => 'I',
+            Exception::Underflow => 'U',
+            Exception::Overflow => 'O',
+            Exception::DivideByZero => 'Z',
+            Exception::Invalid => 'V',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Exception::Inexact => "inexact",
+            Exception::Underflow => "underflow",
+            Exception::Overflow => "overflow",
+            Exception::DivideByZero => "divide by zero",
+            Exception::Invalid => "invalid operation",
+        }
+    }
+}
+
+/// The FCSR rounding mode field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    Nearest,
+    TowardZero,
+    TowardPositive,
+    TowardNegative,
+}
+
+impl RoundingMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            RoundingMode::Nearest => "nearest",
+            RoundingMode::TowardZero => "toward zero",
+            RoundingMode::TowardPositive => "toward +∞",
+            RoundingMode::TowardNegative => "toward −∞",
+        }
+    }
+}
+
+const FLAGS_SHIFT: u32 = 2;
+const ENABLES_SHIFT: u32 = 7;
+const CAUSE_SHIFT: u32 = 12;
+/// Cause bit for unimplemented operations, which has no flag or enable.
+const CAUSE_UNIMPLEMENTED: u32 = 1 << 17;
+const FLUSH_TO_ZERO: u32 = 1 << 24;
+
+/// The floating-point control and status register.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Fcsr(pub u32);
+
+impl Fcsr {
+    pub fn rounding_mode(self) -> RoundingMode {
+        match self.0 & 0b11 {
+            0 => RoundingMode::Nearest,
+            1 => RoundingMode::TowardZero,
+            2 => RoundingMode::TowardPositive,
+            _ => RoundingMode::TowardNegative,
+        }
+    }
+
+    /// Sticky flag: `exception` happened since the flags were last cleared.
+    pub fn flag(self, exception: Exception) -> bool {
+        self.0 & (1 << (FLAGS_SHIFT + exception as u32)) != 0
+    }
+
+    /// Whether `exception` traps instead of just setting its flag.
+    pub fn enabled(self, exception: Exception) -> bool {
+        self.0 & (1 << (ENABLES_SHIFT + exception as u32)) != 0
+    }
+
+    /// Whether the last operation raised `exception`.
+    pub fn cause(self, exception: Exception) -> bool {
+        self.0 & (1 << (CAUSE_SHIFT + exception as u32)) != 0
+    }
+
+    /// Whether the last operation was one the unit does not implement.
+    pub fn unimplemented(self) -> bool {
+        self.0 & CAUSE_UNIMPLEMENTED != 0
+    }
+
+    pub fn flush_to_zero(self) -> bool {
+        self.0 & FLUSH_TO_ZERO != 0
+    }
+
+    /// Condition code `cc`, 0 to 7, as set by `c.cond.fmt`. Code 0 is bit
+    /// 23 and the rest sit above the FS bit.
+    pub fn condition(self, cc: u32) -> bool {
+        let bit = if cc == 0 { 23 } else { 24 + cc };
+        self.0 & (1 << bit) != 0
+    }
+}
+
+/// Coprocessor 1, the floating-point unit: 32 single-precision registers,
+/// paired up for doubles, and the FCSR.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Coprocessor1 {
+    fprs: [u32; 32],
+    fcsr: Fcsr,
+}
+
+impl Coprocessor1 {
+    /// Raw bits of `$f<n>`.
+    pub fn get(&self, n: usize) -> u32 {
+        self.fprs[n]
+    }
+
+    pub fn set(&mut self, n: usize, bits: u32) {
+        self.fprs[n] = bits;
+    }
+
+    /// `$f<n>` read as a single.
+    pub fn single(&self, n: usize) -> f32 {
+        f32::from_bits(self.fprs[n])
+    }
+
+    /// Raw bits of the double in `$f<n>` and `$f<n+1>`, with the low word
+    /// in the even register. None for odd `n`, which cannot hold a double.
+    pub fn double_bits(&self, n: usize) -> Option<u64> {
+        n.is_multiple_of(2)
+            .then(|| (self.fprs[n + 1] as u64) << 32 | self.fprs[n] as u64)
+    }
+
+    pub fn double(&self, n: usize) -> Option<f64> {
+        self.double_bits(n).map(f64::from_bits)
+    }
+
+    pub fn fcsr(&self) -> Fcsr {
+        self.fcsr
+    }
+
+    pub fn set_fcsr(&mut self, fcsr: Fcsr) {
+        self.fcsr = fcsr;
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    address::Address,
+    assembler::{BASE_TEXT_ADDR, Segment},
+};
+
+/// How a data label's contents were declared, used to decode them for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataKind {
+    String,
+    Words,
+    Bytes,
+}
+
+/// A label as it was declared in the source.
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub address: Address,
+    pub segment: Segment,
+    /// 1-based source line of the declaration.
+    pub line: usize,
+    /// The first data directive after the label, if any.
+    pub kind: Option<DataKind>,
+    /// Bytes up to the next data label or the end of the data segment.
+    pub size: usize,
+}
+
+/// Maps text-segment addresses back to the source lines they came from.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// 1-based source line of each instruction word, in address order.
+    lines: Vec<usize>,
+    /// Labels in declaration order.
+    symbols: Vec<SymbolInfo>,
+}
+
+impl DebugInfo {
+    pub fn push(&mut self, line: usize) {
+        self.lines.push(line);
+    }
+
+    pub fn push_symbol(&mut self, name: &str, address: Address, segment: Segment, line: usize) {
+        self.symbols.push(SymbolInfo {
+            name: name.to_string(),
+            address,
+            segment,
+            line,
+            kind: None,
+            size: 0,
+        });
+    }
+
+    /// Records the kind of data emitted at `address` for data labels
+    /// declared there that do not have one yet.
+    pub fn set_data_kind(&mut self, address: Address, kind: DataKind) {
+        for symbol in &mut self.symbols {
+            if symbol.segment == Segment::Data && symbol.address == address {
+                symbol.kind.get_or_insert(kind);
+            }
+        }
+    }
+
+    /// Sizes every data label now that the end of the data segment is known.
+    pub fn finish_data(&mut self, data_end: Address) {
+        let mut starts: Vec<u32> = self
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.segment == Segment::Data)
+            .map(|symbol| symbol.address.0)
+            .collect();
+        starts.sort_unstable();
+
+        for symbol in &mut self.symbols {
+            if symbol.segment != Segment::Data {
+                continue;
+            }
+            let next = starts.partition_point(|&start| start <= symbol.address.0);
+            let end = starts.get(next).map_or(data_end, |&start| Address(start));
+            symbol.size = end.offset_from(symbol.address).unwrap_or(0) as usize;
+        }
+    }
+
+    pub fn symbols(&self) -> &[SymbolInfo] {
+        &self.symbols
+    }
+
+    /// The address of the label `name`.
+    pub fn label(&self, name: &str) -> Option<Address> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.address)
+    }
+
+    /// The text label closest to `address` at or before it, and its address.
+    pub fn text_label_before(&self, address: Address) -> Option<(&str, Address)> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.segment == Segment::Text && symbol.address <= address)
+            .max_by_key(|symbol| symbol.address)
+            .map(|symbol| (symbol.name.as_str(), symbol.address))
+    }
+
+    /// Reads a location given on the command line: a `0x` hex or decimal
+    /// address, or a label.
+    pub fn resolve(&self, location: &str) -> Option<Address> {
+        let parsed = match location.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => location.parse().ok(),
+        };
+        parsed.map(Address).or_else(|| self.label(location))
+    }
+
+    /// The source line that produced the instruction at `address`.
+    pub fn line_of(&self, address: Address) -> Option<usize> {
+        let index = address.word_index(BASE_TEXT_ADDR)?;
+        self.lines.get(index).copied()
+    }
+
+    /// Source lines that produced at least one instruction, ascending.
+    pub fn code_lines(&self) -> BTreeSet<usize> {
+        self.lines.iter().copied().collect()
+    }
+
+    /// The address of the first instruction generated by `line`, if any.
+    pub fn address_of(&self, line: usize) -> Option<Address> {
+        let index = self.lines.iter().position(|&l| l == line)?;
+        Some(BASE_TEXT_ADDR + index * 4)
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::lexer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An assembler message tied to a place in the main source file.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, in characters, where `span` starts.
+    pub column: usize,
+    /// Byte range of the offending text within the source.
+    pub span: Span,
+    /// Where the problem really is when `span` only points at the line
+    /// that led to it, e.g. an `.include`.
+    pub note: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        message: impl Into<String>,
+        source: &str,
+        line: usize,
+        span: Span,
+    ) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            line,
+            column: column(source, span),
+            span,
+            note: None,
+        }
+    }
+
+    /// A diagnostic for the whole of 1-based `line`, without its
+    /// indentation, or for the end of `source` if there is no such line.
+    pub fn for_line(
+        severity: Severity,
+        message: impl Into<String>,
+        source: &str,
+        line: usize,
+    ) -> Self {
+        let start = match line {
+            0 | 1 => 0,
+            line => source
+                .match_indices('\n')
+                .nth(line - 2)
+                .map_or(source.len(), |(i, _)| i + 1),
+        };
+        let text = source[start..].split('\n').next().unwrap_or_default();
+        let indent = text.len() - text.trim_start().len();
+        let span = Span {
+            start: start + indent,
+            end: start + text.trim_end().len().max(indent),
+        };
+        Diagnostic::new(severity, message, source, line.max(1), span)
+    }
+
+    /// The message followed by the note, if there is one.
+    pub fn text(&self) -> String {
+        match &self.note {
+            Some(note) => format!("{} ({})", self.message, note),
+            None => self.message.clone(),
+        }
+    }
+
+    /// Renders the diagnostic for a terminal: the message, where it is in
+    /// `file`, and the source line with the span underlined, in ANSI colors
+    /// if `color` is set.
+    pub fn render(&self, source: &str, file: &str, color: bool) -> String {
+        let paint = |code: &str, text: &str| match color {
+            true => format!("\x1b[{}m{}\x1b[0m", code, text),
+            false => text.to_string(),
+        };
+        let code = severity_style(self.severity);
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let bar = paint(BLUE, "|");
+        let mut out = format!(
+            "{}\n{}{} {}:{}:{}\n",
+            render_message(self.severity, &self.message, color),
+            gutter,
+            paint(BLUE, "-->"),
+            file,
+            self.line,
+            self.column
+        );
+
+        let start = self.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let text = source[line_start..].split('\n').next().unwrap_or_default();
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        // Tabs before the span are kept so the underline lines up.
+        let before = &source[line_start..start];
+        let padding: String = before
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end = self.span.end.clamp(start, line_start + text.len());
+        let carets = "^".repeat(source[start..end].chars().count().max(1));
+        out += &format!("{} {}\n", gutter, bar);
+        out += &format!("{} {} {}\n", paint(BLUE, &number), bar, text);
+        out += &format!("{} {} {}{}\n", gutter, bar, padding, paint(code, &carets));
+        if let Some(note) = &self.note {
+            out += &format!(
+                "{} {} {}: {}\n",
+                gutter,
+                paint(BLUE, "="),
+                paint(CYAN, "note"),
+                note
+            );
+        }
+        out
+    }
+}
+
+/// A message with no place in the source, like `error: message`.
+pub fn render_message(severity: Severity, message: &str, color: bool) -> String {
+    let name = match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    match color {
+        true => format!(
+            "\x1b[{}m{}\x1b[0m\x1b[{}m: {}\x1b[0m",
+            severity_style(severity),
+            name,
+            BOLD,
+            message
+        ),
+        false => format!("{}: {}", name, message),
+    }
+}
+
+fn severity_style(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+    }
+}
+
+// ANSI styles: bold, and bold in a color.
+const BOLD: &str = "1";
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const BLUE: &str = "1;34";
+const CYAN: &str = "1;36";
+
+#[derive(Debug, Error)]
+pub enum ColorError {
+    #[error("Invalid color setting '{0}', expected auto, always or never")]
+    InvalidColor(String),
+}
+
+/// Whether to color terminal output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = ColorError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(ColorError::InvalidColor(other.to_string())),
+        }
+    }
+}
+
+/// 1-based column, in characters, where `span` starts within `source`.
+pub fn column(source: &str, span: Span) -> usize {
+    let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    source[line_start..span.start].chars().count() + 1
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line,
+            self.column,
+            severity,
+            self.text()
+        )
+    }
+}
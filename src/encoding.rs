@@ -0,0 +1,107 @@
+/// The 32-bit machine word of an instruction, split into the fields of its
+/// format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Register {
+        opcode: u32,
+        rs: u32,
+        rt: u32,
+        rd: u32,
+        shamt: u32,
+        funct: u32,
+    },
+    Immediate {
+        opcode: u32,
+        rs: u32,
+        rt: u32,
+        imm: u32,
+    },
+    Jump {
+        opcode: u32,
+        target: u32,
+    },
+}
+
+/// One field of an encoded word, occupying bits `high..=low`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub high: u32,
+    pub low: u32,
+    pub value: u32,
+}
+
+impl Field {
+    pub fn width(&self) -> u32 {
+        self.high - self.low + 1
+    }
+
+    /// The field's bits, most significant first.
+    pub fn binary(&self) -> String {
+        format!("{:0width$b}", self.value, width = self.width() as usize)
+    }
+}
+
+const fn field(name: &'static str, high: u32, low: u32, value: u32) -> Field {
+    Field {
+        name,
+        high,
+        low,
+        value,
+    }
+}
+
+impl Encoding {
+    /// The format's name as used in textbooks: R, I or J.
+    pub fn format(&self) -> &'static str {
+        match self {
+            Encoding::Register { .. } => "R",
+            Encoding::Immediate { .. } => "I",
+            Encoding::Jump { .. } => "J",
+        }
+    }
+
+    /// The fields from the most significant bit down.
+    pub fn fields(&self) -> Vec<Field> {
+        match *self {
+            Encoding::Register {
+                opcode,
+                rs,
+                rt,
+                rd,
+                shamt,
+                funct,
+            } => vec![
+                field("opcode", 31, 26, opcode),
+                field("rs", 25, 21, rs),
+                field("rt", 20, 16, rt),
+                field("rd", 15, 11, rd),
+                field("shamt", 10, 6, shamt),
+                field("funct", 5, 0, funct),
+            ],
+            Encoding::Immediate {
+                opcode,
+                rs,
+                rt,
+                imm,
+            } => vec![
+                field("opcode", 31, 26, opcode),
+                field("rs", 25, 21, rs),
+                field("rt", 20, 16, rt),
+                field("immediate", 15, 0, imm),
+            ],
+            Encoding::Jump { opcode, target } => vec![
+                field("opcode", 31, 26, opcode),
+                field("target", 25, 0, target),
+            ],
+        }
+    }
+
+    /// Packs the fields into the machine word, masking each to its width.
+    pub fn word(&self) -> u32 {
+        self.fields().iter().fold(0, |word, field| {
+            let mask = u32::MAX >> (32 - field.width());
+            word | (field.value & mask) << field.low
+        })
+    }
+}
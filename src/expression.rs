@@ -0,0 +1,657 @@
+use thiserror::Error;
+
+use crate::{
+    address::Address,
+    debug_info::DebugInfo,
+    registers::{Register, RegisterError},
+    simulator::Simulator,
+};
+
+#[derive(Debug, Error)]
+pub enum ExpressionError {
+    #[error("Expected {0}")]
+    Expected(&'static str),
+    #[error("Unexpected '{0}'")]
+    Unexpected(String),
+    #[error("{0}")]
+    Register(#[from] RegisterError),
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("Unknown label '{0}'")]
+    UnknownLabel(String),
+    #[error("0x{0:08x} is not mapped")]
+    Unmapped(u32),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("{0} cannot be used in a constant")]
+    NotConstant(String),
+    #[error("Undefined constant '{0}'")]
+    UndefinedConstant(String),
+    #[error("Arithmetic overflow")]
+    Overflow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unary {
+    Negate,
+    Not,
+    LogicalNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binary {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+/// Width of a memory read: `byte(x)`, `half(x)` or `word(x)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    Byte,
+    Half,
+    Word,
+}
+
+impl Width {
+    fn bytes(self) -> u32 {
+        match self {
+            Width::Byte => 1,
+            Width::Half => 2,
+            Width::Word => 4,
+        }
+    }
+}
+
+/// A debugger expression over the machine state, such as `$sp+8`,
+/// `word(buf+4)` or `$t0 == 5 && $v0 != 0`. Arithmetic wraps at 32 bits;
+/// comparisons, division and `>>` treat values as signed; comparisons and
+/// logical operators give 1 or 0.
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Number(u32),
+    Register(Register),
+    Pc,
+    Hi,
+    Lo,
+    Label(String),
+    Load(Width, Box<Expression>),
+    Unary(Unary, Box<Expression>),
+    Binary(Binary, Box<Expression>, Box<Expression>),
+}
+
+/// What an expression can read.
+pub trait Environment {
+    fn register(&self, register: Register) -> u32;
+    fn pc(&self) -> u32;
+    fn hi(&self) -> u32;
+    fn lo(&self) -> u32;
+    fn label(&self, name: &str) -> Option<u32>;
+    fn load_byte(&self, address: u32) -> Option<u8>;
+}
+
+/// A simulator together with the labels of the program it runs.
+pub struct Machine<'a> {
+    pub simulator: &'a Simulator,
+    pub debug_info: &'a DebugInfo,
+}
+
+impl Environment for Machine<'_> {
+    fn register(&self, register: Register) -> u32 {
+        self.simulator.registers().get(register)
+    }
+
+    fn pc(&self) -> u32 {
+        self.simulator.pc().0
+    }
+
+    fn hi(&self) -> u32 {
+        self.simulator.registers().hi()
+    }
+
+    fn lo(&self) -> u32 {
+        self.simulator.registers().lo()
+    }
+
+    fn label(&self, name: &str) -> Option<u32> {
+        self.debug_info.label(name).map(|address| address.0)
+    }
+
+    fn load_byte(&self, address: u32) -> Option<u8> {
+        self.simulator.memory().read_byte(Address(address))
+    }
+}
+
+/// The simulator alone, for conditions whose labels were already
+/// resolved with [`Expression::resolve_labels`].
+impl Environment for Simulator {
+    fn register(&self, register: Register) -> u32 {
+        self.registers().get(register)
+    }
+
+    fn pc(&self) -> u32 {
+        self.pc().0
+    }
+
+    fn hi(&self) -> u32 {
+        self.registers().hi()
+    }
+
+    fn lo(&self) -> u32 {
+        self.registers().lo()
+    }
+
+    fn label(&self, _name: &str) -> Option<u32> {
+        None
+    }
+
+    fn load_byte(&self, address: u32) -> Option<u8> {
+        self.memory().read_byte(Address(address))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(u32),
+    Register(String),
+    Identifier(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 20] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "/",
+    "%", "~", "!",
+];
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let word_end = |from: usize| {
+            rest[from..]
+                .find(|c| !is_identifier(c))
+                .map_or(rest.len(), |end| from + end)
+        };
+        let len = if c.is_ascii_digit() {
+            let end = word_end(0);
+            let word = &rest[..end];
+            let value = if let Some(hex) = word.strip_prefix("0x") {
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(binary) = word.strip_prefix("0b") {
+                u32::from_str_radix(binary, 2).ok()
+            } else {
+                word.parse::<u32>().ok()
+            };
+            tokens.push(Token::Number(
+                value.ok_or_else(|| ExpressionError::Unexpected(word.to_string()))?,
+            ));
+            end
+        } else if c == '$' {
+            let end = word_end(1);
+            tokens.push(Token::Register(rest[..end].to_string()));
+            end
+        } else if is_identifier(c) {
+            let end = word_end(0);
+            tokens.push(Token::Identifier(rest[..end].to_string()));
+            end
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            1
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(*operator))
+                .ok_or_else(|| ExpressionError::Unexpected(c.to_string()))?;
+            tokens.push(Token::Operator(operator));
+            operator.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Binary operators from loosest to tightest binding.
+const PRECEDENCE: [&[(&str, Binary)]; 9] = [
+    &[("||", Binary::Or)],
+    &[("&&", Binary::And)],
+    &[
+        ("==", Binary::Equal),
+        ("!=", Binary::NotEqual),
+        ("<=", Binary::LessEqual),
+        (">=", Binary::GreaterEqual),
+        ("<", Binary::Less),
+        (">", Binary::Greater),
+    ],
+    &[("|", Binary::BitOr)],
+    &[("^", Binary::BitXor)],
+    &[("&", Binary::BitAnd)],
+    &[("<<", Binary::ShiftLeft), (">>", Binary::ShiftRight)],
+    &[("+", Binary::Add), ("-", Binary::Subtract)],
+    &[
+        ("*", Binary::Multiply),
+        ("/", Binary::Divide),
+        ("%", Binary::Remainder),
+    ],
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn expect_close(&mut self) -> Result<(), ExpressionError> {
+        match self.advance() {
+            Some(Token::Close) => Ok(()),
+            _ => Err(ExpressionError::Expected("')'")),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, ExpressionError> {
+        let Some(operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Operator(symbol)) = self.peek()
+            && let Some(&(_, operator)) = operators.iter().find(|(s, _)| s == symbol)
+        {
+            self.next += 1;
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        let operator = match self.peek() {
+            Some(Token::Operator("-")) => Unary::Negate,
+            Some(Token::Operator("~")) => Unary::Not,
+            Some(Token::Operator("!")) => Unary::LogicalNot,
+            _ => return self.primary(),
+        };
+        self.next += 1;
+        Ok(Expression::Unary(operator, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        match self.advance() {
+            Some(Token::Number(value)) => Ok(Expression::Number(value)),
+            Some(Token::Register(name)) => Ok(match name.as_str() {
+                "$pc" => Expression::Pc,
+                "$hi" => Expression::Hi,
+                "$lo" => Expression::Lo,
+                _ => Expression::Register(name.parse()?),
+            }),
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::Open) => {
+                let width = match name.as_str() {
+                    "byte" => Width::Byte,
+                    "half" => Width::Half,
+                    "word" => Width::Word,
+                    _ => return Err(ExpressionError::UnknownFunction(name)),
+                };
+                self.next += 1;
+                let address = self.binary(0)?;
+                self.expect_close()?;
+                Ok(Expression::Load(width, Box::new(address)))
+            }
+            Some(Token::Identifier(name)) => Ok(Expression::Label(name)),
+            Some(Token::Open) => {
+                let inner = self.binary(0)?;
+                self.expect_close()?;
+                Ok(inner)
+            }
+            Some(Token::Operator(symbol)) => Err(ExpressionError::Unexpected(symbol.to_string())),
+            Some(Token::Close) => Err(ExpressionError::Unexpected(")".to_string())),
+            None => Err(ExpressionError::Expected("a value")),
+        }
+    }
+}
+
+impl std::str::FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+        };
+        let expression = parser.binary(0)?;
+        match parser.advance() {
+            None => Ok(expression),
+            Some(Token::Close) => Err(ExpressionError::Unexpected(")".to_string())),
+            Some(_) => Err(ExpressionError::Expected("an operator")),
+        }
+    }
+}
+
+impl Expression {
+    /// Replaces every label with its address, so the expression can be
+    /// evaluated without the program's debug info.
+    pub fn resolve_labels(self, debug_info: &DebugInfo) -> Result<Expression, ExpressionError> {
+        let resolve = |operand: Box<Expression>| operand.resolve_labels(debug_info).map(Box::new);
+        Ok(match self {
+            Expression::Label(name) => match debug_info.label(&name) {
+                Some(address) => Expression::Number(address.0),
+                None => return Err(ExpressionError::UnknownLabel(name)),
+            },
+            Expression::Load(width, address) => Expression::Load(width, resolve(address)?),
+            Expression::Unary(operator, operand) => Expression::Unary(operator, resolve(operand)?),
+            Expression::Binary(operator, left, right) => {
+                Expression::Binary(operator, resolve(left)?, resolve(right)?)
+            }
+            leaf => leaf,
+        })
+    }
+
+    pub fn evaluate(&self, env: &impl Environment) -> Result<u32, ExpressionError> {
+        let value = match self {
+            Expression::Number(value) => *value,
+            Expression::Register(register) => env.register(*register),
+            Expression::Pc => env.pc(),
+            Expression::Hi => env.hi(),
+            Expression::Lo => env.lo(),
+            Expression::Label(name) => env
+                .label(name)
+                .ok_or_else(|| ExpressionError::UnknownLabel(name.clone()))?,
+            Expression::Load(width, address) => {
+                let address = address.evaluate(env)?;
+                let mut bytes = [0; 4];
+                for i in 0..width.bytes() {
+                    let byte = address.wrapping_add(i);
+                    bytes[i as usize] =
+                        env.load_byte(byte).ok_or(ExpressionError::Unmapped(byte))?;
+                }
+                u32::from_le_bytes(bytes)
+            }
+            Expression::Unary(operator, operand) => {
+                let value = operand.evaluate(env)?;
+                match operator {
+                    Unary::Negate => value.wrapping_neg(),
+                    Unary::Not => !value,
+                    Unary::LogicalNot => (value == 0) as u32,
+                }
+            }
+            Expression::Binary(Binary::Or, left, right) => {
+                (left.evaluate(env)? != 0 || right.evaluate(env)? != 0) as u32
+            }
+            Expression::Binary(Binary::And, left, right) => {
+                (left.evaluate(env)? != 0 && right.evaluate(env)? != 0) as u32
+            }
+            Expression::Binary(operator, left, right) => {
+                let (a, b) = (left.evaluate(env)?, right.evaluate(env)?);
+                let (signed_a, signed_b) = (a as i32, b as i32);
+                match operator {
+                    Binary::Equal => (a == b) as u32,
+                    Binary::NotEqual => (a != b) as u32,
+                    Binary::Less => (signed_a < signed_b) as u32,
+                    Binary::LessEqual => (signed_a <= signed_b) as u32,
+                    Binary::Greater => (signed_a > signed_b) as u32,
+                    Binary::GreaterEqual => (signed_a >= signed_b) as u32,
+                    Binary::BitOr => a | b,
+                    Binary::BitXor => a ^ b,
+                    Binary::BitAnd => a & b,
+                    Binary::ShiftLeft => a.wrapping_shl(b),
+                    Binary::ShiftRight => signed_a.wrapping_shr(b) as u32,
+                    Binary::Add => a.wrapping_add(b),
+                    Binary::Subtract => a.wrapping_sub(b),
+                    Binary::Multiply => a.wrapping_mul(b),
+                    Binary::Divide if b == 0 => return Err(ExpressionError::DivisionByZero),
+                    Binary::Divide => signed_a.wrapping_div(signed_b) as u32,
+                    Binary::Remainder if b == 0 => return Err(ExpressionError::DivisionByZero),
+                    Binary::Remainder => signed_a.wrapping_rem(signed_b) as u32,
+                    Binary::Or | Binary::And => unreachable!("short-circuited above"),
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    /// Evaluates an expression of numbers and the named constants
+    /// `constant` knows, as the assembler does for immediates. Unlike
+    /// [`Expression::evaluate`], arithmetic is exact: overflowing 64 bits
+    /// is an error rather than wrapping.
+    pub fn evaluate_constant(
+        &self,
+        constant: &impl Fn(&str) -> Option<i64>,
+    ) -> Result<i64, ExpressionError> {
+        let not_constant = |what: &str| Err(ExpressionError::NotConstant(what.to_string()));
+        let value = match self {
+            Expression::Number(value) => i64::from(*value),
+            Expression::Register(register) => return not_constant(register.name()),
+            Expression::Pc => return not_constant("$pc"),
+            Expression::Hi => return not_constant("$hi"),
+            Expression::Lo => return not_constant("$lo"),
+            Expression::Load(..) => return not_constant("A memory read"),
+            Expression::Label(name) => {
+                constant(name).ok_or_else(|| ExpressionError::UndefinedConstant(name.clone()))?
+            }
+            Expression::Unary(operator, operand) => {
+                let value = operand.evaluate_constant(constant)?;
+                match operator {
+                    Unary::Negate => value.checked_neg().ok_or(ExpressionError::Overflow)?,
+                    Unary::Not => !value,
+                    Unary::LogicalNot => (value == 0) as i64,
+                }
+            }
+            Expression::Binary(operator, left, right) => {
+                let (a, b) = (
+                    left.evaluate_constant(constant)?,
+                    right.evaluate_constant(constant)?,
+                );
+                let shift = || u32::try_from(b).ok().filter(|&shift| shift < 64);
+                let result = match operator {
+                    Binary::Or => Some((a != 0 || b != 0) as i64),
+                    Binary::And => Some((a != 0 && b != 0) as i64),
+                    Binary::Equal => Some((a == b) as i64),
+                    Binary::NotEqual => Some((a != b) as i64),
+                    Binary::Less => Some((a < b) as i64),
+                    Binary::LessEqual => Some((a <= b) as i64),
+                    Binary::Greater => Some((a > b) as i64),
+                    Binary::GreaterEqual => Some((a >= b) as i64),
+                    Binary::BitOr => Some(a | b),
+                    Binary::BitXor => Some(a ^ b),
+                    Binary::BitAnd => Some(a & b),
+                    Binary::ShiftLeft => {
+                        shift().and_then(|shift| Some(a << shift).filter(|s| s >> shift == a))
+                    }
+                    Binary::ShiftRight => shift().map(|shift| a >> shift),
+                    Binary::Add => a.checked_add(b),
+                    Binary::Subtract => a.checked_sub(b),
+                    Binary::Multiply => a.checked_mul(b),
+                    Binary::Divide | Binary::Remainder if b == 0 => {
+                        return Err(ExpressionError::DivisionByZero);
+                    }
+                    Binary::Divide => a.checked_div(b),
+                    Binary::Remainder => a.checked_rem(b),
+                };
+                result.ok_or(ExpressionError::Overflow)?
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `$t0` is 5, `$t1` is -2, `buf` is at 0x100 and holds the bytes
+    /// 0x100..0x108 as their own low bytes; everything else is zero or
+    /// unmapped.
+    struct Fixed;
+
+    impl Environment for Fixed {
+        fn register(&self, register: Register) -> u32 {
+            match register {
+                Register::T0 => 5,
+                Register::T1 => -2i32 as u32,
+                _ => 0,
+            }
+        }
+
+        fn pc(&self) -> u32 {
+            0x0040_0010
+        }
+
+        fn hi(&self) -> u32 {
+            7
+        }
+
+        fn lo(&self) -> u32 {
+            9
+        }
+
+        fn label(&self, name: &str) -> Option<u32> {
+            (name == "buf").then_some(0x100)
+        }
+
+        fn load_byte(&self, address: u32) -> Option<u8> {
+            (0x100..0x108).contains(&address).then_some(address as u8)
+        }
+    }
+
+    fn evaluate(text: &str) -> Result<u32, ExpressionError> {
+        text.parse::<Expression>()?.evaluate(&Fixed)
+    }
+
+    fn constant(text: &str) -> Result<i64, ExpressionError> {
+        let names = |name: &str| (name == "SIZE").then_some(16);
+        text.parse::<Expression>()?.evaluate_constant(&names)
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(evaluate("1 << 2 + 1").unwrap(), 8);
+        assert_eq!(evaluate("6 & 3 | 8").unwrap(), 10);
+        assert_eq!(evaluate("1 + 1 == 2 && 3 < 4").unwrap(), 1);
+        assert_eq!(evaluate("-2 * -3").unwrap(), 6);
+        assert_eq!(evaluate("~0").unwrap(), u32::MAX);
+        assert_eq!(evaluate("!5 || !0").unwrap(), 1);
+        assert_eq!(evaluate("0x10 + 0b11 - 7 % 4").unwrap(), 16);
+    }
+
+    #[test]
+    fn comparisons_division_and_shifts_are_signed() {
+        assert_eq!(evaluate("$t1 < 0").unwrap(), 1);
+        assert_eq!(evaluate("$t1 >= $t0").unwrap(), 0);
+        assert_eq!(evaluate("$t1 >> 1").unwrap(), -1i32 as u32);
+        assert_eq!(evaluate("$t0 / $t1").unwrap(), -2i32 as u32);
+        assert_eq!(evaluate("$t0 % $t1").unwrap(), 1);
+        assert_eq!(evaluate("0xffffffff + 2").unwrap(), 1);
+    }
+
+    #[test]
+    fn machine_state_is_read_through_the_environment() {
+        assert_eq!(evaluate("$t0 + $8").unwrap(), 10);
+        assert_eq!(evaluate("$pc + 4").unwrap(), 0x0040_0014);
+        assert_eq!(evaluate("$hi * 10 + $lo").unwrap(), 79);
+        assert_eq!(evaluate("byte(buf + 1)").unwrap(), 0x01);
+        assert_eq!(evaluate("half(buf + 2)").unwrap(), 0x0302);
+        assert_eq!(evaluate("word(buf + 4)").unwrap(), 0x0706_0504);
+        assert!(matches!(
+            evaluate("word(buf + 6)"),
+            Err(ExpressionError::Unmapped(0x108))
+        ));
+        assert!(matches!(
+            evaluate("missing + 1"),
+            Err(ExpressionError::UnknownLabel(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error_unless_short_circuited() {
+        assert!(matches!(
+            evaluate("1 / ($t0 - 5)"),
+            Err(ExpressionError::DivisionByZero)
+        ));
+        assert!(matches!(
+            evaluate("1 % 0"),
+            Err(ExpressionError::DivisionByZero)
+        ));
+        assert_eq!(evaluate("0 && 1 / 0").unwrap(), 0);
+        assert_eq!(evaluate("1 || 1 / 0").unwrap(), 1);
+    }
+
+    #[test]
+    fn malformed_expressions_do_not_parse() {
+        let error = |text: &str| text.parse::<Expression>().unwrap_err();
+        assert!(matches!(error(""), ExpressionError::Expected("a value")));
+        assert!(matches!(error("(1 + 2"), ExpressionError::Expected("')'")));
+        assert!(matches!(error("1 + 2)"), ExpressionError::Unexpected(_)));
+        assert!(matches!(
+            error("1 2"),
+            ExpressionError::Expected("an operator")
+        ));
+        assert!(matches!(error("1 + * 2"), ExpressionError::Unexpected(_)));
+        assert!(matches!(error("12ab"), ExpressionError::Unexpected(_)));
+        assert!(matches!(error("$nope"), ExpressionError::Register(_)));
+        assert!(matches!(
+            error("dword(4)"),
+            ExpressionError::UnknownFunction(name) if name == "dword"
+        ));
+    }
+
+    #[test]
+    fn constants_are_exact() {
+        assert_eq!(constant("SIZE * 4 - 1").unwrap(), 63);
+        assert_eq!(constant("0xffffffff + 1").unwrap(), 0x1_0000_0000);
+        assert_eq!(constant("-SIZE >> 2").unwrap(), -4);
+        assert!(matches!(
+            constant("0x7fffffff << 40"),
+            Err(ExpressionError::Overflow)
+        ));
+        assert!(matches!(
+            constant("1 << 64"),
+            Err(ExpressionError::Overflow)
+        ));
+        assert!(matches!(
+            constant("SIZE / 0"),
+            Err(ExpressionError::DivisionByZero)
+        ));
+        assert!(matches!(
+            constant("OTHER"),
+            Err(ExpressionError::UndefinedConstant(name)) if name == "OTHER"
+        ));
+        assert!(matches!(
+            constant("$t0 + 1"),
+            Err(ExpressionError::NotConstant(_))
+        ));
+        assert!(matches!(
+            constant("word(4)"),
+            Err(ExpressionError::NotConstant(_))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+
+use crate::{
+    lexer::{lines, split_words},
+    registers::Register,
+};
+
+/// Column mnemonics and directives start at, after any label.
+pub const MNEMONIC_COLUMN: usize = 8;
+/// Column operands start at.
+pub const OPERAND_COLUMN: usize = 16;
+/// Column comments after code start at, unless the code reaches past it.
+pub const COMMENT_COLUMN: usize = 40;
+
+/// Pads `text` with spaces to `column`, or by one space if it is already
+/// there. Empty text is indented to exactly `column`.
+fn pad(text: &mut String, column: usize) {
+    let width = text.chars().count();
+    let spaces = match width {
+        0 => column,
+        width => column.saturating_sub(width).max(1),
+    };
+    text.extend(std::iter::repeat_n(' ', spaces));
+}
+
+/// The conventional name for a register operand, including the base of a
+/// `offset($base)` operand. Anything else is kept as written.
+fn operand(word: &str) -> Cow<'_, str> {
+    let register = |name: &str| name.parse::<Register>().ok().map(Register::name);
+    if word.starts_with('$')
+        && let Some(name) = register(word)
+    {
+        return Cow::Borrowed(name);
+    }
+    if let Some((offset, base)) = word.split_once('(')
+        && let Some(base) = base.strip_suffix(')')
+        && let Some(name) = register(base)
+    {
+        return Cow::Owned(format!("{}({})", offset, name));
+    }
+    Cow::Borrowed(word)
+}
+
+/// Lays out the code of one line, without its comment: labels first,
+/// then the mnemonic and operands in their columns.
+fn format_code(text: &str) -> String {
+    let mut words = split_words(text).map(|(_, word)| word).peekable();
+    let mut labels = Vec::new();
+    while let Some(label) = words.next_if(|word| !word.starts_with('"') && word.ends_with(':')) {
+        labels.push(label);
+    }
+    let mut code = labels.join(" ");
+    let Some(operator) = words.next() else {
+        return code;
+    };
+    pad(&mut code, MNEMONIC_COLUMN);
+    code.push_str(operator);
+    let operands: Vec<_> = words.map(operand).collect();
+    if !operands.is_empty() {
+        pad(&mut code, OPERAND_COLUMN);
+        code.push_str(&operands.join(", "));
+    }
+    code
+}
+
+/// Formats assembly source: labels at the start of the line, mnemonics,
+/// operands and trailing comments aligned in columns, operands separated
+/// by `, `, and registers by their conventional names. Comments are kept,
+/// runs of blank lines become one, and the text ends with a newline.
+pub fn format(source: &str) -> String {
+    let mut formatted = String::with_capacity(source.len());
+    let mut blank = false;
+    for (line, raw) in lines(source).zip(source.lines()) {
+        let comment = raw[line.text.len()..].trim_end();
+        let mut text = format_code(line.text);
+        if text.is_empty() && comment.is_empty() {
+            blank = !formatted.is_empty();
+            continue;
+        }
+        if blank {
+            formatted.push('\n');
+            blank = false;
+        }
+        if !comment.is_empty() {
+            // A comment on its own line keeps to the margin or the code,
+            // whichever it was nearer.
+            match text.is_empty() {
+                true if raw.starts_with(char::is_whitespace) => pad(&mut text, MNEMONIC_COLUMN),
+                true => {}
+                false => pad(&mut text, COMMENT_COLUMN),
+            }
+            text.push_str(comment);
+        }
+        formatted.push_str(&text);
+        formatted.push('\n');
+    }
+    formatted
+}
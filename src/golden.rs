@@ -0,0 +1,142 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    RuntimeArgs,
+    assembler::Assembler,
+    simulator::{Simulator, SimulatorError},
+    snapshot::Snapshot,
+    syscall_io::SharedIo,
+};
+
+/// Instructions a program may run before its snapshot is taken anyway.
+pub const STEP_LIMIT: u64 = 1_000_000;
+
+/// How a program compared with its golden snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Passed,
+    /// What differs from the snapshot, one difference per entry.
+    Failed(Vec<String>),
+    /// There is no snapshot to compare with yet.
+    Missing,
+    /// The snapshot was written from this run.
+    Updated,
+}
+
+/// The golden snapshot file for `program`: its name with `.golden.json`
+/// in place of the extension.
+pub fn snapshot_path(program: &Path) -> PathBuf {
+    program.with_extension("golden.json")
+}
+
+/// Runs `program` and captures how it ended, its console output, the
+/// registers and every non-zero word of memory. Console input is read
+/// from the program's name with `.in` in place of the extension, if that
+/// exists.
+pub fn snapshot(program: &Path) -> Value {
+    let args = RuntimeArgs {
+        file: program.to_string_lossy().into_owned(),
+        ..RuntimeArgs::default()
+    };
+    let mut assembler = Assembler::new();
+    if let Err(err) = assembler.assemble(&args) {
+        let message = assembler
+            .diagnostics()
+            .last()
+            .map_or_else(|| err.to_string(), ToString::to_string);
+        return json!({ "assembler_error": message });
+    }
+
+    let mut simulator = Simulator::new(assembler.finish());
+    let io = SharedIo::new();
+    if let Ok(input) = fs::read_to_string(program.with_extension("in")) {
+        io.lock().input.extend(input.lines().map(str::to_string));
+    }
+    simulator.set_io(Box::new(io.clone()));
+    let result = match simulator.run_for(STEP_LIMIT) {
+        Ok(()) => "step limit reached".to_string(),
+        Err(SimulatorError::Exit(code)) => format!("exit {}", code),
+        Err(SimulatorError::NoMoreInstructions) => "dropped off bottom".to_string(),
+        Err(SimulatorError::ReturnedFromMain) => "returned from main".to_string(),
+        Err(err) => err.to_string(),
+    };
+
+    let output = std::mem::take(&mut io.lock().output);
+    let mut snapshot = Snapshot::capture(&simulator).to_json();
+    snapshot["result"] = result.into();
+    snapshot["steps"] = simulator.steps().into();
+    snapshot["output"] = output.into();
+    snapshot
+}
+
+/// Describes where `actual` differs from `expected`, naming each place by
+/// its path of keys.
+fn differences(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            let mut keys: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    path => format!("{}.{}", path, key),
+                };
+                let missing = Value::Null;
+                differences(
+                    &path,
+                    expected.get(key).unwrap_or(&missing),
+                    actual.get(key).unwrap_or(&missing),
+                    out,
+                );
+            }
+        }
+        (expected, actual) if expected != actual => {
+            out.push(format!("{}: expected {}, got {}", path, expected, actual))
+        }
+        _ => {}
+    }
+}
+
+/// Snapshots `program` and compares it with its golden file, or with
+/// `update` rewrites the golden file instead.
+pub fn check(program: &Path, update: bool) -> io::Result<Status> {
+    let actual = snapshot(program);
+    let path = snapshot_path(program);
+    if update {
+        fs::write(&path, serde_json::to_string_pretty(&actual)? + "\n")?;
+        return Ok(Status::Updated);
+    }
+    let expected: Value = match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Status::Missing),
+        Err(err) => return Err(err),
+    };
+    let mut found = Vec::new();
+    differences("", &expected, &actual, &mut found);
+    Ok(match found.is_empty() {
+        true => Status::Passed,
+        false => Status::Failed(found),
+    })
+}
+
+/// The `.asm` and `.s` programs in `directory`, by name.
+pub fn programs(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "asm" || extension == "s")
+        {
+            programs.push(path);
+        }
+    }
+    programs.sort();
+    Ok(programs)
+}
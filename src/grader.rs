@@ -0,0 +1,203 @@
+use serde_json::{Value, json};
+
+use crate::{
+    RuntimeArgs,
+    assembler::Assembler,
+    scheduler::{self, Deadline},
+    simulator::{Simulator, SimulatorError},
+    syscall_io::SharedIo,
+};
+
+/// Instructions a graded program may run before it is stopped, unless
+/// configured otherwise.
+pub const DEFAULT_STEP_LIMIT: u64 = 10_000_000;
+
+/// How leniently output is compared with the expected output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Comparison {
+    /// Ignore whitespace at the end of lines and of the output.
+    pub trailing_whitespace: bool,
+    /// Treat every run of spaces and tabs as one space, and ignore it at
+    /// either end of a line.
+    pub whitespace: bool,
+    pub blank_lines: bool,
+}
+
+impl Comparison {
+    /// The lines of `text` that take part in the comparison. A newline at
+    /// the very end never matters, as files usually have one.
+    fn lines(&self, text: &str) -> Vec<String> {
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let mut lines: Vec<String> = text
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .map(|line| match (self.whitespace, self.trailing_whitespace) {
+                (true, _) => line.split_whitespace().collect::<Vec<_>>().join(" "),
+                (false, true) => line.trim_end().to_string(),
+                (false, false) => line.to_string(),
+            })
+            .collect();
+        if self.blank_lines {
+            lines.retain(|line| !line.is_empty());
+        } else if self.trailing_whitespace || self.whitespace {
+            while lines.last().is_some_and(String::is_empty) {
+                lines.pop();
+            }
+        }
+        lines
+    }
+}
+
+/// How a graded run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    WrongOutput,
+    AssemblerError(String),
+    RuntimeError(String),
+    /// The program read more input than it was given.
+    InputExhausted,
+    StepLimit,
+    /// The program ran for longer than the wall-clock limit.
+    Timeout,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Pass => "pass",
+            Outcome::WrongOutput => "wrong_output",
+            Outcome::AssemblerError(_) => "assembler_error",
+            Outcome::RuntimeError(_) => "runtime_error",
+            Outcome::InputExhausted => "input_exhausted",
+            Outcome::StepLimit => "step_limit",
+            Outcome::Timeout => "timeout",
+        }
+    }
+}
+
+/// The result of grading one program.
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    pub outcome: Outcome,
+    pub steps: u64,
+    /// The program's exit code, if it finished.
+    pub exit_code: Option<u32>,
+    pub output: String,
+    /// Expected lines missing from the output as `-line`, and unexpected
+    /// ones as `+line`, in order.
+    pub diff: Vec<String>,
+}
+
+impl Verdict {
+    pub fn passed(&self) -> bool {
+        self.outcome == Outcome::Pass
+    }
+
+    pub fn to_json(&self) -> Value {
+        let error = match &self.outcome {
+            Outcome::AssemblerError(err) | Outcome::RuntimeError(err) => Some(err.as_str()),
+            _ => None,
+        };
+        json!({
+            "verdict": self.outcome.name(),
+            "steps": self.steps,
+            "exit_code": self.exit_code,
+            "error": error,
+            "output": self.output,
+            "diff": self.diff,
+        })
+    }
+}
+
+/// A line diff of `expected` against `actual`, from their longest common
+/// subsequence.
+fn diff(expected: &[String], actual: &[String]) -> Vec<String> {
+    let (n, m) = (expected.len(), actual.len());
+    // common[i][j]: common lines of expected[i..] and actual[j..].
+    let mut common = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = match expected[i] == actual[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+        } else if j == m || i < n && common[i + 1][j] >= common[i][j + 1] {
+            lines.push(format!("-{}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Assembles `args.file` and runs it with `input` as its console input
+/// for at most `step_limit` instructions and `args.timeout`, then compares
+/// what it printed with `expected`. Runs are deterministic except for the
+/// time syscall and timeouts.
+pub fn grade(
+    args: &RuntimeArgs,
+    input: &str,
+    expected: &str,
+    comparison: Comparison,
+    step_limit: u64,
+) -> Verdict {
+    let mut verdict = Verdict {
+        outcome: Outcome::Pass,
+        steps: 0,
+        exit_code: None,
+        output: String::new(),
+        diff: Vec::new(),
+    };
+    let mut assembler = Assembler::new();
+    if let Err(err) = assembler.assemble(args) {
+        // The diagnostic says where the error is.
+        let message = assembler
+            .diagnostics()
+            .last()
+            .map_or_else(|| err.to_string(), ToString::to_string);
+        verdict.outcome = Outcome::AssemblerError(message);
+        return verdict;
+    }
+
+    let mut simulator = Simulator::new(assembler.finish());
+    simulator.set_delay_slots(args.delay_slots);
+    simulator.set_mips64(args.mips64);
+    let io = SharedIo::new();
+    io.lock().input.extend(input.lines().map(str::to_string));
+    simulator.set_io(Box::new(io.clone()));
+
+    let deadline = args.timeout.map(Deadline::new);
+    let result = scheduler::run_within(&mut simulator, step_limit, deadline);
+    verdict.steps = simulator.steps();
+    verdict.output = std::mem::take(&mut io.lock().output);
+    match result {
+        Ok(()) => verdict.outcome = Outcome::StepLimit,
+        Err(SimulatorError::Exit(code)) => verdict.exit_code = Some(code),
+        Err(SimulatorError::NoMoreInstructions | SimulatorError::ReturnedFromMain) => {
+            verdict.exit_code = Some(0)
+        }
+        Err(SimulatorError::InputPending) => verdict.outcome = Outcome::InputExhausted,
+        Err(SimulatorError::Timeout(_)) => verdict.outcome = Outcome::Timeout,
+        Err(err) => verdict.outcome = Outcome::RuntimeError(err.to_string()),
+    }
+
+    verdict.diff = diff(
+        &comparison.lines(expected),
+        &comparison.lines(&verdict.output),
+    );
+    if verdict.outcome == Outcome::Pass && !verdict.diff.is_empty() {
+        verdict.outcome = Outcome::WrongOutput;
+    }
+    verdict
+}
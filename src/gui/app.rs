@@ -0,0 +1,1027 @@
+use std::{
+    any::Any,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+};
+
+use eframe::egui;
+use egui_dock::{DockArea, DockState};
+
+use crate::{
+    RuntimeArgs,
+    address::Address,
+    assembler::{Assembler, Segment},
+    debug_info::DebugInfo,
+    diagnostic::Diagnostic,
+    expression::Expression,
+    gui::{
+        bitmap::BitmapDisplay,
+        buffer::Buffer,
+        cache::CacheWindow,
+        console::ConsolePanel,
+        cp1::Cp1Panel,
+        diagnostics::DiagnosticsPanel,
+        dock::{self, Panels, Tab},
+        editor,
+        encoding::EncodingWindow,
+        files::{self, RecentFiles},
+        find::FindBar,
+        keyboard_display::KeyboardDisplayWindow,
+        memory::MemoryPanel,
+        pipeline::PipelinePanel,
+        registers::RegisterPanel,
+        session::{Session, SessionFile},
+        settings::{Settings, SettingsWindow},
+        shortcuts::Binding,
+        symbols::SymbolWindow,
+        toolbar::{self, Action},
+        watch::WatchPanel,
+    },
+    handle::{self, Command, Outcome, RunState, SimulatorHandle},
+    simulator::Simulator,
+    syscall_io::SharedIo,
+    trace::{TraceFormat, Tracer},
+};
+
+/// Something that would discard editor buffers, waiting on the user to
+/// decide what to do with unsaved changes.
+enum Pending {
+    Close(usize),
+    Quit,
+}
+
+pub struct MipsApp {
+    /// Open files, one per editor tab; there is always at least one.
+    buffers: Vec<Buffer>,
+    /// The buffer in the editor, which is the main file when assembling.
+    active: usize,
+    /// The buffer the machine was assembled from.
+    assembled: Option<usize>,
+    /// Counter for giving each buffer its own editor state.
+    next_buffer: u64,
+    recent: RecentFiles,
+    pending: Option<Pending>,
+    /// Set once the user has agreed to quit, so the close goes through.
+    quitting: bool,
+    /// A panic in the assembler or simulator, shown until dismissed.
+    crash: Option<String>,
+    /// Window title last sent, to only resend it when it changes.
+    title: String,
+    machine: Option<SimulatorHandle>,
+    debug_info: DebugInfo,
+    /// State seen last frame, to report transitions once.
+    last_state: Option<RunState>,
+    /// Instruction the views were last scrolled to, so they only follow the
+    /// PC when it moves.
+    followed_pc: Option<Address>,
+    /// Source line to scroll the editor to on the next frame.
+    reveal_line: Option<usize>,
+    /// Speed slider position in instructions per second.
+    speed_hz: f64,
+    /// Shade the editor gutter by how often each line has executed.
+    show_heat: bool,
+    io: SharedIo,
+    console: ConsolePanel,
+    memory: MemoryPanel,
+    registers: RegisterPanel,
+    cp1: Cp1Panel,
+    pipeline: PipelinePanel,
+    diagnostics: DiagnosticsPanel,
+    watch: WatchPanel,
+    symbols: SymbolWindow,
+    bitmap: BitmapDisplay,
+    cache: CacheWindow,
+    encoding: EncodingWindow,
+    keyboard_display: KeyboardDisplayWindow,
+    settings: Settings,
+    find: FindBar,
+    settings_window: SettingsWindow,
+    dock: DockState<Tab>,
+}
+
+impl MipsApp {
+    /// Creates the app with `file`'s path and contents in the editor, if
+    /// given, and otherwise picks up the session from the last run.
+    pub fn new(
+        ctx: &egui::Context,
+        file: Option<(PathBuf, String)>,
+        storage: Option<&dyn eframe::Storage>,
+    ) -> Self {
+        let mut recent = RecentFiles::load(storage);
+        let session = match file {
+            Some(_) => None,
+            None => Session::load(storage),
+        };
+        let (source, path) = match file {
+            Some((path, source)) => {
+                recent.push(&path);
+                (source, Some(path))
+            }
+            None => (String::new(), None),
+        };
+
+        let settings = Settings::load(storage);
+        settings.apply(ctx);
+
+        let mut app = Self {
+            buffers: vec![Buffer::new(egui::Id::new(("buffer", 0)), source, path)],
+            active: 0,
+            assembled: None,
+            next_buffer: 1,
+            recent,
+            pending: None,
+            quitting: false,
+            crash: None,
+            title: String::new(),
+            machine: None,
+            debug_info: DebugInfo::default(),
+            last_state: None,
+            followed_pc: None,
+            reveal_line: None,
+            speed_hz: toolbar::MAX_HZ,
+            show_heat: false,
+            io: SharedIo::new(),
+            console: ConsolePanel::default(),
+            memory: MemoryPanel::default(),
+            registers: RegisterPanel::default(),
+            cp1: Cp1Panel::default(),
+            pipeline: PipelinePanel::default(),
+            diagnostics: DiagnosticsPanel::default(),
+            watch: WatchPanel::default(),
+            symbols: SymbolWindow::default(),
+            bitmap: BitmapDisplay::default(),
+            cache: CacheWindow::default(),
+            encoding: EncodingWindow::default(),
+            keyboard_display: KeyboardDisplayWindow::default(),
+            settings,
+            find: FindBar::default(),
+            settings_window: SettingsWindow::default(),
+            dock: dock::load_layout(storage),
+        };
+        if let Some(session) = session {
+            app.restore_session(session);
+        }
+        app
+    }
+
+    /// Collects what to bring back next time. Only the session file carries
+    /// the layout; the eframe storage keeps it under its own key.
+    fn session(&self, include_layout: bool) -> Session {
+        let saved: Vec<_> = self
+            .buffers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, buffer)| Some((index, buffer.path.clone()?, buffer)))
+            .collect();
+        Session {
+            active: saved.iter().position(|&(index, ..)| index == self.active),
+            files: saved
+                .into_iter()
+                .map(|(_, path, buffer)| SessionFile {
+                    path,
+                    breakpoints: buffer.breakpoints.keys().copied().collect(),
+                    conditions: buffer
+                        .breakpoints
+                        .iter()
+                        .filter(|(_, condition)| !condition.is_empty())
+                        .map(|(&line, condition)| (line, condition.clone()))
+                        .collect(),
+                })
+                .collect(),
+            watches: self.watch.expressions(),
+            memory: self.memory.view(),
+            layout: include_layout.then(|| self.dock.clone()),
+        }
+    }
+
+    fn restore_session(&mut self, session: Session) {
+        let mut opened = Vec::new();
+        for mut file in session.files {
+            self.open(file.path.clone());
+            let buffer = &mut self.buffers[self.active];
+            if buffer.path.as_ref() == Some(&file.path) {
+                buffer.breakpoints = file
+                    .breakpoints
+                    .into_iter()
+                    .map(|line| (line, file.conditions.remove(&line).unwrap_or_default()))
+                    .collect();
+                opened.push(Some(self.active));
+            } else {
+                opened.push(None);
+            }
+        }
+        if let Some(index) = session
+            .active
+            .and_then(|active| opened.get(active).copied().flatten())
+        {
+            self.active = index;
+        }
+        self.watch.extend(session.watches);
+        self.memory.restore(session.memory);
+        if let Some(layout) = session.layout {
+            self.dock = layout;
+        }
+        self.sync_breakpoints();
+    }
+
+    fn message(&self, text: &str) {
+        self.io.lock().output.push_str(text);
+    }
+
+    fn clear_input(&self) {
+        let mut buffers = self.io.lock();
+        buffers.input.clear();
+        buffers.waiting_for_input = false;
+    }
+
+    /// Drops the machine and everything derived from the assembled buffer.
+    fn drop_machine(&mut self) {
+        self.stop_trace();
+        self.machine = None;
+        self.last_state = None;
+        self.assembled = None;
+        self.debug_info = DebugInfo::default();
+        self.registers.clear();
+        self.cp1.clear();
+        self.memory.clear();
+        self.watch.clear();
+    }
+
+    /// Assembles the active buffer as the main file. `.include`s resolve
+    /// next to it and read open buffers in preference to the disk, so
+    /// unsaved edits are picked up.
+    fn assemble(&mut self, ctx: &egui::Context) {
+        self.drop_machine();
+        self.clear_input();
+
+        let main = &self.buffers[self.active];
+        let assembled = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut assembler = Assembler::new();
+            if let Some(directory) = main.path.as_deref().and_then(|path| path.parent()) {
+                assembler.set_include_dir(directory);
+            }
+            for buffer in &self.buffers {
+                if let Some(path) = &buffer.path {
+                    assembler.add_buffer(path, buffer.source.clone());
+                }
+            }
+            let result = assembler.assemble_source(&main.source, &RuntimeArgs::default());
+            let diagnostics = assembler.diagnostics().to_vec();
+            (result.map(|()| assembler.finish()), diagnostics)
+        }));
+        let (result, diagnostics) = match assembled {
+            Ok(assembled) => assembled,
+            Err(payload) => {
+                self.crashed("assembler", payload.as_ref());
+                return;
+            }
+        };
+        self.diagnostics.set(main, diagnostics);
+        match result {
+            Ok(program) => {
+                self.debug_info = program.debug_info().clone();
+                let ctx = ctx.clone();
+                let (io, delay_slots) = (self.io.clone(), self.settings.delay_slots);
+                let machine = match panic::catch_unwind(AssertUnwindSafe(|| {
+                    SimulatorHandle::spawn(program, io, delay_slots, move || ctx.request_repaint())
+                })) {
+                    Ok(machine) => machine,
+                    Err(payload) => {
+                        self.crashed("simulator", payload.as_ref());
+                        return;
+                    }
+                };
+                self.assembled = Some(self.active);
+                machine.send(Command::SetSpeed(toolbar::speed_from_slider(self.speed_hz)));
+                self.machine = Some(machine);
+                self.sync_breakpoints();
+                self.sync_watchpoints();
+                self.message("Assemble: operation completed successfully.\n");
+            }
+            Err(err) => {
+                self.message(&format!("Assembler Error: {}\n", err));
+                if let Some(location) = self.dock.find_tab(&Tab::Diagnostics) {
+                    self.dock.set_active_tab(location);
+                }
+            }
+        }
+    }
+
+    /// Reports a panic in `component` and drops the machine, which may have
+    /// been left half-built.
+    fn crashed(&mut self, component: &str, payload: &(dyn Any + Send)) {
+        let message = format!(
+            "The {} crashed: {}",
+            component,
+            handle::panic_message(payload)
+        );
+        self.drop_machine();
+        self.message(&format!("\n{}\n", message));
+        self.crash = Some(message);
+    }
+
+    /// Shows the last crash until dismissed.
+    fn crash_dialog(&mut self, ctx: &egui::Context) {
+        let Some(message) = &self.crash else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::Modal::new(egui::Id::new("crash")).show(ctx, |ui| {
+            ui.heading("Internal error");
+            ui.label(message);
+            ui.label("This is a bug in the simulator; the machine was reset.");
+            if ui.button("OK").clicked() {
+                dismissed = true;
+            }
+        });
+        if dismissed {
+            self.crash = None;
+        }
+    }
+
+    /// Shows a diagnostic's line in the editor with its span selected.
+    fn jump_to(&mut self, diagnostic: &Diagnostic, ctx: &egui::Context) {
+        let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| Some(buffer.id) == self.diagnostics.buffer())
+        else {
+            return;
+        };
+        self.active = index;
+        self.reveal_line = Some(diagnostic.line);
+        let buffer = &self.buffers[index];
+        if buffer.source.len() >= diagnostic.span.end {
+            let range = self.diagnostics.char_range(diagnostic);
+            editor::select(ctx, buffer.id, &range);
+            ctx.memory_mut(|memory| memory.request_focus(buffer.id));
+        }
+    }
+
+    /// Parses a breakpoint condition typed in the editor. Blank and invalid
+    /// conditions give an unconditional breakpoint, so a typo cannot make
+    /// one silently stop never.
+    fn condition(&self, text: &str) -> Option<Expression> {
+        if text.trim().is_empty() {
+            return None;
+        }
+        text.parse::<Expression>()
+            .and_then(|condition| condition.resolve_labels(&self.debug_info))
+            .ok()
+    }
+
+    fn sync_watchpoints(&self) {
+        if let Some(machine) = &self.machine {
+            machine.set_watchpoints(self.memory.watchpoints().iter().copied());
+        }
+    }
+
+    /// Sends the assembled buffer's breakpoints that land on an
+    /// instruction to the machine.
+    fn sync_breakpoints(&self) {
+        if let (Some(machine), Some(assembled)) = (&self.machine, self.assembled) {
+            let breakpoints =
+                self.buffers[assembled]
+                    .breakpoints
+                    .iter()
+                    .filter_map(|(&line, condition)| {
+                        let address = self.debug_info.address_of(line)?;
+                        Some((address, self.condition(condition)))
+                    });
+            machine.set_breakpoints(breakpoints);
+        }
+    }
+
+    fn perform(&mut self, action: Action, ctx: &egui::Context) {
+        if action == Action::Assemble {
+            self.assemble(ctx);
+            return;
+        }
+        let Some(machine) = &self.machine else {
+            return;
+        };
+
+        match action {
+            Action::StepBack => {
+                let simulator = machine.lock();
+                self.registers.record(&simulator);
+                self.cp1.record(&simulator);
+                self.watch.record(&simulator, &self.debug_info);
+                drop(simulator);
+                machine.send(Command::StepBack);
+            }
+            Action::Run | Action::Step | Action::StepOver | Action::StepOut => {
+                let simulator = machine.lock();
+                self.registers.record(&simulator);
+                self.cp1.record(&simulator);
+                self.watch.record(&simulator, &self.debug_info);
+                drop(simulator);
+                let command = match action {
+                    Action::Run => Command::Run,
+                    Action::StepOver => Command::StepOver,
+                    Action::StepOut => Command::StepOut,
+                    _ => Command::Step,
+                };
+                machine.send(command);
+            }
+            Action::Pause => machine.send(Command::Pause),
+            Action::SpeedChanged => {
+                machine.send(Command::SetSpeed(toolbar::speed_from_slider(self.speed_hz)))
+            }
+            Action::Reset => {
+                self.registers.clear();
+                self.cp1.clear();
+                self.watch.clear();
+                self.clear_input();
+                machine.send(Command::Reset);
+                self.message("\n-- machine reset --\n");
+            }
+            Action::Assemble => {}
+        }
+    }
+
+    /// Reports a program finishing the first frame its state is seen.
+    fn report_transitions(&mut self) {
+        if let Some(message) = self.machine.as_ref().and_then(SimulatorHandle::take_crash) {
+            let message = format!("The simulator crashed: {}", message);
+            self.message(&format!("\n{}\n", message));
+            self.crash = Some(message);
+        }
+        if let Some(hit) = self
+            .machine
+            .as_ref()
+            .and_then(SimulatorHandle::take_watch_hit)
+        {
+            self.message(&format!("\n-- watchpoint {}: {} --\n", hit.watchpoint, hit));
+        }
+        let state = self.machine.as_ref().map(SimulatorHandle::state);
+        if state == self.last_state {
+            return;
+        }
+
+        if let Some(RunState::Finished(outcome)) = &state {
+            let message = match outcome {
+                Outcome::Exited(_) => "\n-- program is finished running --\n".to_string(),
+                Outcome::DroppedOffBottom => {
+                    "\n-- program is finished running (dropped off bottom) --\n".to_string()
+                }
+                Outcome::ReturnedFromMain => {
+                    "\n-- program is finished running (returned from main) --\n".to_string()
+                }
+                Outcome::Error(err) => format!("\nSimulator Error: {}\n", err),
+            };
+            self.message(&message);
+        }
+        self.last_state = state;
+    }
+
+    fn is_modified(&self) -> bool {
+        self.buffers.iter().any(Buffer::is_modified)
+    }
+
+    /// Opens a new tab, taking over the active one if it is still blank.
+    fn new_buffer(&mut self, source: String, path: Option<PathBuf>) {
+        let id = egui::Id::new(("buffer", self.next_buffer));
+        self.next_buffer += 1;
+        let buffer = Buffer::new(id, source, path);
+        if self.buffers[self.active].is_blank() && self.assembled != Some(self.active) {
+            self.buffers[self.active] = buffer;
+        } else {
+            self.buffers.push(buffer);
+            self.active = self.buffers.len() - 1;
+        }
+    }
+
+    fn close_buffer(&mut self, index: usize) {
+        match self.assembled {
+            Some(assembled) if assembled == index => self.drop_machine(),
+            Some(assembled) if assembled > index => self.assembled = Some(assembled - 1),
+            _ => {}
+        }
+        self.buffers.remove(index);
+        if self.active > index || self.active == self.buffers.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        if self.buffers.is_empty() {
+            self.new_buffer(String::new(), None);
+        }
+    }
+
+    /// Switches to `path`'s tab, opening it in a new one if needed.
+    fn open(&mut self, path: PathBuf) {
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.path.as_ref() == Some(&path))
+        {
+            self.active = index;
+            return;
+        }
+        match fs::read_to_string(&path) {
+            Ok(source) => {
+                self.recent.push(&path);
+                self.new_buffer(source, Some(path));
+            }
+            Err(err) => {
+                self.recent.remove(&path);
+                self.message(&format!("Could not open {}: {}\n", path.display(), err));
+            }
+        }
+    }
+
+    /// Saves buffer `index` to `path`, returning whether it succeeded.
+    fn save_to(&mut self, index: usize, path: PathBuf) -> bool {
+        let buffer = &mut self.buffers[index];
+        match fs::write(&path, &buffer.source) {
+            Ok(()) => {
+                self.recent.push(&path);
+                buffer.saved_source = buffer.source.clone();
+                buffer.path = Some(path);
+                true
+            }
+            Err(err) => {
+                self.message(&format!("Could not save {}: {}\n", path.display(), err));
+                false
+            }
+        }
+    }
+
+    /// Saves buffer `index` to its file, asking for one if there is none yet.
+    fn save(&mut self, index: usize) -> bool {
+        let path = self.buffers[index].path.clone();
+        match path.or_else(|| files::pick_save(None)) {
+            Some(path) => self.save_to(index, path),
+            None => false,
+        }
+    }
+
+    fn save_as(&mut self, index: usize) -> bool {
+        match files::pick_save(self.buffers[index].path.as_deref()) {
+            Some(path) => self.save_to(index, path),
+            None => false,
+        }
+    }
+
+    /// Modified buffers that `pending` would discard.
+    fn unsaved(&self, pending: &Pending) -> Vec<usize> {
+        let affected = match pending {
+            Pending::Close(index) => *index..*index + 1,
+            Pending::Quit => 0..self.buffers.len(),
+        };
+        affected
+            .filter(|&index| self.buffers[index].is_modified())
+            .collect()
+    }
+
+    /// Carries out `pending` now, or asks first if it would discard unsaved changes.
+    fn request(&mut self, pending: Pending, ctx: &egui::Context) {
+        if self.unsaved(&pending).is_empty() {
+            self.proceed(pending, ctx);
+        } else {
+            self.pending = Some(pending);
+        }
+    }
+
+    fn proceed(&mut self, pending: Pending, ctx: &egui::Context) {
+        match pending {
+            Pending::Close(index) => self.close_buffer(index),
+            Pending::Quit => {
+                self.quitting = true;
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            }
+        }
+    }
+
+    /// Asks whether to save before a pending action discards buffers.
+    fn unsaved_changes_dialog(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+
+        let unsaved = self.unsaved(pending);
+        let names: Vec<_> = unsaved
+            .iter()
+            .map(|&index| self.buffers[index].name())
+            .collect();
+        let mut choice = None;
+        egui::Modal::new(egui::Id::new("unsaved_changes")).show(ctx, |ui| {
+            ui.heading("Unsaved changes");
+            ui.label(format!("Save changes to {} first?", names.join(", ")));
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Don't Save").clicked() {
+                    choice = Some(false);
+                }
+                if ui.button("Cancel").clicked() {
+                    self.pending = None;
+                }
+            });
+        });
+
+        let proceed = match choice {
+            Some(true) => unsaved.into_iter().all(|index| self.save(index)),
+            Some(false) => true,
+            None => false,
+        };
+        if proceed && let Some(pending) = self.pending.take() {
+            self.proceed(pending, ctx);
+        }
+    }
+
+    /// Opens every source file dropped onto the window in its own tab.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let hovering = ctx.input(|input| !input.raw.hovered_files.is_empty());
+        if hovering {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("file_drop"),
+            ));
+            let rect = ctx.content_rect();
+            painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(160));
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop a .asm or .s file to open it",
+                egui::TextStyle::Heading.resolve(&ctx.style()),
+                egui::Color32::WHITE,
+            );
+        }
+
+        let dropped: Vec<_> = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .filter(|path| files::is_source(path))
+                .collect()
+        });
+        for path in dropped {
+            self.open(path);
+        }
+    }
+
+    fn update_title(&mut self, ctx: &egui::Context) {
+        let buffer = &self.buffers[self.active];
+        let name = buffer.name();
+        let modified = if buffer.is_modified() { "*" } else { "" };
+        let title = format!("{}{} - MIPS Simulator", name, modified);
+        if title != self.title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+    }
+
+    /// Runs the debugger command bound to a pressed shortcut, unless the
+    /// settings window is capturing a new one.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if self.settings_window.is_recording() {
+            return;
+        }
+        let Some(binding) = self.settings.shortcuts.pressed(ctx) else {
+            return;
+        };
+        let action = match binding {
+            Binding::Continue => Action::Run,
+            Binding::Pause => Action::Pause,
+            Binding::StepOver => Action::StepOver,
+            Binding::StepInto => Action::Step,
+            Binding::StepOut => Action::StepOut,
+            Binding::ToggleBreakpoint => {
+                self.toggle_breakpoint(ctx);
+                return;
+            }
+        };
+        self.perform(action, ctx);
+    }
+
+    /// Toggles a breakpoint on the editor's cursor line.
+    fn toggle_breakpoint(&mut self, ctx: &egui::Context) {
+        let buffer = &mut self.buffers[self.active];
+        let Some(line) = editor::cursor_line(ctx, buffer.id, &buffer.source) else {
+            return;
+        };
+        if buffer.breakpoints.remove(&line).is_none() {
+            buffer.breakpoints.insert(line, String::new());
+        }
+        self.sync_breakpoints();
+    }
+
+    /// Starts tracing the assembled program to a file, or stops and
+    /// reports whether the trace was written.
+    fn trace_menu(&mut self, ui: &mut egui::Ui) {
+        let tracing = self
+            .machine
+            .as_ref()
+            .is_some_and(SimulatorHandle::is_tracing);
+        if tracing {
+            if ui.button("Stop Trace").clicked() {
+                self.stop_trace();
+            }
+            return;
+        }
+        let start = ui
+            .add_enabled(self.machine.is_some(), egui::Button::new("Start Trace…"))
+            .on_disabled_hover_text("Assemble a program first")
+            .clicked();
+        if !start {
+            return;
+        }
+        let Some(path) = files::pick_save_trace(self.buffers[self.active].path.as_deref()) else {
+            return;
+        };
+        match Tracer::create(&path, TraceFormat::for_path(&path)) {
+            Ok(tracer) => {
+                if let Some(machine) = &self.machine {
+                    machine.set_trace(Some(tracer));
+                }
+                self.message(&format!("-- tracing to {} --\n", path.display()));
+            }
+            Err(err) => self.message(&format!(
+                "Could not create trace {}: {}\n",
+                path.display(),
+                err
+            )),
+        }
+    }
+
+    fn stop_trace(&mut self) {
+        let Some(tracer) = self
+            .machine
+            .as_ref()
+            .and_then(|machine| machine.set_trace(None))
+        else {
+            return;
+        };
+        match tracer.finish() {
+            Ok(()) => self.message("-- trace stopped --\n"),
+            Err(err) => self.message(&format!("Could not write trace: {}\n", err)),
+        }
+    }
+
+    fn menu_bar(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        egui::MenuBar::new().ui(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if ui.button("New").clicked() {
+                    self.new_buffer(String::new(), None);
+                }
+                if ui.button("Open…").clicked()
+                    && let Some(path) = files::pick_open(self.buffers[self.active].path.as_deref())
+                {
+                    self.open(path);
+                }
+                ui.add_enabled_ui(!self.recent.paths().is_empty(), |ui| {
+                    ui.menu_button("Open Recent", |ui| {
+                        let mut chosen = None;
+                        for path in self.recent.paths() {
+                            if ui.button(path.display().to_string()).clicked() {
+                                chosen = Some(path.clone());
+                            }
+                        }
+                        if let Some(path) = chosen {
+                            self.open(path);
+                        }
+                    });
+                });
+                ui.separator();
+                if ui.button("Save").clicked() {
+                    self.save(self.active);
+                }
+                if ui.button("Save As…").clicked() {
+                    self.save_as(self.active);
+                }
+                if ui.button("Close").clicked() {
+                    self.request(Pending::Close(self.active), &ctx);
+                }
+                ui.separator();
+                if ui.button("Open Session…").clicked()
+                    && let Some(path) =
+                        files::pick_open_session(self.buffers[self.active].path.as_deref())
+                {
+                    match Session::read(&path) {
+                        Ok(session) => self.restore_session(session),
+                        Err(err) => self.message(&format!(
+                            "Could not open session {}: {}\n",
+                            path.display(),
+                            err
+                        )),
+                    }
+                }
+                if ui.button("Save Session…").clicked()
+                    && let Some(path) =
+                        files::pick_save_session(self.buffers[self.active].path.as_deref())
+                    && let Err(err) = self.session(true).write(&path)
+                {
+                    self.message(&format!(
+                        "Could not save session {}: {}\n",
+                        path.display(),
+                        err
+                    ));
+                }
+                ui.separator();
+                if ui.button("Settings…").clicked() {
+                    self.settings_window.open = true;
+                }
+                ui.separator();
+                if ui.button("Quit").clicked() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+            ui.menu_button("Edit", |ui| self.find.menu(ui));
+            ui.menu_button("View", |ui| {
+                for tab in Tab::ALL.into_iter().filter(|&tab| tab != Tab::Editor) {
+                    let mut open = self.dock.find_tab(&tab).is_some();
+                    if ui.checkbox(&mut open, tab.title()).changed() {
+                        dock::toggle_tab(&mut self.dock, tab);
+                    }
+                }
+                if ui.button("Reset Layout").clicked() {
+                    self.dock = dock::default_layout();
+                }
+                ui.separator();
+                ui.checkbox(&mut self.symbols.open, "Symbol Table");
+                ui.checkbox(&mut self.show_heat, "Execution Heat")
+                    .on_hover_text("Shade lines by how often they ran since the last reset");
+            });
+            ui.menu_button("Tools", |ui| {
+                ui.checkbox(&mut self.bitmap.open, "Bitmap Display");
+                ui.checkbox(&mut self.keyboard_display.open, "Keyboard and Display MMIO");
+                ui.checkbox(&mut self.cache.open, "Cache Simulator");
+                ui.separator();
+                self.trace_menu(ui);
+            });
+        });
+    }
+}
+
+impl eframe::App for MipsApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if ctx.input(|input| input.viewport().close_requested())
+            && !self.quitting
+            && self.is_modified()
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.pending = Some(Pending::Quit);
+        }
+        self.handle_dropped_files(ctx);
+        self.unsaved_changes_dialog(ctx);
+        self.crash_dialog(ctx);
+        self.handle_shortcuts(ctx);
+        self.settings_window.show(ctx, &mut self.settings);
+        self.update_title(ctx);
+        self.report_transitions();
+
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            self.menu_bar(ui);
+            let can_step_back = self
+                .machine
+                .as_ref()
+                .is_some_and(SimulatorHandle::can_step_back);
+            if let Some(action) = toolbar::show(
+                ui,
+                self.last_state.as_ref(),
+                can_step_back,
+                &mut self.speed_hz,
+            ) {
+                self.perform(action, ctx);
+            }
+        });
+
+        if let Some(symbol) = self.symbols.show(ctx, &self.debug_info) {
+            match symbol.segment {
+                Segment::Text => {
+                    if let Some(assembled) = self.assembled {
+                        self.active = assembled;
+                    }
+                    self.reveal_line = Some(symbol.line);
+                }
+                Segment::Data => self.memory.reveal(symbol.address),
+            }
+        }
+
+        let machine = self.machine.as_ref();
+        let (submitted, breakpoints_changed, close, jump, current_pc) = {
+            let mut simulator = machine.map(SimulatorHandle::lock);
+
+            // Only follow the PC while stopped; it moves too fast to track
+            // while running.
+            let current_pc = simulator.as_deref().map(Simulator::pc).filter(|_| {
+                matches!(
+                    self.last_state,
+                    Some(RunState::Paused | RunState::WaitingForInput { .. })
+                )
+            });
+            let follow = current_pc.is_some() && current_pc != self.followed_pc;
+            let current_line = current_pc
+                .and_then(|pc| self.debug_info.line_of(pc))
+                .filter(|_| self.assembled == Some(self.active));
+            let heat = simulator
+                .as_deref()
+                .and_then(Simulator::profile)
+                .filter(|_| self.show_heat && self.assembled == Some(self.active))
+                .map(|profile| profile.by_line(&self.debug_info))
+                .unwrap_or_default();
+
+            self.bitmap.show(ctx, simulator.as_deref());
+            self.keyboard_display.show(ctx, simulator.as_deref_mut());
+            self.cache.show(ctx, simulator.as_deref_mut());
+            self.encoding.show(ctx, simulator.as_deref());
+            // Timing is only modelled while someone is looking at it, as it
+            // keeps the worker off the faster threaded backend.
+            if let Some(simulator) = simulator.as_deref_mut()
+                && self.dock.find_tab(&Tab::Pipeline).is_some()
+            {
+                self.pipeline.attach(simulator);
+            }
+
+            let mut panels = Panels {
+                buffers: &mut self.buffers,
+                active: &mut self.active,
+                settings: &self.settings,
+                find: &mut self.find,
+                debug_info: &self.debug_info,
+                simulator: simulator.as_deref(),
+                io: &self.io,
+                console: &mut self.console,
+                memory: &mut self.memory,
+                registers: &mut self.registers,
+                cp1: &mut self.cp1,
+                pipeline: &mut self.pipeline,
+                diagnostics: &mut self.diagnostics,
+                watch: &mut self.watch,
+                current_pc,
+                current_line,
+                heat,
+                follow,
+                scroll_to: self.reveal_line.take().or(current_line.filter(|_| follow)),
+                submitted: None,
+                inspected: None,
+                edited_register: None,
+                patch: None,
+                breakpoints_changed: false,
+                close: None,
+                jump: None,
+            };
+            DockArea::new(&mut self.dock)
+                .style(egui_dock::Style::from_egui(ctx.style().as_ref()))
+                .show(ctx, &mut panels);
+            if panels.inspected.is_some() {
+                self.encoding.address = panels.inspected;
+            }
+            let outputs = (
+                panels.submitted,
+                panels.breakpoints_changed,
+                panels.close,
+                panels.jump,
+                current_pc,
+            );
+            let (edited_register, patch) = (panels.edited_register, panels.patch);
+            // Written while the simulator is still locked, so the edit lands
+            // before the next step.
+            if let Some((target, value)) = edited_register
+                && let Some(simulator) = simulator.as_deref_mut()
+            {
+                simulator.registers_mut().write(target, value);
+            }
+            if let Some(patch) = patch
+                && let Some(simulator) = simulator.as_deref_mut()
+            {
+                let result = simulator.patch(patch.address, &patch.bytes, patch.allow_text);
+                self.memory.patched(&patch, result);
+            }
+            outputs
+        };
+        self.followed_pc = current_pc;
+
+        if let Some(index) = close {
+            self.request(Pending::Close(index), ctx);
+        }
+        if let Some(diagnostic) = jump {
+            self.jump_to(&diagnostic, ctx);
+        }
+        if let Some(line) = submitted {
+            self.message(&format!("{}\n", line));
+            self.io.lock().input.push_back(line);
+            if let Some(machine) = &self.machine {
+                machine.send(Command::InputReady);
+            }
+        }
+        if breakpoints_changed {
+            self.sync_breakpoints();
+        }
+        if self.memory.take_watchpoints_changed() {
+            self.sync_watchpoints();
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        dock::save_layout(storage, &self.dock);
+        self.recent.save(storage);
+        self.settings.save(storage);
+        self.session(false).save(storage);
+    }
+}
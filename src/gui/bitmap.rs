@@ -0,0 +1,147 @@
+use eframe::egui::{self, Color32, ColorImage, TextureHandle, TextureOptions};
+
+use crate::{address::Address, simulator::Simulator};
+
+const PIXEL_SIZES: [usize; 6] = [1, 2, 4, 8, 16, 32];
+const DISPLAY_SIZES: [usize; 6] = [64, 128, 256, 512, 1024, 2048];
+/// The base addresses MARS offers, with the name of what lives there.
+const BASE_ADDRESSES: [(u32, &str); 5] = [
+    (0x1001_0000, "static data"),
+    (0x1000_0000, "global data"),
+    (0x1000_8000, "$gp"),
+    (0x1004_0000, "heap"),
+    (0xffff_0000, "memory map"),
+];
+
+/// A MARS-style bitmap display: each unit of `unit_width` by `unit_height`
+/// screen pixels shows one word of memory as `0x00RRGGBB`, in row-major
+/// order from `base`.
+pub struct BitmapDisplay {
+    pub open: bool,
+    unit_width: usize,
+    unit_height: usize,
+    display_width: usize,
+    display_height: usize,
+    base: Address,
+    texture: Option<TextureHandle>,
+    /// Reused between frames to avoid reallocating the framebuffer copy.
+    buffer: Vec<u8>,
+}
+
+impl Default for BitmapDisplay {
+    fn default() -> Self {
+        Self {
+            open: false,
+            unit_width: 8,
+            unit_height: 8,
+            display_width: 512,
+            display_height: 256,
+            base: Address(BASE_ADDRESSES[0].0),
+            texture: None,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+fn size_combo(ui: &mut egui::Ui, label: &str, value: &mut usize, options: &[usize]) {
+    egui::ComboBox::from_label(label)
+        .selected_text(value.to_string())
+        .show_ui(ui, |ui| {
+            for &option in options {
+                ui.selectable_value(value, option, option.to_string());
+            }
+        });
+}
+
+impl BitmapDisplay {
+    fn units(&self) -> (usize, usize) {
+        (
+            (self.display_width / self.unit_width).max(1),
+            (self.display_height / self.unit_height).max(1),
+        )
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("bitmap_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                size_combo(ui, "Unit width", &mut self.unit_width, &PIXEL_SIZES);
+                size_combo(ui, "Unit height", &mut self.unit_height, &PIXEL_SIZES);
+                ui.end_row();
+                size_combo(ui, "Display width", &mut self.display_width, &DISPLAY_SIZES);
+                size_combo(
+                    ui,
+                    "Display height",
+                    &mut self.display_height,
+                    &DISPLAY_SIZES,
+                );
+                ui.end_row();
+            });
+
+        let name = |base: u32| {
+            BASE_ADDRESSES
+                .iter()
+                .find(|(address, _)| *address == base)
+                .map_or_else(String::new, |(address, name)| {
+                    format!("0x{:08x} ({})", address, name)
+                })
+        };
+        egui::ComboBox::from_label("Base address")
+            .selected_text(name(self.base.0))
+            .show_ui(ui, |ui| {
+                for (address, _) in BASE_ADDRESSES {
+                    ui.selectable_value(&mut self.base, Address(address), name(address));
+                }
+            });
+    }
+
+    /// Copies the framebuffer out of memory into the display texture.
+    fn refresh(&mut self, ctx: &egui::Context, simulator: Option<&Simulator>) {
+        let (width, height) = self.units();
+        self.buffer.resize(width * height * 4, 0);
+        match simulator {
+            Some(simulator) => simulator.memory().read_into(self.base, &mut self.buffer),
+            None => self.buffer.fill(0),
+        }
+
+        let pixels = self
+            .buffer
+            .chunks_exact(4)
+            .map(|word| Color32::from_rgb(word[2], word[1], word[0]))
+            .collect();
+        let image = ColorImage::new([width, height], pixels);
+
+        match &mut self.texture {
+            Some(texture) => texture.set(image, TextureOptions::NEAREST),
+            None => {
+                self.texture =
+                    Some(ctx.load_texture("bitmap_display", image, TextureOptions::NEAREST))
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, simulator: Option<&Simulator>) {
+        if !self.open {
+            return;
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Bitmap Display")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.settings(ui);
+                ui.separator();
+
+                self.refresh(ctx, simulator);
+                if let Some(texture) = &self.texture {
+                    let size = egui::vec2(self.display_width as f32, self.display_height as f32);
+                    egui::ScrollArea::both()
+                        .max_width(ctx.content_rect().width() * 0.8)
+                        .max_height(ctx.content_rect().height() * 0.8)
+                        .show(ui, |ui| ui.image((texture.id(), size)));
+                }
+            });
+        self.open = open;
+    }
+}
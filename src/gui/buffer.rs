@@ -0,0 +1,80 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use eframe::egui;
+
+/// A source file open in an editor tab.
+pub struct Buffer {
+    /// Identifies the buffer's text edit, so cursor and undo history stay
+    /// with the buffer when switching tabs.
+    pub id: egui::Id,
+    pub source: String,
+    /// File the buffer was loaded from or last saved to.
+    pub path: Option<PathBuf>,
+    /// Contents as of the last load or save, to detect changes.
+    pub saved_source: String,
+    /// Source lines with a breakpoint, whether or not they hold code, and
+    /// the condition typed for each, empty when it always stops.
+    pub breakpoints: BTreeMap<usize, String>,
+}
+
+impl Buffer {
+    pub fn new(id: egui::Id, source: String, path: Option<PathBuf>) -> Self {
+        Self {
+            id,
+            saved_source: source.clone(),
+            source,
+            path,
+            breakpoints: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.source != self.saved_source
+    }
+
+    /// A fresh buffer that can be replaced by the next file opened.
+    pub fn is_blank(&self) -> bool {
+        self.path.is_none() && self.source.is_empty() && !self.is_modified()
+    }
+
+    pub fn name(&self) -> Cow<'_, str> {
+        self.path
+            .as_deref()
+            .and_then(Path::file_name)
+            .map_or("untitled".into(), |name| name.to_string_lossy())
+    }
+}
+
+/// Draws a row of tabs, one per buffer, switching `active` when one is
+/// clicked. Returns a buffer whose close button was clicked.
+pub fn tabs(ui: &mut egui::Ui, buffers: &[Buffer], active: &mut usize) -> Option<usize> {
+    let mut close = None;
+    egui::ScrollArea::horizontal()
+        .id_salt("buffer_tabs")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (index, buffer) in buffers.iter().enumerate() {
+                    let modified = if buffer.is_modified() { "*" } else { "" };
+                    let label = format!("{}{}", buffer.name(), modified);
+                    let response = ui.selectable_label(*active == index, label);
+                    let response = match &buffer.path {
+                        Some(path) => response.on_hover_text(path.display().to_string()),
+                        None => response,
+                    };
+                    if response.clicked() {
+                        *active = index;
+                    }
+                    if response.middle_clicked() || ui.small_button("×").clicked() {
+                        close = Some(index);
+                    }
+                    ui.add_space(6.0);
+                }
+            });
+        });
+    ui.separator();
+    close
+}
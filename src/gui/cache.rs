@@ -0,0 +1,215 @@
+use eframe::egui::{self, Color32, RichText};
+
+use crate::{
+    cache::{AccessKind, Cache, CacheConfig, Policy},
+    simulator::Simulator,
+};
+
+const CACHE_SIZES: [u32; 8] = [64, 128, 256, 512, 1024, 2048, 4096, 8192];
+const BLOCK_SIZES: [u32; 7] = [4, 8, 16, 32, 64, 128, 256];
+const HIT_COLOR: Color32 = Color32::from_rgb(60, 160, 80);
+const MISS_COLOR: Color32 = Color32::from_rgb(200, 70, 60);
+/// Accesses listed under the sets.
+const RECENT: usize = 16;
+
+fn size_combo(ui: &mut egui::Ui, label: &str, value: &mut u32, options: &[u32]) {
+    egui::ComboBox::from_label(label)
+        .selected_text(format!("{} B", value))
+        .show_ui(ui, |ui| {
+            for &option in options {
+                ui.selectable_value(value, option, format!("{} B", option));
+            }
+        });
+}
+
+/// The cache simulator tool: a configurable cache fed by the running
+/// program's fetches, loads and stores, showing its sets and ways, the
+/// outcome of recent accesses and the running hit rate.
+#[derive(Default)]
+pub struct CacheWindow {
+    pub open: bool,
+    config: CacheConfig,
+}
+
+impl CacheWindow {
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("cache_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                size_combo(ui, "Cache size", &mut self.config.size, &CACHE_SIZES);
+                size_combo(ui, "Block size", &mut self.config.block_size, &BLOCK_SIZES);
+                ui.end_row();
+
+                // Keep the associativity valid when the cache shrinks.
+                let blocks = (self.config.size / self.config.block_size).max(1);
+                self.config.associativity = self.config.associativity.min(blocks);
+                let way_name = |ways: u32| match ways {
+                    1 => "direct mapped".to_string(),
+                    ways if ways == blocks => format!("{}-way (fully associative)", ways),
+                    ways => format!("{}-way", ways),
+                };
+                egui::ComboBox::from_label("Associativity")
+                    .selected_text(way_name(self.config.associativity))
+                    .show_ui(ui, |ui| {
+                        let options = (0..=blocks.ilog2()).map(|power| 1 << power);
+                        for ways in options {
+                            ui.selectable_value(
+                                &mut self.config.associativity,
+                                ways,
+                                way_name(ways),
+                            );
+                        }
+                    });
+                egui::ComboBox::from_label("Replacement")
+                    .selected_text(self.config.policy.name())
+                    .show_ui(ui, |ui| {
+                        for policy in Policy::ALL {
+                            ui.selectable_value(&mut self.config.policy, policy, policy.name());
+                        }
+                    });
+                ui.end_row();
+            });
+    }
+
+    /// Attaches a cache with the current configuration to `simulator`,
+    /// starting over whenever the configuration changes.
+    fn attach(&self, simulator: &mut Simulator, restart: bool) -> Result<(), String> {
+        if !restart && simulator.cache().map(Cache::config) == Some(&self.config) {
+            return Ok(());
+        }
+        match Cache::new(self.config) {
+            Ok(cache) => {
+                simulator.set_cache(Some(cache));
+                Ok(())
+            }
+            Err(err) => {
+                simulator.set_cache(None);
+                Err(err.to_string())
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, simulator: Option<&mut Simulator>) {
+        if !self.open {
+            if let Some(simulator) = simulator {
+                simulator.set_cache(None);
+            }
+            return;
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Cache Simulator")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                self.settings(ui);
+                let restart = ui.button("Reset statistics").clicked();
+                ui.separator();
+
+                let Some(simulator) = simulator else {
+                    ui.label("Assemble a program to simulate its cache accesses.");
+                    return;
+                };
+                if let Err(err) = self.attach(simulator, restart) {
+                    ui.colored_label(MISS_COLOR, err);
+                    return;
+                }
+                if let Some(cache) = simulator.cache() {
+                    statistics(ui, cache);
+                    ui.separator();
+                    sets(ui, cache);
+                    ui.separator();
+                    recent(ui, cache);
+                }
+            });
+        self.open = open;
+    }
+}
+
+fn statistics(ui: &mut egui::Ui, cache: &Cache) {
+    ui.horizontal(|ui| {
+        ui.label(format!("{} accesses", cache.accesses()));
+        ui.colored_label(HIT_COLOR, format!("{} hits", cache.hits()));
+        ui.colored_label(MISS_COLOR, format!("{} misses", cache.misses()));
+    });
+    ui.add(
+        egui::ProgressBar::new(cache.hit_rate() as f32)
+            .text(format!("hit rate {:.1}%", cache.hit_rate() * 100.0)),
+    );
+}
+
+/// One row per set and one cell per way, showing the resident tag. The
+/// block touched by the last access is coloured by whether it hit.
+fn sets(ui: &mut egui::Ui, cache: &Cache) {
+    let last = cache.history().back();
+    let config = cache.config();
+
+    egui::ScrollArea::vertical()
+        .id_salt("cache_sets")
+        .max_height(240.0)
+        .show(ui, |ui| {
+            egui::Grid::new("cache_grid").striped(true).show(ui, |ui| {
+                ui.strong("Set");
+                for way in 0..config.associativity {
+                    ui.strong(format!("Way {}", way));
+                }
+                ui.end_row();
+
+                for set in 0..config.sets() {
+                    ui.monospace(set.to_string());
+                    for (way, block) in cache.set(set).iter().enumerate() {
+                        let text = if block.valid {
+                            RichText::new(format!("tag 0x{:x}", block.tag)).monospace()
+                        } else {
+                            RichText::new("empty").monospace().weak()
+                        };
+                        let text = match last {
+                            Some(access) if access.set == set && access.way == way as u32 => {
+                                let color = if access.hit { HIT_COLOR } else { MISS_COLOR };
+                                text.color(Color32::WHITE).background_color(color)
+                            }
+                            _ => text,
+                        };
+                        ui.label(text);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+fn recent(ui: &mut egui::Ui, cache: &Cache) {
+    let history = cache.history();
+    if history.is_empty() {
+        ui.label("Step the program to see its accesses.");
+        return;
+    }
+
+    egui::Grid::new("cache_accesses")
+        .striped(true)
+        .show(ui, |ui| {
+            for heading in ["Access", "Address", "Set", "Tag", "Result"] {
+                ui.strong(heading);
+            }
+            ui.end_row();
+
+            for access in history.iter().rev().take(RECENT) {
+                ui.label(match access.kind {
+                    AccessKind::Fetch => "fetch",
+                    AccessKind::Load => "load",
+                    AccessKind::Store => "store",
+                });
+                ui.monospace(format!("0x{:08x}", access.address.0));
+                ui.monospace(access.set.to_string());
+                ui.monospace(format!("0x{:x}", access.tag));
+                match (access.hit, access.evicted) {
+                    (true, _) => ui.colored_label(HIT_COLOR, "hit"),
+                    (false, Some(tag)) => {
+                        ui.colored_label(MISS_COLOR, format!("miss, evicted 0x{:x}", tag))
+                    }
+                    (false, None) => ui.colored_label(MISS_COLOR, "miss"),
+                };
+                ui.end_row();
+            }
+        });
+}
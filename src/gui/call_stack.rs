@@ -0,0 +1,56 @@
+use eframe::egui::{self, RichText};
+
+use crate::{address::Address, call_stack::symbolize, debug_info::DebugInfo, simulator::Simulator};
+
+/// Lists the calls that led to the PC, innermost first, each named after
+/// the nearest preceding text label and annotated with `$sp` at the time of
+/// the call.
+pub fn show(ui: &mut egui::Ui, simulator: Option<&Simulator>, debug_info: &DebugInfo) {
+    let Some(simulator) = simulator else {
+        ui.label("Assemble a program to inspect its calls.");
+        return;
+    };
+    let frames = simulator
+        .call_stack()
+        .map_or(&[][..], |call_stack| call_stack.frames());
+
+    let location = |address: Address| match debug_info.line_of(address) {
+        Some(line) => format!("{} (line {})", symbolize(debug_info, address), line),
+        None => symbolize(debug_info, address),
+    };
+
+    egui::ScrollArea::vertical()
+        .id_salt("call_stack_scroll")
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            egui::Grid::new("call_stack_grid")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in ["#", "Address", "Location", "$sp at call"] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+
+                    let pc = simulator.pc();
+                    ui.monospace("0");
+                    ui.monospace(format!("0x{:08x}", pc.0));
+                    ui.label(RichText::new(location(pc)).monospace().strong());
+                    ui.label("");
+                    ui.end_row();
+
+                    for (number, frame) in frames.iter().rev().enumerate() {
+                        ui.monospace((number + 1).to_string());
+                        ui.monospace(format!("0x{:08x}", frame.call_site.0));
+                        ui.monospace(location(frame.call_site))
+                            .on_hover_text(format!(
+                                "Called {}, returns to 0x{:08x}",
+                                symbolize(debug_info, frame.entry),
+                                frame.return_address.0
+                            ));
+                        ui.monospace(format!("0x{:08x}", frame.sp));
+                        ui.end_row();
+                    }
+                });
+        });
+}
@@ -0,0 +1,50 @@
+use eframe::egui;
+
+use crate::syscall_io::SharedIo;
+
+/// The Run I/O panel: program output plus an input line for read syscalls.
+#[derive(Default)]
+pub struct ConsolePanel {
+    input: String,
+}
+
+impl ConsolePanel {
+    /// Draws the panel and returns a line of input when the user submits one.
+    pub fn show(&mut self, ui: &mut egui::Ui, io: &SharedIo) -> Option<String> {
+        let (output, waiting) = {
+            let buffers = io.lock();
+            (buffers.output.clone(), buffers.waiting_for_input)
+        };
+
+        let mut submitted = None;
+        egui::TopBottomPanel::bottom("console_input")
+            .frame(egui::Frame::NONE)
+            .show_inside(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(if waiting { "Input ▶" } else { "Input" });
+                    let response = ui.add_enabled(
+                        waiting,
+                        egui::TextEdit::singleline(&mut self.input)
+                            .desired_width(f32::INFINITY)
+                            .hint_text("waiting for a read syscall"),
+                    );
+                    if waiting && !response.has_focus() && !response.lost_focus() {
+                        response.request_focus();
+                    }
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        submitted = Some(std::mem::take(&mut self.input));
+                    }
+                });
+            });
+
+        egui::ScrollArea::vertical()
+            .id_salt("console_scroll")
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                ui.monospace(output);
+            });
+
+        submitted
+    }
+}
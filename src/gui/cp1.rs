@@ -0,0 +1,122 @@
+use eframe::egui::{self, Color32, RichText};
+
+use crate::{
+    cp1::{Coprocessor1, Exception},
+    simulator::Simulator,
+};
+
+const CHANGED_COLOR: Color32 = Color32::from_rgb(255, 200, 60);
+
+/// Coprocessor 1's registers as raw bits, singles and even/odd doubles,
+/// with the FCSR broken into its fields. Like the integer register panel it
+/// remembers the state before the last step to highlight what changed.
+#[derive(Default)]
+pub struct Cp1Panel {
+    before: Option<Coprocessor1>,
+}
+
+impl Cp1Panel {
+    /// Call right before executing so the next frame can highlight changes.
+    pub fn record(&mut self, simulator: &Simulator) {
+        self.before = Some(simulator.cp1().clone());
+    }
+
+    /// Forget the previous state, e.g. after reassembling.
+    pub fn clear(&mut self) {
+        self.before = None;
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, simulator: Option<&Simulator>) {
+        let cp1 = simulator.map(|sim| sim.cp1().clone());
+        let before = self.before.as_ref().filter(|_| cp1.is_some());
+        let cp1 = cp1.unwrap_or_default();
+        let text = |s: String, changed: bool| {
+            let text = RichText::new(s).monospace();
+            if changed {
+                text.color(CHANGED_COLOR).strong()
+            } else {
+                text
+            }
+        };
+
+        egui::ScrollArea::vertical()
+            .id_salt("cp1_scroll")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                egui::Grid::new("cp1_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Name", "Hex", "Float", "Double"] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for n in 0..32 {
+                            let changed = before.is_some_and(|old| old.get(n) != cp1.get(n));
+                            ui.label(text(format!("$f{}", n), changed));
+                            ui.label(text(format!("0x{:08x}", cp1.get(n)), changed));
+                            ui.label(text(cp1.single(n).to_string(), changed));
+                            // The double spans this register and the next.
+                            match cp1.double(n) {
+                                Some(double) => {
+                                    let changed = before.is_some_and(|old| {
+                                        old.double_bits(n) != cp1.double_bits(n)
+                                    });
+                                    ui.label(text(double.to_string(), changed));
+                                }
+                                None => {
+                                    ui.label("");
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                let fcsr = cp1.fcsr();
+                let changed = before.is_some_and(|old| old.fcsr() != fcsr);
+                ui.label(text(format!("FCSR 0x{:08x}", fcsr.0), changed));
+                ui.label(format!("Rounding: {}", fcsr.rounding_mode().name()));
+                if fcsr.flush_to_zero() {
+                    ui.label("Flush to zero");
+                }
+
+                egui::Grid::new("fcsr_grid")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("");
+                        for exception in Exception::ALL {
+                            ui.strong(exception.letter().to_string())
+                                .on_hover_text(exception.name());
+                        }
+                        ui.end_row();
+
+                        for name in ["Cause", "Enables", "Flags"] {
+                            ui.label(name);
+                            for exception in Exception::ALL {
+                                let set = match name {
+                                    "Cause" => fcsr.cause(exception),
+                                    "Enables" => fcsr.enabled(exception),
+                                    _ => fcsr.flag(exception),
+                                };
+                                ui.monospace(if set { "1" } else { "0" });
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if fcsr.unimplemented() {
+                    ui.label("Cause: unimplemented operation");
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Condition codes");
+                    for cc in 0..8 {
+                        ui.monospace(if fcsr.condition(cc) { "1" } else { "0" })
+                            .on_hover_text(format!("cc {}", cc));
+                    }
+                });
+            });
+    }
+}
@@ -0,0 +1,106 @@
+use std::ops::Range;
+
+use eframe::egui::{self, Color32, Label, RichText, Sense};
+
+use crate::{
+    diagnostic::{Diagnostic, Severity},
+    gui::buffer::Buffer,
+};
+
+pub const ERROR_COLOR: Color32 = Color32::from_rgb(230, 80, 70);
+pub const WARNING_COLOR: Color32 = Color32::from_rgb(220, 170, 40);
+
+pub fn severity_color(severity: Severity) -> Color32 {
+    match severity {
+        Severity::Error => ERROR_COLOR,
+        Severity::Warning => WARNING_COLOR,
+    }
+}
+
+/// Errors and warnings from the last assemble, for the buffer that was
+/// assembled.
+#[derive(Default)]
+pub struct DiagnosticsPanel {
+    diagnostics: Vec<Diagnostic>,
+    /// The buffer assembled and its text at the time, so spans are only
+    /// trusted while it is unchanged.
+    buffer: Option<egui::Id>,
+    source: String,
+}
+
+impl DiagnosticsPanel {
+    pub fn set(&mut self, buffer: &Buffer, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+        self.buffer = Some(buffer.id);
+        self.source = buffer.source.clone();
+    }
+
+    pub fn buffer(&self) -> Option<egui::Id> {
+        self.buffer
+    }
+
+    /// Whether `buffer` is the one assembled and is unchanged since.
+    fn is_current(&self, buffer: &Buffer) -> bool {
+        self.buffer == Some(buffer.id) && self.source == buffer.source
+    }
+
+    /// Character range of a diagnostic's span in the assembled source.
+    pub fn char_range(&self, diagnostic: &Diagnostic) -> Range<usize> {
+        let start = self.source[..diagnostic.span.start].chars().count();
+        let len = self.source[diagnostic.span.start..diagnostic.span.end]
+            .chars()
+            .count();
+        start..start + len
+    }
+
+    /// Spans to underline in `buffer`, by character range, while its text
+    /// still matches what was assembled.
+    pub fn underlines(&self, buffer: &Buffer) -> Vec<(Range<usize>, Severity)> {
+        if !self.is_current(buffer) {
+            return Vec::new();
+        }
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| (self.char_range(diagnostic), diagnostic.severity))
+            .collect()
+    }
+
+    /// Lists the diagnostics and returns one the user clicked.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<Diagnostic> {
+        if self.diagnostics.is_empty() {
+            ui.label("No problems found by the last assemble.");
+            return None;
+        }
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_salt("diagnostics_scroll")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                for diagnostic in &self.diagnostics {
+                    let (icon, severity) = match diagnostic.severity {
+                        Severity::Error => ("⊗", "error"),
+                        Severity::Warning => ("⚠", "warning"),
+                    };
+                    let color = severity_color(diagnostic.severity);
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(icon).color(color));
+                        let text = format!(
+                            "{}:{}  {}: {}",
+                            diagnostic.line,
+                            diagnostic.column,
+                            severity,
+                            diagnostic.text()
+                        );
+                        let response = ui
+                            .add(Label::new(RichText::new(text).monospace()).sense(Sense::click()))
+                            .on_hover_text("Click to show in the editor");
+                        if response.clicked() {
+                            clicked = Some(diagnostic.clone());
+                        }
+                    });
+                }
+            });
+        clicked
+    }
+}
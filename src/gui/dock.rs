@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+
+use eframe::egui;
+use egui_dock::{DockState, NodeIndex, TabViewer};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::Address,
+    debug_info::DebugInfo,
+    diagnostic::Diagnostic,
+    gui::{
+        buffer::{self, Buffer},
+        call_stack,
+        console::ConsolePanel,
+        cp1::Cp1Panel,
+        diagnostics::DiagnosticsPanel,
+        editor,
+        find::FindBar,
+        heap,
+        memory::{MemoryPanel, Patch},
+        pipeline::PipelinePanel,
+        registers::RegisterPanel,
+        settings::Settings,
+        stack, text_segment,
+        watch::WatchPanel,
+    },
+    registers::RegisterId,
+    simulator::Simulator,
+    syscall_io::SharedIo,
+};
+
+const LAYOUT_KEY: &str = "dock_layout";
+
+/// A dockable panel of the main window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tab {
+    Editor,
+    Registers,
+    Stack,
+    TextSegment,
+    DataSegment,
+    Console,
+    Diagnostics,
+    Pipeline,
+    Heap,
+    Watch,
+    Coprocessor1,
+    CallStack,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 12] = [
+        Tab::Editor,
+        Tab::Registers,
+        Tab::Stack,
+        Tab::TextSegment,
+        Tab::DataSegment,
+        Tab::Console,
+        Tab::Diagnostics,
+        Tab::Pipeline,
+        Tab::Heap,
+        Tab::Watch,
+        Tab::Coprocessor1,
+        Tab::CallStack,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Tab::Editor => "Edit",
+            Tab::Registers => "Registers",
+            Tab::Stack => "Stack",
+            Tab::TextSegment => "Text Segment",
+            Tab::DataSegment => "Data Segment",
+            Tab::Console => "Run I/O",
+            Tab::Diagnostics => "Diagnostics",
+            Tab::Pipeline => "Pipeline",
+            Tab::Heap => "Heap",
+            Tab::Watch => "Watch",
+            Tab::Coprocessor1 => "Coproc 1",
+            Tab::CallStack => "Call Stack",
+        }
+    }
+}
+
+/// The editor in the middle, the integer and floating-point registers,
+/// stack and watches tabbed on the right, and the segment views and console
+/// along the bottom. The pipeline, heap and
+/// diagnostics are tabbed behind the text segment, data segment and console.
+pub fn default_layout() -> DockState<Tab> {
+    let mut dock = DockState::new(vec![Tab::Editor]);
+    let surface = dock.main_surface_mut();
+    let [editor, _] = surface.split_right(
+        NodeIndex::root(),
+        0.75,
+        vec![
+            Tab::Registers,
+            Tab::Coprocessor1,
+            Tab::Stack,
+            Tab::CallStack,
+            Tab::Watch,
+        ],
+    );
+    let [_, bottom] = surface.split_below(editor, 0.65, vec![Tab::TextSegment, Tab::Pipeline]);
+    let [_, data] = surface.split_right(bottom, 0.33, vec![Tab::DataSegment, Tab::Heap]);
+    surface.split_right(data, 0.5, vec![Tab::Console, Tab::Diagnostics]);
+    dock
+}
+
+/// The saved layout, or the default one if none was saved or it can't be read.
+pub fn load_layout(storage: Option<&dyn eframe::Storage>) -> DockState<Tab> {
+    storage
+        .and_then(|storage| eframe::get_value(storage, LAYOUT_KEY))
+        .unwrap_or_else(default_layout)
+}
+
+pub fn save_layout(storage: &mut dyn eframe::Storage, dock: &DockState<Tab>) {
+    eframe::set_value(storage, LAYOUT_KEY, dock);
+}
+
+/// Reopens `tab` in the focused pane, or closes it if it is open.
+pub fn toggle_tab(dock: &mut DockState<Tab>, tab: Tab) {
+    match dock.find_tab(&tab) {
+        Some(location) => {
+            dock.remove_tab(location);
+        }
+        None => dock.push_to_focused_leaf(tab),
+    }
+}
+
+/// Everything the panels draw from for one frame, and what they report back.
+pub struct Panels<'a> {
+    pub buffers: &'a mut Vec<Buffer>,
+    /// The buffer shown in the editor.
+    pub active: &'a mut usize,
+    pub settings: &'a Settings,
+    pub find: &'a mut FindBar,
+    pub debug_info: &'a DebugInfo,
+    pub simulator: Option<&'a Simulator>,
+    pub io: &'a SharedIo,
+    pub console: &'a mut ConsolePanel,
+    pub memory: &'a mut MemoryPanel,
+    pub registers: &'a mut RegisterPanel,
+    pub cp1: &'a mut Cp1Panel,
+    pub pipeline: &'a mut PipelinePanel,
+    pub diagnostics: &'a mut DiagnosticsPanel,
+    pub watch: &'a mut WatchPanel,
+    pub current_pc: Option<Address>,
+    /// Line of the active buffer the PC is on, if it is the one assembled.
+    pub current_line: Option<usize>,
+    /// Executions per line of the active buffer, for the editor gutter.
+    pub heat: BTreeMap<usize, u64>,
+    /// Whether the PC moved and the views following it should scroll.
+    pub follow: bool,
+    /// Editor line to scroll into view this frame.
+    pub scroll_to: Option<usize>,
+    pub submitted: Option<String>,
+    /// Instruction clicked in the text segment, to show its encoding.
+    pub inspected: Option<Address>,
+    pub breakpoints_changed: bool,
+    /// Register the user overwrote, to write into the simulator.
+    pub edited_register: Option<(RegisterId, u32)>,
+    /// Memory the user overwrote, to write into the simulator.
+    pub patch: Option<Patch>,
+    /// Buffer whose tab the user asked to close.
+    pub close: Option<usize>,
+    /// Diagnostic the user clicked, to show in the editor.
+    pub jump: Option<Diagnostic>,
+}
+
+impl TabViewer for Panels<'_> {
+    type Tab = Tab;
+
+    fn title(&mut self, tab: &mut Tab) -> egui::WidgetText {
+        tab.title().into()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, tab: &mut Tab) {
+        match tab {
+            Tab::Editor => {
+                self.close = buffer::tabs(ui, self.buffers, self.active);
+                let buffer = &mut self.buffers[*self.active];
+                let underlines = self.diagnostics.underlines(buffer);
+                self.breakpoints_changed |= editor::show(
+                    ui,
+                    buffer,
+                    self.current_line,
+                    self.scroll_to,
+                    self.settings,
+                    self.find,
+                    &underlines,
+                    &self.heat,
+                );
+            }
+            Tab::Registers => {
+                let paused = self.current_pc.is_some();
+                if let Some(edit) = self.registers.show(ui, self.simulator, paused) {
+                    self.edited_register = Some(edit);
+                }
+            }
+            Tab::Coprocessor1 => self.cp1.show(ui, self.simulator),
+            Tab::Stack => stack::show(ui, self.simulator, self.debug_info),
+            Tab::CallStack => call_stack::show(ui, self.simulator, self.debug_info),
+            Tab::TextSegment => {
+                if let Some(address) = text_segment::show(
+                    ui,
+                    self.simulator,
+                    self.debug_info,
+                    self.current_pc,
+                    self.follow,
+                ) {
+                    self.inspected = Some(address);
+                }
+            }
+            Tab::DataSegment => {
+                let paused = self.current_pc.is_some();
+                if let Some(patch) = self
+                    .memory
+                    .show(ui, self.simulator, self.debug_info, paused)
+                {
+                    self.patch = Some(patch);
+                }
+            }
+            Tab::Console => {
+                if let Some(line) = self.console.show(ui, self.io) {
+                    self.submitted = Some(line);
+                }
+            }
+            Tab::Diagnostics => self.jump = self.diagnostics.show(ui).or(self.jump.take()),
+            Tab::Pipeline => self.pipeline.show(ui, self.simulator),
+            Tab::Heap => heap::show(ui, self.simulator, self.debug_info),
+            Tab::Watch => self.watch.show(ui, self.simulator, self.debug_info),
+        }
+    }
+
+    /// The editor always stays open; everything else can be closed and
+    /// reopened from the View menu.
+    fn is_closeable(&self, tab: &Tab) -> bool {
+        *tab != Tab::Editor
+    }
+
+    /// Every panel scrolls its own contents.
+    fn scroll_bars(&self, _tab: &Tab) -> [bool; 2] {
+        [false, false]
+    }
+}
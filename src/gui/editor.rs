@@ -0,0 +1,357 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use eframe::egui::{
+    self, Color32, FontId, Key, Modifiers, Sense, TextBuffer,
+    text::{CCursor, CCursorRange},
+    text_edit::{TextEditOutput, TextEditState},
+};
+
+use crate::{
+    diagnostic::Severity,
+    expression::Expression,
+    gui::{
+        buffer::Buffer, diagnostics::severity_color, find::FindBar, highlight::highlight_cached,
+        settings::Settings,
+    },
+    reference,
+};
+
+const BREAKPOINT_COLOR: Color32 = Color32::from_rgb(220, 50, 50);
+const CONDITIONAL_COLOR: Color32 = Color32::from_rgb(230, 150, 40);
+const INVALID_COLOR: Color32 = Color32::from_rgb(230, 80, 70);
+const MATCH_COLOR: Color32 = Color32::from_rgba_unmultiplied_const(80, 160, 255, 40);
+const CURRENT_MATCH_COLOR: Color32 = Color32::from_rgba_unmultiplied_const(80, 160, 255, 110);
+/// Gutter shading of the most executed line; others scale down from it.
+const HEAT_COLOR: Color32 = Color32::from_rgb(240, 90, 40);
+pub const CURRENT_LINE_COLOR: Color32 = Color32::from_rgba_unmultiplied_const(230, 200, 60, 48);
+
+/// Character index of the start of 1-based `line` in `source`.
+fn line_start(source: &str, line: usize) -> usize {
+    source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(|text| text.chars().count())
+        .sum()
+}
+
+/// Draws line numbers with breakpoint markers, shaded by how often each
+/// line executed when `heat` has counts. Clicking a row toggles a
+/// breakpoint on that line, and right-clicking it edits the breakpoint's
+/// condition; returns whether any breakpoint changed.
+fn gutter(
+    ui: &mut egui::Ui,
+    font: &FontId,
+    line_count: usize,
+    breakpoints: &mut BTreeMap<usize, String>,
+    heat: &BTreeMap<usize, u64>,
+) -> bool {
+    let row_height = ui.fonts_mut(|fonts| fonts.row_height(font));
+    let digits = line_count.to_string().len();
+    let char_width = ui.fonts_mut(|fonts| fonts.glyph_width(font, '0'));
+    let marker_width = row_height;
+    let width = marker_width + char_width * digits as f32 + 6.0;
+
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(width, row_height * line_count as f32),
+        Sense::click(),
+    );
+    let painter = ui.painter_at(rect);
+    let text_color = ui.visuals().weak_text_color();
+    let hottest = heat.values().copied().max().unwrap_or(0);
+
+    for index in 0..line_count {
+        let line = index + 1;
+        let top = rect.top() + row_height * index as f32;
+        if let Some(&count) = heat.get(&line).filter(|_| hottest > 0) {
+            // Square root so lines that ran a few times still show.
+            let share = (count as f32 / hottest as f32).sqrt();
+            let row = egui::Rect::from_min_size(
+                egui::pos2(rect.left(), top),
+                egui::vec2(rect.width(), row_height),
+            );
+            painter.rect_filled(row, 0.0, HEAT_COLOR.gamma_multiply(0.15 + 0.6 * share));
+        }
+        if let Some(condition) = breakpoints.get(&line) {
+            let center = egui::pos2(rect.left() + marker_width / 2.0, top + row_height / 2.0);
+            let color = match condition.trim().is_empty() {
+                true => BREAKPOINT_COLOR,
+                false => CONDITIONAL_COLOR,
+            };
+            painter.circle_filled(center, row_height * 0.3, color);
+        }
+        painter.text(
+            egui::pos2(rect.right() - 4.0, top),
+            egui::Align2::RIGHT_TOP,
+            line.to_string(),
+            font.clone(),
+            text_color,
+        );
+    }
+
+    let pointer_line = || {
+        response
+            .interact_pointer_pos()
+            .map(|pos| ((pos.y - rect.top()) / row_height) as usize + 1)
+            .filter(|&line| line <= line_count)
+    };
+
+    if !heat.is_empty()
+        && let Some(line) = response
+            .hover_pos()
+            .map(|pos| ((pos.y - rect.top()) / row_height) as usize + 1)
+    {
+        let total: u64 = heat.values().sum();
+        let count = heat.get(&line).copied().unwrap_or(0);
+        response.clone().on_hover_text(format!(
+            "Executed {} times ({:.1}%)",
+            count,
+            count as f64 * 100.0 / total.max(1) as f64
+        ));
+    }
+
+    let mut changed = false;
+    if response.clicked()
+        && let Some(line) = pointer_line()
+    {
+        if breakpoints.remove(&line).is_none() {
+            breakpoints.insert(line, String::new());
+        }
+        changed = true;
+    }
+
+    // Remember which line the menu is for while it stays open.
+    let menu_line = response.id.with("condition_line");
+    if response.secondary_clicked()
+        && let Some(line) = pointer_line()
+    {
+        ui.data_mut(|data| data.insert_temp(menu_line, line));
+    }
+    response.context_menu(|ui| {
+        let Some(line) = ui.data(|data| data.get_temp::<usize>(menu_line)) else {
+            return;
+        };
+        ui.label(format!("Breakpoint condition for line {}", line));
+        let condition = breakpoints.entry(line).or_insert_with(|| {
+            changed = true;
+            String::new()
+        });
+        let field = ui.add(
+            egui::TextEdit::singleline(condition)
+                .hint_text("$t0 == 5 && word($sp) != 0")
+                .font(egui::TextStyle::Monospace)
+                .desired_width(240.0),
+        );
+        changed |= field.changed();
+        if !condition.trim().is_empty()
+            && let Err(err) = condition.parse::<Expression>()
+        {
+            ui.colored_label(INVALID_COLOR, format!("{}; stops unconditionally", err));
+        }
+        ui.weak("Stops only when the condition is non-zero");
+        if ui.input(|input| input.key_pressed(Key::Enter)) {
+            ui.close();
+        }
+    });
+    changed
+}
+
+/// Replaces the selection with spaces up to the next tab stop, so a
+/// Tab key press indents by `tab_width` columns instead of inserting `\t`.
+fn insert_tab(ctx: &egui::Context, id: egui::Id, source: &mut String, tab_width: usize) {
+    let Some(mut state) = TextEditState::load(ctx, id) else {
+        return;
+    };
+    let Some(range) = state.cursor.char_range() else {
+        return;
+    };
+
+    let selection = range.as_sorted_char_range();
+    let byte = source
+        .char_indices()
+        .nth(selection.start)
+        .map_or(source.len(), |(i, _)| i);
+    let line_start = source[..byte].rfind('\n').map_or(0, |i| i + 1);
+    let column = source[line_start..byte].chars().count();
+    let spaces = " ".repeat(tab_width - column % tab_width);
+
+    source.delete_char_range(selection.clone());
+    let end = selection.start + source.insert_text(&spaces, selection.start);
+    state
+        .cursor
+        .set_char_range(Some(CCursorRange::one(CCursor::new(end))));
+    state.store(ctx, id);
+}
+
+/// Selects `range`, in characters, as if the user had dragged over it.
+pub fn select(ctx: &egui::Context, id: egui::Id, range: &Range<usize>) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::two(
+        CCursor::new(range.start),
+        CCursor::new(range.end),
+    )));
+    state.store(ctx, id);
+}
+
+/// The 1-based line the text cursor of editor `id` is on.
+pub fn cursor_line(ctx: &egui::Context, id: egui::Id, source: &str) -> Option<usize> {
+    let cursor = TextEditState::load(ctx, id)?.cursor.char_range()?.primary;
+    Some(
+        source
+            .chars()
+            .take(cursor.index)
+            .filter(|&c| c == '\n')
+            .count()
+            + 1,
+    )
+}
+
+/// Shows the reference entry for the mnemonic, register or syscall code
+/// under the pointer.
+fn hover_tooltip(output: &TextEditOutput, pointer: egui::Pos2) {
+    let galley = &output.galley;
+    let cursor = galley.cursor_from_pos(pointer - output.galley_pos);
+    let text = galley.text();
+    let byte = text
+        .char_indices()
+        .nth(cursor.index)
+        .map_or(text.len(), |(byte, _)| byte);
+    let Some((span, topic)) = reference::lookup(text, byte) else {
+        return;
+    };
+
+    // The cursor snaps to the nearest character, so check the pointer is
+    // really over the word.
+    let chars = |end: usize| text[..end].chars().count();
+    let start = galley.pos_from_cursor(CCursor::new(chars(span.start)));
+    let end = galley.pos_from_cursor(CCursor::new(chars(span.end)));
+    let rect = egui::Rect::from_min_max(start.min, egui::pos2(end.max.x, start.max.y))
+        .translate(output.galley_pos.to_vec2());
+    if rect.contains(pointer) {
+        output.response.clone().on_hover_ui_at_pointer(|ui| {
+            ui.monospace(topic.to_string());
+        });
+    }
+}
+
+/// Draws the editor for `buffer` and returns whether its breakpoint set
+/// changed. `current_line` is highlighted, and `scroll_to` is scrolled into
+/// view. The find bar sits above the text and highlights its matches in
+/// it. `underlines` marks diagnostics by character range, and `heat` holds
+/// execution counts by line for the gutter.
+#[allow(clippy::too_many_arguments)]
+pub fn show(
+    ui: &mut egui::Ui,
+    buffer: &mut Buffer,
+    current_line: Option<usize>,
+    scroll_to: Option<usize>,
+    settings: &Settings,
+    find: &mut FindBar,
+    underlines: &[(Range<usize>, Severity)],
+    heat: &BTreeMap<usize, u64>,
+) -> bool {
+    let Buffer {
+        id,
+        source,
+        breakpoints,
+        ..
+    } = buffer;
+    let id = *id;
+    if ui.memory(|memory| memory.has_focus(id))
+        && ui.input_mut(|input| input.consume_key(Modifiers::NONE, Key::Tab))
+    {
+        insert_tab(ui.ctx(), id, source, settings.tab_width);
+    }
+
+    find.shortcuts(ui.ctx());
+    find.goto_dialog(ui.ctx(), source.split('\n').count());
+    find.show(ui, source);
+    let selection = find.take_selection();
+    let goto_line = find.take_goto_line();
+    if let Some(range) = &selection {
+        select(ui.ctx(), id, range);
+    }
+    if let Some(line) = goto_line {
+        let start = line_start(source, line);
+        select(ui.ctx(), id, &(start..start));
+        ui.memory_mut(|memory| memory.request_focus(id));
+    }
+    let (matches, current_match) = find.highlights(source);
+
+    let font = settings.editor_font();
+    let mut layouter = |ui: &egui::Ui, buffer: &dyn TextBuffer, wrap_width: f32| {
+        let mut job = highlight_cached(ui.ctx(), buffer.as_str(), &font);
+        job.wrap.max_width = wrap_width;
+        ui.fonts_mut(|fonts| fonts.layout_job(job))
+    };
+
+    let mut changed = false;
+    egui::ScrollArea::both()
+        .id_salt(id.with("scroll"))
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                let line_count = source.split('\n').count();
+                let left = ui.cursor().left();
+                changed = gutter(ui, &font, line_count, breakpoints, heat);
+                let output = egui::TextEdit::multiline(source)
+                    .id(id)
+                    .code_editor()
+                    .font(font.clone())
+                    .margin(egui::Margin::symmetric(4, 0))
+                    .desired_width(f32::INFINITY)
+                    .min_size(ui.available_size())
+                    .layouter(&mut layouter)
+                    .show(ui);
+
+                // Spans a whole source line, gutter included.
+                let line_rect = |line: usize| {
+                    let cursor = CCursor::new(line_start(output.galley.text(), line));
+                    let row = output
+                        .galley
+                        .pos_from_cursor(cursor)
+                        .translate(output.galley_pos.to_vec2());
+                    egui::Rect::from_x_y_ranges(left..=output.response.rect.right(), row.y_range())
+                };
+
+                // Covers a single-line range of characters.
+                let range_rect = |range: &Range<usize>| {
+                    let start = output.galley.pos_from_cursor(CCursor::new(range.start));
+                    let end = output.galley.pos_from_cursor(CCursor::new(range.end));
+                    egui::Rect::from_min_max(start.min, egui::pos2(end.max.x, start.max.y))
+                        .translate(output.galley_pos.to_vec2())
+                };
+
+                if let Some(line) = current_line.filter(|&line| line <= line_count) {
+                    ui.painter()
+                        .rect_filled(line_rect(line), 0.0, CURRENT_LINE_COLOR);
+                }
+                for range in &matches {
+                    let color = match Some(range) == current_match.as_ref() {
+                        true => CURRENT_MATCH_COLOR,
+                        false => MATCH_COLOR,
+                    };
+                    ui.painter().rect_filled(range_rect(range), 2.0, color);
+                }
+                for (range, severity) in underlines {
+                    let rect = range_rect(range);
+                    ui.painter().line_segment(
+                        [rect.left_bottom(), rect.right_bottom()],
+                        egui::Stroke::new(2.0, severity_color(*severity)),
+                    );
+                }
+
+                if let Some(pointer) = output.response.hover_pos() {
+                    hover_tooltip(&output, pointer);
+                }
+
+                if let Some(line) = scroll_to.or(goto_line).filter(|&line| line <= line_count) {
+                    ui.scroll_to_rect(line_rect(line), Some(egui::Align::Center));
+                }
+                if let Some(range) = &selection {
+                    ui.scroll_to_rect(range_rect(range), Some(egui::Align::Center));
+                }
+            });
+        });
+    changed
+}
@@ -0,0 +1,104 @@
+use eframe::egui::{self, Color32, RichText};
+
+use crate::{
+    address::Address, assembler::BASE_TEXT_ADDR, encoding::Field, registers::Register,
+    simulator::Simulator,
+};
+
+/// Background for each field in turn, so adjacent fields stand apart.
+const FIELD_COLORS: [Color32; 6] = [
+    Color32::from_rgb(150, 90, 170),
+    Color32::from_rgb(60, 130, 190),
+    Color32::from_rgb(50, 150, 120),
+    Color32::from_rgb(190, 140, 40),
+    Color32::from_rgb(170, 80, 80),
+    Color32::from_rgb(100, 110, 120),
+];
+
+/// What a field's value means, for the register fields.
+fn meaning(field: &Field) -> String {
+    match field.name {
+        "rs" | "rt" | "rd" => Register::ALL
+            .get(field.value as usize)
+            .map_or_else(String::new, |register| register.name().to_string()),
+        "immediate" => format!("{}", field.value as u16 as i16),
+        _ => String::new(),
+    }
+}
+
+/// Breaks the machine word of an instruction clicked in the text segment
+/// into its fields, with their bit positions.
+#[derive(Default)]
+pub struct EncodingWindow {
+    /// The instruction being inspected; the window is open while set.
+    pub address: Option<Address>,
+}
+
+impl EncodingWindow {
+    pub fn show(&mut self, ctx: &egui::Context, simulator: Option<&Simulator>) {
+        let Some(address) = self.address else {
+            return;
+        };
+        let instruction = address
+            .word_index(BASE_TEXT_ADDR)
+            .and_then(|index| simulator?.instructions().get(index));
+        let Some(&instruction) = instruction else {
+            self.address = None;
+            return;
+        };
+
+        let encoding = instruction.encoding();
+        let fields = encoding.fields();
+        let mut open = true;
+        egui::Window::new("Instruction Encoding")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.monospace(format!("0x{:08x}  {}", address.0, instruction));
+                ui.monospace(format!(
+                    "0x{:08x}  ({}-format)",
+                    encoding.word(),
+                    encoding.format()
+                ));
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 2.0;
+                    for (field, color) in fields.iter().zip(FIELD_COLORS) {
+                        ui.vertical(|ui| {
+                            ui.label(
+                                RichText::new(field.binary())
+                                    .monospace()
+                                    .color(Color32::WHITE)
+                                    .background_color(color),
+                            );
+                            ui.small(format!("{}..{}", field.high, field.low));
+                        });
+                    }
+                });
+                ui.separator();
+
+                egui::Grid::new("encoding_fields")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Field", "Bits", "Binary", "Value", ""] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for (field, color) in fields.iter().zip(FIELD_COLORS) {
+                            ui.label(RichText::new(field.name).color(color).strong());
+                            ui.monospace(format!("{}..{}", field.high, field.low));
+                            ui.monospace(field.binary());
+                            ui.monospace(format!("{} (0x{:x})", field.value, field.value));
+                            ui.monospace(meaning(field));
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if !open {
+            self.address = None;
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use rfd::FileDialog;
+
+/// Extensions offered by the dialogs and accepted when dropped on the window.
+const SOURCE_EXTENSIONS: [&str; 2] = ["asm", "s"];
+const SESSION_EXTENSION: &str = "mipsession";
+const RECENT_KEY: &str = "recent_files";
+const MAX_RECENT: usize = 10;
+
+/// Most recently opened or saved files, newest first, persisted in the
+/// eframe storage as one path per line.
+#[derive(Default)]
+pub struct RecentFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let paths = storage
+            .and_then(|storage| storage.get_string(RECENT_KEY))
+            .map(|list| list.lines().map(PathBuf::from).collect())
+            .unwrap_or_default();
+        Self { paths }
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let list: Vec<_> = self
+            .paths
+            .iter()
+            .map(|path| path.to_string_lossy())
+            .collect();
+        storage.set_string(RECENT_KEY, list.join("\n"));
+    }
+
+    pub fn push(&mut self, path: &Path) {
+        self.paths.retain(|recent| recent != path);
+        self.paths.insert(0, path.to_path_buf());
+        self.paths.truncate(MAX_RECENT);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.paths.retain(|recent| recent != path);
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+/// Whether `path` looks like an assembly source file.
+pub fn is_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            SOURCE_EXTENSIONS
+                .iter()
+                .any(|source| extension.eq_ignore_ascii_case(source))
+        })
+}
+
+fn dialog(current: Option<&Path>) -> FileDialog {
+    let dialog = FileDialog::new()
+        .add_filter("MIPS assembly", &SOURCE_EXTENSIONS)
+        .add_filter("All files", &["*"]);
+    match current.and_then(Path::parent) {
+        Some(directory) => dialog.set_directory(directory),
+        None => dialog,
+    }
+}
+
+pub fn pick_open(current: Option<&Path>) -> Option<PathBuf> {
+    dialog(current).pick_file()
+}
+
+fn session_dialog(current: Option<&Path>) -> FileDialog {
+    let dialog = FileDialog::new().add_filter("MIPS session", &[SESSION_EXTENSION]);
+    match current.and_then(Path::parent) {
+        Some(directory) => dialog.set_directory(directory),
+        None => dialog,
+    }
+}
+
+pub fn pick_open_session(current: Option<&Path>) -> Option<PathBuf> {
+    session_dialog(current).pick_file()
+}
+
+pub fn pick_save_session(current: Option<&Path>) -> Option<PathBuf> {
+    session_dialog(current)
+        .set_file_name(format!("untitled.{}", SESSION_EXTENSION))
+        .save_file()
+}
+
+/// Asks where to write an execution trace. The format follows the chosen
+/// extension.
+pub fn pick_save_trace(current: Option<&Path>) -> Option<PathBuf> {
+    let dialog = FileDialog::new()
+        .add_filter("Text trace", &["txt"])
+        .add_filter("JSON Lines trace", &["jsonl"])
+        .set_file_name("trace.txt");
+    match current.and_then(Path::parent) {
+        Some(directory) => dialog.set_directory(directory),
+        None => dialog,
+    }
+    .save_file()
+}
+
+pub fn pick_save(current: Option<&Path>) -> Option<PathBuf> {
+    let dialog = dialog(current);
+    match current.and_then(Path::file_name) {
+        Some(name) => dialog.set_file_name(name.to_string_lossy()),
+        None => dialog.set_file_name("untitled.asm"),
+    }
+    .save_file()
+}
@@ -0,0 +1,248 @@
+use std::ops::Range;
+
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+const FIND: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::F);
+const GOTO_LINE: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
+/// Matches highlighted at most, to keep huge files responsive.
+const MAX_HIGHLIGHTS: usize = 10_000;
+
+/// One occurrence of the search text, as byte offsets for editing and
+/// character offsets for the text edit cursor.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub bytes: Range<usize>,
+    pub chars: Range<usize>,
+}
+
+/// The editor's find/replace bar and goto-line dialog.
+#[derive(Default)]
+pub struct FindBar {
+    pub open: bool,
+    query: String,
+    replacement: String,
+    match_case: bool,
+    /// Index of the selected match.
+    current: usize,
+    /// Focus the search field on the next frame.
+    focus: bool,
+    /// Text of the goto-line dialog, while it is open.
+    goto: Option<String>,
+    /// Match to select in the editor this frame.
+    select: Option<Range<usize>>,
+    /// Line to move the cursor to this frame.
+    goto_line: Option<usize>,
+}
+
+impl FindBar {
+    /// Every occurrence of the search text in `source`. Case folding is
+    /// ASCII-only so byte offsets stay the same.
+    pub fn matches(&self, source: &str) -> Vec<Match> {
+        if self.query.is_empty() || self.query.contains('\n') {
+            return Vec::new();
+        }
+        let (haystack, needle) = if self.match_case {
+            (source.to_string(), self.query.clone())
+        } else {
+            (source.to_ascii_lowercase(), self.query.to_ascii_lowercase())
+        };
+
+        let mut matches = Vec::new();
+        let mut chars = 0;
+        let mut last = 0;
+        for (byte, text) in haystack.match_indices(&needle) {
+            chars += haystack[last..byte].chars().count();
+            let len = text.chars().count();
+            matches.push(Match {
+                bytes: byte..byte + text.len(),
+                chars: chars..chars + len,
+            });
+            chars += len;
+            last = byte + text.len();
+        }
+        matches
+    }
+
+    pub fn open_find(&mut self) {
+        self.open = true;
+        self.focus = true;
+    }
+
+    pub fn open_goto(&mut self) {
+        self.goto = Some(String::new());
+    }
+
+    /// Menu entries for the find bar and goto-line dialog.
+    pub fn menu(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        let shortcut = |shortcut| ctx.format_shortcut(&shortcut);
+        if ui
+            .add(egui::Button::new("Find/Replace…").shortcut_text(shortcut(FIND)))
+            .clicked()
+        {
+            self.open_find();
+        }
+        if ui
+            .add(egui::Button::new("Go to Line…").shortcut_text(shortcut(GOTO_LINE)))
+            .clicked()
+        {
+            self.open_goto();
+        }
+    }
+
+    /// Handles the find and goto-line shortcuts.
+    pub fn shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.input_mut(|input| input.consume_shortcut(&FIND)) {
+            self.open_find();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&GOTO_LINE)) {
+            self.open_goto();
+        }
+    }
+
+    fn step(&mut self, source: &str, forward: bool) {
+        let matches = self.matches(source);
+        if matches.is_empty() {
+            return;
+        }
+        self.current = match forward {
+            true => (self.current + 1) % matches.len(),
+            false => (self.current + matches.len() - 1) % matches.len(),
+        };
+        self.select = Some(matches[self.current].chars.clone());
+    }
+
+    /// Draws the bar above the editor while it is open.
+    pub fn show(&mut self, ui: &mut egui::Ui, source: &mut String) {
+        if !self.open {
+            return;
+        }
+
+        let matches = self.matches(source);
+        self.current = self.current.min(matches.len().saturating_sub(1));
+
+        ui.horizontal(|ui| {
+            let search = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Find")
+                    .desired_width(180.0),
+            );
+            if std::mem::take(&mut self.focus) {
+                search.request_focus();
+            }
+            if search.changed() {
+                self.current = 0;
+                self.select = self.matches(source).first().map(|m| m.chars.clone());
+            }
+            if search.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                let backward = ui.input(|input| input.modifiers.shift);
+                self.step(source, !backward);
+                search.request_focus();
+            }
+            if ui.button("▲").on_hover_text("Previous match").clicked() {
+                self.step(source, false);
+            }
+            if ui.button("▼").on_hover_text("Next match").clicked() {
+                self.step(source, true);
+            }
+            ui.checkbox(&mut self.match_case, "Match case");
+            ui.label(match matches.len() {
+                0 if self.query.is_empty() => String::new(),
+                0 => "No matches".to_string(),
+                count => format!("{} of {}", self.current + 1, count),
+            });
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.replacement)
+                    .hint_text("Replace")
+                    .desired_width(180.0),
+            );
+            if ui
+                .add_enabled(!matches.is_empty(), egui::Button::new("Replace"))
+                .clicked()
+            {
+                source.replace_range(matches[self.current].bytes.clone(), &self.replacement);
+                // The next match now sits at the same index.
+                let remaining = self.matches(source);
+                self.current = self.current.min(remaining.len().saturating_sub(1));
+                self.select = remaining.get(self.current).map(|m| m.chars.clone());
+            }
+            if ui
+                .add_enabled(!matches.is_empty(), egui::Button::new("Replace All"))
+                .clicked()
+            {
+                for found in matches.iter().rev() {
+                    source.replace_range(found.bytes.clone(), &self.replacement);
+                }
+                self.current = 0;
+            }
+            if ui.button("Close").clicked() || ui.input(|input| input.key_pressed(Key::Escape)) {
+                self.open = false;
+            }
+        });
+        ui.separator();
+    }
+
+    /// Shows the goto-line dialog while it is open.
+    pub fn goto_dialog(&mut self, ctx: &egui::Context, line_count: usize) {
+        let Some(text) = &mut self.goto else {
+            return;
+        };
+
+        let mut open = true;
+        let mut go = false;
+        egui::Window::new("Go to Line")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let field = ui.add(
+                        egui::TextEdit::singleline(text)
+                            .hint_text(format!("1–{}", line_count))
+                            .desired_width(80.0),
+                    );
+                    field.request_focus();
+                    go = field.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                    go |= ui.button("Go").clicked();
+                });
+            });
+
+        if go {
+            if let Ok(line) = text.trim().parse::<usize>() {
+                self.goto_line = Some(line.clamp(1, line_count));
+            }
+            self.goto = None;
+        }
+        if !open || ctx.input(|input| input.key_pressed(Key::Escape)) {
+            self.goto = None;
+        }
+    }
+
+    /// Match ranges to highlight, in characters, and the selected one.
+    pub fn highlights(&self, source: &str) -> (Vec<Range<usize>>, Option<Range<usize>>) {
+        if !self.open {
+            return (Vec::new(), None);
+        }
+        let matches = self.matches(source);
+        let current = matches.get(self.current).map(|m| m.chars.clone());
+        let all = matches
+            .into_iter()
+            .take(MAX_HIGHLIGHTS)
+            .map(|m| m.chars)
+            .collect();
+        (all, current)
+    }
+
+    /// The match the user moved to this frame, to select in the editor.
+    pub fn take_selection(&mut self) -> Option<Range<usize>> {
+        self.select.take()
+    }
+
+    /// The line chosen in the goto-line dialog this frame.
+    pub fn take_goto_line(&mut self) -> Option<usize> {
+        self.goto_line.take()
+    }
+}
@@ -0,0 +1,118 @@
+use eframe::egui::{self, Color32, RichText, Sense};
+
+use crate::{debug_info::DebugInfo, heap::HEAP_BASE, simulator::Simulator};
+
+const REACHABLE_COLOR: Color32 = Color32::from_rgb(70, 150, 110);
+const LEAKED_COLOR: Color32 = Color32::from_rgb(210, 90, 70);
+const MAP_HEIGHT: f32 = 28.0;
+
+/// Shows the program break, a map of the heap with one block per sbrk call,
+/// and the call history. Blocks that nothing points to any more are marked
+/// as likely leaks.
+pub fn show(ui: &mut egui::Ui, simulator: Option<&Simulator>, debug_info: &DebugInfo) {
+    let Some(simulator) = simulator else {
+        ui.label("Assemble a program to inspect its heap.");
+        return;
+    };
+
+    let heap = simulator.heap();
+    let history = heap.history();
+    let reachable = simulator.reachable_allocations();
+    let leaked = reachable.iter().filter(|&&reachable| !reachable).count();
+
+    ui.horizontal(|ui| {
+        ui.monospace(format!("break 0x{:08x}", heap.brk().0));
+        ui.separator();
+        ui.label(format!(
+            "{} bytes in {} sbrk calls",
+            heap.allocated(),
+            history.len()
+        ));
+        if leaked > 0 {
+            ui.separator();
+            ui.colored_label(LEAKED_COLOR, format!("{} unreferenced", leaked))
+                .on_hover_text(
+                    "No register, static data, stack or reachable heap word points \
+                     into these blocks",
+                );
+        }
+    });
+
+    if history.is_empty() {
+        ui.label(format!(
+            "No sbrk calls yet; the heap starts at 0x{:08x}.",
+            HEAP_BASE.0
+        ));
+        return;
+    }
+
+    // The map, with each block as wide as its share of the heap.
+    let (rect, _) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), MAP_HEIGHT), Sense::hover());
+    ui.painter()
+        .rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let total = heap.allocated().max(1) as f32;
+    for (index, (allocation, &reachable)) in history.iter().zip(&reachable).enumerate() {
+        if allocation.size == 0 {
+            continue;
+        }
+        let left = rect.left() + rect.width() * (allocation.address.0 - HEAP_BASE.0) as f32 / total;
+        let width = (rect.width() * allocation.size as f32 / total).max(1.0);
+        let block = egui::Rect::from_min_size(
+            egui::pos2(left, rect.top()),
+            egui::vec2(width, rect.height()),
+        );
+        let color = if reachable {
+            REACHABLE_COLOR
+        } else {
+            LEAKED_COLOR
+        };
+        ui.painter()
+            .rect_filled(block.shrink2(egui::vec2(0.5, 0.0)), 0.0, color);
+        let response = ui.interact(block, ui.id().with(("heap_block", index)), Sense::hover());
+        response.on_hover_text(format!(
+            "#{}: {} bytes at 0x{:08x}",
+            index + 1,
+            allocation.size,
+            allocation.address.0
+        ));
+    }
+    ui.add_space(4.0);
+
+    egui::ScrollArea::vertical()
+        .id_salt("heap_scroll")
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            egui::Grid::new("heap_history")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in ["#", "address", "bytes", "called from", "step"] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+
+                    for (index, (allocation, &reachable)) in
+                        history.iter().zip(&reachable).enumerate()
+                    {
+                        let number = RichText::new((index + 1).to_string()).monospace();
+                        ui.label(match reachable {
+                            true => number,
+                            false => number.color(LEAKED_COLOR),
+                        })
+                        .on_hover_text(match reachable {
+                            true => "Still referenced",
+                            false => "Nothing points into this block",
+                        });
+                        ui.monospace(format!("0x{:08x}", allocation.address.0));
+                        ui.monospace(allocation.size.to_string());
+                        let line = debug_info
+                            .line_of(allocation.pc)
+                            .map_or_else(String::new, |line| format!(" (line {})", line));
+                        ui.monospace(format!("0x{:08x}{}", allocation.pc.0, line));
+                        ui.monospace(allocation.step.to_string());
+                        ui.end_row();
+                    }
+                });
+        });
+}
@@ -0,0 +1,133 @@
+use eframe::egui::{
+    self, Color32, FontId, TextFormat,
+    cache::{ComputerMut, FrameCache},
+    text::LayoutJob,
+};
+
+use crate::{
+    assembler::MNEMONICS,
+    lexer::{TokenKind, classify, lines, split_words},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Plain,
+    Mnemonic,
+    Register,
+    Directive,
+    Label,
+    Number,
+    Text,
+    Comment,
+    Error,
+}
+
+fn color(class: Class, dark_mode: bool) -> Color32 {
+    match (class, dark_mode) {
+        (Class::Plain, true) => Color32::from_gray(210),
+        (Class::Plain, false) => Color32::from_gray(30),
+        (Class::Mnemonic, true) => Color32::from_rgb(86, 156, 214),
+        (Class::Mnemonic, false) => Color32::from_rgb(0, 70, 170),
+        (Class::Register, true) => Color32::from_rgb(220, 160, 90),
+        (Class::Register, false) => Color32::from_rgb(170, 80, 0),
+        (Class::Directive, true) => Color32::from_rgb(197, 134, 192),
+        (Class::Directive, false) => Color32::from_rgb(130, 30, 140),
+        (Class::Label, true) => Color32::from_rgb(220, 220, 170),
+        (Class::Label, false) => Color32::from_rgb(120, 100, 0),
+        (Class::Number, true) => Color32::from_rgb(181, 206, 168),
+        (Class::Number, false) => Color32::from_rgb(9, 134, 88),
+        (Class::Text, true) => Color32::from_rgb(206, 145, 120),
+        (Class::Text, false) => Color32::from_rgb(163, 21, 21),
+        (Class::Comment, true) => Color32::from_rgb(106, 153, 85),
+        (Class::Comment, false) => Color32::from_rgb(0, 128, 0),
+        (Class::Error, _) => Color32::from_rgb(240, 70, 70),
+    }
+}
+
+fn classify_word(word: &str, first: bool) -> Class {
+    match classify(word, first) {
+        Ok(TokenKind::Directive { .. }) => Class::Directive,
+        Ok(TokenKind::Register { .. } | TokenKind::Address { .. }) => Class::Register,
+        Ok(TokenKind::Label { .. }) => Class::Label,
+        Ok(TokenKind::Number { .. } | TokenKind::Expression { .. }) => Class::Number,
+        Ok(TokenKind::Text { .. }) => Class::Text,
+        Ok(TokenKind::Operator { value }) if MNEMONICS.contains(&value) => Class::Mnemonic,
+        Ok(TokenKind::Operator { .. }) | Err(_) => Class::Error,
+    }
+}
+
+struct Highlighter {
+    job: LayoutJob,
+    font: FontId,
+    dark_mode: bool,
+}
+
+impl Highlighter {
+    fn push(&mut self, text: &str, class: Class) {
+        if text.is_empty() {
+            return;
+        }
+        let format = TextFormat::simple(self.font.clone(), color(class, self.dark_mode));
+        self.job.append(text, 0.0, format);
+    }
+
+    /// Appends text between words, which can only hold separators, comments
+    /// and line breaks.
+    fn push_gap(&mut self, gap: &str) {
+        let mut rest = gap;
+        while let Some(start) = rest.find('#') {
+            self.push(&rest[..start], Class::Plain);
+            let end = rest[start..].find('\n').map_or(rest.len(), |i| start + i);
+            self.push(&rest[start..end], Class::Comment);
+            rest = &rest[end..];
+        }
+        self.push(rest, Class::Plain);
+    }
+}
+
+/// Lays out MIPS source with one colour per token class, using the same
+/// word splitting and classification rules as the assembler.
+pub fn highlight(source: &str, font: FontId, dark_mode: bool) -> LayoutJob {
+    let mut highlighter = Highlighter {
+        job: LayoutJob::default(),
+        font,
+        dark_mode,
+    };
+    let mut pos = 0;
+
+    for line in lines(source) {
+        let mut first = true;
+        for (offset, word) in split_words(line.text) {
+            let start = line.offset + offset;
+            highlighter.push_gap(&source[pos..start]);
+
+            let class = classify_word(word, first);
+            highlighter.push(word, class);
+            first = class == Class::Label && word.ends_with(':');
+            pos = start + word.len();
+        }
+    }
+    highlighter.push_gap(&source[pos..]);
+
+    highlighter.job
+}
+
+#[derive(Default)]
+struct CachedHighlighter;
+
+impl ComputerMut<(&str, &FontId, bool), LayoutJob> for CachedHighlighter {
+    fn compute(&mut self, (source, font, dark_mode): (&str, &FontId, bool)) -> LayoutJob {
+        highlight(source, font.clone(), dark_mode)
+    }
+}
+
+/// Like [`highlight`], but reuses last frame's result when nothing changed.
+pub fn highlight_cached(ctx: &egui::Context, source: &str, font: &FontId) -> LayoutJob {
+    let dark_mode = ctx.style().visuals.dark_mode;
+    ctx.memory_mut(|memory| {
+        memory
+            .caches
+            .cache::<FrameCache<LayoutJob, CachedHighlighter>>()
+            .get((source, font, dark_mode))
+    })
+}
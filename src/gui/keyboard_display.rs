@@ -0,0 +1,73 @@
+use eframe::egui;
+
+use crate::simulator::Simulator;
+
+/// The keyboard and display MMIO tool. Characters typed into the keyboard
+/// area are queued for the receiver; the display area shows what the
+/// program wrote to the transmitter.
+#[derive(Default)]
+pub struct KeyboardDisplayWindow {
+    pub open: bool,
+    typed: String,
+}
+
+impl KeyboardDisplayWindow {
+    pub fn show(&mut self, ctx: &egui::Context, simulator: Option<&mut Simulator>) {
+        if !self.open {
+            return;
+        }
+
+        let mut open = self.open;
+        egui::Window::new("Keyboard and Display MMIO")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                let Some(simulator) = simulator else {
+                    ui.label("Assemble a program to attach the device.");
+                    return;
+                };
+                let device = simulator.keyboard_display_mut();
+
+                ui.strong("Display");
+                egui::ScrollArea::vertical()
+                    .id_salt("mmio_display")
+                    .max_height(160.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        let mut display = device.display();
+                        ui.add(
+                            egui::TextEdit::multiline(&mut display)
+                                .code_editor()
+                                .desired_rows(8)
+                                .desired_width(f32::INFINITY),
+                        );
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong("Keyboard");
+                    ui.label(format!("{} key(s) waiting", device.pending_keys()));
+                });
+                let before = self.typed.len();
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.typed)
+                        .code_editor()
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                // Only appended characters count as key presses; editing
+                // what was already typed sends nothing.
+                if let Some(new) = self.typed.get(before..) {
+                    for byte in new.bytes() {
+                        device.push_key(byte);
+                    }
+                }
+
+                if ui.button("Reset").clicked() {
+                    self.typed.clear();
+                    device.clear_display();
+                }
+            });
+        self.open = open;
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashSet;
+
+use eframe::egui::{self, Color32, Key, RichText, Sense};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::Address,
+    assembler::{BASE_DATA_ADDR, BASE_TEXT_ADDR, Segment},
+    debug_info::{DataKind, DebugInfo, SymbolInfo},
+    simulator::{PatchError, Simulator},
+    watchpoint::{WatchKind, Watchpoint},
+};
+
+const BYTES_PER_ROW: usize = 16;
+const PATCHED_COLOR: Color32 = Color32::from_rgb(255, 200, 60);
+const INVALID_COLOR: Color32 = Color32::from_rgb(230, 80, 70);
+const WATCHED_COLOR: Color32 = Color32::from_rgb(90, 160, 255);
+/// Values shown per label before the rest are elided.
+const MAX_VALUES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum View {
+    #[default]
+    Labels,
+    HexDump,
+}
+
+/// Which view the panel shows and where, saved with a session.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryView {
+    pub hex: bool,
+    pub text: bool,
+    pub words: bool,
+    /// First address visible in the hex view.
+    pub address: Option<u32>,
+}
+
+/// Bytes the user typed into the hex view, to write into the simulator.
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub address: Address,
+    pub bytes: Vec<u8>,
+    pub allow_text: bool,
+}
+
+pub struct MemoryPanel {
+    view: View,
+    /// Segment shown in the hex view.
+    segment: Segment,
+    /// Show and edit the hex view a word at a time instead of a byte.
+    words: bool,
+    /// Let edits overwrite instructions in the text segment.
+    allow_text: bool,
+    /// Cell being edited and the text typed so far.
+    editing: Option<(Address, String)>,
+    /// Byte addresses written from the hex view since the last assemble.
+    patched: HashSet<u32>,
+    /// Why the last edit was refused.
+    error: Option<String>,
+    /// Address to scroll into view on the next frame.
+    reveal: Option<Address>,
+    /// First address the hex view showed last frame.
+    top: Option<Address>,
+    watchpoints: Vec<Watchpoint>,
+    /// Set when the watchpoints change, until the app sends them on.
+    watchpoints_changed: bool,
+}
+
+impl Default for MemoryPanel {
+    fn default() -> Self {
+        Self {
+            view: View::default(),
+            segment: Segment::Data,
+            words: false,
+            allow_text: false,
+            editing: None,
+            patched: HashSet::new(),
+            error: None,
+            reveal: None,
+            top: None,
+            watchpoints: Vec::new(),
+            watchpoints_changed: false,
+        }
+    }
+}
+
+impl MemoryPanel {
+    pub fn view(&self) -> MemoryView {
+        MemoryView {
+            hex: self.view == View::HexDump,
+            text: self.segment == Segment::Text,
+            words: self.words,
+            address: self.top.map(|address| address.0),
+        }
+    }
+
+    /// Switches to a saved view, scrolling to its address once shown.
+    pub fn restore(&mut self, view: MemoryView) {
+        self.view = if view.hex {
+            View::HexDump
+        } else {
+            View::Labels
+        };
+        self.segment = if view.text {
+            Segment::Text
+        } else {
+            Segment::Data
+        };
+        self.words = view.words;
+        self.reveal = view.address.map(Address);
+        self.top = self.reveal;
+    }
+
+    /// Forget edits, e.g. after reassembling.
+    pub fn clear(&mut self) {
+        self.editing = None;
+        self.patched.clear();
+        self.error = None;
+    }
+
+    /// Records the outcome of writing `patch`, to highlight the cells it
+    /// changed or explain why it failed.
+    pub fn patched(&mut self, patch: &Patch, result: Result<(), PatchError>) {
+        match result {
+            Ok(()) => {
+                let start = patch.address.0;
+                self.patched
+                    .extend((0..patch.bytes.len() as u32).map(|i| start.wrapping_add(i)));
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Whether the watchpoints changed since the last call.
+    pub fn take_watchpoints_changed(&mut self) -> bool {
+        std::mem::take(&mut self.watchpoints_changed)
+    }
+
+    /// Lists the watchpoints with a button to remove each.
+    fn watchpoint_list(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        for (index, watchpoint) in self.watchpoints.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.colored_label(WATCHED_COLOR, "●");
+                ui.monospace(watchpoint.to_string());
+                if ui.small_button("×").on_hover_text("Remove").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+        if let Some(index) = remove {
+            self.watchpoints.remove(index);
+            self.watchpoints_changed = true;
+        }
+    }
+
+    /// Scrolls the current view to `address` on the next frame.
+    pub fn reveal(&mut self, address: Address) {
+        self.reveal = Some(address);
+    }
+
+    /// Draws the panel. While `editable`, i.e. paused, double-clicking a
+    /// hex cell edits it; returns the bytes entered.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        simulator: Option<&Simulator>,
+        debug_info: &DebugInfo,
+        editable: bool,
+    ) -> Option<Patch> {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.view, View::Labels, "Labels");
+            ui.selectable_value(&mut self.view, View::HexDump, "Hex");
+            if self.view == View::HexDump {
+                ui.separator();
+                ui.selectable_value(&mut self.segment, Segment::Data, ".data");
+                ui.selectable_value(&mut self.segment, Segment::Text, ".text");
+                ui.separator();
+                ui.selectable_value(&mut self.words, false, "Bytes");
+                ui.selectable_value(&mut self.words, true, "Words");
+                if self.segment == Segment::Text {
+                    ui.separator();
+                    ui.checkbox(&mut self.allow_text, "Allow writes")
+                        .on_hover_text("Let edits overwrite instructions");
+                }
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.colored_label(INVALID_COLOR, error);
+        }
+        self.watchpoint_list(ui);
+
+        let Some(simulator) = simulator else {
+            ui.label("Assemble a program to inspect its memory.");
+            return None;
+        };
+        if !editable {
+            self.editing = None;
+        }
+
+        let reveal = self.reveal.take();
+        match (self.view, self.segment) {
+            (View::Labels, _) => {
+                labels(ui, simulator.memory().data(), debug_info, reveal);
+                None
+            }
+            (View::HexDump, Segment::Data) => {
+                let data = simulator.memory().data();
+                self.hex_dump(ui, BASE_DATA_ADDR, data, reveal, editable)
+            }
+            (View::HexDump, Segment::Text) => {
+                let text: Vec<u8> = simulator
+                    .instructions()
+                    .iter()
+                    .flat_map(|instruction| instruction.encode().to_le_bytes())
+                    .collect();
+                let editable = editable && self.allow_text;
+                self.hex_dump(ui, BASE_TEXT_ADDR, &text, reveal, editable)
+            }
+        }
+    }
+
+    /// Draws one cell of `bytes` at `address`, shown as a little-endian
+    /// value, or its text field while it is being edited.
+    fn cell(
+        &mut self,
+        ui: &mut egui::Ui,
+        address: Address,
+        bytes: &[u8],
+        editable: bool,
+    ) -> Option<Patch> {
+        let digits = bytes.len() * 2;
+        let mut value = [0; 4];
+        value[..bytes.len()].copy_from_slice(bytes);
+        let rendered = format!("{:0digits$x}", u32::from_le_bytes(value));
+
+        let mut entered = None;
+        match &mut self.editing {
+            Some((editing, input)) if *editing == address => {
+                let parsed = u32::from_str_radix(input.trim().trim_start_matches("0x"), 16)
+                    .ok()
+                    .filter(|&value| (value as u64) < 1 << (8 * bytes.len()));
+                let field = ui.add(
+                    egui::TextEdit::singleline(input)
+                        .font(egui::TextStyle::Monospace)
+                        .char_limit(digits + 2)
+                        .desired_width(
+                            ui.fonts_mut(|fonts| {
+                                fonts.glyph_width(
+                                    &egui::TextStyle::Monospace.resolve(ui.style()),
+                                    '0',
+                                )
+                            }) * (digits + 1) as f32,
+                        )
+                        .text_color_opt(parsed.is_none().then_some(INVALID_COLOR)),
+                );
+                field.request_focus();
+                if ui.input(|input| input.key_pressed(Key::Escape)) {
+                    self.editing = None;
+                } else if field.lost_focus() {
+                    if let Some(value) = parsed
+                        && ui.input(|input| input.key_pressed(Key::Enter))
+                    {
+                        entered = Some(Patch {
+                            address,
+                            bytes: value.to_le_bytes()[..bytes.len()].to_vec(),
+                            allow_text: self.allow_text,
+                        });
+                    }
+                    self.editing = None;
+                }
+            }
+            _ => {
+                let patched = (0..bytes.len() as u32)
+                    .any(|i| self.patched.contains(&address.0.wrapping_add(i)));
+                let size = bytes.len() as u32;
+                let watched = self
+                    .watchpoints
+                    .iter()
+                    .any(|watchpoint| watchpoint.overlaps(address, size));
+                let mut text = RichText::new(&rendered).monospace();
+                if patched {
+                    text = text.color(PATCHED_COLOR).strong();
+                }
+                if watched {
+                    text = text.underline();
+                }
+                let label = ui.add(egui::Label::new(text).sense(Sense::click()));
+                if editable && label.double_clicked() {
+                    self.editing = Some((address, rendered));
+                }
+                label.context_menu(|ui| {
+                    for kind in [WatchKind::Write, WatchKind::Read, WatchKind::Access] {
+                        if ui.button(format!("Watch {}s", kind.name())).clicked() {
+                            self.watchpoints.push(Watchpoint {
+                                start: address,
+                                len: size,
+                                kind,
+                            });
+                            self.watchpoints_changed = true;
+                        }
+                    }
+                });
+            }
+        }
+        entered
+    }
+
+    fn hex_dump(
+        &mut self,
+        ui: &mut egui::Ui,
+        base: Address,
+        memory: &[u8],
+        reveal: Option<Address>,
+        editable: bool,
+    ) -> Option<Patch> {
+        let rows = memory.len().div_ceil(BYTES_PER_ROW);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        let mut area = egui::ScrollArea::vertical()
+            .id_salt("memory_scroll")
+            .auto_shrink(false);
+        if let Some(offset) = reveal.and_then(|address| address.0.checked_sub(base.0)) {
+            let row = offset as usize / BYTES_PER_ROW;
+            area = area
+                .vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+
+        let cell_size = if self.words { 4 } else { 1 };
+        let mut entered = None;
+        area.show_rows(ui, row_height, rows, |ui, range| {
+            self.top = Some(Address(base.0 + (range.start * BYTES_PER_ROW) as u32));
+            for row in range {
+                let start = row * BYTES_PER_ROW;
+                let bytes = &memory[start..(start + BYTES_PER_ROW).min(memory.len())];
+
+                ui.horizontal(|ui| {
+                    ui.monospace(format!("0x{:08x} ", base.0 as usize + start));
+                    for (i, cell) in bytes.chunks(cell_size).enumerate() {
+                        let address = Address(base.0 + (start + i * cell_size) as u32);
+                        if let Some(patch) = self.cell(ui, address, cell, editable) {
+                            entered = Some(patch);
+                        }
+                    }
+
+                    let ascii: String = bytes
+                        .iter()
+                        .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                        .collect();
+                    ui.monospace(format!(" {}", ascii));
+                });
+            }
+        });
+        entered
+    }
+}
+
+/// Guesses a label's type from the directive that declared its data, falling
+/// back to raw bytes.
+fn kind_of(symbol: &SymbolInfo) -> DataKind {
+    symbol.kind.unwrap_or(DataKind::Bytes)
+}
+
+fn elide(mut values: Vec<String>, total: usize) -> String {
+    if total > values.len() {
+        values.push(format!("… ({} more)", total - values.len()));
+    }
+    values.join(", ")
+}
+
+/// Decodes a label's bytes according to its kind.
+fn decode(kind: DataKind, bytes: &[u8]) -> String {
+    match kind {
+        DataKind::String => {
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            format!("{:?}", String::from_utf8_lossy(&bytes[..end]))
+        }
+        DataKind::Words => {
+            let words = bytes.chunks_exact(4);
+            let total = words.len();
+            let values = words
+                .take(MAX_VALUES)
+                .map(|word| i32::from_le_bytes([word[0], word[1], word[2], word[3]]).to_string())
+                .collect();
+            elide(values, total)
+        }
+        DataKind::Bytes => {
+            let values = bytes
+                .iter()
+                .take(MAX_VALUES)
+                .map(|b| format!("0x{:02x}", b))
+                .collect();
+            elide(values, bytes.len())
+        }
+    }
+}
+
+fn labels(ui: &mut egui::Ui, memory: &[u8], debug_info: &DebugInfo, reveal: Option<Address>) {
+    let mut symbols: Vec<&SymbolInfo> = debug_info
+        .symbols()
+        .iter()
+        .filter(|symbol| symbol.segment == Segment::Data)
+        .collect();
+    symbols.sort_by_key(|symbol| symbol.address.0);
+
+    if symbols.is_empty() {
+        ui.label("The program declares no data labels.");
+        return;
+    }
+
+    egui::ScrollArea::vertical()
+        .id_salt("memory_labels_scroll")
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            egui::Grid::new("memory_labels")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["label", "address", "type", "value"] {
+                        ui.strong(header);
+                    }
+                    ui.end_row();
+
+                    for symbol in symbols {
+                        let Some(start) = symbol.address.offset_from(BASE_DATA_ADDR) else {
+                            continue;
+                        };
+                        let start = start as usize;
+                        let end = (start + symbol.size).min(memory.len());
+                        let bytes = memory.get(start..end).unwrap_or_default();
+                        let kind = kind_of(symbol);
+
+                        let name = ui.monospace(&symbol.name);
+                        if reveal == Some(symbol.address) {
+                            name.scroll_to_me(Some(egui::Align::Center));
+                        }
+                        ui.monospace(format!("0x{:08x}", symbol.address.0));
+                        ui.label(match kind {
+                            DataKind::String => "string",
+                            DataKind::Words => "words",
+                            DataKind::Bytes => "bytes",
+                        });
+                        ui.monospace(decode(kind, bytes));
+                        ui.end_row();
+                    }
+                });
+        });
+}
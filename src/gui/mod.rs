@@ -0,0 +1,50 @@
+mod app;
+mod bitmap;
+mod buffer;
+mod cache;
+mod call_stack;
+mod console;
+mod cp1;
+mod diagnostics;
+mod dock;
+mod editor;
+mod encoding;
+mod files;
+mod find;
+mod heap;
+mod highlight;
+mod keyboard_display;
+mod memory;
+mod pipeline;
+mod registers;
+mod session;
+mod settings;
+mod shortcuts;
+mod stack;
+mod symbols;
+mod text_segment;
+mod toolbar;
+mod watch;
+
+pub use app::MipsApp;
+
+use std::path::PathBuf;
+
+use eframe::egui;
+
+/// Opens the simulator window, optionally with a file's path and contents
+/// loaded into the editor.
+pub fn run(file: Option<(PathBuf, String)>) -> eframe::Result {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("MIPS Simulator")
+            .with_inner_size([1200.0, 800.0]),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "MIPS Simulator",
+        options,
+        Box::new(|cc| Ok(Box::new(MipsApp::new(&cc.egui_ctx, file, cc.storage)))),
+    )
+}
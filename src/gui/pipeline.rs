@@ -0,0 +1,217 @@
+use eframe::egui::{self, Color32, Label, RichText, Sense, Stroke};
+
+use crate::{
+    gui::editor::CURRENT_LINE_COLOR,
+    pipeline::{Pipeline, Stage, Timing},
+    simulator::Simulator,
+};
+
+/// Instructions shown in the multi-cycle diagram.
+const DIAGRAM_ROWS: usize = 12;
+const STAGE_WIDTH: f32 = 130.0;
+const STALL_COLOR: Color32 = Color32::from_rgb(200, 120, 40);
+const FORWARD_COLOR: Color32 = Color32::from_rgb(80, 170, 230);
+
+/// Shows which instruction occupies each stage of the five-stage pipeline,
+/// cycle by cycle, with stalls and forwarding paths.
+pub struct PipelinePanel {
+    pub forwarding: bool,
+    /// Cycle being inspected, or `None` to track the latest one.
+    cycle: Option<u64>,
+}
+
+impl Default for PipelinePanel {
+    fn default() -> Self {
+        Self {
+            forwarding: true,
+            cycle: None,
+        }
+    }
+}
+
+impl PipelinePanel {
+    /// Attaches a timing model to `simulator` if it has none, e.g. after an
+    /// assemble or reset, or replaces it when the forwarding setting changed.
+    pub fn attach(&mut self, simulator: &mut Simulator) {
+        let current = simulator.pipeline().map(Pipeline::forwarding);
+        if current != Some(self.forwarding) {
+            simulator.set_pipeline(Some(Pipeline::new(self.forwarding)));
+            self.cycle = None;
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, simulator: Option<&Simulator>) {
+        ui.checkbox(&mut self.forwarding, "Forwarding")
+            .on_hover_text("Changing this restarts the timing from the next instruction.");
+
+        let Some(pipeline) = simulator.and_then(Simulator::pipeline) else {
+            ui.label("Assemble a program to watch it move through the pipeline.");
+            return;
+        };
+        let history = pipeline.history();
+        let (Some(oldest), Some(newest)) = (history.front(), history.back()) else {
+            ui.label("Step the program to fill the pipeline.");
+            return;
+        };
+
+        ui.label(format!(
+            "{} cycles, {} instructions, CPI {:.2}, {} stall cycles, {} branch penalty cycles",
+            pipeline.cycles(),
+            pipeline.instructions(),
+            pipeline.cpi(),
+            pipeline.stalls(),
+            pipeline.branch_bubbles()
+        ));
+
+        let first = oldest.entered[0];
+        let last = newest.entered[4];
+        let mut cycle = self.cycle.unwrap_or(last).clamp(first, last);
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(cycle > first, egui::Button::new("◀"))
+                .clicked()
+            {
+                cycle -= 1;
+            }
+            ui.monospace(format!("cycle {}", cycle));
+            if ui
+                .add_enabled(cycle < last, egui::Button::new("▶"))
+                .clicked()
+            {
+                cycle += 1;
+            }
+            if ui
+                .add_enabled(self.cycle.is_some(), egui::Button::new("Latest"))
+                .clicked()
+            {
+                cycle = last;
+            }
+        });
+        self.cycle = (cycle != last).then_some(cycle);
+
+        ui.separator();
+        stages(ui, pipeline, cycle);
+        ui.separator();
+        if let Some(clicked) = diagram(ui, pipeline, cycle) {
+            self.cycle = (clicked != last).then_some(clicked);
+        }
+    }
+}
+
+/// The five stage boxes for `cycle`, with arrows for values forwarded into EX.
+fn stages(ui: &mut egui::Ui, pipeline: &Pipeline, cycle: u64) {
+    let occupancy = pipeline.occupancy(cycle);
+    let mut rects = Vec::new();
+
+    ui.horizontal(|ui| {
+        for (stage, timing) in Stage::ALL.into_iter().zip(occupancy) {
+            let response = egui::Frame::group(ui.style()).show(ui, |ui| {
+                ui.set_width(STAGE_WIDTH);
+                ui.vertical_centered(|ui| {
+                    ui.strong(stage.name());
+                    match timing {
+                        Some(timing) => {
+                            ui.monospace(timing.instruction.to_string());
+                            if cycle > timing.entered[stage as usize] {
+                                ui.label(RichText::new("stalled").color(STALL_COLOR));
+                            } else {
+                                ui.weak(format!("0x{:08x}", timing.address.0));
+                            }
+                        }
+                        None => {
+                            ui.weak("bubble");
+                            ui.weak("");
+                        }
+                    }
+                });
+            });
+            rects.push(response.response.rect);
+        }
+    });
+
+    let Some(consumer) = occupancy[Stage::Execute as usize] else {
+        return;
+    };
+    if consumer.entered[2] != cycle {
+        return;
+    }
+    let painter = ui.painter();
+    let execute = rects[Stage::Execute as usize];
+    let stroke = Stroke::new(2.0, FORWARD_COLOR);
+    for (i, forward) in consumer.forwards.iter().enumerate() {
+        let from = rects[forward.from as usize];
+        let drop = 8.0 + 10.0 * i as f32;
+        let start = from.center_bottom();
+        let end = execute.center_bottom() + egui::vec2(10.0 * i as f32, 0.0);
+        let low = start.y.max(end.y) + drop;
+        painter.line_segment([start, egui::pos2(start.x, low)], stroke);
+        painter.line_segment([egui::pos2(start.x, low), egui::pos2(end.x, low)], stroke);
+        painter.arrow(egui::pos2(end.x, low), end - egui::pos2(end.x, low), stroke);
+        painter.text(
+            egui::pos2((start.x + end.x) / 2.0, low),
+            egui::Align2::CENTER_TOP,
+            format!("{:?}", forward.register).to_lowercase(),
+            egui::FontId::monospace(11.0),
+            FORWARD_COLOR,
+        );
+    }
+    let rows = consumer.forwards.len() as f32;
+    ui.add_space(8.0 + 10.0 * rows + if rows > 0.0 { 14.0 } else { 0.0 });
+}
+
+/// The classic multi-cycle diagram for the most recent instructions, one
+/// row each and one column per cycle. Returns a cycle the user clicked.
+fn diagram(ui: &mut egui::Ui, pipeline: &Pipeline, selected: u64) -> Option<u64> {
+    let history = pipeline.history();
+    let rows: Vec<&Timing> = history
+        .iter()
+        .skip(history.len().saturating_sub(DIAGRAM_ROWS))
+        .collect();
+    let first = rows.first()?.entered[0];
+    let last = rows.last()?.entered[4];
+    let mut clicked = None;
+
+    egui::ScrollArea::both()
+        .id_salt("pipeline_diagram")
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            egui::Grid::new("pipeline_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    for cycle in first..=last {
+                        let mut text = RichText::new(cycle.to_string()).monospace();
+                        if cycle == selected {
+                            text = text.background_color(CURRENT_LINE_COLOR);
+                        }
+                        if ui.add(Label::new(text).sense(Sense::click())).clicked() {
+                            clicked = Some(cycle);
+                        }
+                    }
+                    ui.end_row();
+
+                    for timing in rows {
+                        ui.monospace(timing.instruction.to_string());
+                        for cycle in first..=last {
+                            let stage = timing.stage_at(cycle);
+                            let held =
+                                stage.is_some_and(|stage| cycle > timing.entered[stage as usize]);
+                            let name = stage.map_or("", Stage::name);
+                            let mut text = RichText::new(name).monospace();
+                            if held {
+                                text = text.color(STALL_COLOR);
+                            }
+                            if cycle == selected {
+                                text = text.background_color(CURRENT_LINE_COLOR);
+                            }
+                            if ui.add(Label::new(text).sense(Sense::click())).clicked() {
+                                clicked = Some(cycle);
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+    clicked
+}
@@ -0,0 +1,181 @@
+use eframe::egui::{self, Color32, Key, RichText, Sense};
+
+use crate::{
+    registers::{Register, RegisterFile, RegisterId},
+    simulator::Simulator,
+};
+
+const CHANGED_COLOR: Color32 = Color32::from_rgb(255, 200, 60);
+const INVALID_COLOR: Color32 = Color32::from_rgb(230, 80, 70);
+
+/// Parses a value typed into the panel: hex with `0x`, binary with `0b`, or
+/// a decimal that may be negative.
+fn parse_value(text: &str) -> Option<u32> {
+    let text = text.trim().replace('_', "");
+    if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        let value = text.parse::<i64>().ok()?;
+        (i32::MIN as i64..=u32::MAX as i64)
+            .contains(&value)
+            .then_some(value as u32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Hex,
+    Unsigned,
+    Signed,
+}
+
+impl Format {
+    fn render(self, value: u32) -> String {
+        match self {
+            Format::Hex => format!("0x{:08x}", value),
+            Format::Unsigned => value.to_string(),
+            Format::Signed => (value as i32).to_string(),
+        }
+    }
+}
+
+/// The register table, remembering the state before the last step so it can
+/// highlight what that step changed.
+#[derive(Default)]
+pub struct RegisterPanel {
+    format: Format,
+    before: Option<RegisterFile>,
+    /// The register being edited and the text typed so far.
+    editing: Option<(&'static str, String)>,
+}
+
+impl RegisterPanel {
+    /// Call right before executing so the next frame can highlight changes.
+    pub fn record(&mut self, simulator: &Simulator) {
+        self.before = Some(simulator.registers().clone());
+    }
+
+    /// Forget the previous state, e.g. after reassembling.
+    pub fn clear(&mut self) {
+        self.before = None;
+    }
+
+    /// Draws one register. While `editable`, double-clicking the value
+    /// turns it into a text field; returns the value entered there.
+    fn row(
+        &mut self,
+        ui: &mut egui::Ui,
+        target: RegisterId,
+        number: &str,
+        value: u32,
+        changed: bool,
+        editable: bool,
+    ) -> Option<u32> {
+        let name = target.name();
+        let text = |s: String| {
+            let text = RichText::new(s).monospace();
+            if changed {
+                text.color(CHANGED_COLOR).strong()
+            } else {
+                text
+            }
+        };
+        ui.label(text(name.to_string()));
+        ui.label(text(number.to_string()));
+
+        let mut entered = None;
+        match &mut self.editing {
+            Some((editing, input)) if *editing == name && editable => {
+                let valid = parse_value(input);
+                let field = ui.add(
+                    egui::TextEdit::singleline(input)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(90.0)
+                        .text_color_opt(valid.is_none().then_some(INVALID_COLOR)),
+                );
+                field.request_focus();
+                if ui.input(|input| input.key_pressed(Key::Escape)) {
+                    self.editing = None;
+                } else if field.lost_focus() {
+                    if ui.input(|input| input.key_pressed(Key::Enter)) {
+                        entered = valid;
+                    }
+                    self.editing = None;
+                }
+            }
+            _ => {
+                let mut label =
+                    ui.add(egui::Label::new(text(self.format.render(value))).sense(Sense::click()));
+                if editable {
+                    label = label.on_hover_text("Double-click to edit");
+                }
+                if editable && label.double_clicked() {
+                    self.editing = Some((name, self.format.render(value)));
+                }
+            }
+        }
+        ui.end_row();
+        entered
+    }
+
+    /// Draws the table, and returns a register the user changed while the
+    /// simulator is `editable`, i.e. paused, with its new value.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        simulator: Option<&Simulator>,
+        editable: bool,
+    ) -> Option<(RegisterId, u32)> {
+        let editable = editable && simulator.is_some();
+        if !editable {
+            self.editing = None;
+        }
+        let mut edited = None;
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.format, Format::Hex, "Hex");
+            ui.selectable_value(&mut self.format, Format::Unsigned, "Decimal");
+            ui.selectable_value(&mut self.format, Format::Signed, "Signed");
+        });
+
+        let registers = simulator.map_or_else(RegisterFile::default, |sim| sim.registers().clone());
+        let changed: Vec<RegisterId> = self
+            .before
+            .as_ref()
+            .filter(|_| simulator.is_some())
+            .map(|before| registers.diff(before))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|change| change.register)
+            .collect();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("register_grid")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Name");
+                    ui.strong("Number");
+                    ui.strong("Value");
+                    ui.end_row();
+
+                    for (target, value) in registers.iter_all() {
+                        let number = match target {
+                            RegisterId::General(register) => (register as usize).to_string(),
+                            _ => String::new(),
+                        };
+                        // $zero is hardwired, so there is nothing to edit.
+                        let writable = editable && target != RegisterId::General(Register::ZERO);
+                        let changed = changed.contains(&target);
+                        if let Some(value) = self.row(ui, target, &number, value, changed, writable)
+                        {
+                            edited = Some((target, value));
+                        }
+                    }
+                });
+        });
+        edited
+    }
+}
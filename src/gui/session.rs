@@ -0,0 +1,54 @@
+use std::{collections::BTreeMap, fs, io, path::Path, path::PathBuf};
+
+use egui_dock::DockState;
+use serde::{Deserialize, Serialize};
+
+use crate::gui::{dock::Tab, memory::MemoryView};
+
+const SESSION_KEY: &str = "session";
+
+/// An open file and the breakpoints set in it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionFile {
+    pub path: PathBuf,
+    pub breakpoints: Vec<usize>,
+    /// Conditions of the conditional breakpoints, by line.
+    #[serde(default)]
+    pub conditions: BTreeMap<usize, String>,
+}
+
+/// Where the user left off: open files, breakpoints, watches, the memory
+/// view and the panel layout. Kept in the eframe storage between runs and
+/// saved to a file on request. Untitled buffers are not included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub files: Vec<SessionFile>,
+    /// Index into `files` of the tab that was in front.
+    pub active: Option<usize>,
+    pub watches: Vec<String>,
+    pub memory: MemoryView,
+    /// Left out of the eframe storage, which keeps the layout separately.
+    pub layout: Option<DockState<Tab>>,
+}
+
+impl Session {
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Option<Self> {
+        storage.and_then(|storage| eframe::get_value(storage, SESSION_KEY))
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SESSION_KEY, self);
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text)
+    }
+}
@@ -0,0 +1,232 @@
+use eframe::egui::{self, Event, Key, KeyboardShortcut};
+
+use crate::{
+    gui::shortcuts::{self, Binding, Shortcuts},
+    simulator::DelaySlots,
+};
+
+const SETTINGS_KEY: &str = "settings";
+const FONT_SIZES: std::ops::RangeInclusive<f32> = 8.0..=32.0;
+const TAB_WIDTHS: std::ops::RangeInclusive<usize> = 1..=8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+/// User preferences, persisted in the eframe storage as `key=value` lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub theme: Theme,
+    pub font_size: f32,
+    /// Columns between tab stops; the Tab key inserts spaces up to the next.
+    pub tab_width: usize,
+    pub shortcuts: Shortcuts,
+    /// Applied to the machine built by the next assemble.
+    pub delay_slots: DelaySlots,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            font_size: 14.0,
+            tab_width: 4,
+            shortcuts: Shortcuts::default(),
+            delay_slots: DelaySlots::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads saved settings, keeping the default for anything missing or invalid.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let mut settings = Self::default();
+        let Some(saved) = storage.and_then(|storage| storage.get_string(SETTINGS_KEY)) else {
+            return settings;
+        };
+
+        for (key, value) in saved.lines().filter_map(|line| line.split_once('=')) {
+            match (key.trim(), value.trim()) {
+                ("theme", "dark") => settings.theme = Theme::Dark,
+                ("theme", "light") => settings.theme = Theme::Light,
+                ("font_size", size) => {
+                    if let Some(size) = size.parse().ok().filter(|size| FONT_SIZES.contains(size)) {
+                        settings.font_size = size;
+                    }
+                }
+                ("tab_width", width) => {
+                    if let Some(width) = width
+                        .parse()
+                        .ok()
+                        .filter(|width| TAB_WIDTHS.contains(width))
+                    {
+                        settings.tab_width = width;
+                    }
+                }
+                ("delayed_branches", value) => {
+                    settings.delay_slots.branches = value == "true";
+                }
+                ("delayed_loads", value) => settings.delay_slots.loads = value == "true",
+                (key, value) => {
+                    let binding = key.strip_prefix("shortcut.").and_then(|name| {
+                        Binding::ALL
+                            .into_iter()
+                            .find(|binding| binding.key() == name)
+                    });
+                    if let (Some(binding), Some(shortcut)) = (binding, shortcuts::parse(value)) {
+                        settings.shortcuts.set(binding, shortcut);
+                    }
+                }
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        let theme = match self.theme {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        };
+        let mut saved = format!(
+            "theme={}\nfont_size={}\ntab_width={}\ndelayed_branches={}\ndelayed_loads={}",
+            theme,
+            self.font_size,
+            self.tab_width,
+            self.delay_slots.branches,
+            self.delay_slots.loads
+        );
+        for binding in Binding::ALL {
+            saved.push_str(&format!(
+                "\nshortcut.{}={}",
+                binding.key(),
+                shortcuts::format(&self.shortcuts.get(binding))
+            ));
+        }
+        storage.set_string(SETTINGS_KEY, saved);
+    }
+
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_theme(match self.theme {
+            Theme::Dark => egui::Theme::Dark,
+            Theme::Light => egui::Theme::Light,
+        });
+    }
+
+    pub fn editor_font(&self) -> egui::FontId {
+        egui::FontId::monospace(self.font_size)
+    }
+}
+
+#[derive(Default)]
+pub struct SettingsWindow {
+    pub open: bool,
+    /// The binding waiting for a key press to rebind it.
+    recording: Option<Binding>,
+}
+
+impl SettingsWindow {
+    /// Whether a key press is being captured for a shortcut, during which
+    /// shortcuts must not fire.
+    pub fn is_recording(&self) -> bool {
+        self.open && self.recording.is_some()
+    }
+
+    /// Binds the recorded shortcut to the next key pressed; Escape cancels.
+    fn record(&mut self, ctx: &egui::Context, settings: &mut Settings) {
+        let Some(binding) = self.recording else {
+            return;
+        };
+        let pressed = ctx.input(|input| {
+            input.events.iter().find_map(|event| match event {
+                Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some(KeyboardShortcut::new(*modifiers, *key)),
+                _ => None,
+            })
+        });
+        let Some(shortcut) = pressed else {
+            return;
+        };
+        if shortcut.logical_key != Key::Escape {
+            settings.shortcuts.set(binding, shortcut);
+        }
+        self.recording = None;
+    }
+
+    /// Edits `settings` in place, applying theme changes immediately.
+    pub fn show(&mut self, ctx: &egui::Context, settings: &mut Settings) {
+        let theme = settings.theme;
+        self.record(ctx, settings);
+        let mut recording = self.recording;
+
+        egui::Window::new("Settings")
+            .open(&mut self.open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("settings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Theme");
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut settings.theme, Theme::Dark, "Dark");
+                            ui.selectable_value(&mut settings.theme, Theme::Light, "Light");
+                        });
+                        ui.end_row();
+
+                        ui.label("Editor font size");
+                        ui.add(egui::Slider::new(&mut settings.font_size, FONT_SIZES).step_by(1.0));
+                        ui.end_row();
+
+                        ui.label("Tab width");
+                        ui.add(egui::Slider::new(&mut settings.tab_width, TAB_WIDTHS));
+                        ui.end_row();
+                    });
+
+                ui.separator();
+                ui.strong("Simulation");
+                ui.checkbox(&mut settings.delay_slots.branches, "Delayed branches")
+                    .on_hover_text(
+                        "Execute the instruction after a branch or jump before taking it",
+                    );
+                ui.checkbox(&mut settings.delay_slots.loads, "Delayed loads")
+                    .on_hover_text("Write a loaded register only after the next instruction");
+                ui.label("Takes effect when the program is next assembled.");
+
+                ui.separator();
+                ui.strong("Debugger shortcuts");
+                egui::Grid::new("shortcuts_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for binding in Binding::ALL {
+                            ui.label(binding.title());
+                            let text = match recording == Some(binding) {
+                                true => "Press a key…".to_string(),
+                                false => shortcuts::format(&settings.shortcuts.get(binding)),
+                            };
+                            if ui
+                                .button(text)
+                                .on_hover_text("Click, then press the new shortcut")
+                                .clicked()
+                            {
+                                recording = Some(binding);
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                if ui.button("Restore defaults").clicked() {
+                    *settings = Settings::default();
+                }
+            });
+
+        self.recording = recording.filter(|_| self.open);
+        if settings.theme != theme {
+            settings.apply(ctx);
+        }
+    }
+}
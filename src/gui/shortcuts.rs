@@ -0,0 +1,138 @@
+use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
+
+/// A debugger command that can be bound to a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Continue,
+    Pause,
+    StepOver,
+    StepInto,
+    StepOut,
+    ToggleBreakpoint,
+}
+
+impl Binding {
+    pub const ALL: [Binding; 6] = [
+        Binding::Continue,
+        Binding::Pause,
+        Binding::StepOver,
+        Binding::StepInto,
+        Binding::StepOut,
+        Binding::ToggleBreakpoint,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Binding::Continue => "Run / continue",
+            Binding::Pause => "Pause",
+            Binding::StepOver => "Step over",
+            Binding::StepInto => "Step into",
+            Binding::StepOut => "Step out",
+            Binding::ToggleBreakpoint => "Toggle breakpoint",
+        }
+    }
+
+    /// Name of the binding in the saved settings.
+    pub fn key(self) -> &'static str {
+        match self {
+            Binding::Continue => "continue",
+            Binding::Pause => "pause",
+            Binding::StepOver => "step_over",
+            Binding::StepInto => "step_into",
+            Binding::StepOut => "step_out",
+            Binding::ToggleBreakpoint => "toggle_breakpoint",
+        }
+    }
+
+    fn default_shortcut(self) -> KeyboardShortcut {
+        match self {
+            Binding::Continue => KeyboardShortcut::new(Modifiers::NONE, Key::F5),
+            Binding::Pause => KeyboardShortcut::new(Modifiers::NONE, Key::F6),
+            Binding::StepOver => KeyboardShortcut::new(Modifiers::NONE, Key::F10),
+            Binding::StepInto => KeyboardShortcut::new(Modifiers::NONE, Key::F11),
+            Binding::StepOut => KeyboardShortcut::new(Modifiers::SHIFT, Key::F11),
+            Binding::ToggleBreakpoint => KeyboardShortcut::new(Modifiers::COMMAND, Key::B),
+        }
+    }
+}
+
+/// Writes a shortcut as `Ctrl+Shift+F11`, the form the settings store.
+/// `Ctrl` stands for the command key on macOS.
+pub fn format(shortcut: &KeyboardShortcut) -> String {
+    let modifiers = shortcut.modifiers;
+    let mut parts = Vec::new();
+    if modifiers.command || modifiers.ctrl {
+        parts.push("Ctrl");
+    }
+    if modifiers.alt {
+        parts.push("Alt");
+    }
+    if modifiers.shift {
+        parts.push("Shift");
+    }
+    parts.push(shortcut.logical_key.name());
+    parts.join("+")
+}
+
+/// Reads a shortcut written by [`format`].
+pub fn parse(text: &str) -> Option<KeyboardShortcut> {
+    let (modifier_names, key) = match text.rsplit_once('+') {
+        Some((modifiers, key)) => (modifiers, key),
+        None => ("", text),
+    };
+    let mut modifiers = Modifiers::NONE;
+    for name in modifier_names.split('+').filter(|name| !name.is_empty()) {
+        modifiers |= match name {
+            "Ctrl" => Modifiers::COMMAND,
+            "Alt" => Modifiers::ALT,
+            "Shift" => Modifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    Some(KeyboardShortcut::new(modifiers, Key::from_name(key)?))
+}
+
+/// The key each [`Binding`] is on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shortcuts {
+    shortcuts: [KeyboardShortcut; Binding::ALL.len()],
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            shortcuts: Binding::ALL.map(Binding::default_shortcut),
+        }
+    }
+}
+
+impl Shortcuts {
+    pub fn get(&self, binding: Binding) -> KeyboardShortcut {
+        self.shortcuts[binding as usize]
+    }
+
+    pub fn set(&mut self, binding: Binding, shortcut: KeyboardShortcut) {
+        self.shortcuts[binding as usize] = shortcut;
+    }
+
+    /// Consumes the first pressed shortcut and returns its binding.
+    pub fn pressed(&self, ctx: &egui::Context) -> Option<Binding> {
+        // Shortcuts with more modifiers first, so Shift+F11 is not taken
+        // for F11.
+        let mut bindings = Binding::ALL;
+        bindings.sort_by_key(|&binding| {
+            let modifiers = self.get(binding).modifiers;
+            std::cmp::Reverse(
+                [modifiers.command, modifiers.alt, modifiers.shift]
+                    .into_iter()
+                    .filter(|&held| held)
+                    .count(),
+            )
+        });
+        ctx.input_mut(|input| {
+            bindings
+                .into_iter()
+                .find(|&binding| input.consume_shortcut(&self.get(binding)))
+        })
+    }
+}
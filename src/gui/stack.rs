@@ -0,0 +1,65 @@
+use eframe::egui::{self, Color32, RichText};
+
+use crate::{
+    address::Address, debug_info::DebugInfo, memory::STACK_TOP, registers::Register,
+    simulator::Simulator,
+};
+
+const FRAME_COLOR: Color32 = Color32::from_rgba_unmultiplied_const(90, 140, 230, 40);
+/// Words shown above `$sp`.
+const MAX_WORDS: u32 = 256;
+
+/// Shows the words from `$sp` upward. The rows between `$sp` and `$fp`
+/// are shaded as the current frame, and words that hold a text address
+/// are annotated with the source line they point to.
+pub fn show(ui: &mut egui::Ui, simulator: Option<&Simulator>, debug_info: &DebugInfo) {
+    let Some(simulator) = simulator else {
+        ui.label("Assemble a program to inspect its stack.");
+        return;
+    };
+
+    let sp = simulator.registers().get(Register::SP) & !3;
+    let fp = simulator.registers().get(Register::FP);
+    let words = STACK_TOP.0.saturating_sub(sp).div_ceil(4).min(MAX_WORDS) as usize;
+    if words == 0 {
+        ui.label(format!("$sp (0x{:08x}) is outside the stack.", sp));
+        return;
+    }
+
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    egui::ScrollArea::vertical()
+        .id_salt("stack_scroll")
+        .auto_shrink(false)
+        .show_rows(ui, row_height, words, |ui, range| {
+            for row in range {
+                let address = Address(sp) + row * 4;
+                let value = simulator.memory().read_word(address);
+
+                let mut notes = Vec::new();
+                if address.0 == sp {
+                    notes.push("← $sp".to_string());
+                }
+                if address.0 == fp & !3 {
+                    notes.push("← $fp".to_string());
+                }
+                if let Some(line) = value.and_then(|word| debug_info.line_of(Address(word))) {
+                    notes.push(format!("→ line {}", line));
+                }
+
+                let value = value.map_or_else(|| "--------".to_string(), |w| format!("{:08x}", w));
+                let text = RichText::new(format!(
+                    "0x{:08x}  {}  {}",
+                    address.0,
+                    value,
+                    notes.join(" ")
+                ))
+                .monospace();
+
+                if fp > sp && address.0 <= fp {
+                    ui.label(text.background_color(FRAME_COLOR));
+                } else {
+                    ui.label(text);
+                }
+            }
+        });
+}
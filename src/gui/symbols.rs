@@ -0,0 +1,81 @@
+use eframe::egui::{self, Label, RichText, Sense};
+
+use crate::{
+    assembler::Segment,
+    debug_info::{DebugInfo, SymbolInfo},
+};
+
+/// The symbol table window. Double-clicking a row asks the app to jump to
+/// that symbol.
+#[derive(Default)]
+pub struct SymbolWindow {
+    pub open: bool,
+    filter: String,
+}
+
+impl SymbolWindow {
+    pub fn show<'a>(
+        &mut self,
+        ctx: &egui::Context,
+        debug_info: &'a DebugInfo,
+    ) -> Option<&'a SymbolInfo> {
+        let mut chosen = None;
+        let filter = &mut self.filter;
+
+        egui::Window::new("Symbol Table")
+            .open(&mut self.open)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Filter:");
+                    ui.text_edit_singleline(filter);
+                });
+
+                let mut symbols: Vec<&SymbolInfo> = debug_info
+                    .symbols()
+                    .iter()
+                    .filter(|symbol| symbol.name.contains(filter.as_str()))
+                    .collect();
+                symbols.sort_by_key(|symbol| symbol.address.0);
+
+                if symbols.is_empty() {
+                    ui.label("No symbols. Assemble a program with labels.");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("symbol_grid")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Label");
+                            ui.strong("Address");
+                            ui.strong("Segment");
+                            ui.end_row();
+
+                            for symbol in symbols {
+                                let segment = match symbol.segment {
+                                    Segment::Text => ".text",
+                                    Segment::Data => ".data",
+                                };
+                                let cells = [
+                                    symbol.name.clone(),
+                                    format!("0x{:08x}", symbol.address.0),
+                                    segment.to_string(),
+                                ];
+                                for cell in cells {
+                                    let label = Label::new(RichText::new(cell).monospace())
+                                        .sense(Sense::click());
+                                    if ui.add(label).double_clicked() {
+                                        chosen = Some(symbol);
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+
+        chosen
+    }
+}
@@ -0,0 +1,67 @@
+use eframe::egui::{self, Label, RichText, Sense};
+
+use crate::{
+    address::Address, assembler::BASE_TEXT_ADDR, debug_info::DebugInfo,
+    gui::editor::CURRENT_LINE_COLOR, simulator::Simulator,
+};
+
+/// Lists the assembled text segment, highlighting the instruction at
+/// `current` and scrolling it into view when `scroll` is set. Returns the
+/// address of an instruction the user clicked.
+pub fn show(
+    ui: &mut egui::Ui,
+    simulator: Option<&Simulator>,
+    debug_info: &DebugInfo,
+    current: Option<Address>,
+    scroll: bool,
+) -> Option<Address> {
+    let Some(simulator) = simulator else {
+        ui.label("Assemble a program to list its instructions.");
+        return None;
+    };
+
+    let instructions = simulator.instructions();
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    let current_row = current
+        .and_then(|pc| pc.word_index(BASE_TEXT_ADDR))
+        .filter(|&row| row < instructions.len());
+
+    let mut area = egui::ScrollArea::vertical()
+        .id_salt("text_segment_scroll")
+        .auto_shrink(false);
+    if let Some(row) = current_row.filter(|_| scroll) {
+        let spacing = row_height + ui.spacing().item_spacing.y;
+        let visible = ui.available_height();
+        area = area.vertical_scroll_offset((row as f32 * spacing - visible / 2.0).max(0.0));
+    }
+
+    let mut clicked = None;
+    area.show_rows(ui, row_height, instructions.len(), |ui, range| {
+        for row in range {
+            let address = BASE_TEXT_ADDR + row * 4;
+            let line = debug_info
+                .line_of(address)
+                .map_or_else(String::new, |line| line.to_string());
+            let instruction = instructions[row];
+            let mut text = RichText::new(format!(
+                "0x{:08x}  0x{:08x}  {:>5}  {}",
+                address.0,
+                instruction.encode(),
+                line,
+                instruction
+            ))
+            .monospace();
+            if Some(row) == current_row {
+                text = text.background_color(CURRENT_LINE_COLOR);
+            }
+
+            let response = ui
+                .add(Label::new(text).sense(Sense::click()))
+                .on_hover_text("Click to inspect the encoding");
+            if response.clicked() {
+                clicked = Some(address);
+            }
+        }
+    });
+    clicked
+}
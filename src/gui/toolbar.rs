@@ -0,0 +1,125 @@
+use eframe::egui;
+
+use crate::{handle::RunState, scheduler::Speed};
+
+/// Slider positions at or beyond this many instructions per second mean
+/// "unlimited".
+pub const MAX_HZ: f64 = 10_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Assemble,
+    Run,
+    Pause,
+    Step,
+    StepOver,
+    StepOut,
+    StepBack,
+    Reset,
+    SpeedChanged,
+}
+
+/// Maps the slider position to a [`Speed`].
+pub fn speed_from_slider(hz: f64) -> Speed {
+    if hz >= MAX_HZ {
+        Speed::Unlimited
+    } else {
+        Speed::Hz(hz)
+    }
+}
+
+/// Draws the run controls, enabled according to the machine's state
+/// (`None` when nothing is assembled) and whether an executed instruction
+/// can be undone, and returns the clicked action.
+pub fn show(
+    ui: &mut egui::Ui,
+    state: Option<&RunState>,
+    can_step_back: bool,
+    hz: &mut f64,
+) -> Option<Action> {
+    let paused = matches!(state, Some(RunState::Paused));
+    let stopped = matches!(
+        state,
+        Some(RunState::Paused | RunState::WaitingForInput { .. } | RunState::Finished(_))
+    );
+    let running = matches!(state, Some(RunState::Running));
+    let assembled = state.is_some();
+
+    let buttons = [
+        (
+            Action::Assemble,
+            "🔧 Assemble",
+            "Assemble the current file",
+            true,
+        ),
+        (Action::Run, "▶ Run", "Run continuously", paused),
+        (Action::Pause, "⏸ Pause", "Pause execution", running),
+        (
+            Action::StepBack,
+            "⏮ Back",
+            "Undo the last instruction",
+            stopped && can_step_back,
+        ),
+        (Action::Step, "⏭ Step", "Execute one instruction", paused),
+        (
+            Action::StepOver,
+            "↷ Over",
+            "Step, running over a subroutine call",
+            paused,
+        ),
+        (
+            Action::StepOut,
+            "⤴ Out",
+            "Run until the current subroutine returns",
+            paused,
+        ),
+        (
+            Action::Reset,
+            "⟲ Reset",
+            "Reset to the freshly assembled state",
+            assembled,
+        ),
+    ];
+
+    let mut clicked = None;
+    ui.horizontal(|ui| {
+        for (action, label, hint, enabled) in buttons {
+            if ui
+                .add_enabled(enabled, egui::Button::new(label))
+                .on_hover_text(hint)
+                .clicked()
+            {
+                clicked = Some(action);
+            }
+        }
+
+        ui.separator();
+        ui.label("Speed");
+        let slider = egui::Slider::new(hz, 1.0..=MAX_HZ)
+            .logarithmic(true)
+            .custom_formatter(|value, _| {
+                if value >= MAX_HZ {
+                    "max".to_string()
+                } else {
+                    format!("{:.0} inst/s", value)
+                }
+            });
+        if ui
+            .add(slider)
+            .on_hover_text("Instructions per second while running")
+            .changed()
+        {
+            clicked = Some(Action::SpeedChanged);
+        }
+
+        ui.separator();
+        ui.label(match state {
+            None => "Not assembled",
+            Some(RunState::Paused) => "Paused",
+            Some(RunState::Running) => "Running",
+            Some(RunState::WaitingForInput { .. }) => "Waiting for input",
+            Some(RunState::Finished(_)) => "Finished",
+        });
+    });
+    clicked
+}
@@ -0,0 +1,158 @@
+use eframe::egui::{self, Color32, Key, RichText};
+
+use crate::{
+    debug_info::DebugInfo,
+    expression::{Expression, ExpressionError, Machine},
+    simulator::Simulator,
+};
+
+const CHANGED_COLOR: Color32 = Color32::from_rgb(255, 200, 60);
+const ERROR_COLOR: Color32 = Color32::from_rgb(230, 80, 70);
+
+struct Watch {
+    text: String,
+    /// The parsed expression, or why it did not parse.
+    expression: Result<Expression, String>,
+}
+
+/// Expressions re-evaluated against the machine every frame, highlighted
+/// when the last step changed their value.
+#[derive(Default)]
+pub struct WatchPanel {
+    watches: Vec<Watch>,
+    input: String,
+    /// Values before the last step, one per watch.
+    before: Vec<Option<u32>>,
+}
+
+fn evaluate(watch: &Watch, machine: &Machine) -> Result<u32, String> {
+    let expression = watch.expression.as_ref().map_err(Clone::clone)?;
+    expression.evaluate(machine).map_err(|err| err.to_string())
+}
+
+impl WatchPanel {
+    /// Call right before executing so the next frame can highlight changes.
+    pub fn record(&mut self, simulator: &Simulator, debug_info: &DebugInfo) {
+        let machine = Machine {
+            simulator,
+            debug_info,
+        };
+        self.before = self
+            .watches
+            .iter()
+            .map(|watch| evaluate(watch, &machine).ok())
+            .collect();
+    }
+
+    /// Forget the previous values, e.g. after reassembling.
+    pub fn clear(&mut self) {
+        self.before.clear();
+    }
+
+    pub fn expressions(&self) -> Vec<String> {
+        self.watches
+            .iter()
+            .map(|watch| watch.text.clone())
+            .collect()
+    }
+
+    /// Adds watches for `expressions`, e.g. from a saved session.
+    pub fn extend(&mut self, expressions: impl IntoIterator<Item = String>) {
+        for text in expressions {
+            let expression = text.parse().map_err(|err: ExpressionError| err.to_string());
+            self.watches.push(Watch { text, expression });
+        }
+    }
+
+    fn add(&mut self) {
+        let text = self.input.trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        self.extend([text]);
+        self.input.clear();
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        simulator: Option<&Simulator>,
+        debug_info: &DebugInfo,
+    ) {
+        ui.horizontal(|ui| {
+            let field = ui.add(
+                egui::TextEdit::singleline(&mut self.input)
+                    .hint_text("$t0, $sp+8, word(buf+4)…")
+                    .desired_width(200.0),
+            );
+            let entered = field.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+            if entered || ui.button("Add").clicked() {
+                self.add();
+                field.request_focus();
+            }
+        });
+
+        let mut remove = None;
+        egui::ScrollArea::vertical()
+            .id_salt("watch_scroll")
+            .auto_shrink(false)
+            .show(ui, |ui| {
+                egui::Grid::new("watch_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Expression", "Hex", "Decimal", ""] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for (index, watch) in self.watches.iter().enumerate() {
+                            ui.monospace(&watch.text);
+                            let value = simulator.map(|simulator| {
+                                let machine = Machine {
+                                    simulator,
+                                    debug_info,
+                                };
+                                evaluate(watch, &machine)
+                            });
+                            match value {
+                                Some(Ok(value)) => {
+                                    let changed = self
+                                        .before
+                                        .get(index)
+                                        .is_some_and(|&before| before != Some(value));
+                                    let text = |s: String| match changed {
+                                        true => RichText::new(s)
+                                            .monospace()
+                                            .color(CHANGED_COLOR)
+                                            .strong(),
+                                        false => RichText::new(s).monospace(),
+                                    };
+                                    ui.label(text(format!("0x{:08x}", value)));
+                                    ui.label(text((value as i32).to_string()));
+                                }
+                                Some(Err(err)) => {
+                                    ui.colored_label(ERROR_COLOR, err);
+                                    ui.label("");
+                                }
+                                None => {
+                                    ui.weak("—");
+                                    ui.label("");
+                                }
+                            }
+                            if ui.small_button("×").on_hover_text("Remove").clicked() {
+                                remove = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(index) = remove {
+            self.watches.remove(index);
+            if index < self.before.len() {
+                self.before.remove(index);
+            }
+        }
+    }
+}
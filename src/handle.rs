@@ -0,0 +1,457 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use crate::{
+    address::Address,
+    assembler::Program,
+    expression::Expression,
+    journal,
+    scheduler::{Speed, Throttle},
+    simulator::{DelaySlots, Simulator, SimulatorError, Stopped},
+    stepping::UntilReturn,
+    syscall_io::SyscallIo,
+    trace::Tracer,
+    watchpoint::{WatchHit, Watchpoint},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Run,
+    Pause,
+    Step,
+    /// Steps, running over a subroutine call at the PC.
+    StepOver,
+    /// Runs until the current subroutine returns.
+    StepOut,
+    /// Undoes the last instruction executed.
+    StepBack,
+    /// Rebuilds the machine from the assembled program.
+    Reset,
+    /// New input was queued for a program blocked in a read syscall.
+    InputReady,
+    /// Changes how fast `Run` executes.
+    SetSpeed(Speed),
+}
+
+/// Why a program stopped for good.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Exited(u32),
+    DroppedOffBottom,
+    ReturnedFromMain,
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunState {
+    Paused,
+    Running,
+    /// Blocked in a read syscall; `resume_running` records whether it was
+    /// running continuously or single-stepping when it blocked.
+    WaitingForInput {
+        resume_running: bool,
+    },
+    Finished(Outcome),
+}
+
+struct Shared {
+    simulator: Mutex<Simulator>,
+    state: Mutex<RunState>,
+    /// The watchpoint that last paused the machine, until the front-end
+    /// takes it.
+    watch_hit: Mutex<Option<WatchHit>>,
+    /// Why the simulator last panicked, until the front-end takes it.
+    crash: Mutex<Option<String>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The message a panic was raised with, if it had one.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Owns a simulator running on a background thread, controlled by
+/// [`Command`]s so a front-end never blocks on execution.
+pub struct SimulatorHandle {
+    shared: Arc<Shared>,
+    commands: Option<Sender<Command>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SimulatorHandle {
+    /// Starts a paused machine for `program` with the given delay slots.
+    /// `notify` is called from the worker thread whenever the state visibly
+    /// changes, e.g. to request a repaint.
+    pub fn spawn<Io>(
+        program: Program,
+        io: Io,
+        delay_slots: DelaySlots,
+        notify: impl Fn() + Send + 'static,
+    ) -> Self
+    where
+        Io: SyscallIo + Clone + 'static,
+    {
+        let build = move || {
+            let mut simulator = Simulator::new(program.clone());
+            simulator.set_io(Box::new(io.clone()));
+            simulator.set_delay_slots(delay_slots);
+            simulator.set_journal_depth(journal::DEFAULT_DEPTH);
+            simulator.set_call_tracking(true);
+            simulator.set_profiling(true);
+            simulator
+        };
+
+        let shared = Arc::new(Shared {
+            simulator: Mutex::new(build()),
+            state: Mutex::new(RunState::Paused),
+            watch_hit: Mutex::new(None),
+            crash: Mutex::new(None),
+        });
+        let (commands, receiver) = mpsc::channel();
+
+        let worker = Worker {
+            shared: shared.clone(),
+            receiver,
+            throttle: Throttle::new(Speed::Unlimited),
+            build: Box::new(build),
+            until: None,
+            notify: Box::new(notify),
+        };
+        let thread = thread::spawn(move || worker.run());
+
+        Self {
+            shared,
+            commands: Some(commands),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn send(&self, command: Command) {
+        if let Some(commands) = &self.commands {
+            // The worker only exits once the handle is dropped.
+            let _ = commands.send(command);
+        }
+    }
+
+    pub fn state(&self) -> RunState {
+        lock(&self.shared.state).clone()
+    }
+
+    /// Replaces the addresses where a continuous run pauses, each with an
+    /// optional condition; see [`Simulator::add_conditional_breakpoint`].
+    pub fn set_breakpoints(
+        &self,
+        breakpoints: impl IntoIterator<Item = (Address, Option<Expression>)>,
+    ) {
+        let mut simulator = lock(&self.shared.simulator);
+        simulator.clear_breakpoints();
+        for (address, condition) in breakpoints {
+            match condition {
+                Some(condition) => simulator.add_conditional_breakpoint(address, condition),
+                None => simulator.add_breakpoint(address),
+            };
+        }
+    }
+
+    /// Replaces the watched memory ranges.
+    pub fn set_watchpoints(&self, watchpoints: impl IntoIterator<Item = Watchpoint>) {
+        let mut simulator = lock(&self.shared.simulator);
+        simulator.clear_watchpoints();
+        for watchpoint in watchpoints {
+            simulator.add_watchpoint(watchpoint);
+        }
+    }
+
+    /// Starts or stops tracing, returning the previous tracer so it can be
+    /// finished. The trace carries on across resets.
+    pub fn set_trace(&self, trace: Option<Tracer>) -> Option<Tracer> {
+        lock(&self.shared.simulator).set_trace(trace)
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        lock(&self.shared.simulator).trace().is_some()
+    }
+
+    /// The watchpoint hit since the last call, if any.
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        lock(&self.shared.watch_hit).take()
+    }
+
+    /// Why the simulator panicked since the last call, if it did. The
+    /// machine has already been reset by then.
+    pub fn take_crash(&self) -> Option<String> {
+        lock(&self.shared.crash).take()
+    }
+
+    /// Whether there is an executed instruction to step back over.
+    pub fn can_step_back(&self) -> bool {
+        !lock(&self.shared.simulator).journal().is_empty()
+    }
+
+    /// Locks the machine for inspection. Hold the guard briefly: the worker
+    /// cannot execute while it is held.
+    pub fn lock(&self) -> MutexGuard<'_, Simulator> {
+        lock(&self.shared.simulator)
+    }
+}
+
+impl Drop for SimulatorHandle {
+    fn drop(&mut self) {
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct Worker {
+    shared: Arc<Shared>,
+    receiver: Receiver<Command>,
+    throttle: Throttle,
+    build: Box<dyn Fn() -> Simulator + Send>,
+    /// Set while running to finish a step-over or step-out.
+    until: Option<UntilReturn>,
+    notify: Box<dyn Fn() + Send>,
+}
+
+impl Worker {
+    fn state(&self) -> RunState {
+        lock(&self.shared.state).clone()
+    }
+
+    fn set_state(&self, state: RunState) {
+        *lock(&self.shared.state) = state;
+    }
+
+    /// Runs until a subroutine returns, like a continuous run that pauses
+    /// there.
+    fn run_until(&mut self, until: UntilReturn) {
+        self.resume();
+        self.until = Some(until);
+    }
+
+    /// Starts running continuously, past any breakpoint at the PC.
+    fn resume(&mut self) {
+        lock(&self.shared.simulator).skip_breakpoint();
+        self.start();
+    }
+
+    /// Starts running continuously, stopping at any breakpoint at the PC.
+    fn start(&mut self) {
+        self.throttle.reset();
+        self.set_state(RunState::Running);
+    }
+
+    fn run(mut self) {
+        loop {
+            let running = self.state() == RunState::Running;
+            let command = if running {
+                match self
+                    .receiver
+                    .recv_timeout(self.throttle.time_until_due(Instant::now()))
+                {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match self.receiver.recv() {
+                    Ok(command) => Some(command),
+                    Err(_) => return,
+                }
+            };
+
+            if let Some(command) = command {
+                self.guard(|worker| worker.handle(command));
+                (self.notify)();
+                continue;
+            }
+
+            let due = self.throttle.due(Instant::now());
+            if due > 0 {
+                self.guard(|worker| worker.execute(due, true));
+                self.throttle.record(due);
+                (self.notify)();
+            }
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        match (command, self.state()) {
+            (Command::Run, RunState::Paused) => self.resume(),
+            (Command::Pause, RunState::Running) => {
+                self.until = None;
+                self.set_state(RunState::Paused);
+            }
+            (Command::Step, RunState::Paused) => self.execute(1, false),
+            (Command::StepOver, RunState::Paused) => {
+                self.execute(1, false);
+                // Breakpoints in the subroutine, even at its entry, still stop.
+                if self.state() == RunState::Paused && lock(&self.shared.simulator).called() {
+                    self.start();
+                    self.until = Some(UntilReturn::step_out());
+                }
+            }
+            (Command::StepOut, RunState::Paused) => self.run_until(UntilReturn::step_out()),
+            (Command::InputReady, RunState::WaitingForInput { resume_running }) => {
+                if resume_running {
+                    self.resume();
+                } else {
+                    self.set_state(RunState::Paused);
+                    self.execute(1, false);
+                }
+            }
+            (
+                Command::StepBack,
+                RunState::Paused | RunState::WaitingForInput { .. } | RunState::Finished(_),
+            ) if lock(&self.shared.simulator).step_back() => self.set_state(RunState::Paused),
+            (Command::SetSpeed(speed), _) => self.throttle.set_speed(speed),
+            (Command::Reset, _) => self.reset(),
+            _ => {}
+        }
+    }
+
+    /// Rebuilds the machine from the program, keeping its breakpoints,
+    /// watchpoints and trace.
+    fn reset(&mut self) {
+        let mut simulator = lock(&self.shared.simulator);
+        let mut old = std::mem::replace(&mut *simulator, (self.build)());
+        simulator.set_trace(old.set_trace(None));
+        simulator.take_stop_points(old);
+        drop(simulator);
+        self.until = None;
+        self.set_state(RunState::Paused);
+    }
+
+    /// Runs `f`, and if the simulator panics part way through, resets the
+    /// machine and records why instead of letting the worker die with a
+    /// half-updated machine.
+    fn guard(&mut self, f: impl FnOnce(&mut Self)) {
+        let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| f(self))) else {
+            return;
+        };
+        self.reset();
+        *lock(&self.shared.crash) = Some(panic_message(payload.as_ref()));
+    }
+
+    /// Runs one instruction, reporting whether it hit a watchpoint.
+    fn step(&self, simulator: &mut Simulator) -> Result<bool, SimulatorError> {
+        let result = simulator.step();
+        let hit = simulator.take_watch_hit();
+        let stopped = hit.is_some();
+        if stopped {
+            *lock(&self.shared.watch_hit) = hit;
+        }
+        result.map(|()| stopped)
+    }
+
+    /// Runs up to `count` instructions, stopping early at a breakpoint or
+    /// watchpoint, or where a step-over or step-out ends. Returns whether
+    /// it stopped.
+    fn run_batch(&mut self, simulator: &mut Simulator, count: u64) -> Result<bool, SimulatorError> {
+        let result = match &mut self.until {
+            Some(until) => simulator.run_until(until, count),
+            None => simulator.continue_for(count).map(|()| false),
+        };
+        match result {
+            Ok(done) => Ok(done),
+            Err(Stopped::Breakpoint(_)) => Ok(true),
+            Err(Stopped::Watchpoint(hit)) => {
+                *lock(&self.shared.watch_hit) = Some(hit);
+                Ok(true)
+            }
+            Err(Stopped::Halted(err)) => Err(err),
+        }
+    }
+
+    fn execute(&mut self, count: u64, running: bool) {
+        let shared = self.shared.clone();
+        let mut simulator = lock(&shared.simulator);
+        let result = if running {
+            self.run_batch(&mut simulator, count)
+        } else {
+            (0..count)
+                .try_for_each(|_| self.step(&mut simulator).map(drop))
+                .map(|()| false)
+        };
+        drop(simulator);
+
+        let err = match result {
+            Ok(false) => return,
+            Ok(true) => {
+                self.until = None;
+                self.set_state(RunState::Paused);
+                return;
+            }
+            Err(err) => err,
+        };
+
+        let state = match err {
+            SimulatorError::InputPending => RunState::WaitingForInput {
+                resume_running: running,
+            },
+            SimulatorError::Exit(code) => RunState::Finished(Outcome::Exited(code)),
+            SimulatorError::NoMoreInstructions => RunState::Finished(Outcome::DroppedOffBottom),
+            SimulatorError::ReturnedFromMain => RunState::Finished(Outcome::ReturnedFromMain),
+            err => RunState::Finished(Outcome::Error(err.to_string())),
+        };
+        if matches!(state, RunState::Finished(_)) {
+            self.until = None;
+        }
+        self.set_state(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{RuntimeArgs, assembler::Assembler, registers::Register, syscall_io::SharedIo};
+
+    #[test]
+    fn step_over_runs_the_delay_slot_of_the_return() {
+        let mut assembler = Assembler::new();
+        let source = ".text\nmain:\n  jal f\n  nop\nafter:\n  li $v0, 10\n  syscall\n\
+                      f:\n  jr $ra\n  addiu $t0, $zero, 7\n";
+        assembler
+            .assemble_source(source, &RuntimeArgs::default())
+            .unwrap();
+        let program = assembler.finish();
+        let after = program.debug_info().label("after").unwrap();
+        let delay_slots = DelaySlots {
+            branches: true,
+            loads: false,
+        };
+        let handle = SimulatorHandle::spawn(program, SharedIo::new(), delay_slots, || {});
+
+        handle.send(Command::StepOver);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !(handle.lock().pc() == after && handle.state() == RunState::Paused) {
+            assert!(
+                Instant::now() < deadline,
+                "stopped at {}",
+                handle.lock().pc()
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(handle.lock().registers().get(Register::T0), 7);
+    }
+}
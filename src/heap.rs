@@ -0,0 +1,187 @@
+use thiserror::Error;
+
+use crate::{address::Address, debug_info::DebugInfo, memory::Memory};
+
+/// Where the program break starts, as in MARS.
+pub const HEAP_BASE: Address = Address(0x1004_0000);
+
+#[derive(Debug, Error)]
+pub enum HeapError {
+    #[error("sbrk amount {0} is negative")]
+    Negative(i32),
+    #[error("sbrk of {requested} bytes would grow the heap into the stack")]
+    Exhausted { requested: u32 },
+}
+
+/// One sbrk call.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    /// Start of the block handed out, the break before the call.
+    pub address: Address,
+    /// Bytes added to the heap, after rounding up to a whole word.
+    pub size: u32,
+    /// The syscall that asked for it.
+    pub pc: Address,
+    /// Instructions executed before the call.
+    pub step: u64,
+}
+
+impl Allocation {
+    pub fn contains(&self, address: u32) -> bool {
+        (self.address.0..(self.address + self.size).0).contains(&address)
+    }
+}
+
+/// The program break and every sbrk call that moved it.
+#[derive(Debug, Clone)]
+pub struct Heap {
+    brk: Address,
+    history: Vec<Allocation>,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            brk: HEAP_BASE,
+            history: Vec::new(),
+        }
+    }
+}
+
+impl Heap {
+    /// The current program break: one past the last allocated byte.
+    pub fn brk(&self) -> Address {
+        self.brk
+    }
+
+    /// Every sbrk call in order, including zero-byte ones.
+    pub fn history(&self) -> &[Allocation] {
+        &self.history
+    }
+
+    /// Bytes allocated since the program started.
+    pub fn allocated(&self) -> u32 {
+        self.brk.offset_from(HEAP_BASE).unwrap_or(0)
+    }
+
+    /// Forgets every sbrk call after the first `len`, moving the break back.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if let Some(first) = self.history.get(len) {
+            self.brk = first.address;
+            self.history.truncate(len);
+        }
+    }
+
+    /// Grows the heap by `amount` bytes, rounded up to a word, and returns
+    /// the start of the new block. `limit` is the lowest address the heap
+    /// may not reach, normally `$sp`.
+    pub fn sbrk(
+        &mut self,
+        amount: i32,
+        limit: Address,
+        pc: Address,
+        step: u64,
+    ) -> Result<Address, HeapError> {
+        if amount < 0 {
+            return Err(HeapError::Negative(amount));
+        }
+        let size = (amount as u32).next_multiple_of(4);
+        let end = self
+            .brk
+            .checked_add(size)
+            .ok()
+            .filter(|&end| end <= limit)
+            .ok_or(HeapError::Exhausted { requested: size })?;
+
+        let address = self.brk;
+        self.brk = end;
+        self.history.push(Allocation {
+            address,
+            size,
+            pc,
+            step,
+        });
+        Ok(address)
+    }
+
+    /// Which allocations a program could still reach, by conservatively
+    /// treating every word in `roots`, and in each reachable block, as a
+    /// possible pointer. Blocks nothing points into have likely leaked.
+    /// Zero-byte calls count as reachable.
+    pub fn reachable(&self, roots: impl IntoIterator<Item = u32>, memory: &Memory) -> Vec<bool> {
+        let mut reachable: Vec<bool> = self.history.iter().map(|a| a.size == 0).collect();
+        let mut pending: Vec<usize> = Vec::new();
+        let mark = |word: u32, reachable: &mut Vec<bool>, pending: &mut Vec<usize>| {
+            // Blocks are laid out in order, so the last one starting at or
+            // below `word` is the only one that can hold it.
+            let index = self
+                .history
+                .partition_point(|allocation| allocation.address.0 <= word);
+            if let Some(index) = index.checked_sub(1)
+                && self.history[index].contains(word)
+                && !reachable[index]
+            {
+                reachable[index] = true;
+                pending.push(index);
+            }
+        };
+
+        for word in roots {
+            mark(word, &mut reachable, &mut pending);
+        }
+        while let Some(index) = pending.pop() {
+            let block = self.history[index];
+            for offset in (0..block.size).step_by(4) {
+                if let Some(word) = memory.read_word(block.address + offset) {
+                    mark(word, &mut reachable, &mut pending);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+/// Prints the heap's size and every sbrk call with the line that made it.
+/// `reachable` marks, per call, whether the block is still referenced, as
+/// [`Heap::reachable`] finds.
+pub fn print_report(heap: &Heap, debug_info: &DebugInfo, reachable: &[bool]) {
+    let history = heap.history();
+    let leaked: u32 = history
+        .iter()
+        .zip(reachable)
+        .filter(|&(_, &reachable)| !reachable)
+        .map(|(allocation, _)| allocation.size)
+        .sum();
+    println!(
+        "Heap: {} bytes in {} allocation(s), break at 0x{:08x}",
+        heap.allocated(),
+        history.len(),
+        heap.brk().0
+    );
+    if history.is_empty() {
+        return;
+    }
+    if leaked > 0 {
+        println!("  {} bytes no longer referenced", leaked);
+    }
+    println!(
+        "  {:>3} {:<10} {:>8} {:<10} {:>6} {:>10}",
+        "#", "address", "bytes", "pc", "line", "step"
+    );
+    for (index, (allocation, &reachable)) in history.iter().zip(reachable).enumerate() {
+        let line = debug_info
+            .line_of(allocation.pc)
+            .map_or_else(|| "-".to_string(), |line| line.to_string());
+        let row = format!(
+            "  {:>3} 0x{:08x} {:>8} 0x{:08x} {:>6} {:>10} {}",
+            index,
+            allocation.address.0,
+            allocation.size,
+            allocation.pc.0,
+            line,
+            allocation.step,
+            if reachable { "" } else { "unreferenced" }
+        );
+        println!("{}", row.trim_end());
+    }
+}
@@ -0,0 +1,63 @@
+use std::io::{self, Write};
+
+use crate::{assembler::Instruction, memory::Endianness, simulator::Simulator};
+
+/// A segment of an assembled program that can be exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Text,
+    Data,
+}
+
+impl std::str::FromStr for Segment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Segment::Text),
+            "data" => Ok(Segment::Data),
+            _ => Err(format!("Unknown segment '{}', expected text or data", s)),
+        }
+    }
+}
+
+/// The machine word of every instruction, in address order.
+pub fn text_words(instructions: &[Instruction]) -> Vec<u32> {
+    instructions.iter().map(Instruction::encode).collect()
+}
+
+/// The data segment as words in the given byte order, up to its last
+/// non-zero word, since the assembler reserves far more of it than most
+/// programs use.
+pub fn data_words(data: &[u8], endianness: Endianness) -> Vec<u32> {
+    let mut words: Vec<u32> = data
+        .chunks(4)
+        .map(|chunk| {
+            let mut bytes = [0; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            u32::from_le_bytes(endianness.arrange(bytes))
+        })
+        .collect();
+    while words.last() == Some(&0) {
+        words.pop();
+    }
+    words
+}
+
+/// The words of `segment` as the simulator would start running them, so
+/// files copied in with `--load` are included.
+pub fn segment_words(simulator: &Simulator, segment: Segment) -> Vec<u32> {
+    match segment {
+        Segment::Text => text_words(simulator.instructions()),
+        Segment::Data => data_words(simulator.memory().data(), simulator.memory().endianness()),
+    }
+}
+
+/// Writes one word per line as eight hex digits, the format Verilog's
+/// `$readmemh` reads and MARS writes for hexadecimal text dumps.
+pub fn write_hex(words: &[u32], out: &mut impl Write) -> io::Result<()> {
+    for word in words {
+        writeln!(out, "{:08x}", word)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use thiserror::Error;
+
+use crate::{assembler::MNEMONICS, plugin, reference};
+
+/// Stands for every pseudo-instruction in allow and deny lists.
+pub const PSEUDO: &str = "pseudo";
+
+#[derive(Debug, Error)]
+pub enum IsaProfileError {
+    #[error("Unknown instruction '{0}'")]
+    UnknownMnemonic(String),
+    #[error("Line {0}: expected 'allow' or 'deny' followed by instructions")]
+    InvalidLine(usize),
+    #[error("Cannot read ISA profile '{0}': {1}")]
+    Unreadable(String, std::io::Error),
+}
+
+/// Which instructions a program may use, e.g. to keep an assignment to a
+/// subset of the ISA. Everything is allowed unless restricted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IsaProfile {
+    /// When set, only these may be used.
+    allowed: Option<BTreeSet<String>>,
+    denied: BTreeSet<String>,
+}
+
+/// Checks that every name is a mnemonic or [`PSEUDO`].
+fn names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Vec<String>, IsaProfileError> {
+    names
+        .into_iter()
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            match name == PSEUDO
+                || MNEMONICS.contains(&name.as_str())
+                || plugin::lookup(&name).is_some()
+            {
+                true => Ok(name),
+                false => Err(IsaProfileError::UnknownMnemonic(name)),
+            }
+        })
+        .collect()
+}
+
+impl IsaProfile {
+    /// Restricts programs to `mnemonics`, on top of any earlier allow list.
+    pub fn allow<'a>(
+        &mut self,
+        mnemonics: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), IsaProfileError> {
+        self.allowed
+            .get_or_insert_default()
+            .extend(names(mnemonics)?);
+        Ok(())
+    }
+
+    /// Forbids `mnemonics` even if they are allowed.
+    pub fn deny<'a>(
+        &mut self,
+        mnemonics: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), IsaProfileError> {
+        self.denied.extend(names(mnemonics)?);
+        Ok(())
+    }
+
+    /// Reads a profile file: lines of `allow` or `deny` followed by
+    /// instructions separated by spaces or commas. `#` starts a comment.
+    pub fn load(path: &Path) -> Result<Self, IsaProfileError> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| IsaProfileError::Unreadable(path.display().to_string(), err))?;
+        let mut profile = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split([' ', '\t', ',']).filter(|word| !word.is_empty());
+            match words.next() {
+                Some("allow") => profile.allow(words)?,
+                Some("deny") => profile.deny(words)?,
+                Some(_) => return Err(IsaProfileError::InvalidLine(number + 1)),
+                None => {}
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Whether programs may use `mnemonic`.
+    pub fn allows(&self, mnemonic: &str) -> bool {
+        let mnemonic = mnemonic.to_lowercase();
+        let pseudo = reference::instruction(&mnemonic).is_some_and(|doc| doc.pseudo);
+        let listed =
+            |set: &BTreeSet<String>| set.contains(&mnemonic) || pseudo && set.contains(PSEUDO);
+        !listed(&self.denied) && self.allowed.as_ref().is_none_or(listed)
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+
+use crate::{
+    address::Address, call_stack::CallStack, cp0::Coprocessor0, cp1::Coprocessor1,
+    registers::RegisterFile, simulator::Delayed,
+};
+
+/// Instructions a debugger front-end lets the user step back over.
+pub const DEFAULT_DEPTH: usize = 10_000;
+
+/// What one instruction changed, enough to undo it. Console output and
+/// consumed input are not undone.
+#[derive(Debug, Clone)]
+pub struct Undo {
+    pub(crate) registers: RegisterFile,
+    /// Coprocessor 0 before an instruction that may change it.
+    pub(crate) cp0: Option<Coprocessor0>,
+    pub(crate) cp1: Coprocessor1,
+    pub(crate) steps: u64,
+    pub(crate) delayed: Delayed,
+    /// sbrk calls made before the instruction.
+    pub(crate) heap: usize,
+    /// Old value of each byte the instruction wrote, in write order.
+    pub(crate) memory: Vec<(Address, u8)>,
+    /// The call stack before a call or return, when one is tracked.
+    pub(crate) call_stack: Option<CallStack>,
+}
+
+/// A bounded history of executed instructions that can be undone one at a
+/// time, newest first. The oldest are forgotten once `depth` is reached;
+/// a depth of zero records nothing.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    undos: VecDeque<Undo>,
+    depth: usize,
+}
+
+impl Journal {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            undos: VecDeque::new(),
+            depth,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Changes the depth, forgetting the oldest entries past it.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.undos.len() > depth {
+            self.undos.pop_front();
+        }
+    }
+
+    /// Instructions that can currently be stepped back over.
+    pub fn len(&self) -> usize {
+        self.undos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undos.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undos.clear();
+    }
+
+    pub(crate) fn push(&mut self, undo: Undo) {
+        if self.depth == 0 {
+            return;
+        }
+        if self.undos.len() == self.depth {
+            self.undos.pop_front();
+        }
+        self.undos.push_back(undo);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Undo> {
+        self.undos.pop_back()
+    }
+}
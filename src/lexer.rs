@@ -1,26 +1,89 @@
-use std::{fs::File, io::Read};
+use std::{borrow::Cow, fs, io, sync::OnceLock};
 
 use thiserror::Error;
 
+use crate::{
+    registers::{Register, RegisterError},
+    suggest::{self, did_you_mean},
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Directive {
-    DataDirective,
-    TextDirective,
-    GlobalDirective,
-    AsciiDirective,
-    AsciizDirective,
-    ByteDirective,
-    WordDirective,
+    Data,
+    Text,
+    Global,
+    Ascii,
+    Asciiz,
+    Byte,
+    Word,
+    /// 64-bit integers.
+    Dword,
+    /// Single-precision floats.
+    Float,
+    /// Double-precision floats.
+    Double,
+    Include,
+    Incbin,
+    Eqv,
+    Space,
+}
+
+/// Byte range of a token within the source it was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum TokenKind<'a> {
+    Directive {
+        kind: Directive,
+    },
+    Register {
+        value: Register,
+    },
+    Label {
+        name: &'a str,
+        decl: bool,
+    },
+    Number {
+        value: i32,
+    },
+    /// An operand built from numbers and constants with operators, such as
+    /// `SIZE*4+2`, evaluated by the assembler.
+    Expression {
+        text: &'a str,
+    },
+    /// A memory operand, `offset($base)`. The offset is left as text for
+    /// the assembler to evaluate, and may be empty.
+    Address {
+        offset: &'a str,
+        base: Register,
+    },
+    Operator {
+        value: &'a str,
+    },
+    Text {
+        value: Cow<'a, str>,
+    },
 }
 
+/// A token borrowing its text from the source string.
 #[derive(Debug, Clone)]
-pub enum Token {
-    Directive { kind: Directive },
-    Register { value: String },
-    Label { name: String, decl: bool },
-    Number { value: i32 },
-    Operator { value: String },
-    Text { value: String },
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Span,
+}
+
+/// One source line, with comments already stripped.
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    /// 1-based line number.
+    pub number: usize,
+    pub text: &'a str,
+    /// Byte offset of `text` within the source.
+    pub offset: usize,
 }
 
 #[derive(Debug, Error)]
@@ -29,27 +92,55 @@ pub enum TokenizerError {
     OpenFileError(String),
     #[error("Failed to read file '{0}'")]
     ReadFileError(String),
-    #[error("Unknown directive '{0}'")]
-    UnknownDirective(String),
+    #[error("Unknown directive '{0}'{suggestion}", suggestion = did_you_mean(.1))]
+    UnknownDirective(String, Option<&'static str>),
     #[error("Invalid byte ''{0}'")]
     InvalidByte(String),
+    #[error("Invalid register: {0}")]
+    InvalidRegister(#[from] RegisterError),
 }
 
+/// Every directive the assembler understands, including the alternate
+/// spellings other assemblers use.
+pub const DIRECTIVES: &[&str] = &[
+    ".ascii", ".asciiz", ".asciz", ".byte", ".data", ".double", ".dword", ".eqv", ".float",
+    ".global", ".globl", ".incbin", ".include", ".int", ".long", ".quad", ".space", ".string",
+    ".text", ".word",
+];
+
+/// Characters that make an operand a constant expression.
+const EXPRESSION_OPERATORS: &str = "+-*/%()&|^~<>!=";
+
 fn parse_directive(token: &str) -> Result<Directive, TokenizerError> {
     match token {
-        ".data" => Ok(Directive::DataDirective),
-        ".text" => Ok(Directive::TextDirective),
-        ".globl" => Ok(Directive::GlobalDirective),
-        ".ascii" => Ok(Directive::AsciiDirective),
-        ".asciiz" => Ok(Directive::AsciizDirective),
-        ".byte" => Ok(Directive::ByteDirective),
-        ".word" => Ok(Directive::WordDirective),
-        other => Err(TokenizerError::UnknownDirective(other.to_string())),
+        ".data" => Ok(Directive::Data),
+        ".text" => Ok(Directive::Text),
+        ".globl" | ".global" => Ok(Directive::Global),
+        ".ascii" => Ok(Directive::Ascii),
+        ".asciiz" | ".asciz" | ".string" => Ok(Directive::Asciiz),
+        ".byte" => Ok(Directive::Byte),
+        ".word" | ".int" | ".long" => Ok(Directive::Word),
+        // `.dword` is an integer like `.quad`; `.double` is a float.
+        ".dword" | ".quad" => Ok(Directive::Dword),
+        ".float" => Ok(Directive::Float),
+        ".double" => Ok(Directive::Double),
+        ".include" => Ok(Directive::Include),
+        ".incbin" => Ok(Directive::Incbin),
+        ".eqv" => Ok(Directive::Eqv),
+        ".space" => Ok(Directive::Space),
+        other => Err(TokenizerError::UnknownDirective(
+            other.to_string(),
+            suggest::closest(other, DIRECTIVES.iter().copied()),
+        )),
     }
 }
 
-fn unescape_string(s: &str) -> String {
-    let mut result = String::new();
+fn unescape_string(s: &str) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+
+    let mut result = String::with_capacity(s.len());
     let mut chars = s.chars();
 
     while let Some(c) = chars.next() {
@@ -66,101 +157,170 @@ fn unescape_string(s: &str) -> String {
             result.push(c);
         }
     }
-    result
+    Cow::Owned(result)
 }
 
-pub fn tokenize(file_name: &str) -> Result<Vec<Vec<Token>>, TokenizerError> {
-    let mut file =
-        File::open(file_name).map_err(|_| TokenizerError::OpenFileError(file_name.to_string()))?;
-    let mut contents = String::new();
-    let mut all_tokens = Vec::new();
+/// The file name that stands for standard input.
+pub const STDIN_NAME: &str = "-";
+
+/// Standard input, read whole the first time it is asked for, since it can
+/// only be read once.
+static STDIN_SOURCE: OnceLock<io::Result<String>> = OnceLock::new();
+
+/// Reads a source file, or standard input for [`STDIN_NAME`], so generated
+/// programs can be piped in.
+pub fn read_source(file_name: &str) -> Result<String, TokenizerError> {
+    let result = match file_name {
+        STDIN_NAME => match STDIN_SOURCE.get_or_init(|| io::read_to_string(io::stdin())) {
+            Ok(source) => Ok(source.clone()),
+            Err(err) => Err(err.kind()),
+        },
+        _ => fs::read_to_string(file_name).map_err(|err| err.kind()),
+    };
+    result.map_err(|kind| match kind {
+        io::ErrorKind::InvalidData => TokenizerError::ReadFileError(file_name.to_string()),
+        _ => TokenizerError::OpenFileError(file_name.to_string()),
+    })
+}
 
-    file.read_to_string(&mut contents)
-        .map_err(|_| TokenizerError::ReadFileError(file_name.to_string()))?;
-    let mut lines = contents.lines();
+/// Splits source text into lines, cutting each at a `#` comment that is not
+/// inside a string or character literal.
+pub fn lines(source: &str) -> impl Iterator<Item = Line<'_>> {
+    let base = source.as_ptr() as usize;
+    source.lines().enumerate().map(move |(i, text)| {
+        let mut inside_string = false;
+        let mut inside_byte = false;
+        let mut end = text.len();
 
-    while let Some(mut line) = lines.next() {
-        if line.starts_with("#") {
-            continue;
+        for (pos, c) in text.char_indices() {
+            match c {
+                '"' if !inside_byte => inside_string = !inside_string,
+                '\'' if !inside_string => inside_byte = !inside_byte,
+                '#' if !inside_string && !inside_byte => {
+                    end = pos;
+                    break;
+                }
+                _ => {}
+            }
         }
 
-        if let Some((before, _)) = line.split_once("#") {
-            line = before;
+        Line {
+            number: i + 1,
+            text: &text[..end],
+            offset: text.as_ptr() as usize - base,
         }
+    })
+}
 
-        let mut tokens = Vec::new();
+/// Yields `(offset, text)` for each whitespace- or comma-separated word,
+/// keeping quoted strings and character literals intact.
+pub fn split_words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut chars = line.char_indices().peekable();
+
+    std::iter::from_fn(move || {
+        while chars
+            .next_if(|&(_, c)| c.is_whitespace() || c == ',')
+            .is_some()
+        {}
+
+        let (start, _) = *chars.peek()?;
+        let mut end = line.len();
         let mut inside_string = false;
         let mut inside_byte = false;
 
-        let raw_tokens: Vec<&str> = line
-            .split(|c: char| {
-                if c == '"' && !inside_byte {
-                    inside_string = !inside_string;
-                    return false;
-                } else if c == '\'' && !inside_string {
-                    inside_byte = !inside_byte;
-                    return false;
-                } else if inside_string {
-                    return false;
-                } else if inside_byte {
-                    return false;
-                } else {
-                    c.is_whitespace() || c == ','
-                }
-            })
-            .filter(|s| !s.is_empty())
-            .collect();
-
-        for (i, token) in raw_tokens.iter().enumerate() {
-            if token.starts_with(".") {
-                let directive = parse_directive(token)?;
-                tokens.push(Token::Directive { kind: directive });
-            } else if token.starts_with('"') && token.ends_with('"') {
-                let value = unescape_string(&token[1..token.len() - 1]);
-                tokens.push(Token::Text { value });
-            } else if token.starts_with('\'') && token.ends_with('\'') {
-                let unescaped = unescape_string(&token[1..token.len() - 1]);
-                let bytes = unescaped.as_bytes();
-
-                if bytes.len() != 1 {
-                    return Err(TokenizerError::InvalidByte(unescaped));
-                }
-
-                tokens.push(Token::Number {
-                    value: bytes[0] as i32,
-                });
-            } else if token.starts_with("0b")
-                && let Ok(value) = i32::from_str_radix(&token[2..], 2)
-            {
-                tokens.push(Token::Number { value });
-            } else if token.starts_with("0x")
-                && let Ok(value) = i32::from_str_radix(&token[2..], 16)
-            {
-                tokens.push(Token::Number { value });
-            } else if let Ok(value) = token.parse::<i32>() {
-                tokens.push(Token::Number { value });
-            } else if token.starts_with("$") {
-                tokens.push(Token::Register {
-                    value: token.to_string(),
-                });
-            } else if token.ends_with(":") {
-                let name = token.trim_end_matches(":");
-                tokens.push(Token::Label {
-                    name: name.to_string(),
-                    decl: true,
-                });
-            } else if i == 0 {
-                tokens.push(Token::Operator {
-                    value: token.to_string(),
-                });
-            } else {
-                tokens.push(Token::Label {
-                    name: token.to_string(),
-                    decl: false,
-                });
+        while let Some(&(pos, c)) = chars.peek() {
+            if c == '"' && !inside_byte {
+                inside_string = !inside_string;
+            } else if c == '\'' && !inside_string {
+                inside_byte = !inside_byte;
+            } else if !inside_string && !inside_byte && (c.is_whitespace() || c == ',') {
+                end = pos;
+                break;
             }
+            chars.next();
+        }
+
+        Some((start, &line[start..end]))
+    })
+}
+
+/// Classifies a single word. `first` marks the operator position: the first
+/// word of a line, or the first after a label declaration.
+pub fn classify(token: &str, first: bool) -> Result<TokenKind<'_>, TokenizerError> {
+    let kind = if token.starts_with(".") {
+        TokenKind::Directive {
+            kind: parse_directive(token)?,
+        }
+    } else if token.len() >= 2 && token.starts_with('"') && token.ends_with('"') {
+        TokenKind::Text {
+            value: unescape_string(&token[1..token.len() - 1]),
         }
-        all_tokens.push(tokens);
+    } else if token.len() >= 2 && token.starts_with('\'') && token.ends_with('\'') {
+        let unescaped = unescape_string(&token[1..token.len() - 1]);
+        let bytes = unescaped.as_bytes();
+
+        if bytes.len() != 1 {
+            return Err(TokenizerError::InvalidByte(unescaped.into_owned()));
+        }
+
+        TokenKind::Number {
+            value: bytes[0] as i32,
+        }
+    } else if token.starts_with("0b")
+        && let Ok(value) = i32::from_str_radix(&token[2..], 2)
+    {
+        TokenKind::Number { value }
+    } else if token.starts_with("0x")
+        && let Ok(value) = i32::from_str_radix(&token[2..], 16)
+    {
+        TokenKind::Number { value }
+    } else if let Ok(value) = token.parse::<i32>() {
+        TokenKind::Number { value }
+    } else if let Some((offset, base)) = token
+        .strip_suffix(')')
+        .and_then(|token| token.rsplit_once('('))
+        .filter(|(_, base)| base.starts_with('$'))
+    {
+        TokenKind::Address {
+            offset,
+            base: base.parse()?,
+        }
+    } else if token.starts_with("$") {
+        TokenKind::Register {
+            value: token.parse()?,
+        }
+    } else if let Some(name) = token.strip_suffix(":") {
+        TokenKind::Label { name, decl: true }
+    } else if first {
+        TokenKind::Operator { value: token }
+    } else if token.contains(|c| EXPRESSION_OPERATORS.contains(c)) && !token.contains('$') {
+        TokenKind::Expression { text: token }
+    } else {
+        TokenKind::Label {
+            name: token,
+            decl: false,
+        }
+    };
+    Ok(kind)
+}
+
+/// Tokenizes a single line into `out`, which is cleared first so callers can
+/// reuse one buffer for the whole file.
+pub fn tokenize_line<'a>(line: &Line<'a>, out: &mut Vec<Token<'a>>) -> Result<(), TokenizerError> {
+    out.clear();
+
+    for (offset, word) in split_words(line.text) {
+        let start = line.offset + offset;
+        let first = out
+            .iter()
+            .all(|token| matches!(token.kind, TokenKind::Label { decl: true, .. }));
+        out.push(Token {
+            kind: classify(word, first)?,
+            span: Span {
+                start,
+                end: start + word.len(),
+            },
+        });
     }
-    Ok(all_tokens)
+    Ok(())
 }
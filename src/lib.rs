@@ -0,0 +1,154 @@
+pub mod address;
+pub mod assembler;
+pub mod backend;
+pub mod batch;
+pub mod bench;
+pub mod branch_predictor;
+pub mod cache;
+pub mod cache_hierarchy;
+pub mod call_stack;
+pub mod coverage;
+pub mod cp0;
+pub mod cp1;
+pub mod debug_info;
+pub mod diagnostic;
+pub mod encoding;
+pub mod expression;
+pub mod formatter;
+pub mod golden;
+pub mod grader;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod handle;
+pub mod heap;
+pub mod hex_export;
+pub mod intern;
+pub mod isa_profile;
+pub mod journal;
+pub mod lexer;
+pub mod lsp;
+pub mod memory;
+pub mod mix;
+pub mod mmio;
+pub mod pipeline;
+pub mod plugin;
+pub mod profile;
+pub mod reference;
+pub mod registers;
+pub mod repl;
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod simulator;
+pub mod snapshot;
+pub mod stack_check;
+pub mod stepping;
+pub mod suggest;
+pub mod syscall_io;
+pub mod syscall_trace;
+pub mod tasks;
+pub mod timing;
+pub mod tlb;
+pub mod trace;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod uninit;
+pub mod watchpoint;
+
+use std::time::Duration;
+
+use backend::Backend;
+use branch_predictor::PredictorKind;
+use cache_hierarchy::HierarchyConfig;
+use diagnostic::ColorChoice;
+use isa_profile::IsaProfile;
+use memory::Endianness;
+use scheduler::Speed;
+use simulator::DelaySlots;
+use timing::CpiConfig;
+use trace::TraceFormat;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RuntimeArgs {
+    pub file: String,
+    pub args: bool,
+    pub help: bool,
+    pub tokens: bool,
+    pub instructions: bool,
+    pub version: bool,
+    pub memory: bool,
+    /// Whether to color diagnostics.
+    pub color: ColorChoice,
+    /// Instructions the program may use.
+    pub isa: IsaProfile,
+    pub backend: Backend,
+    pub speed: Speed,
+    /// Wall-clock time the program may run for.
+    pub timeout: Option<Duration>,
+    /// Give branches or loads a delay slot, as SPIM's bare machine does.
+    pub delay_slots: DelaySlots,
+    /// Accept and execute the MIPS64 doubleword instructions.
+    pub mips64: bool,
+    /// Byte order of the assembled data and of word loads and stores.
+    pub endianness: Endianness,
+    /// Translate data addresses through the TLB.
+    pub virtual_memory: bool,
+    /// Address or label exceptions jump to.
+    pub exception_handler: Option<String>,
+    /// Addresses or labels to report the machine state at.
+    pub breakpoints: Vec<String>,
+    /// Memory ranges to report loads and stores of.
+    pub watchpoints: Vec<String>,
+    /// Files to copy into memory before running, as
+    /// `<address|label>=<file>`.
+    pub loads: Vec<String>,
+    /// Address or label to start at instead of the entry point.
+    pub entry: Option<String>,
+    /// Registers to set before running, as `<register>=<value>`.
+    pub presets: Vec<String>,
+    /// Segments to write as hex words instead of running, as
+    /// `<text|data>=<file>`.
+    pub dumps: Vec<String>,
+    /// File to write an execution trace to.
+    pub trace: Option<String>,
+    /// Format of the trace; by default chosen from its extension.
+    pub trace_format: Option<TraceFormat>,
+    /// Log every syscall with its arguments and results to stderr.
+    pub trace_syscalls: bool,
+    /// Report the most executed lines at exit.
+    pub profile: bool,
+    /// Report executions per mnemonic and per instruction class at exit.
+    pub mix: bool,
+    /// Model the five-stage pipeline, with or without forwarding, and
+    /// report its timing at exit.
+    pub pipeline: Option<bool>,
+    /// Predict branches with this predictor and report its accuracy at
+    /// exit.
+    pub predictor: Option<PredictorKind>,
+    /// Simulate split L1 caches and an optional L2, and report their
+    /// statistics at exit.
+    pub caches: Option<HierarchyConfig>,
+    /// File to save every line of console input to.
+    pub record: Option<String>,
+    /// File of console input to read before the terminal.
+    pub replay: Option<String>,
+    /// Rhai script whose callbacks run alongside the program.
+    pub script: Option<String>,
+    /// Report every sbrk allocation at exit.
+    pub heap: bool,
+    /// Charge each instruction class these cycles and report the total at
+    /// exit.
+    pub cpi: Option<CpiConfig>,
+    /// Report stack misuse at exit.
+    pub check_stack: bool,
+    /// Report loads of memory that was never written at exit.
+    pub check_uninit: bool,
+    /// Report which lines never executed at exit.
+    pub coverage: bool,
+    /// File to write lcov coverage data to at exit.
+    pub lcov: Option<String>,
+    /// File to save the registers and memory to at exit, for `diff`.
+    pub snapshot: Option<String>,
+}
@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use serde_json::{Value, json};
+
+use crate::{
+    RuntimeArgs,
+    assembler::Assembler,
+    diagnostic::Severity,
+    lexer::{DIRECTIVES, Span, TokenKind, classify, lines, split_words},
+    reference,
+    registers::Register,
+};
+
+/// JSON-RPC error code for a request the server does not handle.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Reads one message framed with a `Content-Length` header, or None at the
+/// end of input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// An LSP position: a line and a UTF-16 offset within it.
+fn position(source: &str, offset: usize) -> Value {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+fn range(source: &str, span: Span) -> Value {
+    json!({ "start": position(source, span.start), "end": position(source, span.end) })
+}
+
+/// The byte offset of an LSP position, clamped to the end of its line.
+fn offset(source: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let line_start = match line {
+        0 => 0,
+        line => source.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+    let text = source[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, c) in text.char_indices() {
+        if units >= character {
+            return Some(line_start + index);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + text.len())
+}
+
+/// The local path of a `file://` URI.
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail.get(..2)) {
+            (b'%', Some(hex)) => {
+                let hex = std::str::from_utf8(hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Label declarations in `source`, with the span of each name.
+fn declarations(source: &str) -> Vec<(&str, Span)> {
+    lines(source)
+        .flat_map(|line| {
+            split_words(line.text).filter_map(move |(start, word)| {
+                let name = word.strip_suffix(':')?;
+                let start = line.offset + start;
+                Some((
+                    name,
+                    Span {
+                        start,
+                        end: start + name.len(),
+                    },
+                ))
+            })
+        })
+        .collect()
+}
+
+/// The label named at byte `offset`, whether declared or used there.
+fn label_at(source: &str, offset: usize) -> Option<&str> {
+    let line = lines(source)
+        .find(|line| (line.offset..=line.offset + line.text.len()).contains(&offset))?;
+    let mut first = true;
+    for (start, word) in split_words(line.text) {
+        let start = line.offset + start;
+        let kind = classify(word, first).ok();
+        first = matches!(kind, Some(TokenKind::Label { decl: true, .. }));
+        if (start..=start + word.len()).contains(&offset)
+            && let Some(TokenKind::Label { name, .. }) = kind
+        {
+            return Some(name);
+        }
+    }
+    None
+}
+
+/// A language server for MIPS assembly over stdin and stdout, giving
+/// editors the assembler's diagnostics, reference docs on hover,
+/// go-to-definition for labels and completion.
+#[derive(Default)]
+pub struct Server {
+    /// Open documents by URI.
+    documents: HashMap<String, String>,
+    shutting_down: bool,
+}
+
+impl Server {
+    /// Serves requests until the client sends `exit` or closes the input.
+    /// Returns whether the client shut the server down first.
+    pub fn run(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+        while let Some(message) = read_message(input)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            if method == "exit" {
+                break;
+            }
+            let params = &message["params"];
+            let reply = match method {
+                "initialize" => Some(Ok(self.initialize())),
+                "shutdown" => {
+                    self.shutting_down = true;
+                    Some(Ok(Value::Null))
+                }
+                "textDocument/hover" => Some(Ok(self.hover(params))),
+                "textDocument/definition" => Some(Ok(self.definition(params))),
+                "textDocument/completion" => Some(Ok(self.completion(params))),
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    self.open(document["uri"].as_str(), document["text"].as_str(), output)?;
+                    None
+                }
+                "textDocument/didChange" => {
+                    // Full sync: the last change holds the whole text.
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+                    self.open(params["textDocument"]["uri"].as_str(), text, output)?;
+                    None
+                }
+                "textDocument/didClose" => {
+                    if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                        self.documents.remove(uri);
+                        publish(output, uri, Vec::new())?;
+                    }
+                    None
+                }
+                _ => Some(Err(method)),
+            };
+            // Notifications have no id and get no reply.
+            let (Some(reply), Some(id)) = (reply, message.get("id")) else {
+                continue;
+            };
+            let response = match reply {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(method) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": METHOD_NOT_FOUND,
+                        "message": format!("Unsupported method '{}'", method),
+                    },
+                }),
+            };
+            write_message(output, &response)?;
+        }
+        Ok(self.shutting_down)
+    }
+
+    fn initialize(&self) -> Value {
+        json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "completionProvider": { "triggerCharacters": ["$", "."] },
+            },
+            "serverInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    /// Stores a document's new text and publishes its diagnostics.
+    fn open(
+        &mut self,
+        uri: Option<&str>,
+        text: Option<&str>,
+        output: &mut impl Write,
+    ) -> io::Result<()> {
+        let (Some(uri), Some(text)) = (uri, text) else {
+            return Ok(());
+        };
+        let diagnostics = diagnose(uri, text);
+        self.documents.insert(uri.to_string(), text.to_string());
+        publish(output, uri, diagnostics)
+    }
+
+    /// The document and byte offset a position request points at.
+    fn locate<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a str, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let source = self.documents.get(uri)?;
+        Some((uri, source, offset(source, &params["position"])?))
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, source, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        match reference::lookup(source, offset) {
+            Some((span, topic)) => json!({
+                "contents": { "kind": "markdown", "value": format!("```\n{}\n```", topic) },
+                "range": range(source, span),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some((uri, source, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        let declaration = label_at(source, offset).and_then(|label| {
+            declarations(source)
+                .into_iter()
+                .find(|&(name, _)| name == label)
+        });
+        match declaration {
+            Some((_, span)) => json!({ "uri": uri, "range": range(source, span) }),
+            None => Value::Null,
+        }
+    }
+
+    /// Mnemonics and directives in the operator position, registers and
+    /// labels after it.
+    fn completion(&self, params: &Value) -> Value {
+        let Some((_, source, offset)) = self.locate(params) else {
+            return Value::Null;
+        };
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let before = &source[line_start..offset];
+        let words: Vec<&str> = split_words(before)
+            .map(|(_, word)| word)
+            .filter(|word| !word.ends_with(':'))
+            .collect();
+        let operator = match words.as_slice() {
+            [] => true,
+            [word] => !before.ends_with([' ', '\t', ',']) && !word.starts_with('$'),
+            _ => false,
+        };
+
+        // LSP completion item kinds.
+        const FUNCTION: u32 = 3;
+        const VARIABLE: u32 = 6;
+        const KEYWORD: u32 = 14;
+        const REFERENCE: u32 = 18;
+        let mut items = Vec::new();
+        if operator {
+            items.extend(reference::INSTRUCTIONS.iter().map(|doc| {
+                json!({
+                    "label": doc.mnemonic,
+                    "kind": FUNCTION,
+                    "detail": doc.format,
+                    "documentation": doc.semantics,
+                })
+            }));
+            items.extend(
+                DIRECTIVES
+                    .iter()
+                    .map(|directive| json!({ "label": directive, "kind": KEYWORD })),
+            );
+        } else {
+            items.extend(Register::ALL.iter().map(|&register| {
+                json!({
+                    "label": register.name(),
+                    "kind": VARIABLE,
+                    "detail": reference::register_usage(register),
+                })
+            }));
+            items.extend(
+                declarations(source)
+                    .into_iter()
+                    .map(|(name, _)| json!({ "label": name, "kind": REFERENCE })),
+            );
+        }
+        Value::Array(items)
+    }
+}
+
+/// Assembles `text` and converts what the assembler reports.
+fn diagnose(uri: &str, text: &str) -> Vec<Value> {
+    let mut assembler = Assembler::new();
+    if let Some(directory) = uri_path(uri).as_deref().and_then(|path| path.parent()) {
+        assembler.set_include_dir(directory);
+    }
+    // Any error is also the last diagnostic.
+    let _ = assembler.assemble_source(text, &RuntimeArgs::default());
+    assembler
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "range": range(text, diagnostic.span),
+                "severity": match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                },
+                "source": env!("CARGO_PKG_NAME"),
+                "message": diagnostic.text(),
+            })
+        })
+        .collect()
+}
+
+fn publish(output: &mut impl Write, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+    write_message(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }),
+    )
+}
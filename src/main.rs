@@ -1,27 +1,64 @@
-mod address;
-mod assembler;
-mod lexer;
-mod registers;
-mod simulator;
-
-use simulator::Simulator;
-use std::{env, process};
-
-use crate::simulator::SimulatorError;
-
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
-pub struct RuntimeArgs {
-    file: String,
-    args: bool,
-    help: bool,
-    tokens: bool,
-    instructions: bool,
-    version: bool,
-    memory: bool,
-}
-
-fn parse_args() -> RuntimeArgs {
-    let args: Vec<String> = env::args().collect();
+#[cfg(feature = "scripting")]
+use mips_sim::scripting::Script;
+use mips_sim::{
+    RuntimeArgs, assembler,
+    batch::{self, Limits},
+    bench,
+    branch_predictor::{self, BranchPredictor},
+    cache_hierarchy::{CacheHierarchy, HierarchyConfig},
+    coverage::{self, Coverage},
+    debug_info::DebugInfo,
+    diagnostic::{self, ColorChoice, Diagnostic, Severity},
+    expression::Expression,
+    formatter,
+    golden::{self, Status},
+    grader::{self, Comparison},
+    heap, hex_export,
+    isa_profile::{IsaProfile, IsaProfileError},
+    journal, lexer, lsp,
+    memory::Endianness,
+    mix,
+    pipeline::Pipeline,
+    profile::{self, Profile},
+    registers::RegisterId,
+    repl,
+    scheduler::{self, Deadline, Throttle},
+    simulator::{DelaySlots, Simulator, SimulatorError, Stopped},
+    snapshot::{self, Snapshot},
+    syscall_io::{RecordingIo, ReplayIo, StdIo, SyscallIo},
+    syscall_trace::SyscallTracer,
+    tasks::{self, RoundRobin, TaskStatus},
+    timing::{CpiConfig, CpiModel},
+    trace::{TraceFormat, Tracer},
+};
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::Path,
+    process,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+fn option_value(args: &[String], short: &str, long: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg == short || arg == long)?;
+    args.get(position + 1).cloned()
+}
+
+/// Every value given for an option that may be repeated.
+fn option_values(args: &[String], short: &str, long: &str) -> Vec<String> {
+    args.windows(2)
+        .filter(|pair| pair[0] == short || pair[0] == long)
+        .map(|pair| pair[1].clone())
+        .collect()
+}
+
+/// Reads the options of a run of the program named by `args[1]`.
+fn parse_args(args: &[String]) -> RuntimeArgs {
     let mut cli_args = RuntimeArgs::default();
 
     cli_args.file = match args.get(1) {
@@ -39,12 +76,963 @@ fn parse_args() -> RuntimeArgs {
     cli_args.version = args.contains(&"-v".to_string()) || args.contains(&"--version".to_string());
     cli_args.instructions =
         args.contains(&"-i".to_string()) || args.contains(&"--instructions".to_string());
+    cli_args.profile = args.contains(&"-p".to_string()) || args.contains(&"--profile".to_string());
+    cli_args.mix = args.contains(&"--mix".to_string());
+    cli_args.coverage = args.contains(&"--coverage".to_string());
+    cli_args.color = parse_color(args);
+    cli_args.check_stack = args.contains(&"--check-stack".to_string());
+    cli_args.check_uninit = args.contains(&"--check-uninit".to_string());
+    cli_args.heap = args.contains(&"--heap".to_string());
+    if let Some(kind) = option_value(args, "--predictor", "--predictor") {
+        cli_args.predictor = Some(kind.parse().unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        }));
+    }
+    let cache = |short, long| {
+        option_value(args, short, long).map(|config| {
+            config.parse().unwrap_or_else(|err| {
+                println!("{}", err);
+                process::exit(1);
+            })
+        })
+    };
+    let (l1i, l1d, l2) = (
+        cache("--l1i", "--l1i"),
+        cache("--l1d", "--l1d"),
+        cache("--l2", "--l2"),
+    );
+    if l1i.is_some() || l1d.is_some() || l2.is_some() {
+        let defaults = HierarchyConfig::default();
+        cli_args.caches = Some(HierarchyConfig {
+            l1i: l1i.unwrap_or(defaults.l1i),
+            l1d: l1d.unwrap_or(defaults.l1d),
+            l2,
+            ..defaults
+        });
+    }
+    let cpi = match option_value(args, "--cpi-config", "--cpi-config") {
+        Some(path) => Some(CpiConfig::load(Path::new(&path))),
+        None => option_value(args, "--cpi", "--cpi").map(|costs| costs.parse()),
+    };
+    cli_args.cpi = cpi.map(|config| {
+        config.unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        })
+    });
+    if args.contains(&"--pipeline".to_string()) {
+        cli_args.pipeline = Some(!args.contains(&"--no-forwarding".to_string()));
+    }
+    cli_args.lcov = option_value(args, "--lcov", "--lcov");
+    cli_args.snapshot = option_value(args, "--snapshot", "--snapshot");
+    cli_args.script = option_value(args, "--script", "--script");
+
+    if let Some(backend) = option_value(args, "-b", "--backend") {
+        cli_args.backend = backend.parse().unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        });
+    }
+
+    if let Some(timeout) = option_value(args, "--timeout", "--timeout") {
+        cli_args.timeout = Some(scheduler::parse_duration(&timeout).unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        }));
+    }
+
+    if let Some(speed) = option_value(args, "--hz", "--hz") {
+        cli_args.speed = speed.parse().unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        });
+    }
+
+    cli_args.breakpoints = option_values(args, "-B", "--break");
+    cli_args.watchpoints = option_values(args, "-W", "--watchpoint");
+    cli_args.loads = option_values(args, "--load", "--load");
+    cli_args.entry = option_value(args, "--entry", "--entry");
+    cli_args.presets = option_values(args, "--set", "--set");
+    cli_args.dumps = option_values(args, "--dump", "--dump");
+    cli_args.delay_slots = parse_delay_slots(args);
+    cli_args.mips64 = args.contains(&"--mips64".to_string());
+    cli_args.endianness = parse_endianness(args);
+    cli_args.isa = parse_isa_args(args);
+    parse_exception_args(args, &mut cli_args);
+    parse_trace_args(args, &mut cli_args);
+    parse_replay_args(args, &mut cli_args);
+
+    cli_args
+}
+
+/// Reads `--color=<when>` or `--color <when>`.
+fn parse_color(args: &[String]) -> ColorChoice {
+    let value = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--color=").map(str::to_string))
+        .or_else(|| option_value(args, "--color", "--color"));
+    value.map_or(ColorChoice::Auto, |value| {
+        value.parse().unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        })
+    })
+}
+
+fn parse_delay_slots(args: &[String]) -> DelaySlots {
+    DelaySlots {
+        branches: args.contains(&"--delayed-branches".to_string()),
+        loads: args.contains(&"--delayed-loads".to_string()),
+    }
+}
+
+fn parse_endianness(args: &[String]) -> Endianness {
+    match args.contains(&"--big-endian".to_string()) {
+        true => Endianness::Big,
+        false => Endianness::Little,
+    }
+}
+
+/// Reads `--isa-profile`, then applies `--allow` and `--deny` on top.
+fn parse_isa_args(args: &[String]) -> IsaProfile {
+    let exit = |err: IsaProfileError| -> ! {
+        println!("{}", err);
+        process::exit(1);
+    };
+    let mut profile = match option_value(args, "--isa-profile", "--isa-profile") {
+        Some(path) => IsaProfile::load(Path::new(&path)).unwrap_or_else(|err| exit(err)),
+        None => IsaProfile::default(),
+    };
+    for list in option_values(args, "--allow", "--allow") {
+        profile
+            .allow(list.split(','))
+            .unwrap_or_else(|err| exit(err));
+    }
+    for list in option_values(args, "--deny", "--deny") {
+        profile
+            .deny(list.split(','))
+            .unwrap_or_else(|err| exit(err));
+    }
+    profile
+}
+
+fn parse_exception_args(args: &[String], cli_args: &mut RuntimeArgs) {
+    cli_args.virtual_memory = args.contains(&"--vm".to_string());
+    cli_args.exception_handler = option_value(args, "--exception-handler", "--exception-handler");
+}
+
+fn parse_replay_args(args: &[String], cli_args: &mut RuntimeArgs) {
+    cli_args.record = option_value(args, "--record", "--record");
+    cli_args.replay = option_value(args, "--replay", "--replay");
+}
+
+fn parse_trace_args(args: &[String], cli_args: &mut RuntimeArgs) {
+    cli_args.trace = option_value(args, "--trace", "--trace");
+    cli_args.trace_syscalls = args.contains(&"--trace-syscalls".to_string());
+    if let Some(format) = option_value(args, "--trace-format", "--trace-format") {
+        cli_args.trace_format = Some(format.parse().unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        }));
+    }
+}
+
+/// Prints where the program stopped and its registers.
+fn report_breakpoint(simulator: &Simulator, debug_info: &DebugInfo) {
+    let pc = simulator.pc();
+    match debug_info.line_of(pc) {
+        Some(line) => println!("\n-- breakpoint at 0x{:08x} (line {}) --", pc.0, line),
+        None => println!("\n-- breakpoint at 0x{:08x} --", pc.0),
+    }
+    repl::print_registers(simulator);
+}
+
+/// Exit code of a run stopped by `--timeout`, as `timeout(1)` uses.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Ends the process if the run has not finished shortly after `timeout`,
+/// e.g. because it is blocked reading console input and never gets to
+/// check its deadline.
+fn start_watchdog(timeout: Duration, finished: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        thread::sleep(timeout + Duration::from_millis(100));
+        if !finished.load(Ordering::Relaxed) {
+            println!("\n-- program timed out after {:?} --", timeout);
+            process::exit(TIMEOUT_EXIT_CODE);
+        }
+    });
+}
+
+/// Reports a breakpoint or watchpoint the run stopped at, or returns why
+/// it halted.
+fn report_stop(
+    stopped: Stopped,
+    simulator: &Simulator,
+    debug_info: &DebugInfo,
+) -> Option<SimulatorError> {
+    match stopped {
+        Stopped::Breakpoint(_) => report_breakpoint(simulator, debug_info),
+        Stopped::Watchpoint(hit) => {
+            println!("\n-- watchpoint {}: {} --", hit.watchpoint, hit);
+        }
+        Stopped::Halted(err) => return Some(err),
+    }
+    None
+}
+
+/// Runs the program under the script at `path` and reports what the
+/// script found. Returns the simulator, why it halted and whether any
+/// check failed.
+#[cfg(feature = "scripting")]
+fn run_script(
+    path: &str,
+    simulator: Simulator,
+    debug_info: &DebugInfo,
+) -> (Simulator, SimulatorError, bool) {
+    let mut script = Script::load(Path::new(path), simulator, debug_info).unwrap_or_else(|err| {
+        println!("{}", err);
+        process::exit(1);
+    });
+    let err = loop {
+        let stopped = script.run();
+        let simulator = script.simulator();
+        if let Some(err) = report_stop(stopped, &simulator, debug_info) {
+            break err;
+        }
+    };
+    let failures = script.failures();
+    for failure in &failures {
+        println!("\n-- script: {} --", failure);
+    }
+    (script.into_simulator(), err, !failures.is_empty())
+}
+
+#[cfg(not(feature = "scripting"))]
+fn run_script(
+    _path: &str,
+    _simulator: Simulator,
+    _debug_info: &DebugInfo,
+) -> (Simulator, SimulatorError, bool) {
+    println!(
+        "--script needs {} built with the 'scripting' feature",
+        env!("CARGO_PKG_NAME")
+    );
+    process::exit(1);
+}
+
+/// Assembles `args.file` and sets up its breakpoints and watchpoints.
+/// Returns None if it does not assemble, and exits on a bad breakpoint or
+/// watchpoint.
+fn load(args: &RuntimeArgs) -> Option<(Simulator, DebugInfo)> {
+    let program = assemble(args)?;
+    Some(build_simulator(args, program))
+}
 
-    return cli_args;
+/// Assembles `args.file`, printing its diagnostics. Returns None if it
+/// does not assemble.
+fn assemble(args: &RuntimeArgs) -> Option<assembler::Program> {
+    let mut assembler = assembler::Assembler::new();
+    let result = assembler.assemble(args);
+    let source = lexer::read_source(&args.file).unwrap_or_default();
+    let color = args.color.enabled();
+    for diagnostic in assembler.diagnostics() {
+        print!("{}", diagnostic.render(&source, &args.file, color));
+    }
+    if let Err(err) = result {
+        // Errors from before the first line, like a missing file, have
+        // no diagnostic.
+        if !assembler
+            .diagnostics()
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            println!(
+                "{}",
+                diagnostic::render_message(Severity::Error, &err.to_string(), color)
+            );
+        }
+        return None;
+    }
+    Some(assembler.finish())
+}
+
+/// Sets up a simulator for `program` as `args` ask. Exits on a bad
+/// breakpoint, watchpoint or load.
+fn build_simulator(args: &RuntimeArgs, program: assembler::Program) -> (Simulator, DebugInfo) {
+    let debug_info = program.debug_info().clone();
+    let mut simulator = Simulator::new(program);
+    simulator.set_backend(args.backend);
+    simulator.set_delay_slots(args.delay_slots);
+    simulator.set_virtual_memory(args.virtual_memory);
+    simulator.set_mips64(args.mips64);
+    let exit = |err: String| -> ! {
+        println!("{}", err);
+        process::exit(1);
+    };
+    if let Some(handler) = &args.exception_handler {
+        let address = debug_info
+            .resolve(handler)
+            .unwrap_or_else(|| exit(format!("Unknown exception handler '{}'", handler)));
+        simulator.set_exception_handler(Some(address));
+    }
+    for breakpoint in &args.breakpoints {
+        let breakpoint =
+            repl::parse_breakpoint(&debug_info, breakpoint).unwrap_or_else(|err| exit(err));
+        repl::add_breakpoint(&mut simulator, breakpoint);
+    }
+    for watchpoint in &args.watchpoints {
+        let watchpoint =
+            repl::parse_watchpoint(&debug_info, watchpoint).unwrap_or_else(|err| exit(err));
+        simulator.add_watchpoint(watchpoint);
+    }
+    let mut io: Box<dyn SyscallIo> = Box::new(StdIo);
+    if let Some(path) = &args.replay {
+        io = Box::new(
+            ReplayIo::load(Path::new(path), io)
+                .unwrap_or_else(|err| exit(format!("Could not read replay {}: {}", path, err))),
+        );
+    }
+    if let Some(path) = &args.record {
+        io = Box::new(
+            RecordingIo::create(Path::new(path), io).unwrap_or_else(|err| {
+                exit(format!("Could not create recording {}: {}", path, err))
+            }),
+        );
+    }
+    simulator.set_io(io);
+    if let Some(path) = &args.trace {
+        let path = Path::new(path);
+        let format = args
+            .trace_format
+            .unwrap_or_else(|| TraceFormat::for_path(path));
+        let tracer = Tracer::create(path, format).unwrap_or_else(|err| {
+            exit(format!(
+                "Could not create trace {}: {}",
+                path.display(),
+                err
+            ))
+        });
+        simulator.set_trace(Some(tracer));
+    }
+    if args.trace_syscalls {
+        simulator.set_syscall_trace(Some(SyscallTracer::stderr()));
+    }
+    simulator.set_profiling(args.profile || args.mix || args.coverage || args.lcov.is_some());
+    simulator.set_pipeline(args.pipeline.map(Pipeline::new));
+    simulator.set_stack_checking(args.check_stack);
+    simulator.set_uninit_checking(args.check_uninit);
+    simulator.set_timing_model(args.cpi.map(CpiModel::new));
+    if let Some(config) = args.caches {
+        let hierarchy = CacheHierarchy::new(config).unwrap_or_else(|err| exit(err.to_string()));
+        simulator.set_cache_hierarchy(Some(hierarchy));
+    }
+    simulator.set_branch_predictor(
+        args.predictor
+            .map(|kind| BranchPredictor::new(kind, branch_predictor::DEFAULT_INDEX_BITS)),
+    );
+    // After the checkers are set up, so the loaded bytes count as written.
+    for load in &args.loads {
+        let Some((location, path)) = load.split_once('=') else {
+            exit(format!(
+                "Invalid load '{}', expected <address|label>=<file>",
+                load
+            ));
+        };
+        let address = debug_info
+            .resolve(location)
+            .unwrap_or_else(|| exit(format!("Unknown load address '{}'", location)));
+        let bytes =
+            fs::read(path).unwrap_or_else(|err| exit(format!("Could not read {}: {}", path, err)));
+        simulator
+            .patch(address, &bytes, false)
+            .unwrap_or_else(|err| exit(format!("Could not load {}: {}", path, err)));
+    }
+    if let Some(entry) = &args.entry {
+        let address = debug_info
+            .resolve(entry)
+            .unwrap_or_else(|| exit(format!("Unknown entry point '{}'", entry)));
+        simulator.set_pc(address);
+    }
+    for preset in &args.presets {
+        let Some((register, value)) = preset.split_once('=') else {
+            exit(format!(
+                "Invalid register setting '{}', expected <register>=<value>",
+                preset
+            ));
+        };
+        let register: RegisterId = register
+            .parse()
+            .unwrap_or_else(|err| exit(format!("{}", err)));
+        let value = value
+            .parse::<Expression>()
+            .and_then(|value| value.resolve_labels(&debug_info))
+            .and_then(|value| value.evaluate(&simulator))
+            .unwrap_or_else(|err| exit(format!("Invalid value '{}': {}", value, err)));
+        simulator.registers_mut().write(register, value);
+    }
+    (simulator, debug_info)
+}
+
+fn run_debug(raw_args: &[String]) {
+    let Some(file) = raw_args.get(2) else {
+        println!(
+            "Usage: {} debug <file> [--journal <depth>] [--trace <file>] [--replay <file>]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(1);
+    };
+    let args = RuntimeArgs {
+        file: file.clone(),
+        breakpoints: option_values(raw_args, "-B", "--break"),
+        watchpoints: option_values(raw_args, "-W", "--watchpoint"),
+        entry: option_value(raw_args, "--entry", "--entry"),
+        presets: option_values(raw_args, "--set", "--set"),
+        delay_slots: parse_delay_slots(raw_args),
+        mips64: raw_args.contains(&"--mips64".to_string()),
+        endianness: parse_endianness(raw_args),
+        color: parse_color(raw_args),
+        ..RuntimeArgs::default()
+    };
+    let mut args = args;
+    parse_trace_args(raw_args, &mut args);
+    parse_exception_args(raw_args, &mut args);
+    parse_replay_args(raw_args, &mut args);
+    args.isa = parse_isa_args(raw_args);
+    let depth = match option_value(raw_args, "--journal", "--journal") {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            println!("Invalid journal depth '{}'", value);
+            process::exit(1);
+        }),
+        None => journal::DEFAULT_DEPTH,
+    };
+
+    let Some((mut simulator, debug_info)) = load(&args) else {
+        process::exit(1);
+    };
+    simulator.set_journal_depth(depth);
+    simulator.set_call_tracking(true);
+    simulator.set_profiling(true);
+    repl::run(&mut simulator, &debug_info, &args.file);
+    finish_trace(&mut simulator);
+}
+
+/// Prints the profile and coverage reports asked for, and writes the lcov
+/// file.
+fn report_profile(args: &RuntimeArgs, profile: &Profile, debug_info: &DebugInfo) {
+    if args.profile {
+        let source = lexer::read_source(&args.file).ok();
+        println!();
+        profile::print_report(profile, debug_info, profile::DEFAULT_TOP, source.as_deref());
+    }
+    let coverage = Coverage::new(profile, debug_info);
+    if args.coverage {
+        println!();
+        coverage::print_summary(&coverage);
+    }
+    if let Some(path) = &args.lcov
+        && let Err(err) = coverage.save_lcov(Path::new(path), &args.file)
+    {
+        println!("Could not write {}: {}", path, err);
+    }
+}
+
+/// Flushes the trace, if one is being written, and reports a failure.
+fn finish_trace(simulator: &mut Simulator) {
+    if let Some(Err(err)) = simulator.set_trace(None).map(Tracer::finish) {
+        println!("Could not write trace: {}", err);
+    }
+}
+
+fn run_tasks(raw_args: &[String]) {
+    let quantum = match option_value(raw_args, "-q", "--quantum") {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            println!("Invalid quantum '{}'", value);
+            process::exit(1);
+        }),
+        None => tasks::DEFAULT_QUANTUM,
+    };
+    let files: Vec<&String> = raw_args
+        .iter()
+        .enumerate()
+        .skip(2)
+        .filter(|&(i, arg)| {
+            !arg.starts_with('-') && !matches!(raw_args[i - 1].as_str(), "-q" | "--quantum")
+        })
+        .map(|(_, arg)| arg)
+        .collect();
+    if files.is_empty() {
+        println!(
+            "Usage: {} tasks <file> <file>... [-q, --quantum <instructions>]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(1);
+    }
+
+    let mut round_robin = RoundRobin::new(quantum);
+    for file in files {
+        let args = RuntimeArgs {
+            file: file.clone(),
+            ..RuntimeArgs::default()
+        };
+        let Some((simulator, _)) = load(&args) else {
+            process::exit(1);
+        };
+        round_robin.add(file.as_str(), simulator);
+    }
+
+    round_robin.run(|round_robin, slice| {
+        let task = &round_robin.tasks()[slice.task];
+        let ready = round_robin
+            .tasks()
+            .iter()
+            .filter(|task| task.status == TaskStatus::Ready)
+            .count();
+        match slice.preempted_at {
+            // With a single task left there is nothing to switch to.
+            Some(pc) if ready > 1 => println!(
+                "\n-- timer: {} preempted after {} instructions at 0x{:08x} --",
+                task.name, slice.executed, pc.0
+            ),
+            Some(_) => {}
+            None => println!("\n-- {} finished --", task.name),
+        }
+    });
+    println!();
+    round_robin.print_summary();
+}
+
+/// Grades a program against expected output and prints the verdict as
+/// JSON. The exit code is 0 only if it passed.
+fn run_grade(raw_args: &[String]) {
+    let usage = || -> ! {
+        println!(
+            "Usage: {} grade <file> --expect <file> [--stdin <file>] [--max-steps <count>]",
+            env!("CARGO_PKG_NAME")
+        );
+        println!("           [--timeout <duration>]");
+        println!("           [--ignore-trailing-whitespace] [--ignore-whitespace]");
+        println!("           [--ignore-blank-lines]");
+        process::exit(2);
+    };
+    let (Some(file), Some(expect)) = (
+        raw_args.get(2),
+        option_value(raw_args, "--expect", "--expect"),
+    ) else {
+        usage();
+    };
+    let read = |path: &str| {
+        fs::read_to_string(path).unwrap_or_else(|err| {
+            println!("Could not read {}: {}", path, err);
+            process::exit(2);
+        })
+    };
+    let expected = read(&expect);
+    let input =
+        option_value(raw_args, "--stdin", "--stdin").map_or_else(String::new, |path| read(&path));
+    let step_limit = match option_value(raw_args, "--max-steps", "--max-steps") {
+        Some(value) => value.parse().unwrap_or_else(|_| usage()),
+        None => grader::DEFAULT_STEP_LIMIT,
+    };
+    let flag = |name: &str| raw_args.contains(&name.to_string());
+    let comparison = Comparison {
+        trailing_whitespace: flag("--ignore-trailing-whitespace"),
+        whitespace: flag("--ignore-whitespace"),
+        blank_lines: flag("--ignore-blank-lines"),
+    };
+    let timeout = option_value(raw_args, "--timeout", "--timeout").map(|value| {
+        scheduler::parse_duration(&value).unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(2);
+        })
+    });
+    let args = RuntimeArgs {
+        file: file.clone(),
+        delay_slots: parse_delay_slots(raw_args),
+        mips64: flag("--mips64"),
+        endianness: parse_endianness(raw_args),
+        isa: parse_isa_args(raw_args),
+        timeout,
+        ..RuntimeArgs::default()
+    };
+
+    let verdict = grader::grade(&args, &input, &expected, comparison, step_limit);
+    println!("{}", verdict.to_json());
+    if !verdict.passed() {
+        process::exit(1);
+    }
+}
+
+/// Compares every program in a directory with its golden snapshot, or with
+/// `--update` rewrites the snapshots. The exit code is 1 if any differ.
+fn run_golden(raw_args: &[String]) {
+    let Some(directory) = raw_args.get(2).filter(|arg| !arg.starts_with('-')) else {
+        println!(
+            "Usage: {} golden <directory> [--update]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(2);
+    };
+    let update = raw_args.contains(&"--update".to_string());
+    let programs = golden::programs(Path::new(directory)).unwrap_or_else(|err| {
+        println!("Could not read {}: {}", directory, err);
+        process::exit(2);
+    });
+
+    let mut failed = 0;
+    for program in &programs {
+        let name = program.display();
+        match golden::check(program, update) {
+            Ok(Status::Passed) => println!("ok       {}", name),
+            Ok(Status::Updated) => println!("updated  {}", name),
+            Ok(Status::Missing) => {
+                failed += 1;
+                println!("missing  {} (run with --update to create it)", name);
+            }
+            Ok(Status::Failed(differences)) => {
+                failed += 1;
+                println!("FAILED   {}", name);
+                for difference in differences {
+                    println!("    {}", difference);
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                println!("error    {}: {}", name, err);
+            }
+        }
+    }
+    println!("\n{} programs, {} failed", programs.len(), failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+/// Runs every program in a directory and reports how each ended, as a
+/// table or with `--json` as JSON. The exit code is 1 if any failed to
+/// exit.
+fn run_batch(raw_args: &[String]) {
+    let usage = || -> ! {
+        println!(
+            "Usage: {} run --batch <directory> [--timeout <duration>] [--max-steps <count>] [--json]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(2);
+    };
+    let Some(directory) = option_value(raw_args, "--batch", "--batch") else {
+        usage();
+    };
+    let mut limits = Limits::default();
+    if let Some(value) = option_value(raw_args, "--timeout", "--timeout") {
+        limits.timeout = scheduler::parse_duration(&value).unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(2);
+        });
+    }
+    if let Some(value) = option_value(raw_args, "--max-steps", "--max-steps") {
+        limits.steps = value.parse().unwrap_or_else(|_| usage());
+    }
+    let reports = batch::run_all(Path::new(&directory), limits).unwrap_or_else(|err| {
+        println!("Could not read {}: {}", directory, err);
+        process::exit(2);
+    });
+    match raw_args.contains(&"--json".to_string()) {
+        true => println!("{}", batch::to_json(&reports)),
+        false => batch::print_table(&reports),
+    }
+    if reports.iter().any(|report| report.failed()) {
+        process::exit(1);
+    }
+}
+
+/// How often `run --watch` looks at the source for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs a program and then again every time its source changes. Console
+/// input typed in one run is replayed in the next, and anything more the
+/// program reads is added for the run after. The recording is deleted
+/// when Ctrl-C stops watching.
+fn run_watch(raw_args: &[String]) {
+    let Some(file) = option_value(raw_args, "--watch", "--watch") else {
+        println!(
+            "Usage: {} run --watch <file> [options]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(2);
+    };
+    // Reuse the normal options, with the file where a run expects it.
+    let mut args = vec![raw_args[0].clone(), file.clone()];
+    let mut rest = raw_args.iter().skip(2);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--watch" => {
+                rest.next();
+            }
+            _ => args.push(arg.clone()),
+        }
+    }
+    let mut args = parse_args(&args);
+    let input = env::temp_dir().join(format!(
+        "{}-watch-{}.in",
+        env!("CARGO_PKG_NAME"),
+        process::id()
+    ));
+    args.record = Some(input.to_string_lossy().into_owned());
+    let recording = input.clone();
+    let handler = ctrlc::set_handler(move || {
+        // Missing if nothing has been recorded yet.
+        let _ = fs::remove_file(&recording);
+        process::exit(130);
+    });
+    if let Err(err) = handler {
+        println!("Could not handle Ctrl-C: {}", err);
+        process::exit(1);
+    }
+
+    let modified = || {
+        fs::metadata(&file)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    loop {
+        let version = modified();
+        println!("-- running {} --", file);
+        run_program(&args);
+        if input.exists() {
+            // The recording is read in full before being replaced.
+            args.replay = args.record.clone();
+        }
+        println!("\n-- watching {} for changes, Ctrl-C to stop --", file);
+        while modified() == version {
+            thread::sleep(WATCH_INTERVAL);
+        }
+        println!();
+    }
+}
+
+/// Formats the files in place, or with `--check` only lists the ones that
+/// are not formatted. Without files it formats stdin to stdout, for
+/// editors to run on save.
+fn run_fmt(raw_args: &[String]) {
+    let check = raw_args.contains(&"--check".to_string());
+    let files: Vec<&String> = raw_args
+        .iter()
+        .skip(2)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            println!("Could not read stdin: {}", err);
+            process::exit(1);
+        }
+        print!("{}", formatter::format(&source));
+        return;
+    }
+
+    let mut unformatted = false;
+    for file in files {
+        let source = mips_sim::lexer::read_source(file).unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        });
+        let formatted = formatter::format(&source);
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", file);
+            unformatted = true;
+        } else if let Err(err) = fs::write(file, formatted) {
+            println!("Could not write {}: {}", file, err);
+            process::exit(1);
+        }
+    }
+    if unformatted {
+        process::exit(1);
+    }
+}
+
+/// Compares two snapshots saved with `--snapshot`, or golden files. The
+/// exit code is 1 if they differ.
+fn run_diff(args: &[String]) {
+    let (Some(before), Some(after)) = (args.get(2), args.get(3)) else {
+        println!(
+            "Usage: {} diff <before> <after> [--json]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(1);
+    };
+    let load = |path: &String| {
+        Snapshot::load(Path::new(path)).unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        })
+    };
+    let difference = snapshot::diff(&load(before), &load(after));
+    match args.contains(&"--json".to_string()) {
+        true => println!("{}", difference.to_json()),
+        false => print!("{}", difference),
+    }
+    if !difference.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Serves the language server protocol on stdin and stdout. The exit code
+/// is 1 if the client exits without shutting the server down first.
+fn run_lsp() {
+    let mut server = lsp::Server::default();
+    match server.run(&mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("Language server error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_bench(args: &[String]) {
+    let iterations = match option_value(args, "-n", "--iterations") {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            println!("Invalid iteration count '{}'", value);
+            process::exit(1);
+        }),
+        None => 10,
+    };
+
+    match bench::run_benchmarks(iterations) {
+        Ok(results) => bench::print_report(&results),
+        Err(err) => {
+            println!("Assembler Error: {:?}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Debugs a program in the terminal UI.
+#[cfg(feature = "tui")]
+fn run_tui(raw_args: &[String]) {
+    let Some(file) = raw_args.get(2) else {
+        println!(
+            "Usage: {} tui <file> [-B, --break <location>] [-W, --watchpoint <location>]",
+            env!("CARGO_PKG_NAME")
+        );
+        process::exit(1);
+    };
+    let mut args = RuntimeArgs {
+        file: file.clone(),
+        breakpoints: option_values(raw_args, "-B", "--break"),
+        watchpoints: option_values(raw_args, "-W", "--watchpoint"),
+        loads: option_values(raw_args, "--load", "--load"),
+        entry: option_value(raw_args, "--entry", "--entry"),
+        presets: option_values(raw_args, "--set", "--set"),
+        delay_slots: parse_delay_slots(raw_args),
+        mips64: raw_args.contains(&"--mips64".to_string()),
+        endianness: parse_endianness(raw_args),
+        color: parse_color(raw_args),
+        ..RuntimeArgs::default()
+    };
+    parse_exception_args(raw_args, &mut args);
+    args.isa = parse_isa_args(raw_args);
+
+    let Some(program) = assemble(&args) else {
+        process::exit(1);
+    };
+    // Checked once here, so rebuilding on reset cannot fail.
+    let (_, debug_info) = build_simulator(&args, program.clone());
+    let source = lexer::read_source(&args.file).unwrap_or_default();
+    let build = || build_simulator(&args, program.clone()).0;
+    if let Err(err) = mips_sim::tui::run(build, debug_info, &source, &args.file) {
+        println!("TUI Error: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_args: &[String]) {
+    println!(
+        "tui needs {} built with the 'tui' feature",
+        env!("CARGO_PKG_NAME")
+    );
+    process::exit(1);
+}
+
+/// Serves the simulator as a WebSocket control API.
+#[cfg(feature = "server")]
+fn run_serve(args: &[String]) {
+    let address = option_value(args, "-l", "--listen")
+        .unwrap_or_else(|| mips_sim::server::DEFAULT_ADDRESS.to_string());
+    let options = mips_sim::server::ServeOptions {
+        include_dir: option_value(args, "--include-dir", "--include-dir").map(Into::into),
+        allowed_origins: option_values(args, "--allow-origin", "--allow-origin"),
+    };
+    if let Err(err) = mips_sim::server::serve(&address, options) {
+        println!("Server Error: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "server"))]
+fn run_serve(_args: &[String]) {
+    println!(
+        "serve needs {} built with the 'server' feature",
+        env!("CARGO_PKG_NAME")
+    );
+    process::exit(1);
+}
+
+#[cfg(feature = "gui")]
+fn run_gui(args: &[String]) {
+    let file = args.get(2).map(|file| {
+        let source = mips_sim::lexer::read_source(file).unwrap_or_else(|err| {
+            println!("{}", err);
+            process::exit(1);
+        });
+        (file.into(), source)
+    });
+
+    if let Err(err) = mips_sim::gui::run(file) {
+        println!("GUI Error: {}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "gui"))]
+fn run_gui(_args: &[String]) {
+    println!(
+        "This build of {} has no GUI support",
+        env!("CARGO_PKG_NAME")
+    );
+    process::exit(1);
 }
 
 fn main() {
-    let args = parse_args();
+    let raw_args: Vec<String> = env::args().collect();
+    match raw_args.get(1).map(String::as_str) {
+        Some("bench") => return run_bench(&raw_args),
+        Some("gui") => return run_gui(&raw_args),
+        Some("debug") => return run_debug(&raw_args),
+        Some("tui") => return run_tui(&raw_args),
+        Some("tasks") => return run_tasks(&raw_args),
+        Some("lsp") => return run_lsp(),
+        Some("serve") => return run_serve(&raw_args),
+        Some("fmt") => return run_fmt(&raw_args),
+        Some("grade") => return run_grade(&raw_args),
+        Some("golden") => return run_golden(&raw_args),
+        Some("diff") => return run_diff(&raw_args),
+        Some("run") if raw_args.contains(&"--watch".to_string()) => return run_watch(&raw_args),
+        Some("run") if raw_args.contains(&"--batch".to_string()) => return run_batch(&raw_args),
+        _ => {}
+    }
+
+    // `run <file> [options]` is the same as `<file> [options]`.
+    let mut raw_args = raw_args;
+    if raw_args.get(1).is_some_and(|arg| arg == "run") {
+        raw_args.remove(1);
+    }
+    let args = parse_args(&raw_args);
     let package_name = env!("CARGO_PKG_NAME");
     let package_version = env!("CARGO_PKG_VERSION");
 
@@ -54,13 +1042,100 @@ fn main() {
     }
 
     if args.help {
-        println!("Usage: {} <file> [options]", package_name);
+        println!("Usage: {} [run] <file> [options]", package_name);
+        println!("       {} bench [-n, --iterations <count>]", package_name);
+        println!("       {} gui [file]", package_name);
+        println!("       {} tui <file> [--break <location>]...", package_name);
+        println!("       {} lsp", package_name);
+        println!(
+            "       {} serve [-l, --listen <address>] [--include-dir <directory>] [--allow-origin <origin>]...",
+            package_name
+        );
+        println!("       {} fmt [--check] [file]...", package_name);
+        println!("       {} golden <directory> [--update]", package_name);
+        println!("       {} diff <before> <after> [--json]", package_name);
+        println!("       {} run --watch <file> [options]", package_name);
+        println!(
+            "       {} run --batch <directory> [--timeout <duration>] [--max-steps <count>] [--json]",
+            package_name
+        );
+        println!(
+            "       {} grade <file> --expect <file> [--stdin <file>] [--ignore-...]",
+            package_name
+        );
+        println!(
+            "       {} tasks <file> <file>... [-q, --quantum <instructions>]",
+            package_name
+        );
+        println!(
+            "       {} debug <file> [--journal <depth>] [-B ...] [-W ...] [--trace <file>]",
+            package_name
+        );
+        println!("A <file> of '-' reads the program from standard input.");
         println!("Options:");
         println!("  -h, --help     Print this help message");
         println!("  -a, --args     Print the arguments");
         println!("  -t, --tokens   Print the tokens");
         println!("  -i, --instructions   Print the instructions");
         println!("  -m, --memory   Print the memory");
+        println!("  -b, --backend  Execution backend: interpreter (default) or threaded");
+        println!("      --hz       Instructions per second, or 'unlimited' (default)");
+        println!("      --timeout  Stop after <duration> of wall-clock time, e.g. 5s or");
+        println!("                 500ms, with exit code 124");
+        println!("      --delayed-branches  Execute the instruction after a branch or jump");
+        println!("                 before taking it");
+        println!("      --delayed-loads  Write a loaded register after the next instruction");
+        println!("      --mips64   Enable 64-bit registers and the doubleword instructions");
+        println!("      --big-endian  Lay out data and access words most significant byte first");
+        println!("      --vm       Translate data addresses below 0x80000000 through the TLB");
+        println!("      --exception-handler  Jump to <location> on exceptions instead of");
+        println!("                 stopping; the handler returns with eret");
+        println!("  -B, --break    Print the registers on reaching an address or label,");
+        println!("                 optionally only '<location> if <condition>'");
+        println!("  -W, --watchpoint  Report loads or stores touching");
+        println!("                 <location>[+<bytes>][:r|w|rw]");
+        println!("      --load     Copy a file into memory before running, as");
+        println!("                 <address|label>=<file>");
+        println!("      --entry    Start at <address|label> instead of the entry point");
+        println!("      --set      Set a register before running, as <register>=<value>,");
+        println!("                 e.g. --set '$a0=5' or --set '$a1=buffer+4'");
+        println!("      --dump     Write a segment as hex words for Verilog's $readmemh");
+        println!("                 or a MARS .mc dump, as <text|data>=<file>, and exit");
+        println!("                 without running");
+        println!("      --allow    Only accept the comma-separated instructions; 'pseudo'");
+        println!("                 stands for every pseudo-instruction");
+        println!("      --deny     Reject the comma-separated instructions");
+        println!("      --isa-profile  Read 'allow ...' and 'deny ...' lines from <file>");
+        println!("  -p, --profile  Report the most executed lines at exit");
+        println!("      --mix      Report executions per mnemonic and class at exit");
+        println!("      --pipeline Report cycles and CPI on a five-stage pipeline at exit");
+        println!("      --no-forwarding  Model the pipeline without forwarding");
+        println!("      --cpi      Report cycles at exit, charging <class>=<cycles>,...");
+        println!("                 for alu, load, store, branch-taken, branch-not-taken,");
+        println!("                 jump and syscall (default 1, 2, 1, 3, 1, 2, 10)");
+        println!("      --cpi-config  Read the --cpi costs from <file>, one per line");
+        println!("      --l1i, --l1d, --l2  Simulate caches given as");
+        println!("                 <size>:<block size>:<ways>[:lru|fifo|random]");
+        println!("      --predictor  Report branch prediction accuracy at exit with");
+        println!("                 always-taken, 1-bit, 2-bit or gshare");
+        println!("      --check-stack  Report reads below $sp, writes into the caller's");
+        println!("                 frame and unbalanced $sp at returns at exit");
+        println!("      --check-uninit  Report loads of stack, heap or other memory the");
+        println!("                 program never wrote at exit");
+        println!("      --heap     Report every sbrk allocation and its line at exit");
+        println!("      --coverage  Report the lines that never executed at exit");
+        println!("      --lcov     Write lcov coverage data to <file> at exit");
+        println!("      --snapshot  Save the registers and memory to <file> at exit");
+        println!("      --record   Save every line of console input to <file>");
+        println!("      --replay   Read console input from a --record <file> first");
+        println!("      --script   Run the Rhai callbacks in <file> alongside the program;");
+        println!("                 needs the 'scripting' feature");
+        println!("      --trace    Write a record per executed instruction to <file>");
+        println!("      --trace-format  text or jsonl (default: jsonl for .jsonl files)");
+        println!("      --trace-syscalls  Log every syscall with its arguments and results");
+        println!("                 to stderr");
+        println!("      --color    Color diagnostics: auto (default, when printing to a");
+        println!("                 terminal), always or never");
         println!("  -v, --version  Print program version");
         return;
     }
@@ -69,38 +1144,151 @@ fn main() {
         println!("{:?}", args);
     }
 
-    let mut assembler = assembler::Assembler::new();
-    if let Err(err) = assembler.assemble(&args) {
-        println!("Assembler Error: {:?}", err);
-        return;
+    process::exit(run_program(&args));
+}
+
+/// Writes each `--dump <text|data>=<file>` as hex words. Returns the exit
+/// code.
+fn dump_segments(args: &RuntimeArgs, simulator: &Simulator) -> i32 {
+    for dump in &args.dumps {
+        let Some((segment, path)) = dump.split_once('=') else {
+            println!("Invalid dump '{}', expected <text|data>=<file>", dump);
+            return 1;
+        };
+        let segment: hex_export::Segment = match segment.parse() {
+            Ok(segment) => segment,
+            Err(err) => {
+                println!("{}", err);
+                return 1;
+            }
+        };
+        let words = hex_export::segment_words(simulator, segment);
+        let written = fs::File::create(path)
+            .map(io::BufWriter::new)
+            .and_then(|mut out| hex_export::write_hex(&words, &mut out));
+        if let Err(err) = written {
+            println!("Could not write {}: {}", path, err);
+            return 1;
+        }
     }
+    0
+}
 
-    let memory = assembler.take_memory();
+/// Assembles and runs `args.file`, printing what was asked for at exit.
+/// Returns the exit code.
+fn run_program(args: &RuntimeArgs) -> i32 {
+    let Some((mut simulator, debug_info)) = load(args) else {
+        return 0;
+    };
 
     if args.memory {
-        println!("{:?}", memory);
+        println!("{:?}", simulator.memory().data());
+    }
+    if !args.dumps.is_empty() {
+        return dump_segments(args, &simulator);
     }
-
-    let instructions = assembler.get_instructions();
-    let entry = assembler.get_entry_point();
-
-    let mut simulator = Simulator::new(instructions, memory, entry);
 
     let mut exit_code = 0;
-    loop {
-        if let Err(err) = simulator.step() {
-            match err {
-                SimulatorError::Exit(value) => {
-                    exit_code = value as i32;
-                    println!("\n-- program is finished running --");
-                }
-                SimulatorError::NoMoreInstructions => {
-                    println!("\n-- program is finished running (dropped off bottom) --");
+    let finished = Arc::new(AtomicBool::new(false));
+    if let Some(timeout) = args.timeout {
+        start_watchdog(timeout, finished.clone());
+    }
+    let err = match &args.script {
+        Some(path) => {
+            let (finished, err, failed) = run_script(path, simulator, &debug_info);
+            simulator = finished;
+            if failed {
+                exit_code = 1;
+            }
+            err
+        }
+        None => {
+            let mut throttle = Throttle::new(args.speed);
+            let deadline = args.timeout.map(Deadline::new);
+            loop {
+                let stopped = scheduler::run_throttled(&mut simulator, &mut throttle, deadline);
+                if let Some(err) = report_stop(stopped, &simulator, &debug_info) {
+                    break err;
                 }
-                _ => println!("Simulator Error: {:?}", err),
             }
-            break;
         }
+    };
+    finished.store(true, Ordering::Relaxed);
+    match err {
+        SimulatorError::Exit(value) => {
+            exit_code = exit_code.max(value as i32);
+            println!("\n-- program is finished running --");
+        }
+        SimulatorError::NoMoreInstructions => {
+            println!("\n-- program is finished running (dropped off bottom) --");
+        }
+        SimulatorError::ReturnedFromMain => {
+            println!("\n-- program is finished running (returned from main) --");
+        }
+        SimulatorError::Timeout(timeout) => {
+            exit_code = TIMEOUT_EXIT_CODE;
+            println!("\n-- program timed out after {:?} --", timeout);
+        }
+        err => {
+            let color = args.color.enabled();
+            let message = format!("Simulator error: {}", err);
+            let source = lexer::read_source(&args.file).unwrap_or_default();
+            match debug_info.line_of(simulator.pc()) {
+                Some(line) => print!(
+                    "{}",
+                    Diagnostic::for_line(Severity::Error, message, &source, line)
+                        .render(&source, &args.file, color)
+                ),
+                None => println!(
+                    "{}",
+                    diagnostic::render_message(Severity::Error, &message, color)
+                ),
+            }
+        }
+    }
+    finish_trace(&mut simulator);
+    if let Some(path) = &args.snapshot
+        && let Err(err) = Snapshot::capture(&simulator).save(Path::new(path))
+    {
+        println!("Could not write snapshot {}: {}", path, err);
+    }
+    if let Some(profile) = simulator.profile() {
+        report_profile(args, profile, &debug_info);
+    }
+    if args.mix
+        && let Some(mix) = simulator.instruction_mix()
+    {
+        println!();
+        mix::print_report(&mix);
+    }
+    if let Some(pipeline) = simulator.pipeline() {
+        println!();
+        pipeline.print_summary();
+    }
+    if let Some(timing) = simulator.timing_model() {
+        println!();
+        timing.print_summary();
+    }
+    if let Some(hierarchy) = simulator.cache_hierarchy() {
+        println!();
+        hierarchy.print_summary();
+    }
+    if let Some(predictor) = simulator.branch_predictor() {
+        println!();
+        predictor.print_summary(&debug_info);
+    }
+    if args.heap {
+        println!();
+        let reachable = simulator.reachable_allocations();
+        heap::print_report(simulator.heap(), &debug_info, &reachable);
+    }
+    if let Some(checker) = simulator.stack_checker() {
+        println!();
+        checker.print_report(&debug_info);
+    }
+    if let Some(checker) = simulator.uninit_checker() {
+        println!();
+        checker.print_report(&debug_info);
     }
-    process::exit(exit_code);
+    exit_code
 }
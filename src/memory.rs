@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::{address::Address, assembler::BASE_DATA_ADDR};
+
+/// Lowest address backed by memory; everything below it belongs to the
+/// text segment.
+pub const MAPPED_BASE: Address = Address(0x1000_0000);
+/// One past the highest stack address.
+pub const STACK_TOP: Address = Address(0x8000_0000);
+/// Start of the kernel segments, which the TLB never maps.
+pub const KERNEL_BASE: Address = Address(0x8000_0000);
+/// Where `$sp` points when a program starts.
+pub const INITIAL_SP: Address = Address(0x7fff_effc);
+/// What `$ra` holds when a program starts. It is below the text segment
+/// and never holds code, so a `jr $ra` from `main` lands here and ends the
+/// run.
+pub const EXIT_ADDRESS: Address = Address(0x003f_fffc);
+/// Where `$gp` points when a program starts.
+pub const INITIAL_GP: Address = Address(0x1000_8000);
+
+const PAGE_SIZE: usize = 4096;
+
+/// The order the bytes of a word are stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// Least significant byte first, as in MARS and SPIM on x86.
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// Puts little-endian `bytes` in this order, or back.
+    pub fn arrange<const N: usize>(self, mut bytes: [u8; N]) -> [u8; N] {
+        if self == Endianness::Big {
+            bytes.reverse();
+        }
+        bytes
+    }
+}
+
+/// The simulated address space. The assembled data segment is kept
+/// contiguous; every other address from [`MAPPED_BASE`] up lives in pages
+/// allocated on first write, so the stack, heap and memory-mapped devices
+/// all read as zero until written. Addresses below `MAPPED_BASE` read as
+/// `None` and ignore writes.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    data: Vec<u8>,
+    pages: HashMap<u32, Box<[u8; PAGE_SIZE]>>,
+    /// Previous value of every byte written while logging, for undo.
+    log: Option<Vec<(Address, u8)>>,
+    endianness: Endianness,
+}
+
+impl Memory {
+    /// Builds the address space around an assembled data segment.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            pages: HashMap::new(),
+            log: None,
+            endianness: Endianness::default(),
+        }
+    }
+
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// The data segment's bytes, starting at `BASE_DATA_ADDR`.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn data_offset(&self, address: Address) -> Option<usize> {
+        let offset = address.offset_from(BASE_DATA_ADDR)? as usize;
+        (offset < self.data.len()).then_some(offset)
+    }
+
+    fn split(address: Address) -> (u32, usize) {
+        (address.0 / PAGE_SIZE as u32, address.0 as usize % PAGE_SIZE)
+    }
+
+    pub fn read_byte(&self, address: Address) -> Option<u8> {
+        if let Some(offset) = self.data_offset(address) {
+            return Some(self.data[offset]);
+        }
+        if address < MAPPED_BASE {
+            return None;
+        }
+
+        let (page, offset) = Self::split(address);
+        Some(self.pages.get(&page).map_or(0, |page| page[offset]))
+    }
+
+    pub fn write_byte(&mut self, address: Address, value: u8) {
+        if self.log.is_some()
+            && let Some(old) = self.read_byte(address)
+            && let Some(log) = &mut self.log
+        {
+            log.push((address, old));
+        }
+        if let Some(offset) = self.data_offset(address) {
+            self.data[offset] = value;
+        } else if address >= MAPPED_BASE {
+            let (page, offset) = Self::split(address);
+            self.pages
+                .entry(page)
+                .or_insert_with(|| Box::new([0; PAGE_SIZE]))[offset] = value;
+        }
+    }
+
+    /// Starts recording the old value of each byte written.
+    pub(crate) fn start_log(&mut self) {
+        self.log = Some(Vec::new());
+    }
+
+    /// Stops recording, returning the old values in write order.
+    pub(crate) fn take_log(&mut self) -> Vec<(Address, u8)> {
+        self.log.take().unwrap_or_default()
+    }
+
+    /// Reads a `size`-byte value, at most a word, in the memory's byte
+    /// order.
+    pub fn read(&self, address: Address, size: u32) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..size {
+            let byte = self.read_byte(address.checked_add(i).ok()?)?;
+            value |= (byte as u32) << self.shift(i, size);
+        }
+        Some(value)
+    }
+
+    /// Writes the low `size` bytes of `value` in the memory's byte order.
+    pub fn write(&mut self, address: Address, size: u32, value: u32) {
+        for i in 0..size {
+            let byte = (value >> self.shift(i, size)) as u8;
+            self.write_byte(address.wrapping_add(i), byte);
+        }
+    }
+
+    /// How far up a value of `size` bytes its `i`th byte in memory sits.
+    fn shift(&self, i: u32, size: u32) -> u32 {
+        match self.endianness {
+            Endianness::Little => i * 8,
+            Endianness::Big => (size - 1 - i) * 8,
+        }
+    }
+
+    /// Reads a word in the memory's byte order.
+    pub fn read_word(&self, address: Address) -> Option<u32> {
+        self.read(address, 4)
+    }
+
+    /// Writes a word in the memory's byte order.
+    pub fn write_word(&mut self, address: Address, value: u32) {
+        self.write(address, 4, value)
+    }
+
+    /// Fills `buffer` with the bytes starting at `address`, a page at a time.
+    /// Unmapped addresses, and any past the top of the address space, read
+    /// as zero.
+    pub fn read_into(&self, address: Address, buffer: &mut [u8]) {
+        let mut done = 0;
+        while done < buffer.len() {
+            let Ok(current) = address.checked_add(done as u32) else {
+                buffer[done..].fill(0);
+                return;
+            };
+            let (page, offset) = Self::split(current);
+            let chunk = (PAGE_SIZE - offset).min(buffer.len() - done);
+            let out = &mut buffer[done..done + chunk];
+
+            match (self.data_offset(current), self.pages.get(&page)) {
+                (Some(start), _) => {
+                    let end = (start + chunk).min(self.data.len());
+                    out[..end - start].copy_from_slice(&self.data[start..end]);
+                    done += end - start;
+                    continue;
+                }
+                (None, Some(page)) if current >= MAPPED_BASE => {
+                    out.copy_from_slice(&page[offset..offset + chunk])
+                }
+                _ => out.fill(0),
+            }
+            done += chunk;
+        }
+    }
+
+    /// Every non-zero aligned word in the data segment and the pages
+    /// written since, by address.
+    pub fn nonzero_words(&self) -> Vec<(Address, u32)> {
+        let word = |chunk: &[u8]| {
+            let bytes = [chunk[0], chunk[1], chunk[2], chunk[3]];
+            u32::from_le_bytes(self.endianness.arrange(bytes))
+        };
+        let mut words: Vec<_> = self
+            .data
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, chunk)| (BASE_DATA_ADDR + (i * 4), word(chunk)))
+            .collect();
+        for (&page, bytes) in &self.pages {
+            let base = page * PAGE_SIZE as u32;
+            words.extend(
+                bytes
+                    .chunks_exact(4)
+                    .enumerate()
+                    .map(|(i, chunk)| (Address(base + i as u32 * 4), word(chunk))),
+            );
+        }
+        words.retain(|&(_, value)| value != 0);
+        words.sort_by_key(|&(address, _)| address);
+        words
+    }
+
+    /// Reads the NUL-terminated string at `address`, stopping early at the
+    /// end of mapped memory.
+    pub fn read_string(&self, address: Address) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut next = Some(address);
+        while let Some(byte) = next.and_then(|a| self.read_byte(a)).filter(|&b| b != 0) {
+            bytes.push(byte);
+            next = next.and_then(|a| a.checked_add(1).ok());
+        }
+        bytes
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    assembler::{Instruction, InstructionClass},
+    profile::Profile,
+};
+
+/// Executed instructions counted by mnemonic and by class, built from a
+/// [`Profile`] of the text segment they came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionMix {
+    mnemonics: BTreeMap<String, u64>,
+    classes: BTreeMap<InstructionClass, u64>,
+    total: u64,
+}
+
+impl InstructionMix {
+    pub fn new(profile: &Profile, instructions: &[Instruction]) -> Self {
+        let mut mix = Self {
+            mnemonics: BTreeMap::new(),
+            classes: BTreeMap::new(),
+            total: 0,
+        };
+        for (index, instruction) in instructions.iter().enumerate() {
+            let count = profile.count_at(index);
+            if count == 0 {
+                continue;
+            }
+            let text = instruction.to_string();
+            let mnemonic = text.split_whitespace().next().unwrap_or_default();
+            *mix.mnemonics.entry(mnemonic.to_string()).or_insert(0) += count;
+            *mix.classes.entry(instruction.class()).or_insert(0) += count;
+            mix.total += count;
+        }
+        mix
+    }
+
+    /// Instructions executed in total.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Executions per mnemonic, most executed first, ties by name.
+    pub fn by_mnemonic(&self) -> Vec<(&str, u64)> {
+        let mut counts: Vec<_> = self
+            .mnemonics
+            .iter()
+            .map(|(mnemonic, &count)| (mnemonic.as_str(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// Executions per class, including classes that never ran.
+    pub fn by_class(&self) -> Vec<(InstructionClass, u64)> {
+        InstructionClass::ALL
+            .into_iter()
+            .map(|class| (class, self.classes.get(&class).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    /// The share of all executions `count` makes up, as a percentage.
+    pub fn percent(&self, count: u64) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => count as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+/// Prints a histogram of executions per mnemonic, then totals per class.
+pub fn print_report(mix: &InstructionMix) {
+    const BAR_WIDTH: u64 = 40;
+    println!("Instruction mix ({} instructions executed):", mix.total());
+    if mix.total() == 0 {
+        return;
+    }
+    let mnemonics = mix.by_mnemonic();
+    let widest = mnemonics.first().map_or(1, |&(_, count)| count.max(1));
+    for (mnemonic, count) in &mnemonics {
+        let bar = "#".repeat(((count * BAR_WIDTH).div_ceil(widest)) as usize);
+        println!(
+            "  {:<8} {:>12} {:>6.2}%  {}",
+            mnemonic,
+            count,
+            mix.percent(*count),
+            bar
+        );
+    }
+    println!("By class:");
+    for (class, count) in mix.by_class() {
+        println!(
+            "  {:<8} {:>12} {:>6.2}%",
+            class.name(),
+            count,
+            mix.percent(count)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+use crate::address::Address;
+
+pub const RECEIVER_CONTROL: Address = Address(0xffff_0000);
+pub const RECEIVER_DATA: Address = Address(0xffff_0004);
+pub const TRANSMITTER_CONTROL: Address = Address(0xffff_0008);
+pub const TRANSMITTER_DATA: Address = Address(0xffff_000c);
+
+const READY: u32 = 1;
+const INTERRUPT_ENABLE: u32 = 2;
+/// Instructions the transmitter stays busy after accepting a character.
+const TRANSMIT_DELAY: u32 = 5;
+/// Writing this character clears the display instead of printing it.
+const CLEAR_DISPLAY: u8 = 0x0c;
+
+fn ready(ready: bool) -> u32 {
+    if ready { READY } else { 0 }
+}
+
+/// The MARS keyboard and display device, mapped at `0xffff0000`.
+///
+/// The receiver's ready bit is set while a typed character waits in its data
+/// register and cleared when the program reads it. The transmitter's ready
+/// bit drops for a few instructions after each character written. The
+/// interrupt-enable bits are stored but no interrupts are raised, since the
+/// simulator has no exception handling yet.
+#[derive(Debug, Clone, Default)]
+pub struct KeyboardDisplay {
+    /// Keys typed but not yet moved into the receiver.
+    keys: VecDeque<u8>,
+    receiver_data: u8,
+    receiver_ready: bool,
+    receiver_control: u32,
+    transmitter_control: u32,
+    /// Instructions until the transmitter is ready again; zero when ready.
+    busy: u32,
+    display: String,
+}
+
+impl KeyboardDisplay {
+    pub fn contains(address: Address) -> bool {
+        (RECEIVER_CONTROL..TRANSMITTER_DATA + 4_u32).contains(&address)
+    }
+
+    /// Queues a typed key for the receiver.
+    pub fn push_key(&mut self, key: u8) {
+        self.keys.push_back(key);
+        self.deliver();
+    }
+
+    /// Keys typed but not yet read by the program.
+    pub fn pending_keys(&self) -> usize {
+        self.keys.len() + usize::from(self.receiver_ready)
+    }
+
+    /// Everything the program has written to the transmitter.
+    pub fn display(&self) -> &str {
+        &self.display
+    }
+
+    pub fn clear_display(&mut self) {
+        self.display.clear();
+    }
+
+    fn deliver(&mut self) {
+        if !self.receiver_ready
+            && let Some(key) = self.keys.pop_front()
+        {
+            self.receiver_data = key;
+            self.receiver_ready = true;
+        }
+    }
+
+    /// Advances the device by one executed instruction.
+    pub fn tick(&mut self) {
+        self.busy = self.busy.saturating_sub(1);
+    }
+
+    pub fn read_word(&mut self, address: Address) -> u32 {
+        match address {
+            RECEIVER_CONTROL => self.receiver_control | ready(self.receiver_ready),
+            RECEIVER_DATA => {
+                let data = self.receiver_data.into();
+                self.receiver_ready = false;
+                self.deliver();
+                data
+            }
+            TRANSMITTER_CONTROL => self.transmitter_control | ready(self.busy == 0),
+            _ => 0,
+        }
+    }
+
+    pub fn write_word(&mut self, address: Address, value: u32) {
+        match address {
+            RECEIVER_CONTROL => self.receiver_control = value & INTERRUPT_ENABLE,
+            TRANSMITTER_CONTROL => self.transmitter_control = value & INTERRUPT_ENABLE,
+            // Characters sent while the transmitter is busy are dropped, as on
+            // the real device.
+            TRANSMITTER_DATA if self.busy == 0 => {
+                match value as u8 {
+                    CLEAR_DISPLAY => self.display.clear(),
+                    byte => self.display.push(byte as char),
+                }
+                self.busy = TRANSMIT_DELAY;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+
+use crate::{
+    address::Address,
+    assembler::{Instruction, InstructionClass},
+    registers::Register,
+};
+
+/// Instructions kept for display after they retire.
+const HISTORY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Fetch,
+    Decode,
+    Execute,
+    Memory,
+    WriteBack,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Fetch,
+        Stage::Decode,
+        Stage::Execute,
+        Stage::Memory,
+        Stage::WriteBack,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Fetch => "IF",
+            Stage::Decode => "ID",
+            Stage::Execute => "EX",
+            Stage::Memory => "MEM",
+            Stage::WriteBack => "WB",
+        }
+    }
+}
+
+/// An operand bypassed into EX instead of being read from the register file.
+#[derive(Debug, Clone, Copy)]
+pub struct Forward {
+    pub register: Register,
+    /// Address of the instruction that produced the value.
+    pub producer: Address,
+    /// Stage the producer was in when the value was forwarded: `Memory` for
+    /// the EX/MEM latch, `WriteBack` for MEM/WB.
+    pub from: Stage,
+}
+
+/// When one retired instruction passed through each stage.
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub address: Address,
+    pub instruction: Instruction,
+    /// Cycle the instruction entered each stage, indexed like [`Stage::ALL`].
+    pub entered: [u64; 5],
+    pub forwards: Vec<Forward>,
+}
+
+impl Timing {
+    /// The stage the instruction occupies during `cycle`, if any.
+    pub fn stage_at(&self, cycle: u64) -> Option<Stage> {
+        Stage::ALL.into_iter().enumerate().find_map(|(i, stage)| {
+            let start = self.entered[i];
+            let end = self.entered.get(i + 1).copied().unwrap_or(start + 1);
+            (start..end).contains(&cycle).then_some(stage)
+        })
+    }
+
+    /// Cycles the instruction spent held in ID waiting for its operands.
+    pub fn stalls(&self) -> u64 {
+        self.entered[2] - self.entered[1] - 1
+    }
+}
+
+/// The most recent writer of a register.
+#[derive(Debug, Clone, Copy)]
+struct Producer {
+    address: Address,
+    entered: [u64; 5],
+    load: bool,
+}
+
+/// A trace-driven model of the classic five-stage MIPS pipeline.
+///
+/// The functional simulator retires instructions in program order and the
+/// model works out when each one would have passed through IF, ID, EX, MEM
+/// and WB: instructions advance in order, operands wait in ID until they
+/// can be read or forwarded into EX, and a taken branch or jump costs one
+/// fetch bubble as it is resolved in ID.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    forwarding: bool,
+    previous: Option<[u64; 5]>,
+    /// Set after a taken branch: the earliest cycle its target can be fetched.
+    redirect: Option<u64>,
+    producers: [Option<Producer>; 32],
+    history: VecDeque<Timing>,
+    instructions: u64,
+    stalls: u64,
+    /// Fetch cycles lost to taken branches and jumps.
+    branch_bubbles: u64,
+}
+
+impl Pipeline {
+    pub fn new(forwarding: bool) -> Self {
+        Self {
+            forwarding,
+            previous: None,
+            redirect: None,
+            producers: [None; 32],
+            history: VecDeque::with_capacity(HISTORY),
+            instructions: 0,
+            stalls: 0,
+            branch_bubbles: 0,
+        }
+    }
+
+    pub fn forwarding(&self) -> bool {
+        self.forwarding
+    }
+
+    /// The earliest cycle an instruction reading `register` can enter EX.
+    fn operand_ready(&self, register: Register) -> u64 {
+        // $zero never changes, so it never waits.
+        if register as usize == 0 {
+            return 0;
+        }
+        match self.producers[register as usize] {
+            None => 0,
+            // Loaded values leave MEM; ALU results leave EX.
+            Some(producer) if self.forwarding && producer.load => producer.entered[4],
+            Some(producer) if self.forwarding => producer.entered[3],
+            // Written in the first half of WB, read in the second half of ID.
+            Some(producer) => producer.entered[4] + 1,
+        }
+    }
+
+    /// Records the timing of an instruction that just executed at `address`,
+    /// after which the program continued at `next_pc`.
+    pub fn retire(&mut self, address: Address, instruction: Instruction, next_pc: Address) {
+        let previous = self.previous.unwrap_or([0; 5]);
+        let first = self.previous.is_none();
+        let sources = instruction.sources();
+
+        let mut entered = [0; 5];
+        for stage in 0..5 {
+            let mut cycle = if first {
+                stage as u64
+            } else {
+                previous[stage] + 1
+            };
+            if stage > 0 {
+                cycle = cycle.max(entered[stage - 1] + 1);
+            }
+            // A stage frees up only once the older instruction moves on.
+            if !first && stage < 4 {
+                cycle = cycle.max(previous[stage + 1]);
+            }
+            if stage == 0
+                && let Some(redirect) = self.redirect.take()
+            {
+                self.branch_bubbles += redirect.saturating_sub(cycle);
+                cycle = cycle.max(redirect);
+            }
+            if stage == 2 {
+                for register in sources.into_iter().flatten() {
+                    cycle = cycle.max(self.operand_ready(register));
+                }
+            }
+            entered[stage] = cycle;
+        }
+
+        let mut forwards = Vec::new();
+        if self.forwarding {
+            for register in sources.into_iter().flatten() {
+                let Some(producer) = self.producers[register as usize] else {
+                    continue;
+                };
+                if register as usize == 0 || entered[2] > producer.entered[4] {
+                    continue;
+                }
+                let from = if entered[2] == producer.entered[4] {
+                    Stage::WriteBack
+                } else {
+                    Stage::Memory
+                };
+                forwards.push(Forward {
+                    register,
+                    producer: producer.address,
+                    from,
+                });
+            }
+        }
+
+        let class = instruction.class();
+        if let Some(register) = instruction.destination() {
+            self.producers[register as usize] = Some(Producer {
+                address,
+                entered,
+                load: class == InstructionClass::Load,
+            });
+        }
+        if next_pc.0 != address.0.wrapping_add(4) {
+            self.redirect = Some(entered[2]);
+        }
+
+        let timing = Timing {
+            address,
+            instruction,
+            entered,
+            forwards,
+        };
+        self.stalls += timing.stalls();
+        self.instructions += 1;
+        self.previous = Some(entered);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(timing);
+    }
+
+    /// Cycles until the last retired instruction leaves WB.
+    pub fn cycles(&self) -> u64 {
+        self.previous.map_or(0, |entered| entered[4] + 1)
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Total cycles instructions spent stalled in ID.
+    pub fn stalls(&self) -> u64 {
+        self.stalls
+    }
+
+    /// Total fetch cycles lost to taken branches and jumps.
+    pub fn branch_bubbles(&self) -> u64 {
+        self.branch_bubbles
+    }
+
+    pub fn cpi(&self) -> f64 {
+        if self.instructions == 0 {
+            return 0.0;
+        }
+        self.cycles() as f64 / self.instructions as f64
+    }
+
+    /// Prints the cycle count, CPI and where the extra cycles went.
+    pub fn print_summary(&self) {
+        println!(
+            "Pipeline (forwarding {}): {} cycles, {} instructions, CPI {:.3}",
+            if self.forwarding { "on" } else { "off" },
+            self.cycles(),
+            self.instructions,
+            self.cpi()
+        );
+        println!("  Data hazard stalls: {} cycles", self.stalls);
+        println!("  Branch penalties:   {} cycles", self.branch_bubbles);
+    }
+
+    /// The most recently retired instructions, oldest first.
+    pub fn history(&self) -> &VecDeque<Timing> {
+        &self.history
+    }
+
+    /// Which recent instruction occupies each stage during `cycle`.
+    pub fn occupancy(&self, cycle: u64) -> [Option<&Timing>; 5] {
+        let mut stages = [None; 5];
+        for timing in &self.history {
+            if let Some(stage) = timing.stage_at(cycle) {
+                stages[stage as usize] = Some(timing);
+            }
+        }
+        stages
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    assembler::InstructionClass,
+    encoding::Encoding,
+    lexer::TokenKind,
+    registers::Register,
+    simulator::{Simulator, SimulatorError},
+};
+
+/// Operands of a custom instruction, named after the MIPS fields. Each
+/// plugin uses the ones it needs and leaves the rest at `$zero` and 0.
+#[derive(Debug, Clone, Copy)]
+pub struct CustomOperands {
+    pub rd: Register,
+    pub rs: Register,
+    pub rt: Register,
+    pub imm: i32,
+}
+
+impl Default for CustomOperands {
+    fn default() -> Self {
+        Self {
+            rd: Register::ZERO,
+            rs: Register::ZERO,
+            rt: Register::ZERO,
+            imm: 0,
+        }
+    }
+}
+
+/// An instruction added by a library user, e.g. a course-specific
+/// `crc32`. Register one with [`register`]; the assembler and decoder try
+/// plugins after the built-in instructions.
+pub trait InstructionPlugin: Send + Sync {
+    fn mnemonic(&self) -> &str;
+
+    /// Reads the operands written after the mnemonic.
+    fn parse(&self, operands: &[TokenKind]) -> Result<CustomOperands, String>;
+
+    fn encode(&self, operands: &CustomOperands) -> Encoding;
+
+    /// Recognises a machine word this plugin encodes.
+    fn decode(&self, word: u32) -> Option<CustomOperands>;
+
+    fn execute(
+        &self,
+        operands: &CustomOperands,
+        simulator: &mut Simulator,
+    ) -> Result<(), SimulatorError>;
+
+    fn class(&self) -> InstructionClass {
+        InstructionClass::Alu
+    }
+
+    /// Registers the instruction reads, for hazard detection.
+    fn sources(&self, _operands: &CustomOperands) -> [Option<Register>; 2] {
+        [None, None]
+    }
+
+    /// The register the instruction writes, for hazard detection.
+    fn destination(&self, _operands: &CustomOperands) -> Option<Register> {
+        None
+    }
+
+    /// The instruction as disassembled; by default the mnemonic and the
+    /// three registers.
+    fn display(&self, operands: &CustomOperands) -> String {
+        format!(
+            "{} {}, {}, {}",
+            self.mnemonic(),
+            operands.rd.name(),
+            operands.rs.name(),
+            operands.rt.name()
+        )
+    }
+}
+
+static PLUGINS: RwLock<Vec<Arc<dyn InstructionPlugin>>> = RwLock::new(Vec::new());
+
+/// Adds `plugin` for every assembler and simulator in the process.
+/// Returns false, leaving the plugin out, if its mnemonic is taken.
+pub fn register(plugin: impl InstructionPlugin + 'static) -> bool {
+    let mut plugins = PLUGINS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mnemonic = plugin.mnemonic();
+    if crate::assembler::MNEMONICS.contains(&mnemonic)
+        || plugins.iter().any(|other| other.mnemonic() == mnemonic)
+    {
+        return false;
+    }
+    plugins.push(Arc::new(plugin));
+    true
+}
+
+/// The plugin registered with index `index`.
+pub fn get(index: u16) -> Option<Arc<dyn InstructionPlugin>> {
+    let plugins = PLUGINS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    plugins.get(index as usize).cloned()
+}
+
+/// The index and plugin for `mnemonic`.
+pub fn lookup(mnemonic: &str) -> Option<(u16, Arc<dyn InstructionPlugin>)> {
+    let plugins = PLUGINS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    plugins
+        .iter()
+        .position(|plugin| plugin.mnemonic() == mnemonic)
+        .map(|index| (index as u16, plugins[index].clone()))
+}
+
+/// The first plugin that decodes `word`, with the operands it found.
+pub fn decode(word: u32) -> Option<(u16, CustomOperands)> {
+    let plugins = PLUGINS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    plugins
+        .iter()
+        .enumerate()
+        .find_map(|(index, plugin)| Some((index as u16, plugin.decode(word)?)))
+}
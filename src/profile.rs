@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use crate::{address::Address, assembler::BASE_TEXT_ADDR, debug_info::DebugInfo};
+
+/// Lines shown by [`print_report`] unless asked for more.
+pub const DEFAULT_TOP: usize = 10;
+
+/// How often each instruction of the text segment has executed. Stepping
+/// back does not uncount anything: the profile records work done, not the
+/// machine's state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    counts: Vec<u64>,
+}
+
+/// Executions attributed to one source line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineCount {
+    pub line: usize,
+    pub count: u64,
+}
+
+impl Profile {
+    /// An empty profile for a text segment of `len` instructions.
+    pub fn new(len: usize) -> Self {
+        Self {
+            counts: vec![0; len],
+        }
+    }
+
+    pub(crate) fn record(&mut self, index: usize) {
+        if let Some(count) = self.counts.get_mut(index) {
+            *count += 1;
+        }
+    }
+
+    /// Times the instruction at `address` has executed.
+    pub fn count(&self, address: Address) -> u64 {
+        address
+            .word_index(BASE_TEXT_ADDR)
+            .map_or(0, |index| self.count_at(index))
+    }
+
+    /// Times the `index`th instruction of the text segment has executed.
+    pub fn count_at(&self, index: usize) -> u64 {
+        self.counts.get(index).copied().unwrap_or(0)
+    }
+
+    /// Instructions executed in total.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+
+    /// Executions per source line, adding up the instructions a pseudo-
+    /// instruction expanded to. Lines that never ran are left out.
+    pub fn by_line(&self, debug_info: &DebugInfo) -> BTreeMap<usize, u64> {
+        let mut lines = BTreeMap::new();
+        for (index, &count) in self.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            if let Some(line) = debug_info.line_of(BASE_TEXT_ADDR + index * 4) {
+                *lines.entry(line).or_insert(0) += count;
+            }
+        }
+        lines
+    }
+
+    /// The `top` most executed lines, hottest first.
+    pub fn hottest(&self, debug_info: &DebugInfo, top: usize) -> Vec<LineCount> {
+        let mut lines: Vec<_> = self
+            .by_line(debug_info)
+            .into_iter()
+            .map(|(line, count)| LineCount { line, count })
+            .collect();
+        lines.sort_by(|a, b| b.count.cmp(&a.count).then(a.line.cmp(&b.line)));
+        lines.truncate(top);
+        lines
+    }
+}
+
+/// Prints the `top` hottest lines with their share of all executions.
+/// `source` supplies each line's text when available.
+pub fn print_report(profile: &Profile, debug_info: &DebugInfo, top: usize, source: Option<&str>) {
+    let total = profile.total();
+    println!("Hottest lines ({} instructions executed):", total);
+    if total == 0 {
+        return;
+    }
+    let lines: Vec<&str> = source.map_or_else(Vec::new, |source| source.lines().collect());
+    for LineCount { line, count } in profile.hottest(debug_info, top) {
+        let text = lines.get(line - 1).map_or("", |text| text.trim());
+        let row = format!(
+            "  line {:>5} {:>12} {:>6.2}%  {}",
+            line,
+            count,
+            count as f64 * 100.0 / total as f64,
+            text
+        );
+        println!("{}", row.trim_end());
+    }
+}
@@ -0,0 +1,889 @@
+use std::fmt;
+
+use crate::{
+    lexer::{Span, TokenKind, classify, lines, split_words},
+    registers::Register,
+};
+
+/// Reference entry for an instruction mnemonic.
+#[derive(Debug)]
+pub struct InstructionDoc {
+    pub mnemonic: &'static str,
+    /// Operand syntax, e.g. `addi rt, rs, imm`.
+    pub format: &'static str,
+    pub semantics: &'static str,
+    /// Whether the assembler expands it into other instructions.
+    pub pseudo: bool,
+}
+
+/// Reference entry for a syscall service, selected by the value in `$v0`.
+#[derive(Debug)]
+pub struct SyscallDoc {
+    pub code: u32,
+    pub name: &'static str,
+    pub arguments: &'static str,
+    pub result: &'static str,
+    pub semantics: &'static str,
+}
+
+pub const INSTRUCTIONS: &[InstructionDoc] = &[
+    InstructionDoc {
+        mnemonic: "abs",
+        format: "abs rd, rs",
+        semantics: "rd = |rs| (sra + xor + subu through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "add",
+        format: "add rd, rs, rt",
+        semantics: "rd = rs + rt, trapping on signed overflow",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "addi",
+        format: "addi rt, rs, imm",
+        semantics: "rt = rs + sign-extended imm, trapping on signed overflow",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "addiu",
+        format: "addiu rt, rs, imm",
+        semantics: "rt = rs + sign-extended imm, ignoring overflow",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "addu",
+        format: "addu rd, rs, rt",
+        semantics: "rd = rs + rt, ignoring overflow",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "and",
+        format: "and rd, rs, rt",
+        semantics: "rd = rs & rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "andi",
+        format: "andi rt, rs, imm",
+        semantics: "rt = rs & zero-extended imm",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "beq",
+        format: "beq rs, rt, label",
+        semantics: "if rs == rt, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "bge",
+        format: "bge rs, rt, label",
+        semantics: "if rs >= rt (signed), branch to label (slt + beq through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bgeu",
+        format: "bgeu rs, rt, label",
+        semantics: "if rs >= rt (unsigned), branch to label (sltu + beq through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bgez",
+        format: "bgez rs, label",
+        semantics: "if rs >= 0, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "bgezal",
+        format: "bgezal rs, label",
+        semantics: "$ra = return address; if rs >= 0, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "bgt",
+        format: "bgt rs, rt, label",
+        semantics: "if rs > rt (signed), branch to label (slt + bne through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bgtu",
+        format: "bgtu rs, rt, label",
+        semantics: "if rs > rt (unsigned), branch to label (sltu + bne through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bgtz",
+        format: "bgtz rs, label",
+        semantics: "if rs > 0, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "ble",
+        format: "ble rs, rt, label",
+        semantics: "if rs <= rt (signed), branch to label (slt + beq through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bleu",
+        format: "bleu rs, rt, label",
+        semantics: "if rs <= rt (unsigned), branch to label (sltu + beq through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "blez",
+        format: "blez rs, label",
+        semantics: "if rs <= 0, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "blt",
+        format: "blt rs, rt, label",
+        semantics: "if rs < rt (signed), branch to label (slt + bne through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bltu",
+        format: "bltu rs, rt, label",
+        semantics: "if rs < rt (unsigned), branch to label (sltu + bne through $at)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "bltz",
+        format: "bltz rs, label",
+        semantics: "if rs < 0, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "bltzal",
+        format: "bltzal rs, label",
+        semantics: "$ra = return address; if rs < 0, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "bne",
+        format: "bne rs, rt, label",
+        semantics: "if rs != rt, branch to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dadd",
+        format: "dadd rd, rs, rt",
+        semantics: "rd = rs + rt on 64 bits, trapping on overflow (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "daddi",
+        format: "daddi rt, rs, imm",
+        semantics: "rt = rs + sign-extended imm on 64 bits, trapping on overflow (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "daddiu",
+        format: "daddiu rt, rs, imm",
+        semantics: "rt = rs + sign-extended imm on 64 bits, ignoring overflow (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "daddu",
+        format: "daddu rd, rs, rt",
+        semantics: "rd = rs + rt on 64 bits, ignoring overflow (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "div",
+        format: "div rs, rt",
+        semantics: "lo = rs / rt, hi = rs % rt, signed; unchanged when rt is 0",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "divu",
+        format: "divu rs, rt",
+        semantics: "lo = rs / rt, hi = rs % rt, unsigned; unchanged when rt is 0",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsll",
+        format: "dsll rd, rt, sa",
+        semantics: "rd = rt << sa on 64 bits, sa up to 63 (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsll32",
+        format: "dsll32 rd, rt, sa",
+        semantics: "rd = rt << (sa + 32) (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsra",
+        format: "dsra rd, rt, sa",
+        semantics: "rd = rt >> sa on 64 bits, copying the sign bit, sa up to 63 (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsra32",
+        format: "dsra32 rd, rt, sa",
+        semantics: "rd = rt >> (sa + 32), copying the sign bit (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsrl",
+        format: "dsrl rd, rt, sa",
+        semantics: "rd = rt >> sa on 64 bits, shifting in zeros, sa up to 63 (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsrl32",
+        format: "dsrl32 rd, rt, sa",
+        semantics: "rd = rt >> (sa + 32), shifting in zeros (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsub",
+        format: "dsub rd, rs, rt",
+        semantics: "rd = rs - rt on 64 bits, trapping on overflow (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "dsubu",
+        format: "dsubu rd, rs, rt",
+        semantics: "rd = rs - rt on 64 bits, ignoring overflow (MIPS64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "eret",
+        format: "eret",
+        semantics: "Returns from an exception handler to EPC",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "j",
+        format: "j label",
+        semantics: "jump to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "jal",
+        format: "jal label",
+        semantics: "$ra = return address; jump to label",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "jalr",
+        format: "jalr rd, rs",
+        semantics: "rd = return address; jump to the address in rs (rd is $ra if omitted)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "jr",
+        format: "jr rs",
+        semantics: "jump to the address in rs",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "la",
+        format: "la rt, label",
+        semantics: "rt = address of label (lui + ori)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "lb",
+        format: "lb rt, offset(rs)",
+        semantics: "rt = the sign-extended byte at rs + offset",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "lbu",
+        format: "lbu rt, offset(rs)",
+        semantics: "rt = the zero-extended byte at rs + offset",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "ld",
+        format: "ld rt, offset(rs)",
+        semantics: "rt = doubleword at rs + offset, 8-byte aligned (--mips64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "lh",
+        format: "lh rt, offset(rs)",
+        semantics: "rt = the sign-extended halfword at rs + offset, which must be aligned",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "lhu",
+        format: "lhu rt, offset(rs)",
+        semantics: "rt = the zero-extended halfword at rs + offset, which must be aligned",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "li",
+        format: "li rt, imm",
+        semantics: "rt = imm (addiu or lui when it fits, else lui + addiu)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "lui",
+        format: "lui rt, imm",
+        semantics: "rt = imm << 16",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "lw",
+        format: "lw rt, offset(rs)",
+        semantics: "rt = the word at rs + offset, which must be aligned",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "lwl",
+        format: "lwl rt, offset(rs)",
+        semantics: "Loads rs + offset up to the most significant byte of its word into the top of rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "lwr",
+        format: "lwr rt, offset(rs)",
+        semantics: "Loads rs + offset down to the least significant byte of its word into the bottom of rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "mfc0",
+        format: "mfc0 rt, rd",
+        semantics: "rt = coprocessor 0 register rd, e.g. $8 BadVAddr, $14 EPC",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "mfhi",
+        format: "mfhi rd",
+        semantics: "rd = hi",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "mflo",
+        format: "mflo rd",
+        semantics: "rd = lo",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "move",
+        format: "move rd, rs",
+        semantics: "rd = rs (addu rd, $zero, rs; daddu with --mips64)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "mtc0",
+        format: "mtc0 rt, rd",
+        semantics: "Coprocessor 0 register rd = rt, e.g. $2 EntryLo, $10 EntryHi",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "mthi",
+        format: "mthi rs",
+        semantics: "hi = rs",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "mtlo",
+        format: "mtlo rs",
+        semantics: "lo = rs",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "mul",
+        format: "mul rd, rs, rt",
+        semantics: "rd = low word of rs * rt (mult + mflo)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "mult",
+        format: "mult rs, rt",
+        semantics: "hi, lo = rs * rt as a signed 64-bit product",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "multu",
+        format: "multu rs, rt",
+        semantics: "hi, lo = rs * rt as an unsigned 64-bit product",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "neg",
+        format: "neg rd, rs",
+        semantics: "rd = -rs, trapping on overflow (sub rd, $zero, rs)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "nop",
+        format: "nop",
+        semantics: "Does nothing (sll $zero, $zero, 0)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "nor",
+        format: "nor rd, rs, rt",
+        semantics: "rd = ~(rs | rt)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "not",
+        format: "not rd, rs",
+        semantics: "rd = ~rs (nor rd, rs, $zero)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "or",
+        format: "or rd, rs, rt",
+        semantics: "rd = rs | rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "ori",
+        format: "ori rt, rs, imm",
+        semantics: "rt = rs | zero-extended imm",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "rem",
+        format: "rem rd, rs, rt",
+        semantics: "rd = rs % rt, signed (div + mfhi)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sb",
+        format: "sb rt, offset(rs)",
+        semantics: "Stores the low byte of rt at rs + offset",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sd",
+        format: "sd rt, offset(rs)",
+        semantics: "Stores all of rt at rs + offset, 8-byte aligned (--mips64)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "seq",
+        format: "seq rd, rs, rt",
+        semantics: "rd = 1 if rs == rt, else 0",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sge",
+        format: "sge rd, rs, rt",
+        semantics: "rd = 1 if rs >= rt (signed), else 0",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sgeu",
+        format: "sgeu rd, rs, rt",
+        semantics: "rd = 1 if rs >= rt (unsigned), else 0",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sgt",
+        format: "sgt rd, rs, rt",
+        semantics: "rd = 1 if rs > rt (signed), else 0 (slt rd, rt, rs)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sgtu",
+        format: "sgtu rd, rs, rt",
+        semantics: "rd = 1 if rs > rt (unsigned), else 0 (sltu rd, rt, rs)",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sh",
+        format: "sh rt, offset(rs)",
+        semantics: "Stores the low halfword of rt at rs + offset, which must be aligned",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sle",
+        format: "sle rd, rs, rt",
+        semantics: "rd = 1 if rs <= rt (signed), else 0",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sleu",
+        format: "sleu rd, rs, rt",
+        semantics: "rd = 1 if rs <= rt (unsigned), else 0",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sll",
+        format: "sll rd, rt, shamt",
+        semantics: "rd = rt << shamt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sllv",
+        format: "sllv rd, rt, rs",
+        semantics: "rd = rt << (rs & 31)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "slt",
+        format: "slt rd, rs, rt",
+        semantics: "rd = 1 if rs < rt (signed), else 0",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sltu",
+        format: "sltu rd, rs, rt",
+        semantics: "rd = 1 if rs < rt (unsigned), else 0",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sne",
+        format: "sne rd, rs, rt",
+        semantics: "rd = 1 if rs != rt, else 0",
+        pseudo: true,
+    },
+    InstructionDoc {
+        mnemonic: "sra",
+        format: "sra rd, rt, shamt",
+        semantics: "rd = rt >> shamt, copying the sign bit in",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "srav",
+        format: "srav rd, rt, rs",
+        semantics: "rd = rt >> (rs & 31), copying the sign bit in",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "srl",
+        format: "srl rd, rt, shamt",
+        semantics: "rd = rt >> shamt, shifting zeros in",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "srlv",
+        format: "srlv rd, rt, rs",
+        semantics: "rd = rt >> (rs & 31), shifting zeros in",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sub",
+        format: "sub rd, rs, rt",
+        semantics: "rd = rs - rt, trapping on signed overflow",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "subu",
+        format: "subu rd, rs, rt",
+        semantics: "rd = rs - rt, ignoring overflow",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "sw",
+        format: "sw rt, offset(rs)",
+        semantics: "Stores rt at rs + offset, which must be aligned",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "swl",
+        format: "swl rt, offset(rs)",
+        semantics: "Stores the top of rt from rs + offset up to the most significant byte of its word",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "swr",
+        format: "swr rt, offset(rs)",
+        semantics: "Stores the bottom of rt from rs + offset down to the least significant byte of its word",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "syscall",
+        format: "syscall",
+        semantics: "Requests the service numbered in $v0",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "teq",
+        format: "teq rs, rt",
+        semantics: "Trap if rs == rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "teqi",
+        format: "teqi rs, imm",
+        semantics: "Trap if rs == sign-extended imm",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tge",
+        format: "tge rs, rt",
+        semantics: "Trap if rs >= rt (signed)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tgei",
+        format: "tgei rs, imm",
+        semantics: "Trap if rs >= sign-extended imm (signed)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tgeiu",
+        format: "tgeiu rs, imm",
+        semantics: "Trap if rs >= sign-extended imm (unsigned)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tgeu",
+        format: "tgeu rs, rt",
+        semantics: "Trap if rs >= rt (unsigned)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tlbp",
+        format: "tlbp",
+        semantics: "Index = TLB entry matching EntryHi, or bit 31 set if none",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tlbr",
+        format: "tlbr",
+        semantics: "EntryHi, EntryLo = TLB entry Index",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tlbwi",
+        format: "tlbwi",
+        semantics: "TLB entry Index = EntryHi, EntryLo",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tlbwr",
+        format: "tlbwr",
+        semantics: "TLB entry Random = EntryHi, EntryLo",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tlt",
+        format: "tlt rs, rt",
+        semantics: "Trap if rs < rt (signed)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tlti",
+        format: "tlti rs, imm",
+        semantics: "Trap if rs < sign-extended imm (signed)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tltiu",
+        format: "tltiu rs, imm",
+        semantics: "Trap if rs < sign-extended imm (unsigned)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tltu",
+        format: "tltu rs, rt",
+        semantics: "Trap if rs < rt (unsigned)",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tne",
+        format: "tne rs, rt",
+        semantics: "Trap if rs != rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "tnei",
+        format: "tnei rs, imm",
+        semantics: "Trap if rs != sign-extended imm",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "xor",
+        format: "xor rd, rs, rt",
+        semantics: "rd = rs ^ rt",
+        pseudo: false,
+    },
+    InstructionDoc {
+        mnemonic: "xori",
+        format: "xori rt, rs, imm",
+        semantics: "rt = rs ^ zero-extended imm",
+        pseudo: false,
+    },
+];
+
+pub const SYSCALLS: &[SyscallDoc] = &[
+    SyscallDoc {
+        code: 1,
+        name: "print_int",
+        arguments: "$a0 = integer",
+        result: "none",
+        semantics: "Prints $a0 as a signed decimal",
+    },
+    SyscallDoc {
+        code: 4,
+        name: "print_string",
+        arguments: "$a0 = address of null-terminated string",
+        result: "none",
+        semantics: "Prints the string at $a0",
+    },
+    SyscallDoc {
+        code: 5,
+        name: "read_int",
+        arguments: "none",
+        result: "$v0 = integer read",
+        semantics: "Reads a line of input as a signed decimal",
+    },
+    SyscallDoc {
+        code: 9,
+        name: "sbrk",
+        arguments: "$a0 = number of bytes",
+        result: "$v0 = address of the block",
+        semantics: "Grows the heap by $a0 bytes, rounded up to a word",
+    },
+    SyscallDoc {
+        code: 10,
+        name: "exit",
+        arguments: "none",
+        result: "none",
+        semantics: "Ends the program with exit code 0",
+    },
+    SyscallDoc {
+        code: 17,
+        name: "exit2",
+        arguments: "$a0 = exit code",
+        result: "none",
+        semantics: "Ends the program with the exit code in $a0",
+    },
+    SyscallDoc {
+        code: 30,
+        name: "time",
+        arguments: "none",
+        result: "$a0 = low word, $a1 = high word",
+        semantics: "System time in milliseconds since the Unix epoch",
+    },
+];
+
+/// What a register is for under the O32 calling convention.
+pub fn register_usage(register: Register) -> &'static str {
+    match register as usize {
+        0 => "Always zero; writes are ignored",
+        1 => "Assembler temporary, used by pseudo-instructions",
+        2..=3 => "Function results and syscall codes",
+        4..=7 => "Function arguments, not preserved across calls",
+        8..=15 | 24..=25 => "Temporary, not preserved across calls",
+        16..=23 => "Saved, preserved across calls",
+        26..=27 => "Reserved for the exception handler",
+        28 => "Global pointer, into the middle of the data segment",
+        29 => "Stack pointer",
+        30 => "Frame pointer, preserved across calls",
+        _ => "Return address, set by jal",
+    }
+}
+
+pub fn instruction(mnemonic: &str) -> Option<&'static InstructionDoc> {
+    INSTRUCTIONS.iter().find(|doc| doc.mnemonic == mnemonic)
+}
+
+pub fn syscall(code: u32) -> Option<&'static SyscallDoc> {
+    SYSCALLS.iter().find(|doc| doc.code == code)
+}
+
+impl fmt::Display for InstructionDoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format)?;
+        if self.pseudo {
+            write!(f, "  (pseudo-instruction)")?;
+        }
+        write!(f, "\n{}", self.semantics)
+    }
+}
+
+impl fmt::Display for SyscallDoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syscall {}: {}\n{}\nArguments: {}\nReturns: {}",
+            self.code, self.name, self.semantics, self.arguments, self.result
+        )
+    }
+}
+
+/// What a word under the cursor refers to.
+#[derive(Debug)]
+pub enum Topic {
+    Instruction(&'static InstructionDoc),
+    Syscall(&'static SyscallDoc),
+    Register(Register),
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Instruction(doc) => doc.fmt(f),
+            Topic::Syscall(doc) => doc.fmt(f),
+            Topic::Register(register) => write!(
+                f,
+                "{} (register {})\n{}",
+                register.name(),
+                *register as usize,
+                register_usage(*register)
+            ),
+        }
+    }
+}
+
+/// Mnemonics that can load a syscall code into `$v0` from their last
+/// operand.
+const LOADS_IMMEDIATE: &[&str] = &["li", "addi", "addiu", "ori"];
+
+/// Finds the reference topic for the word at byte `offset` in `source`: an
+/// instruction mnemonic, a register, or the code in an instruction that
+/// loads a constant into `$v0`. Returns the word's span with it.
+pub fn lookup(source: &str, offset: usize) -> Option<(Span, Topic)> {
+    let line = lines(source)
+        .find(|line| (line.offset..=line.offset + line.text.len()).contains(&offset))?;
+
+    let mut first = true;
+    let mut mnemonic = None;
+    let mut destination = None;
+    let mut operands = 0;
+    let mut hovered = None;
+    for (start, word) in split_words(line.text) {
+        let start = line.offset + start;
+        let span = Span {
+            start,
+            end: start + word.len(),
+        };
+        let kind = classify(word, first).ok();
+        let is_hovered = (span.start..span.end).contains(&offset);
+        match kind {
+            Some(TokenKind::Label { decl: true, .. }) => continue,
+            Some(TokenKind::Operator { value }) if first => {
+                first = false;
+                mnemonic = Some(value);
+                if is_hovered {
+                    return Some((span, Topic::Instruction(instruction(value)?)));
+                }
+                continue;
+            }
+            _ => first = false,
+        }
+
+        operands += 1;
+        if operands == 1
+            && let Some(TokenKind::Register { value }) = &kind
+        {
+            destination = Some(*value as usize);
+        }
+        if is_hovered && let Some(TokenKind::Register { value }) = kind {
+            return Some((span, Topic::Register(value)));
+        }
+        if is_hovered && let Some(TokenKind::Number { value }) = kind {
+            hovered = Some((span, value));
+        }
+    }
+
+    let (span, code) = hovered?;
+    let loads_v0 = mnemonic.is_some_and(|m| LOADS_IMMEDIATE.contains(&m))
+        && destination == Some(Register::V0 as usize);
+    if !loads_v0 {
+        return None;
+    }
+    Some((span, Topic::Syscall(syscall(code as u32)?)))
+}
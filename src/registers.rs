@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::address::Address;
+
 #[derive(Debug, Error)]
 pub enum RegisterError {
     #[error("There is no register named '{0}' in this processor")]
@@ -7,7 +9,8 @@ pub enum RegisterError {
 }
 
 #[repr(usize)]
-#[derive(Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     ZERO = 0,
     AT = 1,
@@ -43,6 +46,53 @@ pub enum Register {
     RA = 31,
 }
 
+impl Register {
+    pub const ALL: [Register; 32] = [
+        Register::ZERO,
+        Register::AT,
+        Register::V0,
+        Register::V1,
+        Register::A0,
+        Register::A1,
+        Register::A2,
+        Register::A3,
+        Register::T0,
+        Register::T1,
+        Register::T2,
+        Register::T3,
+        Register::T4,
+        Register::T5,
+        Register::T6,
+        Register::T7,
+        Register::S0,
+        Register::S1,
+        Register::S2,
+        Register::S3,
+        Register::S4,
+        Register::S5,
+        Register::S6,
+        Register::S7,
+        Register::T8,
+        Register::T9,
+        Register::K0,
+        Register::K1,
+        Register::GP,
+        Register::SP,
+        Register::FP,
+        Register::RA,
+    ];
+
+    /// The conventional assembler name, e.g. `$t0`.
+    pub fn name(self) -> &'static str {
+        const NAMES: [&str; 32] = [
+            "$zero", "$at", "$v0", "$v1", "$a0", "$a1", "$a2", "$a3", "$t0", "$t1", "$t2", "$t3",
+            "$t4", "$t5", "$t6", "$t7", "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7",
+            "$t8", "$t9", "$k0", "$k1", "$gp", "$sp", "$fp", "$ra",
+        ];
+        NAMES[self as usize]
+    }
+}
+
 impl std::str::FromStr for Register {
     type Err = RegisterError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -79,23 +129,159 @@ impl std::str::FromStr for Register {
             "$sp" => Ok(Register::SP),
             "$fp" => Ok(Register::FP),
             "$ra" => Ok(Register::RA),
-            other => Err(RegisterError::NoSuchRegister(other.to_string())),
+            other => other
+                .strip_prefix('$')
+                .and_then(|number| number.parse::<usize>().ok())
+                .and_then(|number| Register::ALL.get(number).copied())
+                .ok_or_else(|| RegisterError::NoSuchRegister(other.to_string())),
+        }
+    }
+}
+
+/// Any register a program or debugger can name: a general register, the
+/// PC, HI or LO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterId {
+    General(Register),
+    Pc,
+    Hi,
+    Lo,
+}
+
+impl RegisterId {
+    pub fn name(self) -> &'static str {
+        match self {
+            RegisterId::General(register) => register.name(),
+            RegisterId::Pc => "pc",
+            RegisterId::Hi => "hi",
+            RegisterId::Lo => "lo",
+        }
+    }
+}
+
+impl From<Register> for RegisterId {
+    fn from(register: Register) -> Self {
+        RegisterId::General(register)
+    }
+}
+
+impl std::str::FromStr for RegisterId {
+    type Err = RegisterError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches('$') {
+            "pc" => Ok(RegisterId::Pc),
+            "hi" => Ok(RegisterId::Hi),
+            "lo" => Ok(RegisterId::Lo),
+            _ => s.parse().map(RegisterId::General),
         }
     }
 }
 
-#[derive(Debug, Default)]
-pub struct RegisterFile([u32; 32]);
+/// A register whose value differs between two [`RegisterFile`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: RegisterId,
+    pub old: u32,
+    pub new: u32,
+}
+
+/// The general registers, PC, HI and LO. Registers are 64 bits wide so the
+/// MIPS64 mode can use them whole; 32-bit writes sign-extend, as MIPS64
+/// defines, so MIPS32 programs only ever see the low half.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RegisterFile {
+    gprs: [u64; 32],
+    pc: Address,
+    hi: u32,
+    lo: u32,
+}
 
 impl RegisterFile {
     pub fn get(&self, r: Register) -> u32 {
-        self.0[r as usize]
+        self.gprs[r as usize] as u32
     }
 
     pub fn set(&mut self, r: Register, val: u32) {
+        self.set64(r, val as i32 as i64 as u64);
+    }
+
+    /// The whole 64-bit register.
+    pub fn get64(&self, r: Register) -> u64 {
+        self.gprs[r as usize]
+    }
+
+    pub fn set64(&mut self, r: Register, val: u64) {
         let idx = r as usize;
         if idx != 0 {
-            self.0[idx] = val;
+            self.gprs[idx] = val;
         }
     }
+
+    /// Address of the next instruction to fetch.
+    pub fn pc(&self) -> Address {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: Address) {
+        self.pc = pc;
+    }
+
+    pub fn hi(&self) -> u32 {
+        self.hi
+    }
+
+    pub fn lo(&self) -> u32 {
+        self.lo
+    }
+
+    pub fn set_hi(&mut self, val: u32) {
+        self.hi = val;
+    }
+
+    pub fn set_lo(&mut self, val: u32) {
+        self.lo = val;
+    }
+
+    /// Reads any register by id; 32 bits, like [`RegisterFile::get`].
+    pub fn read(&self, id: RegisterId) -> u32 {
+        match id {
+            RegisterId::General(register) => self.get(register),
+            RegisterId::Pc => self.pc.0,
+            RegisterId::Hi => self.hi,
+            RegisterId::Lo => self.lo,
+        }
+    }
+
+    pub fn write(&mut self, id: RegisterId, val: u32) {
+        match id {
+            RegisterId::General(register) => self.set(register, val),
+            RegisterId::Pc => self.pc = Address(val),
+            RegisterId::Hi => self.hi = val,
+            RegisterId::Lo => self.lo = val,
+        }
+    }
+
+    /// The general registers and their 32-bit values, in number order.
+    pub fn iter(&self) -> impl Iterator<Item = (Register, u32)> + '_ {
+        Register::ALL
+            .into_iter()
+            .map(|register| (register, self.get(register)))
+    }
+
+    /// Every register, general ones first, then PC, HI and LO.
+    pub fn iter_all(&self) -> impl Iterator<Item = (RegisterId, u32)> + '_ {
+        self.iter()
+            .map(|(register, value)| (RegisterId::General(register), value))
+            .chain([RegisterId::Pc, RegisterId::Hi, RegisterId::Lo].map(|id| (id, self.read(id))))
+    }
+
+    /// The registers that differ from `before`, a snapshot taken earlier
+    /// with `clone`, in the order of [`RegisterFile::iter_all`].
+    pub fn diff(&self, before: &RegisterFile) -> Vec<RegisterChange> {
+        self.iter_all()
+            .zip(before.iter_all())
+            .filter(|((_, new), (_, old))| new != old)
+            .map(|((register, new), (_, old))| RegisterChange { register, old, new })
+            .collect()
+    }
 }
@@ -0,0 +1,449 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    address::Address,
+    call_stack::symbolize,
+    coverage::{self, Coverage},
+    debug_info::DebugInfo,
+    expression::{Expression, Machine},
+    heap, mix, profile,
+    registers::Register,
+    simulator::{Simulator, SimulatorError, Stopped},
+    snapshot::{self, Snapshot},
+    trace::{TraceFormat, Tracer},
+    watchpoint::{WatchKind, Watchpoint},
+};
+
+const HELP: &str = "\
+Commands:
+  s, step [n]           Execute n instructions (default 1)
+  n, next               Step, running over any subroutine call
+  finish                Run until the current subroutine returns
+  b, back [n]           Undo the last n instructions (default 1)
+  c, continue           Run to the next breakpoint or watchpoint
+  break <loc> [if <e>]  Stop at an address or label, optionally only when e
+  watch <loc>[+<n>][:r|w|rw]
+                        Stop after loads or stores touching n bytes at loc
+  delete <loc>          Remove the breakpoint at loc
+  bt, backtrace         Show the calls that led to the PC
+  heap                  Show every sbrk allocation
+  tlb                   Show the coprocessor 0 registers and valid TLB entries
+  trace <file> [text|jsonl]
+                        Write a record per executed instruction to file
+  trace off             Stop tracing
+  profile [n|reset]     Show the n most executed lines, or start counting over
+  mix                   Show executions per mnemonic and instruction class
+  coverage [file]       Show the lines never executed, or write them as lcov
+  snapshot <file>       Save the registers and memory to file
+  diff <file>           Show what changed since the snapshot in file
+  p, print <e>          Evaluate an expression, e.g. word($sp+4)
+  r, regs               Show the registers
+  h, help               Show this help
+  q, quit               Leave the debugger";
+
+/// Reads a breakpoint given as `<address|label> [if <condition>]`.
+pub fn parse_breakpoint(
+    debug_info: &DebugInfo,
+    text: &str,
+) -> Result<(Address, Option<Expression>), String> {
+    let (location, condition) = match text.split_once(" if ") {
+        Some((location, condition)) => (location.trim(), Some(condition)),
+        None => (text.trim(), None),
+    };
+    let address = debug_info
+        .resolve(location)
+        .ok_or_else(|| format!("Unknown breakpoint location '{}'", location))?;
+    let condition = condition
+        .map(|condition| {
+            condition
+                .parse::<Expression>()
+                .and_then(|parsed| parsed.resolve_labels(debug_info))
+                .map_err(|err| format!("Invalid breakpoint condition '{}': {}", condition, err))
+        })
+        .transpose()?;
+    Ok((address, condition))
+}
+
+/// Reads a watchpoint given as `<address|label>[+<bytes>][:r|w|rw]`,
+/// watching a word for writes by default.
+pub fn parse_watchpoint(debug_info: &DebugInfo, text: &str) -> Result<Watchpoint, String> {
+    let (range, kind) = match text.rsplit_once(':') {
+        Some((range, kind)) => (range, kind.parse()?),
+        None => (text, WatchKind::Write),
+    };
+    let (location, len) = match range.split_once('+') {
+        Some((location, len)) => {
+            let len = len
+                .parse()
+                .ok()
+                .filter(|&len| len > 0)
+                .ok_or_else(|| format!("Invalid watchpoint length '{}'", len))?;
+            (location, len)
+        }
+        None => (range, 4),
+    };
+    let start = debug_info
+        .resolve(location)
+        .ok_or_else(|| format!("Unknown watchpoint location '{}'", location))?;
+    Ok(Watchpoint { start, len, kind })
+}
+
+pub fn add_breakpoint(
+    simulator: &mut Simulator,
+    (address, condition): (Address, Option<Expression>),
+) {
+    match condition {
+        Some(condition) => simulator.add_conditional_breakpoint(address, condition),
+        None => simulator.add_breakpoint(address),
+    };
+}
+
+/// Prints the general registers four to a row, then the PC, HI and LO.
+pub fn print_registers(simulator: &Simulator) {
+    let registers = simulator.registers();
+    for row in Register::ALL.chunks(4) {
+        let cells: Vec<_> = row
+            .iter()
+            .map(|&register| match simulator.mips64() {
+                true => format!(
+                    "{:>5} 0x{:016x}",
+                    register.name(),
+                    registers.get64(register)
+                ),
+                false => format!("{:>5} 0x{:08x}", register.name(), registers.get(register)),
+            })
+            .collect();
+        println!("{}", cells.join("  "));
+    }
+    println!(
+        "   pc 0x{:08x}     hi 0x{:08x}     lo 0x{:08x}",
+        simulator.pc().0,
+        simulator.registers().hi(),
+        simulator.registers().lo()
+    );
+}
+
+/// Prints the instruction the PC is on and its source line.
+fn print_location(simulator: &Simulator, debug_info: &DebugInfo) {
+    let pc = simulator.pc();
+    let mut instruction = simulator.current_instruction().map_or_else(
+        || "(no instruction)".to_string(),
+        |instruction| instruction.to_string(),
+    );
+    if let Some(target) = simulator.pending_jump() {
+        instruction.push_str(&format!("  [delay slot, then 0x{:08x}]", target.0));
+    }
+    match debug_info.line_of(pc) {
+        Some(line) => println!("0x{:08x} (line {}): {}", pc.0, line, instruction),
+        None => println!("0x{:08x}: {}", pc.0, instruction),
+    }
+}
+
+/// Prints the coprocessor 0 registers and every valid TLB entry.
+fn print_tlb(simulator: &Simulator) {
+    let cp0 = simulator.cp0();
+    println!(
+        "   Index 0x{:08x}  Random 0x{:08x} EntryHi 0x{:08x} EntryLo 0x{:08x}",
+        cp0.index, cp0.random, cp0.entry_hi, cp0.entry_lo
+    );
+    println!(
+        "  Status 0x{:08x}   Cause 0x{:08x}     EPC 0x{:08x} BadVAddr 0x{:08x}",
+        cp0.status, cp0.cause, cp0.epc, cp0.bad_vaddr
+    );
+    for (index, entry) in cp0.tlb().entries().iter().enumerate() {
+        if !entry.valid() {
+            continue;
+        }
+        let asid = match entry.global() {
+            true => "global".to_string(),
+            false => format!("asid {}", entry.asid()),
+        };
+        println!(
+            "  {:>2}: 0x{:05x}000 -> 0x{:05x}000 {:<8} {}",
+            index,
+            entry.vpn(),
+            entry.pfn(),
+            asid,
+            match entry.dirty() {
+                true => "writable",
+                false => "read-only",
+            }
+        );
+    }
+}
+
+/// Prints the innermost frame first: the PC, then each call site that is
+/// still waiting for its call to return, with `$sp` at the time of the call.
+fn print_backtrace(simulator: &Simulator, debug_info: &DebugInfo) {
+    let line = |address: Address| {
+        debug_info
+            .line_of(address)
+            .map_or_else(String::new, |line| format!(" (line {})", line))
+    };
+    let pc = simulator.pc();
+    println!(
+        "#0  0x{:08x} in {}{}",
+        pc.0,
+        symbolize(debug_info, pc),
+        line(pc)
+    );
+    let frames = simulator
+        .call_stack()
+        .map_or(&[][..], |stack| stack.frames());
+    for (number, frame) in frames.iter().rev().enumerate() {
+        println!(
+            "#{:<2} 0x{:08x} in {}{}, $sp 0x{:08x}",
+            number + 1,
+            frame.call_site.0,
+            symbolize(debug_info, frame.call_site),
+            line(frame.call_site),
+            frame.sp
+        );
+    }
+}
+
+/// Reports why execution stopped. Returns false once the program is over.
+fn report(stopped: Stopped) -> bool {
+    match stopped {
+        Stopped::Breakpoint(address) => {
+            println!("Breakpoint at 0x{:08x}", address.0);
+            true
+        }
+        Stopped::Watchpoint(hit) => {
+            println!("Watchpoint {}: {}", hit.watchpoint, hit);
+            true
+        }
+        Stopped::Halted(SimulatorError::Exit(code)) => {
+            println!("-- program is finished running (exit code {}) --", code);
+            false
+        }
+        Stopped::Halted(SimulatorError::NoMoreInstructions) => {
+            println!("-- program is finished running (dropped off bottom) --");
+            false
+        }
+        Stopped::Halted(SimulatorError::ReturnedFromMain) => {
+            println!("-- program is finished running (returned from main) --");
+            false
+        }
+        Stopped::Halted(err) => {
+            println!("Simulator Error: {}", err);
+            false
+        }
+    }
+}
+
+/// Handles `trace <file> [format]` and `trace off`, replacing any trace
+/// already being written.
+fn trace(simulator: &mut Simulator, argument: &str) -> Result<(), String> {
+    if let Some(Err(err)) = simulator.set_trace(None).map(Tracer::finish) {
+        println!("Could not write trace: {}", err);
+    }
+    let mut words = argument.split_whitespace();
+    let path = match words.next() {
+        None => return Err("Usage: trace <file> [text|jsonl] | trace off".to_string()),
+        Some("off") => return Ok(()),
+        Some(path) => Path::new(path),
+    };
+    let format = match words.next() {
+        Some(format) => format.parse()?,
+        None => TraceFormat::for_path(path),
+    };
+    let tracer = Tracer::create(path, format)
+        .map_err(|err| format!("Could not create trace {}: {}", path.display(), err))?;
+    simulator.set_trace(Some(tracer));
+    println!("Tracing to {} as {}", path.display(), format.name());
+    Ok(())
+}
+
+fn count(argument: Option<&str>) -> Result<u64, String> {
+    argument.map_or(Ok(1), |text| {
+        text.parse()
+            .map_err(|_| format!("Invalid count '{}'", text))
+    })
+}
+
+/// An interactive debugger on stdin: step forwards and backwards, set
+/// breakpoints and watchpoints, and inspect the machine. Program input is
+/// read from the same stdin when a syscall asks for it. `file` names the
+/// program's source in coverage data.
+pub fn run(simulator: &mut Simulator, debug_info: &DebugInfo, file: &str) {
+    println!("Type 'help' for a list of commands.");
+    print_location(simulator, debug_info);
+    let mut finished = false;
+    let stdin = io::stdin();
+    loop {
+        print!("(mips) ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let line = line.trim();
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (line, None),
+        };
+
+        let result = match command {
+            "" => Ok(()),
+            "s" | "step" => count(argument).map(|count| {
+                if finished {
+                    println!("The program has finished; step back or quit.");
+                    return;
+                }
+                for _ in 0..count {
+                    if let Err(err) = simulator.step() {
+                        finished = !report(Stopped::Halted(err));
+                        break;
+                    }
+                    if let Some(hit) = simulator.take_watch_hit() {
+                        report(Stopped::Watchpoint(hit));
+                        break;
+                    }
+                }
+                print_location(simulator, debug_info);
+            }),
+            "n" | "next" | "finish" => {
+                if finished {
+                    println!("The program has finished; step back or quit.");
+                } else {
+                    let result = match command {
+                        "finish" => simulator.step_out(),
+                        _ => simulator.step_over(),
+                    };
+                    if let Err(stopped) = result {
+                        finished = !report(stopped);
+                    }
+                    print_location(simulator, debug_info);
+                }
+                Ok(())
+            }
+            "b" | "back" => count(argument).map(|count| {
+                let undone = (0..count).take_while(|_| simulator.step_back()).count();
+                if undone > 0 {
+                    finished = false;
+                }
+                if (undone as u64) < count {
+                    println!(
+                        "Stepped back {} instructions; the journal is empty.",
+                        undone
+                    );
+                }
+                print_location(simulator, debug_info);
+            }),
+            "c" | "continue" => {
+                if finished {
+                    println!("The program has finished; step back or quit.");
+                } else {
+                    simulator.skip_breakpoint();
+                    finished = !report(simulator.run());
+                    print_location(simulator, debug_info);
+                }
+                Ok(())
+            }
+            "break" => parse_breakpoint(debug_info, argument.unwrap_or_default())
+                .map(|breakpoint| add_breakpoint(simulator, breakpoint)),
+            "watch" => parse_watchpoint(debug_info, argument.unwrap_or_default())
+                .map(|watchpoint| simulator.add_watchpoint(watchpoint)),
+            "delete" => {
+                let location = argument.unwrap_or_default();
+                match debug_info.resolve(location) {
+                    Some(address) if simulator.remove_breakpoint(address) => Ok(()),
+                    _ => Err(format!("No breakpoint at '{}'", location)),
+                }
+            }
+            "p" | "print" => argument
+                .unwrap_or_default()
+                .parse::<Expression>()
+                .and_then(|expression| {
+                    expression.evaluate(&Machine {
+                        simulator,
+                        debug_info,
+                    })
+                })
+                .map(|value| println!("0x{:08x} ({})", value, value as i32))
+                .map_err(|err| err.to_string()),
+            "profile" => match (argument, simulator.profile_mut()) {
+                (_, None) => Err("Profiling is off".to_string()),
+                (Some("reset"), Some(profile)) => {
+                    profile.clear();
+                    Ok(())
+                }
+                (argument, Some(profile)) => {
+                    let top = argument.map_or(Ok(profile::DEFAULT_TOP), |text| {
+                        text.parse()
+                            .map_err(|_| format!("Invalid count '{}'", text))
+                    });
+                    top.map(|top| profile::print_report(profile, debug_info, top, None))
+                }
+            },
+            "mix" => match simulator.instruction_mix() {
+                None => Err("Profiling is off".to_string()),
+                Some(instruction_mix) => {
+                    mix::print_report(&instruction_mix);
+                    Ok(())
+                }
+            },
+            "coverage" => match simulator.profile() {
+                None => Err("Profiling is off".to_string()),
+                Some(profile) => {
+                    let coverage = Coverage::new(profile, debug_info);
+                    match argument {
+                        Some(path) => coverage
+                            .save_lcov(Path::new(path), file)
+                            .map_err(|err| format!("Could not write {}: {}", path, err)),
+                        None => {
+                            coverage::print_summary(&coverage);
+                            Ok(())
+                        }
+                    }
+                }
+            },
+            "snapshot" => match argument {
+                None => Err("Usage: snapshot <file>".to_string()),
+                Some(path) => Snapshot::capture(simulator)
+                    .save(Path::new(path))
+                    .map_err(|err| format!("Could not write {}: {}", path, err)),
+            },
+            "diff" => match argument {
+                None => Err("Usage: diff <file>".to_string()),
+                Some(path) => Snapshot::load(Path::new(path))
+                    .map(|before| {
+                        print!("{}", snapshot::diff(&before, &Snapshot::capture(simulator)))
+                    })
+                    .map_err(|err| err.to_string()),
+            },
+            "trace" => trace(simulator, argument.unwrap_or_default()),
+            "heap" => {
+                let reachable = simulator.reachable_allocations();
+                heap::print_report(simulator.heap(), debug_info, &reachable);
+                Ok(())
+            }
+            "tlb" => {
+                print_tlb(simulator);
+                Ok(())
+            }
+            "bt" | "backtrace" => {
+                print_backtrace(simulator, debug_info);
+                Ok(())
+            }
+            "r" | "regs" => {
+                print_registers(simulator);
+                Ok(())
+            }
+            "h" | "help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            "q" | "quit" => return,
+            other => Err(format!("Unknown command '{}'; try 'help'", other)),
+        };
+        if let Err(err) = result {
+            println!("{}", err);
+        }
+    }
+}
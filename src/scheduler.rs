@@ -0,0 +1,207 @@
+use std::{
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::simulator::{Simulator, SimulatorError, Stopped};
+
+/// Upper bound on how many instructions a single batch may run, so a paused
+/// or retuned scheduler gets control back promptly even at high rates.
+pub const MAX_BATCH: u64 = 10_000;
+
+#[derive(Debug, Error)]
+pub enum SpeedError {
+    #[error("Invalid speed '{0}', expected a positive number of instructions per second")]
+    InvalidSpeed(String),
+    #[error("Invalid duration '{0}', expected e.g. '500ms', '5s' or '2m'")]
+    InvalidDuration(String),
+}
+
+/// Reads a wall-clock duration: a number of seconds, optionally suffixed
+/// with `ms`, `s` or `m`.
+pub fn parse_duration(s: &str) -> Result<Duration, SpeedError> {
+    let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let scale = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        _ => return Err(SpeedError::InvalidDuration(s.to_string())),
+    };
+    match number.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(Duration::from_secs_f64(value * scale)),
+        _ => Err(SpeedError::InvalidDuration(s.to_string())),
+    }
+}
+
+/// Target execution rate in instructions per second.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Speed {
+    #[default]
+    Unlimited,
+    Hz(f64),
+}
+
+impl FromStr for Speed {
+    type Err = SpeedError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "unlimited" || s == "max" {
+            return Ok(Speed::Unlimited);
+        }
+
+        match s.parse::<f64>() {
+            Ok(hz) if hz.is_finite() && hz > 0.0 => Ok(Speed::Hz(hz)),
+            _ => Err(SpeedError::InvalidSpeed(s.to_string())),
+        }
+    }
+}
+
+/// Paces execution to a [`Speed`] by telling the caller how many instructions
+/// are due, instead of spinning until the next one is.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    speed: Speed,
+    origin: Instant,
+    executed: u64,
+}
+
+impl Throttle {
+    pub fn new(speed: Speed) -> Self {
+        Self {
+            speed,
+            origin: Instant::now(),
+            executed: 0,
+        }
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Changes the rate, restarting the schedule so a slow rate doesn't
+    /// inherit a backlog from a faster one.
+    pub fn set_speed(&mut self, speed: Speed) {
+        *self = Self::new(speed);
+    }
+
+    /// Restarts the schedule from now, e.g. when resuming after a pause, so
+    /// the time spent paused doesn't turn into a burst of due instructions.
+    pub fn reset(&mut self) {
+        self.set_speed(self.speed);
+    }
+
+    /// How many instructions may run right now.
+    pub fn due(&self, now: Instant) -> u64 {
+        match self.speed {
+            Speed::Unlimited => MAX_BATCH,
+            Speed::Hz(hz) => {
+                let elapsed = now.saturating_duration_since(self.origin).as_secs_f64();
+                let allowed = (elapsed * hz) as u64 + 1;
+                allowed.saturating_sub(self.executed).min(MAX_BATCH)
+            }
+        }
+    }
+
+    /// How long until the next instruction becomes due.
+    pub fn time_until_due(&self, now: Instant) -> Duration {
+        match self.speed {
+            Speed::Unlimited => Duration::ZERO,
+            Speed::Hz(hz) => {
+                let next = self.origin + Duration::from_secs_f64(self.executed as f64 / hz);
+                next.saturating_duration_since(now)
+            }
+        }
+    }
+
+    pub fn record(&mut self, executed: u64) {
+        self.executed += executed;
+    }
+
+    /// Blocks until at least one instruction is due and returns how many are.
+    pub fn wait(&mut self) -> u64 {
+        loop {
+            let now = Instant::now();
+            let due = self.due(now);
+            if due > 0 {
+                return due;
+            }
+            thread::sleep(self.time_until_due(now));
+        }
+    }
+}
+
+/// A wall-clock limit on a run.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    start: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    /// A deadline `timeout` from now.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            timeout,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Fails with [`SimulatorError::Timeout`] once the deadline has passed.
+    pub fn check(&self) -> Result<(), SimulatorError> {
+        match self.start.elapsed() >= self.timeout {
+            true => Err(SimulatorError::Timeout(self.timeout)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Runs the simulator at the throttle's rate until it finishes, reaches a
+/// breakpoint or passes the deadline, like [`Simulator::run`].
+pub fn run_throttled(
+    simulator: &mut Simulator,
+    throttle: &mut Throttle,
+    deadline: Option<Deadline>,
+) -> Stopped {
+    if throttle.speed() == Speed::Unlimited && deadline.is_none() {
+        return simulator.run();
+    }
+
+    loop {
+        if let Some(Err(err)) = deadline.map(|deadline| deadline.check()) {
+            return Stopped::Halted(err);
+        }
+        let due = throttle.wait();
+        if let Err(stopped) = simulator.continue_for(due) {
+            return stopped;
+        }
+        throttle.record(due);
+    }
+}
+
+/// Executes up to `count` instructions like [`Simulator::run_for`], in
+/// batches so the deadline is checked between them.
+pub fn run_within(
+    simulator: &mut Simulator,
+    count: u64,
+    deadline: Option<Deadline>,
+) -> Result<(), SimulatorError> {
+    let mut remaining = count;
+    while remaining > 0 {
+        if let Some(deadline) = deadline {
+            deadline.check()?;
+        }
+        let batch = remaining.min(MAX_BATCH);
+        simulator.run_for(batch)?;
+        remaining -= batch;
+    }
+    Ok(())
+}
@@ -0,0 +1,277 @@
+use std::{
+    cell::{Ref, RefCell},
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+    rc::Rc,
+};
+
+use rhai::{AST, Dynamic, Engine, EvalAltResult, FnPtr, INT};
+use thiserror::Error;
+
+use crate::{
+    address::Address,
+    assembler::Instruction,
+    debug_info::DebugInfo,
+    registers::Register,
+    simulator::{Simulator, SimulatorError, Stopped},
+};
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("Could not read script: {0}")]
+    Io(#[from] io::Error),
+    #[error("Script error: {0}")]
+    Eval(String),
+}
+
+/// Callbacks a script registered.
+#[derive(Default)]
+struct Hooks {
+    breakpoints: BTreeMap<Address, Vec<FnPtr>>,
+    /// Breakpoints only the script asked for, which do not stop the run.
+    owned: BTreeSet<Address>,
+    syscalls: Vec<FnPtr>,
+    exits: Vec<FnPtr>,
+}
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// A Rhai script run alongside a program, for checks and automation:
+///
+/// ```text
+/// on_break("sort_done", || {
+///     let arr = label("arr");
+///     for i in 0..9 {
+///         assert(word(arr + i * 4) <= word(arr + i * 4 + 4), `arr[${i}] > arr[${i + 1}]`);
+///     }
+/// });
+/// on_syscall(|service| if service == 10 { print(`exiting after ${steps()} steps`) });
+/// on_exit(|code| assert(code == 0, "non-zero exit code"));
+/// ```
+///
+/// Callbacks read the machine with `reg("$t0")`, `hi()`, `lo()`, `pc()`,
+/// `steps()`, `word(address)`, `byte(address)` and `label(name)`. A failed
+/// `assert(condition, message)`, `fail(message)` or error in a callback is
+/// recorded in [`Script::failures`] and the run goes on.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    simulator: Rc<RefCell<Simulator>>,
+    hooks: Rc<RefCell<Hooks>>,
+    failures: Rc<RefCell<Vec<String>>>,
+}
+
+impl Script {
+    /// Reads the script at `path` and runs its top level, which registers
+    /// the callbacks.
+    pub fn load(
+        path: &Path,
+        simulator: Simulator,
+        debug_info: &DebugInfo,
+    ) -> std::result::Result<Script, ScriptError> {
+        let source = fs::read_to_string(path)?;
+        Script::new(&source, simulator, debug_info)
+    }
+
+    pub fn new(
+        source: &str,
+        simulator: Simulator,
+        debug_info: &DebugInfo,
+    ) -> std::result::Result<Script, ScriptError> {
+        let mut script = Script {
+            engine: Engine::new(),
+            ast: AST::empty(),
+            simulator: Rc::new(RefCell::new(simulator)),
+            hooks: Rc::default(),
+            failures: Rc::default(),
+        };
+        script.register_api(Rc::new(debug_info.clone()));
+        script.ast = script
+            .engine
+            .compile(source)
+            .map_err(|err| ScriptError::Eval(err.to_string()))?;
+        script
+            .engine
+            .run_ast(&script.ast)
+            .map_err(|err| ScriptError::Eval(err.to_string()))?;
+        Ok(script)
+    }
+
+    fn register_api(&mut self, debug_info: Rc<DebugInfo>) {
+        let engine = &mut self.engine;
+
+        let (simulator, hooks, symbols) = (
+            self.simulator.clone(),
+            self.hooks.clone(),
+            debug_info.clone(),
+        );
+        engine.register_fn(
+            "on_break",
+            move |location: &str, callback: FnPtr| -> Result<()> {
+                let address = symbols
+                    .resolve(location)
+                    .ok_or_else(|| format!("Unknown location '{}'", location))?;
+                let mut hooks = hooks.borrow_mut();
+                if simulator.borrow_mut().add_breakpoint(address) {
+                    hooks.owned.insert(address);
+                }
+                hooks.breakpoints.entry(address).or_default().push(callback);
+                Ok(())
+            },
+        );
+        let hooks = self.hooks.clone();
+        engine.register_fn("on_syscall", move |callback: FnPtr| {
+            hooks.borrow_mut().syscalls.push(callback);
+        });
+        let hooks = self.hooks.clone();
+        engine.register_fn("on_exit", move |callback: FnPtr| {
+            hooks.borrow_mut().exits.push(callback);
+        });
+
+        engine.register_fn("label", move |name: &str| -> Result<INT> {
+            let address = debug_info
+                .label(name)
+                .ok_or_else(|| format!("Unknown label '{}'", name))?;
+            Ok(address.0 as INT)
+        });
+        let simulator = self.simulator.clone();
+        engine.register_fn("reg", move |name: &str| -> Result<INT> {
+            let register: Register = name
+                .parse()
+                .map_err(|_| format!("Unknown register '{}'", name))?;
+            Ok(simulator.borrow().registers().get(register) as INT)
+        });
+        let simulator = self.simulator.clone();
+        engine.register_fn("hi", move || simulator.borrow().registers().hi() as INT);
+        let simulator = self.simulator.clone();
+        engine.register_fn("lo", move || simulator.borrow().registers().lo() as INT);
+        let simulator = self.simulator.clone();
+        engine.register_fn("pc", move || simulator.borrow().pc().0 as INT);
+        let simulator = self.simulator.clone();
+        engine.register_fn("steps", move || simulator.borrow().steps() as INT);
+        let simulator = self.simulator.clone();
+        engine.register_fn("word", move |address: INT| -> Result<INT> {
+            let word = simulator
+                .borrow()
+                .memory()
+                .read_word(Address(address as u32))
+                .ok_or_else(|| format!("Address 0x{:08x} is not mapped", address as u32))?;
+            Ok(word as INT)
+        });
+        let simulator = self.simulator.clone();
+        engine.register_fn("byte", move |address: INT| -> Result<INT> {
+            let byte = simulator
+                .borrow()
+                .memory()
+                .read_byte(Address(address as u32))
+                .ok_or_else(|| format!("Address 0x{:08x} is not mapped", address as u32))?;
+            Ok(byte as INT)
+        });
+
+        let failures = self.failures.clone();
+        engine.register_fn("fail", move |message: &str| {
+            failures.borrow_mut().push(message.to_string());
+        });
+        let failures = self.failures.clone();
+        engine.register_fn("assert", move |condition: bool, message: &str| {
+            if !condition {
+                failures.borrow_mut().push(message.to_string());
+            }
+        });
+    }
+
+    /// Calls every callback in `callbacks` with `args`, recording errors as
+    /// failures.
+    fn call<const N: usize>(&self, callbacks: &[FnPtr], args: [Dynamic; N]) {
+        for callback in callbacks {
+            if let Err(err) = callback.call::<Dynamic>(&self.engine, &self.ast, args.clone()) {
+                self.failures.borrow_mut().push(err.to_string());
+            }
+        }
+    }
+
+    /// Runs the program like [`Simulator::run`], calling the script's
+    /// callbacks on the way. Breakpoints only the script set do not stop
+    /// the run.
+    pub fn run(&mut self) -> Stopped {
+        loop {
+            let watch_syscalls = !self.hooks.borrow().syscalls.is_empty();
+            let stopped = match watch_syscalls {
+                true => self.run_to_syscall(),
+                false => self.simulator.borrow_mut().run(),
+            };
+            match stopped {
+                Stopped::Breakpoint(address) => {
+                    let callbacks = self.hooks.borrow().breakpoints.get(&address).cloned();
+                    let Some(callbacks) = callbacks else {
+                        return stopped;
+                    };
+                    self.call(&callbacks, []);
+                    if !self.hooks.borrow().owned.contains(&address) {
+                        return stopped;
+                    }
+                }
+                Stopped::Halted(
+                    SimulatorError::Exit(_)
+                    | SimulatorError::NoMoreInstructions
+                    | SimulatorError::ReturnedFromMain,
+                ) => {
+                    let code = match &stopped {
+                        Stopped::Halted(SimulatorError::Exit(code)) => *code,
+                        _ => 0,
+                    };
+                    let callbacks = self.hooks.borrow().exits.clone();
+                    self.call(&callbacks, [Dynamic::from(code as INT)]);
+                    return stopped;
+                }
+                stopped => return stopped,
+            }
+        }
+    }
+
+    /// Runs until a stop, calling the syscall callbacks with `$v0` before
+    /// every syscall.
+    fn run_to_syscall(&mut self) -> Stopped {
+        loop {
+            let service = {
+                let mut simulator = self.simulator.borrow_mut();
+                if let Some(address) = simulator.check_breakpoint() {
+                    return Stopped::Breakpoint(address);
+                }
+                match simulator.current_instruction() {
+                    Some(Instruction::SystemCall) => Some(simulator.registers().get(Register::V0)),
+                    _ => None,
+                }
+            };
+            if let Some(service) = service {
+                let callbacks = self.hooks.borrow().syscalls.clone();
+                self.call(&callbacks, [Dynamic::from(service as INT)]);
+            }
+            let mut simulator = self.simulator.borrow_mut();
+            // The breakpoint was checked above.
+            simulator.skip_breakpoint();
+            if let Err(stopped) = simulator.continue_for(1) {
+                return stopped;
+            }
+        }
+    }
+
+    /// What failed so far, in order.
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.borrow().clone()
+    }
+
+    pub fn simulator(&self) -> Ref<'_, Simulator> {
+        self.simulator.borrow()
+    }
+
+    /// Ends the script and hands back the simulator.
+    pub fn into_simulator(self) -> Simulator {
+        drop(self.engine);
+        match Rc::try_unwrap(self.simulator) {
+            Ok(simulator) => simulator.into_inner(),
+            Err(_) => unreachable!("only the engine shares the simulator"),
+        }
+    }
+}
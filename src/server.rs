@@ -0,0 +1,394 @@
+use std::{
+    io,
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use serde_json::{Map, Value, json};
+use tungstenite::{
+    Message,
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    http::StatusCode,
+};
+
+use crate::{
+    RuntimeArgs,
+    address::Address,
+    assembler::{Assembler, FileAccess, Program},
+    debug_info::DebugInfo,
+    diagnostic::Severity,
+    registers::RegisterId,
+    simulator::{Simulator, SimulatorError, Stopped},
+    syscall_io::SharedIo,
+};
+
+/// Address `serve` listens on unless told otherwise.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9001";
+/// Instructions `run` executes before giving up, unless asked otherwise.
+pub const DEFAULT_RUN_STEPS: u64 = 10_000_000;
+/// The most bytes one `memory` request may read.
+pub const MAX_MEMORY_READ: u64 = 64 * 1024;
+
+/// JSON-RPC error codes.
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A request that is valid but cannot be carried out now, e.g. stepping
+/// before anything is loaded.
+const NOT_READY: i64 = -32000;
+
+/// Why a request failed, as a JSON-RPC error code and message.
+type RequestError = (i64, String);
+
+fn invalid(message: impl Into<String>) -> RequestError {
+    (INVALID_PARAMS, message.into())
+}
+
+fn not_ready(message: impl Into<String>) -> RequestError {
+    (NOT_READY, message.into())
+}
+
+/// Reads an address given as a number, a `0x` hex string or a label.
+fn location(value: &Value, debug_info: Option<&DebugInfo>) -> Result<Address, RequestError> {
+    if let Some(number) = value.as_u64() {
+        return u32::try_from(number)
+            .map(Address)
+            .map_err(|_| invalid(format!("Address {} is out of range", number)));
+    }
+    let text = value
+        .as_str()
+        .ok_or_else(|| invalid("Expected an address or label"))?;
+    debug_info
+        .and_then(|debug_info| debug_info.resolve(text))
+        .ok_or_else(|| invalid(format!("Unknown location '{}'", text)))
+}
+
+/// What `serve` lets clients reach besides their own machines.
+#[derive(Debug, Clone, Default)]
+pub struct ServeOptions {
+    /// The only directory `.include` and `.incbin` may read from. Without
+    /// one, clients cannot read files at all.
+    pub include_dir: Option<PathBuf>,
+    /// Web page origins allowed to connect, e.g. `http://localhost:8080`.
+    /// Browsers let any page open a WebSocket to localhost, so others are
+    /// refused; clients that send no Origin are not browsers and may.
+    pub allowed_origins: Vec<String>,
+}
+
+/// One client's machine: the program it last assembled and the simulator
+/// running it.
+pub struct Session {
+    program: Option<Program>,
+    simulator: Option<Simulator>,
+    io: SharedIo,
+    /// What the client's `.include` and `.incbin` may read.
+    file_access: FileAccess,
+}
+
+impl Session {
+    pub fn new(file_access: FileAccess) -> Self {
+        Self {
+            program: None,
+            simulator: None,
+            io: SharedIo::new(),
+            file_access,
+        }
+    }
+
+    /// Answers one request, given its method and parameters.
+    pub fn handle(&mut self, method: &str, params: &Value) -> Result<Value, RequestError> {
+        match method {
+            "assemble" => self.assemble(params),
+            "load" => self.load(),
+            "step" => self.step(params),
+            "run" => self.run(params),
+            "registers" => self.registers(),
+            "memory" => self.memory(params),
+            "input" => self.input(params),
+            "breakpoints" => self.breakpoints(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method '{}'", method))),
+        }
+    }
+
+    fn simulator(&mut self) -> Result<&mut Simulator, RequestError> {
+        self.simulator
+            .as_mut()
+            .ok_or_else(|| not_ready("Nothing is loaded; send 'load' first"))
+    }
+
+    /// Assembles `source`, keeping the program for `load` if it assembled.
+    fn assemble(&mut self, params: &Value) -> Result<Value, RequestError> {
+        let source = params["source"]
+            .as_str()
+            .ok_or_else(|| invalid("Expected 'source'"))?;
+        let mut assembler = Assembler::new();
+        assembler.set_file_access(self.file_access.clone());
+        let result = assembler.assemble_source(source, &RuntimeArgs::default());
+        let diagnostics: Vec<Value> = assembler
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                json!({
+                    "severity": match diagnostic.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                    },
+                    "line": diagnostic.line,
+                    "column": diagnostic.column,
+                    "message": diagnostic.text(),
+                })
+            })
+            .collect();
+        let error = result.as_ref().err().map(ToString::to_string);
+        self.program = result.is_ok().then(|| assembler.finish());
+        Ok(json!({
+            "ok": error.is_none(),
+            "error": error,
+            "diagnostics": diagnostics,
+        }))
+    }
+
+    /// Starts the assembled program from the beginning, with an empty
+    /// console.
+    fn load(&mut self) -> Result<Value, RequestError> {
+        let program = self
+            .program
+            .clone()
+            .ok_or_else(|| not_ready("Nothing is assembled; send 'assemble' first"))?;
+        self.io = SharedIo::new();
+        let mut simulator = Simulator::new(program);
+        simulator.set_io(Box::new(self.io.clone()));
+        let instructions = simulator.instructions().len();
+        self.simulator = Some(simulator);
+        Ok(json!({ "instructions": instructions, "state": self.state(None) }))
+    }
+
+    /// Executes `count` instructions, one by default.
+    fn step(&mut self, params: &Value) -> Result<Value, RequestError> {
+        let count = params["count"].as_u64().unwrap_or(1);
+        let simulator = self.simulator()?;
+        simulator.skip_breakpoint();
+        let mut stopped = None;
+        for _ in 0..count {
+            let result = simulator.step();
+            if let Some(hit) = simulator.take_watch_hit() {
+                stopped = Some(Stopped::Watchpoint(hit));
+                break;
+            }
+            if let Err(err) = result {
+                stopped = Some(Stopped::Halted(err));
+                break;
+            }
+        }
+        Ok(self.state(stopped))
+    }
+
+    /// Runs until a breakpoint, the end of the program, a pending input
+    /// syscall or `max_steps` instructions.
+    fn run(&mut self, params: &Value) -> Result<Value, RequestError> {
+        let max_steps = params["max_steps"].as_u64().unwrap_or(DEFAULT_RUN_STEPS);
+        let simulator = self.simulator()?;
+        simulator.skip_breakpoint();
+        let stopped = simulator.continue_for(max_steps).err();
+        Ok(self.state(stopped))
+    }
+
+    fn registers(&mut self) -> Result<Value, RequestError> {
+        let registers = self.simulator()?.registers();
+        let values: Map<String, Value> = registers
+            .iter_all()
+            .map(|(id, value)| (id.name().to_string(), json!(value)))
+            .collect();
+        Ok(Value::Object(values))
+    }
+
+    /// Reads `length` bytes from `address`; unmapped bytes read as null.
+    fn memory(&mut self, params: &Value) -> Result<Value, RequestError> {
+        let length = params["length"].as_u64().unwrap_or(4);
+        if length > MAX_MEMORY_READ {
+            return Err(invalid(format!(
+                "At most {} bytes can be read at once",
+                MAX_MEMORY_READ
+            )));
+        }
+        let debug_info = self.program.as_ref().map(Program::debug_info);
+        let address = location(&params["address"], debug_info)?;
+        let memory = self.simulator()?.memory();
+        let bytes: Vec<Value> = (0..length as u32)
+            .map(|i| json!(memory.read_byte(address.wrapping_add(i))))
+            .collect();
+        Ok(json!({ "address": address.0, "bytes": bytes }))
+    }
+
+    /// Queues a line of console input for the program.
+    fn input(&mut self, params: &Value) -> Result<Value, RequestError> {
+        let line = params["line"]
+            .as_str()
+            .ok_or_else(|| invalid("Expected 'line'"))?;
+        self.io.lock().input.push_back(line.to_string());
+        Ok(Value::Null)
+    }
+
+    /// Replaces every breakpoint with `locations`.
+    fn breakpoints(&mut self, params: &Value) -> Result<Value, RequestError> {
+        let locations = params["locations"]
+            .as_array()
+            .ok_or_else(|| invalid("Expected 'locations'"))?;
+        let debug_info = self.program.as_ref().map(Program::debug_info);
+        let addresses = locations
+            .iter()
+            .map(|value| location(value, debug_info))
+            .collect::<Result<Vec<_>, _>>()?;
+        let simulator = self.simulator()?;
+        simulator.clear_breakpoints();
+        for &address in &addresses {
+            simulator.add_breakpoint(address);
+        }
+        Ok(json!(
+            addresses
+                .iter()
+                .map(|address| address.0)
+                .collect::<Vec<_>>()
+        ))
+    }
+
+    /// The machine's state after a step or run, with why it stopped and
+    /// the console output printed since the last request.
+    fn state(&mut self, stopped: Option<Stopped>) -> Value {
+        let output = std::mem::take(&mut self.io.lock().output);
+        let waiting_for_input = self.io.lock().waiting_for_input;
+        let (status, detail) = match stopped {
+            None => ("paused", Value::Null),
+            Some(Stopped::Breakpoint(address)) => ("breakpoint", json!(address.0)),
+            Some(Stopped::Watchpoint(hit)) => ("watchpoint", json!(hit.to_string())),
+            Some(Stopped::Halted(SimulatorError::InputPending)) => ("input", Value::Null),
+            Some(Stopped::Halted(SimulatorError::Exit(code))) => ("exited", json!(code)),
+            Some(Stopped::Halted(SimulatorError::NoMoreInstructions)) => {
+                ("dropped_off_bottom", Value::Null)
+            }
+            Some(Stopped::Halted(SimulatorError::ReturnedFromMain)) => {
+                ("returned_from_main", Value::Null)
+            }
+            Some(Stopped::Halted(err)) => ("error", json!(err.to_string())),
+        };
+        let (pc, steps) = self.simulator.as_ref().map_or((0, 0), |simulator| {
+            (
+                simulator.registers().read(RegisterId::Pc),
+                simulator.steps(),
+            )
+        });
+        json!({
+            "status": status,
+            "detail": detail,
+            "pc": pc,
+            "steps": steps,
+            "output": output,
+            "waiting_for_input": waiting_for_input,
+        })
+    }
+}
+
+/// Answers one JSON-RPC message, or None for a notification.
+fn respond(session: &mut Session, text: &str) -> Option<Value> {
+    let message: Value = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(err) => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": format!("Invalid JSON: {}", err) },
+            }));
+        }
+    };
+    let method = message["method"].as_str().unwrap_or_default();
+    let reply = session.handle(method, &message["params"]);
+    let id = message.get("id")?;
+    Some(match reply {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    })
+}
+
+/// Accepts a handshake only from an allowed origin, or from a client that
+/// is not a browser and sends none.
+struct OriginCheck<'a>(&'a ServeOptions);
+
+impl Callback for OriginCheck<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let Some(origin) = request.headers().get("Origin") else {
+            return Ok(response);
+        };
+        let origin = origin.to_str().unwrap_or_default();
+        if self
+            .0
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed == origin)
+        {
+            return Ok(response);
+        }
+        let mut error = ErrorResponse::new(Some(format!("Origin '{}' is not allowed", origin)));
+        *error.status_mut() = StatusCode::FORBIDDEN;
+        Err(error)
+    }
+}
+
+/// Serves one client until it disconnects.
+fn serve_client(stream: TcpStream, options: &ServeOptions) -> Result<(), Box<tungstenite::Error>> {
+    let mut socket =
+        tungstenite::accept_hdr(stream, OriginCheck(options)).map_err(|err| match err {
+            tungstenite::HandshakeError::Failure(err) => err,
+            tungstenite::HandshakeError::Interrupted(_) => {
+                tungstenite::Error::Io(io::ErrorKind::WouldBlock.into())
+            }
+        })?;
+    let file_access = match &options.include_dir {
+        Some(directory) => FileAccess::Within(directory.clone()),
+        None => FileAccess::Nothing,
+    };
+    let mut session = Session::new(file_access);
+    loop {
+        let text = match socket.read()? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(()),
+            _ => continue,
+        };
+        if let Some(response) = respond(&mut session, &text) {
+            socket.send(Message::text(response.to_string()))?;
+        }
+    }
+}
+
+/// Accepts WebSocket clients on `address`, each with a machine of its own
+/// driven by JSON-RPC requests: `assemble`, `load`, `step`, `run`,
+/// `registers`, `memory`, `input` and `breakpoints`.
+pub fn serve(address: &str, options: ServeOptions) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("Listening on ws://{}", listener.local_addr()?);
+    let options = Arc::new(options);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let options = options.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().ok();
+            if let Err(err) = serve_client(stream, &options)
+                && !matches!(
+                    *err,
+                    tungstenite::Error::ConnectionClosed
+                        | tungstenite::Error::AlreadyClosed
+                        | tungstenite::Error::Protocol(
+                            tungstenite::error::ProtocolError::ResetWithoutClosingHandshake
+                        )
+                )
+            {
+                println!("Client {:?}: {}", peer, err);
+            }
+        });
+    }
+    Ok(())
+}
@@ -1,14 +1,41 @@
 use std::{
-    collections::HashMap,
-    time::{SystemTime, SystemTimeError, UNIX_EPOCH},
+    collections::BTreeMap,
+    time::{Duration, SystemTime, SystemTimeError, UNIX_EPOCH},
 };
 
 use thiserror::Error;
 
 use crate::{
     address::Address,
-    assembler::{BASE_DATA_ADDR, Instruction, MEMORY_SIZE},
+    assembler::{BASE_DATA_ADDR, BASE_TEXT_ADDR, Instruction, InstructionClass, Program},
+    backend::{self, Backend, ThreadedCode},
+    branch_predictor::BranchPredictor,
+    cache::{AccessKind, Cache},
+    cache_hierarchy::CacheHierarchy,
+    call_stack::{CallStack, Frame},
+    cp0::{Coprocessor0, Exception, ExceptionCode},
+    cp1::Coprocessor1,
+    expression::Expression,
+    heap::{Heap, HeapError},
+    journal::{Journal, Undo},
+    memory::{
+        EXIT_ADDRESS, Endianness, INITIAL_GP, INITIAL_SP, KERNEL_BASE, MAPPED_BASE, Memory,
+        STACK_TOP,
+    },
+    mix::InstructionMix,
+    mmio::KeyboardDisplay,
+    pipeline::Pipeline,
+    plugin,
+    profile::Profile,
     registers::{Register, RegisterError, RegisterFile},
+    stack_check::StackChecker,
+    stepping::UntilReturn,
+    syscall_io::{StdIo, SyscallIo},
+    syscall_trace::{SyscallCall, SyscallTracer},
+    timing::CpiModel,
+    trace::{MemoryAccess, Tracer},
+    uninit::UninitChecker,
+    watchpoint::{WatchHit, Watchpoint},
 };
 
 #[derive(Debug, Error)]
@@ -21,57 +48,1018 @@ pub enum SimulatorError {
     Exit(u32),
     #[error("No more instructions")]
     NoMoreInstructions,
+    /// `main` returned to the address `$ra` started with.
+    #[error("Returned from main")]
+    ReturnedFromMain,
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Waiting for input")]
+    InputPending,
     #[error("Wrong input type: {0}")]
     WrongInputType(String),
     #[error("Invalid system time: {0}")]
     InvalidSystemTime(#[from] SystemTimeError),
+    #[error("Heap error: {0}")]
+    HeapError(#[from] HeapError),
+    #[error("Unhandled exception: {0}")]
+    UnhandledException(Exception),
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[error("Reserved instruction: {0} needs MIPS64 mode")]
+    ReservedInstruction(Instruction),
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
 }
 
+#[derive(Debug, Error)]
+pub enum PatchError {
+    #[error("0x{0:08x} is in the text segment, which is write-protected")]
+    TextProtected(u32),
+    #[error("0x{0:08x} is not mapped")]
+    Unmapped(u32),
+    #[error("0x{1:08x} at 0x{0:08x} is not a supported instruction")]
+    Undecodable(u32, u32),
+}
+
+/// Why [`Simulator::run`] returned.
 #[derive(Debug)]
+pub enum Stopped {
+    /// The PC reached a breakpoint; the instruction there has not run yet.
+    Breakpoint(Address),
+    /// The last instruction touched watched memory.
+    Watchpoint(WatchHit),
+    /// The program finished or failed.
+    Halted(SimulatorError),
+}
+
+impl From<SimulatorError> for Stopped {
+    fn from(err: SimulatorError) -> Self {
+        Stopped::Halted(err)
+    }
+}
+
+/// Which instructions take effect one instruction late, as on the
+/// original MIPS R2000 and in SPIM's bare-machine mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DelaySlots {
+    /// Branches and jumps execute the instruction after them, the delay
+    /// slot, before continuing at their target.
+    pub branches: bool,
+    /// A loaded register only receives its value after the next
+    /// instruction, which still sees the old one.
+    pub loads: bool,
+}
+
+/// A jump or load an instruction left for after the next one, in
+/// delay-slot mode.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Delayed {
+    jump: Option<Address>,
+    load: Option<(Register, u64)>,
+}
+
 pub struct Simulator {
-    memory: [u8; MEMORY_SIZE],
-    registers: RegisterFile,
-    instructions: HashMap<Address, Instruction>,
-    pc: Address,
+    memory: Memory,
+    keyboard_display: KeyboardDisplay,
+    heap: Heap,
+    pub(crate) registers: RegisterFile,
+    cp0: Coprocessor0,
+    cp1: Coprocessor1,
+    instructions: Vec<Instruction>,
+    steps: u64,
+    delay_slots: DelaySlots,
+    /// Set by a taken branch or jump to where execution continues.
+    pub(crate) jump: Option<Address>,
+    /// Set by a load to the register it writes and the value loaded.
+    load: Option<(Register, u64)>,
+    delayed: Delayed,
+    /// Execute the 64-bit instructions instead of treating them as reserved.
+    mips64: bool,
+    /// Translate user-segment data addresses through the TLB.
+    virtual_memory: bool,
+    /// Where exceptions transfer control; without one they halt the run.
+    exception_handler: Option<Address>,
+    /// Set by a load or store that raised an exception, until the
+    /// instruction finishes.
+    exception: Option<Exception>,
+    /// Whether the last instruction executed called a subroutine.
+    called: bool,
+    /// Breakpoints and the condition, if any, under which each stops.
+    breakpoints: BTreeMap<Address, Option<Expression>>,
+    /// Breakpoint the next run starts past instead of stopping at again.
+    resume_at: Option<Address>,
+    watchpoints: Vec<Watchpoint>,
+    /// Set by a load or store that hit a watchpoint, until a run reports it.
+    watch_hit: Option<WatchHit>,
+    journal: Journal,
+    call_stack: Option<CallStack>,
+    trace: Option<Tracer>,
+    syscall_trace: Option<SyscallTracer>,
+    profile: Option<Profile>,
+    predictor: Option<BranchPredictor>,
+    stack_check: Option<StackChecker>,
+    uninit_check: Option<UninitChecker>,
+    timing: Option<CpiModel>,
+    threaded: Option<ThreadedCode>,
+    pipeline: Option<Pipeline>,
+    cache: Option<Cache>,
+    hierarchy: Option<CacheHierarchy>,
+    io: Box<dyn SyscallIo>,
 }
 
 impl Simulator {
-    pub fn new(
-        instructions: HashMap<Address, Instruction>,
-        memory: Vec<u8>,
-        entry: Address,
-    ) -> Simulator {
-        let mut mem_array = [0u8; MEMORY_SIZE];
-        let len = memory.len().min(MEMORY_SIZE);
-        mem_array[..len].copy_from_slice(&memory[..len]);
+    pub fn new(program: Program) -> Simulator {
+        let endianness = program.endianness();
+        let (instructions, memory, entry) = program.into_parts();
+        let mut memory = Memory::new(memory);
+        memory.set_endianness(endianness);
+
+        let mut registers = RegisterFile::default();
+        registers.set(Register::SP, INITIAL_SP.0);
+        registers.set(Register::GP, INITIAL_GP.0);
+        registers.set(Register::RA, EXIT_ADDRESS.0);
+        registers.set_pc(entry);
 
         Simulator {
-            memory: mem_array,
-            registers: RegisterFile::default(),
+            memory,
+            keyboard_display: KeyboardDisplay::default(),
+            heap: Heap::default(),
+            registers,
+            cp0: Coprocessor0::default(),
+            cp1: Coprocessor1::default(),
             instructions,
-            pc: entry,
+            steps: 0,
+            delay_slots: DelaySlots::default(),
+            jump: None,
+            load: None,
+            delayed: Delayed::default(),
+            mips64: false,
+            virtual_memory: false,
+            exception_handler: None,
+            exception: None,
+            called: false,
+            breakpoints: BTreeMap::new(),
+            resume_at: None,
+            watchpoints: Vec::new(),
+            watch_hit: None,
+            journal: Journal::new(0),
+            call_stack: None,
+            trace: None,
+            syscall_trace: None,
+            profile: None,
+            predictor: None,
+            stack_check: None,
+            uninit_check: None,
+            timing: None,
+            threaded: None,
+            pipeline: None,
+            cache: None,
+            hierarchy: None,
+            io: Box::new(StdIo),
+        }
+    }
+
+    /// Redirects console syscalls, which default to the process's stdin/stdout.
+    pub fn set_io(&mut self, io: Box<dyn SyscallIo>) {
+        self.io = io;
+    }
+
+    /// Selects the execution backend, compiling the text segment if needed.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.threaded = match backend {
+            Backend::Interpreter => None,
+            Backend::Threaded => Some(backend::compile(&self.instructions)),
+        };
+    }
+
+    /// Selects which instructions have delay slots; by default none do, as
+    /// in SPIM.
+    pub fn set_delay_slots(&mut self, delay_slots: DelaySlots) {
+        self.delay_slots = delay_slots;
+    }
+
+    pub fn delay_slots(&self) -> DelaySlots {
+        self.delay_slots
+    }
+
+    /// Enables the doubleword instructions. Addresses stay 32 bits wide, as
+    /// in MIPS64's compatibility address space.
+    pub fn set_mips64(&mut self, enabled: bool) {
+        self.mips64 = enabled;
+    }
+
+    pub fn mips64(&self) -> bool {
+        self.mips64
+    }
+
+    /// Translates data addresses below `0x80000000` through the TLB in
+    /// coprocessor 0, raising TLB exceptions for unmapped pages. Kernel
+    /// addresses, and instruction fetches, stay untranslated.
+    pub fn set_virtual_memory(&mut self, enabled: bool) {
+        self.virtual_memory = enabled;
+    }
+
+    pub fn virtual_memory(&self) -> bool {
+        self.virtual_memory
+    }
+
+    /// Makes exceptions jump to `handler`, which returns with `eret`,
+    /// instead of halting the run with [`SimulatorError::UnhandledException`].
+    pub fn set_exception_handler(&mut self, handler: Option<Address>) {
+        self.exception_handler = handler;
+    }
+
+    pub fn exception_handler(&self) -> Option<Address> {
+        self.exception_handler
+    }
+
+    /// The physical address a data access to `address` goes to.
+    pub fn translate(&self, address: Address, write: bool) -> Result<Address, Exception> {
+        match self.virtual_memory && address < KERNEL_BASE {
+            true => self.cp0.translate(address, write),
+            false => Ok(address),
+        }
+    }
+
+    /// Where a branch or jump before the instruction at the PC continues
+    /// once it has executed, if the PC is in a delay slot.
+    pub fn pending_jump(&self) -> Option<Address> {
+        self.delayed.jump
+    }
+
+    /// Attaches a pipeline timing model that is fed every instruction from
+    /// now on, or detaches it with `None`. While attached, execution goes
+    /// through the interpreter so no instruction is missed.
+    pub fn set_pipeline(&mut self, pipeline: Option<Pipeline>) {
+        self.pipeline = pipeline;
+    }
+
+    pub fn pipeline(&self) -> Option<&Pipeline> {
+        self.pipeline.as_ref()
+    }
+
+    /// Attaches a cache model that sees every instruction fetch and data
+    /// access from now on, or detaches it with `None`. Like the pipeline
+    /// model, it keeps execution on the interpreter while attached.
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.cache = cache;
+    }
+
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// Attaches split L1 caches and an optional L2 that see every fetch
+    /// and data access from now on, alongside any [`Simulator::set_cache`]
+    /// model, or detaches them with `None`.
+    pub fn set_cache_hierarchy(&mut self, hierarchy: Option<CacheHierarchy>) {
+        self.hierarchy = hierarchy;
+    }
+
+    pub fn cache_hierarchy(&self) -> Option<&CacheHierarchy> {
+        self.hierarchy.as_ref()
+    }
+
+    fn cache_access(&mut self, kind: AccessKind, address: Address) {
+        if let Some(cache) = &mut self.cache {
+            cache.access(kind, address);
         }
+        if let Some(hierarchy) = &mut self.hierarchy {
+            hierarchy.access(kind, address);
+        }
+    }
+
+    /// Whether a timing model or the call stack needs to see each
+    /// instruction as it executes.
+    fn observed(&self) -> bool {
+        self.pipeline.is_some()
+            || self.cache.is_some()
+            || self.hierarchy.is_some()
+            || self.call_stack.is_some()
+            || self.trace.is_some()
+            || self.profile.is_some()
+            || self.predictor.is_some()
+            || self.virtual_memory
+            || self.stack_check.is_some()
+            || self.uninit_check.is_some()
+            || self.timing.is_some()
+    }
+
+    /// Starts or stops checking loads, stores, calls and returns for stack
+    /// misuse. Checking keeps runs off the threaded backend.
+    pub fn set_stack_checking(&mut self, enabled: bool) {
+        self.stack_check = enabled.then(StackChecker::default);
+    }
+
+    pub fn stack_checker(&self) -> Option<&StackChecker> {
+        self.stack_check.as_ref()
     }
 
-    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), SimulatorError> {
+    /// Starts or stops reporting loads of memory the program never wrote.
+    /// The assembled data segment counts as written. Checking keeps runs
+    /// off the threaded backend.
+    pub fn set_uninit_checking(&mut self, enabled: bool) {
+        let data_end = BASE_DATA_ADDR + self.memory.data().len();
+        let data = BASE_DATA_ADDR.0..data_end.0;
+        self.uninit_check = enabled.then(|| UninitChecker::new(data));
+    }
+
+    pub fn uninit_checker(&self) -> Option<&UninitChecker> {
+        self.uninit_check.as_ref()
+    }
+
+    /// Runs `predictor` alongside execution, or stops predicting with None.
+    /// Like the timing models it keeps runs off the threaded backend.
+    pub fn set_branch_predictor(&mut self, predictor: Option<BranchPredictor>) {
+        self.predictor = predictor;
+    }
+
+    pub fn branch_predictor(&self) -> Option<&BranchPredictor> {
+        self.predictor.as_ref()
+    }
+
+    /// Charges each executed instruction a fixed cost for its class, or
+    /// stops with None. Like the other timing models it keeps runs off the
+    /// threaded backend.
+    pub fn set_timing_model(&mut self, timing: Option<CpiModel>) {
+        self.timing = timing;
+    }
+
+    pub fn timing_model(&self) -> Option<&CpiModel> {
+        self.timing.as_ref()
+    }
+
+    /// Starts counting executions per instruction from zero, or stops with
+    /// false. Profiling keeps runs off the threaded backend.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(|| Profile::new(self.instructions.len()));
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn profile_mut(&mut self) -> Option<&mut Profile> {
+        self.profile.as_mut()
+    }
+
+    /// Executions per mnemonic and class so far, while profiling.
+    pub fn instruction_mix(&self) -> Option<InstructionMix> {
+        self.profile
+            .as_ref()
+            .map(|profile| InstructionMix::new(profile, &self.instructions))
+    }
+
+    /// Starts writing a record per executed instruction to `trace`, or stops
+    /// with None, returning the previous tracer so it can be finished.
+    /// Tracing keeps runs off the threaded backend.
+    pub fn set_trace(&mut self, trace: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.trace, trace)
+    }
+
+    /// Starts or stops logging every syscall with its arguments and
+    /// results.
+    pub fn set_syscall_trace(&mut self, trace: Option<SyscallTracer>) {
+        self.syscall_trace = trace;
+    }
+
+    pub fn trace(&self) -> Option<&Tracer> {
+        self.trace.as_ref()
+    }
+
+    /// Starts or stops reconstructing the call stack from the calls and
+    /// returns executed, which keeps runs off the threaded backend. Tracking
+    /// starts with an empty stack.
+    pub fn set_call_tracking(&mut self, enabled: bool) {
+        self.call_stack = enabled.then(CallStack::default);
+    }
+
+    /// The calls not yet returned from, if tracking is on.
+    pub fn call_stack(&self) -> Option<&CallStack> {
+        self.call_stack.as_ref()
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn keyboard_display(&self) -> &KeyboardDisplay {
+        &self.keyboard_display
+    }
+
+    pub fn keyboard_display_mut(&mut self) -> &mut KeyboardDisplay {
+        &mut self.keyboard_display
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// Which sbrk allocations the program can still reach from its
+    /// registers, static data or stack; see [`Heap::reachable`].
+    pub fn reachable_allocations(&self) -> Vec<bool> {
+        let registers = Register::ALL.map(|register| self.registers.get(register));
+        let data = (0..self.memory.data().len() / 4)
+            .filter_map(|i| self.memory.read_word(BASE_DATA_ADDR + i * 4));
+        let sp = self.registers.get(Register::SP) & !3;
+        let stack = (sp..STACK_TOP.0)
+            .step_by(4)
+            .filter_map(|address| self.memory.read_word(Address(address)));
+        let roots = registers
+            .into_iter()
+            .chain([self.registers.hi(), self.registers.lo()])
+            .chain(data)
+            .chain(stack);
+        self.heap.reachable(roots, &self.memory)
+    }
+
+    /// Translates a data access, recording the exception if it faults.
+    fn translate_access(&mut self, address: Address, write: bool) -> Option<Address> {
+        match self.translate(address, write) {
+            Ok(physical) => Some(physical),
+            Err(exception) => {
+                self.exception.get_or_insert(exception);
+                None
+            }
+        }
+    }
+
+    /// Records an address error for an access to `address`, unless the
+    /// instruction already raised an exception.
+    fn address_error(&mut self, code: ExceptionCode, address: Address) {
+        self.exception.get_or_insert(Exception { code, address });
+    }
+
+    /// Raises a trap exception at the current instruction if `condition`
+    /// holds.
+    fn trap(&mut self, condition: bool) {
+        if condition {
+            let address = self.registers.pc();
+            self.exception.get_or_insert(Exception {
+                code: ExceptionCode::Trap,
+                address,
+            });
+        }
+    }
+
+    /// Raises an overflow exception at the current instruction, leaving the
+    /// destination register unchanged, or without an exception handler
+    /// stops the run with [`SimulatorError::ArithmeticOverflow`].
+    pub(crate) fn overflow(&mut self) -> Result<(), SimulatorError> {
+        if self.exception_handler.is_none() {
+            return Err(SimulatorError::ArithmeticOverflow);
+        }
+        let address = self.registers.pc();
+        self.exception.get_or_insert(Exception {
+            code: ExceptionCode::Overflow,
+            address,
+        });
+        Ok(())
+    }
+
+    /// Loads a `size`-byte value, zero-extended, as the program sees it,
+    /// routing memory-mapped device registers to their device. With virtual
+    /// memory on, a load from an unmapped page reads nothing and raises an
+    /// exception, as does one from an unaligned address or from below the
+    /// mapped memory.
+    pub fn load(&mut self, address: Address, size: u32) -> Option<u32> {
+        if !address.0.is_multiple_of(size) {
+            self.address_error(ExceptionCode::AddressErrorLoad, address);
+            return None;
+        }
+        let value = if KeyboardDisplay::contains(address) {
+            let mask = u32::MAX >> (32 - size * 8);
+            Some(self.keyboard_display.read_word(address) & mask)
+        } else {
+            if let Some(check) = &mut self.stack_check {
+                let sp = self.registers.get(Register::SP);
+                check.load(self.registers.pc(), address, sp, self.heap.brk());
+            }
+            let physical = self.translate_access(address, false)?;
+            if let Some(check) = &mut self.uninit_check {
+                check.load(self.registers.pc(), physical, size);
+            }
+            self.cache_access(AccessKind::Load, physical);
+            let value = self.memory.read(physical, size);
+            match value {
+                Some(value) => self.watch(address, size, false, value, value),
+                None => self.address_error(ExceptionCode::AddressErrorLoad, address),
+            }
+            value
+        };
+        if let (Some(trace), Some(value)) = (&mut self.trace, value) {
+            trace.access(MemoryAccess {
+                address,
+                size,
+                write: false,
+                value,
+            });
+        }
+        value
+    }
+
+    /// Stores the low `size` bytes of `value` as the program sees it,
+    /// routing memory-mapped device registers to their device. With virtual
+    /// memory on, a store to an unmapped or clean page writes nothing and
+    /// raises an exception, as does one to an unaligned address or below the
+    /// mapped memory.
+    pub fn store(&mut self, address: Address, size: u32, value: u32) {
+        if !address.0.is_multiple_of(size) {
+            self.address_error(ExceptionCode::AddressErrorStore, address);
+            return;
+        }
+        let value = value & (u32::MAX >> (32 - size * 8));
+        if KeyboardDisplay::contains(address) {
+            self.keyboard_display.write_word(address, value);
+            self.trace_store(address, size, value);
+            return;
+        }
+        if let Some(check) = &mut self.stack_check {
+            check.store(self.registers.pc(), address);
+        }
+        let Some(physical) = self.translate_access(address, true) else {
+            return;
+        };
+        if physical < MAPPED_BASE {
+            self.address_error(ExceptionCode::AddressErrorStore, address);
+            return;
+        }
+        if let Some(check) = &mut self.uninit_check {
+            check.store(physical, size);
+        }
+        self.cache_access(AccessKind::Store, physical);
+        if !self.watchpoints.is_empty() {
+            let old = self.memory.read(physical, size).unwrap_or(0);
+            self.watch(address, size, true, old, value);
+        }
+        self.memory.write(physical, size, value);
+        self.trace_store(address, size, value);
+    }
+
+    /// Records a store that went through, like `load` records only loads
+    /// that read something.
+    fn trace_store(&mut self, address: Address, size: u32, value: u32) {
+        if let Some(trace) = &mut self.trace {
+            trace.access(MemoryAccess {
+                address,
+                size,
+                write: true,
+                value,
+            });
+        }
+    }
+
+    /// Loads the doubleword `ld` reads as two words, the more significant
+    /// one first in big-endian memory. An address that isn't 8-byte aligned
+    /// raises an exception and reads nothing.
+    fn load_double(&mut self, address: Address) -> Option<u64> {
+        if !address.0.is_multiple_of(8) {
+            self.address_error(ExceptionCode::AddressErrorLoad, address);
+            return None;
+        }
+        let first = self.load(address, 4)? as u64;
+        let second = self.load(address + 4_u32, 4)? as u64;
+        Some(match self.memory.endianness() {
+            Endianness::Little => second << 32 | first,
+            Endianness::Big => first << 32 | second,
+        })
+    }
+
+    /// Stores the doubleword `sd` writes as two words, the same way
+    /// [`Simulator::load_double`] reads it back.
+    fn store_double(&mut self, address: Address, value: u64) {
+        if !address.0.is_multiple_of(8) {
+            self.address_error(ExceptionCode::AddressErrorStore, address);
+            return;
+        }
+        let (high, low) = ((value >> 32) as u32, value as u32);
+        let (first, second) = match self.memory.endianness() {
+            Endianness::Little => (low, high),
+            Endianness::Big => (high, low),
+        };
+        self.store(address, 4, first);
+        if self.exception.is_none() {
+            self.store(address + 4_u32, 4, second);
+        }
+    }
+
+    /// Which byte of its word `address` is, counting up from the least
+    /// significant, in the memory's byte order.
+    fn significance(&self, address: Address) -> u32 {
+        match self.memory.endianness() {
+            Endianness::Little => address.0 & 3,
+            Endianness::Big => 3 - (address.0 & 3),
+        }
+    }
+
+    /// Stores the bytes `swl` and `swr` write: the byte of the word at
+    /// `address` with significance `i` gets byte `i + lift` of `value`,
+    /// where there is one.
+    fn store_partial(&mut self, address: Address, value: u32, lift: i32) {
+        let aligned = address.0 & !3;
+        for offset in 0..4 {
+            let byte = self.significance(Address(aligned + offset)) as i32 + lift;
+            if (0..4).contains(&byte) {
+                self.store(Address(aligned + offset), 1, value >> (byte * 8));
+            }
+        }
+    }
+
+    /// Records a hit if a `size`-byte access at `address` is watched.
+    fn watch(&mut self, address: Address, size: u32, write: bool, old: u32, new: u32) {
+        if self.watch_hit.is_some() {
+            return;
+        }
+        if let Some(&watchpoint) = self
+            .watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.triggered_by(address, size, write))
+        {
+            self.watch_hit = Some(WatchHit {
+                watchpoint,
+                pc: self.registers.pc(),
+                address,
+                write,
+                old,
+                new,
+            });
+        }
+    }
+
+    /// Starts recording how to undo what happens next.
+    fn begin_undo(&mut self) -> Undo {
+        self.memory.start_log();
+        Undo {
+            registers: self.registers.clone(),
+            cp0: (self.virtual_memory
+                || self
+                    .current_instruction()
+                    .is_some_and(|instruction| instruction.uses_cp0()))
+            .then(|| self.cp0.clone()),
+            cp1: self.cp1.clone(),
+            steps: self.steps,
+            delayed: self.delayed,
+            heap: self.heap.history().len(),
+            memory: Vec::new(),
+            call_stack: self.call_stack.clone().filter(|_| {
+                self.current_instruction()
+                    .is_some_and(|instruction| instruction.is_call() || instruction.is_return())
+            }),
+        }
+    }
+
+    /// Finishes recording `undo`, collecting the memory written since
+    /// [`Simulator::begin_undo`].
+    fn end_undo(&mut self, undo: &mut Undo) {
+        undo.memory = self.memory.take_log();
+    }
+
+    /// Puts the machine back as it was when `undo` began.
+    fn undo(&mut self, undo: Undo) {
+        for &(address, byte) in undo.memory.iter().rev() {
+            self.memory.write_byte(address, byte);
+        }
+        self.heap.truncate(undo.heap);
+        self.registers = undo.registers;
+        if let Some(cp0) = undo.cp0 {
+            self.cp0 = cp0;
+        }
+        self.cp1 = undo.cp1;
+        self.steps = undo.steps;
+        self.delayed = undo.delayed;
+        if let Some(call_stack) = undo.call_stack {
+            self.call_stack = Some(call_stack);
+        }
+    }
+
+    /// How many instructions to remember for [`Simulator::step_back`]; zero,
+    /// the default, turns recording off and keeps runs fast.
+    pub fn set_journal_depth(&mut self, depth: usize) {
+        self.journal.set_depth(depth);
+    }
+
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Undoes the most recent instruction, putting back the registers,
+    /// PC, memory and heap it changed. Console output and consumed input
+    /// stay as they are. Returns false when there is nothing to undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(undo) = self.journal.pop() else {
+            return false;
+        };
+        self.undo(undo);
+        true
+    }
+
+    /// Executes one instruction, recording how to undo it if it retired.
+    fn step_journaled(&mut self) -> Result<(), SimulatorError> {
+        let mut undo = self.begin_undo();
+        let result = self.execute_for(1);
+        self.end_undo(&mut undo);
+        if self.steps != undo.steps {
+            self.journal.push(undo);
+        }
+        result
+    }
+
+    /// Writes `bytes` starting at `address` on behalf of a debugger, without
+    /// going through caches or devices. Text-segment words are only
+    /// patched with `allow_text`, and must still decode afterwards. Nothing
+    /// is written unless every byte can be.
+    pub fn patch(
+        &mut self,
+        address: Address,
+        bytes: &[u8],
+        allow_text: bool,
+    ) -> Result<(), PatchError> {
+        let text_end = BASE_TEXT_ADDR.0 as u64 + 4 * self.instructions.len() as u64;
+        // Text words are patched as the program would see them in memory.
+        let endianness = self.memory.endianness();
+        let mut text_words = BTreeMap::new();
+        let mut data = Vec::new();
+        for (i, &byte) in bytes.iter().enumerate() {
+            let current = address.wrapping_add(i as u32);
+            if (BASE_TEXT_ADDR.0 as u64..text_end).contains(&(current.0 as u64)) {
+                if !allow_text {
+                    return Err(PatchError::TextProtected(current.0));
+                }
+                let index = current.word_index(BASE_TEXT_ADDR).unwrap_or_default();
+                let word = text_words.entry(index).or_insert_with(|| {
+                    endianness.arrange(self.instructions[index].encode().to_le_bytes())
+                });
+                word[current.0 as usize % 4] = byte;
+            } else if current < MAPPED_BASE {
+                return Err(PatchError::Unmapped(current.0));
+            } else {
+                data.push((current, byte));
+            }
+        }
+
+        let mut decoded = Vec::new();
+        for (index, bytes) in text_words {
+            let word = u32::from_le_bytes(endianness.arrange(bytes));
+            let instruction = Instruction::decode(word).ok_or(PatchError::Undecodable(
+                (BASE_TEXT_ADDR + 4 * index).0,
+                word,
+            ))?;
+            decoded.push((index, instruction));
+        }
+
+        if !decoded.is_empty() {
+            for (index, instruction) in decoded {
+                self.instructions[index] = instruction;
+            }
+            if self.threaded.is_some() {
+                self.threaded = Some(backend::compile(&self.instructions));
+            }
+        }
+        for (address, byte) in data {
+            self.memory.write_byte(address, byte);
+            if let Some(check) = &mut self.uninit_check {
+                check.store(address, 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// The text segment, one entry per word starting at `BASE_TEXT_ADDR`.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn registers(&self) -> &RegisterFile {
+        &self.registers
+    }
+
+    /// Lets a debugger change register values between steps.
+    pub fn registers_mut(&mut self) -> &mut RegisterFile {
+        &mut self.registers
+    }
+
+    pub fn cp0(&self) -> &Coprocessor0 {
+        &self.cp0
+    }
+
+    pub fn cp0_mut(&mut self) -> &mut Coprocessor0 {
+        &mut self.cp0
+    }
+
+    pub fn cp1(&self) -> &Coprocessor1 {
+        &self.cp1
+    }
+
+    pub fn cp1_mut(&mut self) -> &mut Coprocessor1 {
+        &mut self.cp1
+    }
+
+    pub fn pc(&self) -> Address {
+        self.registers.pc()
+    }
+
+    /// Moves execution to `pc`; the next step fetches from there. A jump
+    /// or load still in a delay slot is dropped.
+    pub fn set_pc(&mut self, pc: Address) {
+        self.registers.set_pc(pc);
+        self.delayed = Delayed::default();
+    }
+
+    /// Number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub(crate) fn execute_instruction(
+        &mut self,
+        instruction: Instruction,
+    ) -> Result<(), SimulatorError> {
+        if instruction.is_mips64() && !self.mips64 {
+            return Err(SimulatorError::ReservedInstruction(instruction));
+        }
         match instruction {
             Instruction::AddImmediate { res, reg, imm } => {
+                match (self.registers.get(reg) as i32).checked_add(imm) {
+                    Some(value) => self.registers.set(res, value as u32),
+                    None => self.overflow()?,
+                }
+            }
+            Instruction::AddImmediateUnsigned { res, reg, imm } => {
                 let value = self.registers.get(reg).wrapping_add(imm as u32);
                 self.registers.set(res, value);
             }
+            Instruction::Add { res, reg, ret } => {
+                match (self.registers.get(reg) as i32).checked_add(self.registers.get(ret) as i32) {
+                    Some(value) => self.registers.set(res, value as u32),
+                    None => self.overflow()?,
+                }
+            }
+            Instruction::Subtract { res, reg, ret } => {
+                match (self.registers.get(reg) as i32).checked_sub(self.registers.get(ret) as i32) {
+                    Some(value) => self.registers.set(res, value as u32),
+                    None => self.overflow()?,
+                }
+            }
+            Instruction::SubtractUnsigned { res, reg, ret } => {
+                let value = self
+                    .registers
+                    .get(reg)
+                    .wrapping_sub(self.registers.get(ret));
+                self.registers.set(res, value);
+            }
             Instruction::LoadUpperImmediate { res, imm } => {
                 let value = (imm as u32) << 16;
                 self.registers.set(res, value);
             }
             Instruction::OrImmediate { res, reg, imm } => {
-                let value = self.registers.get(reg) | (imm as u32);
-                self.registers.set(res, value);
+                // 64-bit code builds constants with `ori` after `dsll`, so
+                // the upper half is kept rather than sign-extended.
+                let value = self.registers.get64(reg) | (imm as u32 as u64);
+                match self.mips64 {
+                    true => self.registers.set64(res, value),
+                    false => self.registers.set(res, value as u32),
+                }
+            }
+            Instruction::ShiftLeftLogical { res, reg, shamt } => {
+                self.registers.set(res, self.registers.get(reg) << shamt);
+            }
+            Instruction::ShiftRightLogical { res, reg, shamt } => {
+                self.registers.set(res, self.registers.get(reg) >> shamt);
+            }
+            Instruction::ShiftRightArithmetic { res, reg, shamt } => {
+                let value = self.registers.get(reg) as i32 >> shamt;
+                self.registers.set(res, value as u32);
+            }
+            Instruction::ShiftLeftLogicalVariable { res, reg, ret } => {
+                let amount = self.registers.get(ret) & 0x1f;
+                self.registers.set(res, self.registers.get(reg) << amount);
+            }
+            Instruction::ShiftRightLogicalVariable { res, reg, ret } => {
+                let amount = self.registers.get(ret) & 0x1f;
+                self.registers.set(res, self.registers.get(reg) >> amount);
+            }
+            Instruction::ShiftRightArithmeticVariable { res, reg, ret } => {
+                let amount = self.registers.get(ret) & 0x1f;
+                let value = self.registers.get(reg) as i32 >> amount;
+                self.registers.set(res, value as u32);
+            }
+            Instruction::Multiply { reg, ret } => {
+                let product =
+                    self.registers.get(reg) as i32 as i64 * self.registers.get(ret) as i32 as i64;
+                self.registers.set_hi((product >> 32) as u32);
+                self.registers.set_lo(product as u32);
+            }
+            Instruction::MultiplyUnsigned { reg, ret } => {
+                let product = self.registers.get(reg) as u64 * self.registers.get(ret) as u64;
+                self.registers.set_hi((product >> 32) as u32);
+                self.registers.set_lo(product as u32);
+            }
+            // The result of dividing by zero is undefined; like MARS, leave
+            // HI and LO as they were.
+            Instruction::Divide { reg, ret } => {
+                let (dividend, divisor) = (
+                    self.registers.get(reg) as i32,
+                    self.registers.get(ret) as i32,
+                );
+                if divisor != 0 {
+                    self.registers.set_hi(dividend.wrapping_rem(divisor) as u32);
+                    self.registers.set_lo(dividend.wrapping_div(divisor) as u32);
+                }
+            }
+            Instruction::DivideUnsigned { reg, ret } => {
+                let (dividend, divisor) = (self.registers.get(reg), self.registers.get(ret));
+                if divisor != 0 {
+                    self.registers.set_hi(dividend % divisor);
+                    self.registers.set_lo(dividend / divisor);
+                }
+            }
+            Instruction::MoveFromHi { res } => self.registers.set(res, self.registers.hi()),
+            Instruction::MoveFromLo { res } => self.registers.set(res, self.registers.lo()),
+            Instruction::MoveToHi { reg } => self.registers.set_hi(self.registers.get(reg)),
+            Instruction::MoveToLo { reg } => self.registers.set_lo(self.registers.get(reg)),
+            // Comparing whole registers is right in either mode, since
+            // sign-extending keeps the order of 32-bit values.
+            Instruction::SetLessThan { res, reg, ret } => {
+                let less = (self.registers.get64(reg) as i64) < (self.registers.get64(ret) as i64);
+                self.registers.set(res, less as u32);
+            }
+            Instruction::SetLessThanUnsigned { res, reg, ret } => {
+                let less = self.registers.get64(reg) < self.registers.get64(ret);
+                self.registers.set(res, less as u32);
+            }
+            // Registers hold 32-bit values sign-extended, and bitwise
+            // operations keep them that way, so these work on the whole
+            // register in either mode.
+            Instruction::And { res, reg, ret } => {
+                let value = self.registers.get64(reg) & self.registers.get64(ret);
+                self.registers.set64(res, value);
+            }
+            Instruction::Or { res, reg, ret } => {
+                let value = self.registers.get64(reg) | self.registers.get64(ret);
+                self.registers.set64(res, value);
+            }
+            Instruction::Xor { res, reg, ret } => {
+                let value = self.registers.get64(reg) ^ self.registers.get64(ret);
+                self.registers.set64(res, value);
+            }
+            Instruction::Nor { res, reg, ret } => {
+                let value = !(self.registers.get64(reg) | self.registers.get64(ret));
+                self.registers.set64(res, value);
+            }
+            Instruction::AndImmediate { res, reg, imm } => {
+                let value = self.registers.get64(reg) & (imm as u32 as u64 & 0xffff);
+                self.registers.set64(res, value);
+            }
+            Instruction::XorImmediate { res, reg, imm } => {
+                let value = self.registers.get64(reg) ^ (imm as u32 as u64 & 0xffff);
+                self.registers.set64(res, value);
             }
             Instruction::SystemCall => {
                 self.handle_syscall()?;
             }
+            Instruction::BranchEqual { reg, ret, offset } => {
+                if self.registers.get64(reg) == self.registers.get64(ret) {
+                    self.jump = Some(self.branch_target(offset));
+                }
+            }
+            Instruction::BranchNotEqual { reg, ret, offset } => {
+                if self.registers.get64(reg) != self.registers.get64(ret) {
+                    self.jump = Some(self.branch_target(offset));
+                }
+            }
+            Instruction::BranchLessThanZero { reg, offset } => {
+                if (self.registers.get64(reg) as i64) < 0 {
+                    self.jump = Some(self.branch_target(offset));
+                }
+            }
+            Instruction::BranchGreaterEqualZero { reg, offset } => {
+                if self.registers.get64(reg) as i64 >= 0 {
+                    self.jump = Some(self.branch_target(offset));
+                }
+            }
+            Instruction::BranchLessEqualZero { reg, offset } => {
+                if self.registers.get64(reg) as i64 <= 0 {
+                    self.jump = Some(self.branch_target(offset));
+                }
+            }
+            Instruction::BranchGreaterThanZero { reg, offset } => {
+                if self.registers.get64(reg) as i64 > 0 {
+                    self.jump = Some(self.branch_target(offset));
+                }
+            }
+            // Compare before linking, in case the register is `$ra`.
+            Instruction::BranchLessThanZeroAndLink { reg, offset } => {
+                if (self.registers.get64(reg) as i64) < 0 {
+                    self.jump = Some(self.branch_target(offset));
+                }
+                self.registers.set(Register::RA, self.return_address().0);
+            }
+            Instruction::BranchGreaterEqualZeroAndLink { reg, offset } => {
+                if self.registers.get64(reg) as i64 >= 0 {
+                    self.jump = Some(self.branch_target(offset));
+                }
+                self.registers.set(Register::RA, self.return_address().0);
+            }
             Instruction::AddUnsigned { res, reg, ret } => {
                 let value = self
                     .registers
@@ -79,46 +1067,331 @@ impl Simulator {
                     .wrapping_add(self.registers.get(ret));
                 self.registers.set(res, value);
             }
+            Instruction::Jump { target } => {
+                self.jump = Some(self.jump_target(target));
+            }
+            Instruction::JumpAndLink { target } => {
+                self.jump = Some(self.jump_target(target));
+                self.registers.set(Register::RA, self.return_address().0);
+            }
+            Instruction::JumpRegister { reg } => {
+                self.jump = Some(Address(self.registers.get(reg)));
+            }
+            Instruction::JumpAndLinkRegister { res, reg } => {
+                // Read before linking, in case both are the same register.
+                self.jump = Some(Address(self.registers.get(reg)));
+                self.registers.set(res, self.return_address().0);
+            }
+            Instruction::LoadWord { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(value) = self.load(address, 4) {
+                    self.load_word(res, value);
+                }
+            }
+            Instruction::LoadByte { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(value) = self.load(address, 1) {
+                    self.load_word(res, value as u8 as i8 as i32 as u32);
+                }
+            }
+            Instruction::LoadByteUnsigned { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(value) = self.load(address, 1) {
+                    self.load_word(res, value);
+                }
+            }
+            Instruction::LoadHalf { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(value) = self.load(address, 2) {
+                    self.load_word(res, value as u16 as i16 as i32 as u32);
+                }
+            }
+            Instruction::LoadHalfUnsigned { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(value) = self.load(address, 2) {
+                    self.load_word(res, value);
+                }
+            }
+            // Both merge part of the aligned word into the register: `lwl`
+            // from the addressed byte up to the most significant one, into
+            // the top, and `lwr` from it down, into the bottom.
+            Instruction::LoadWordLeft { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(word) = self.load(Address(address.0 & !3), 4) {
+                    let shift = 8 * (3 - self.significance(address));
+                    let kept = self.registers.get(res) & ((1 << shift) - 1);
+                    self.load_word(res, word << shift | kept);
+                }
+            }
+            Instruction::LoadWordRight { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(word) = self.load(Address(address.0 & !3), 4) {
+                    let shift = 8 * self.significance(address);
+                    let kept = self.registers.get(res) & !(u32::MAX >> shift);
+                    self.load_word(res, word >> shift | kept);
+                }
+            }
+            Instruction::StoreWordLeft { ret, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                let lift = 3 - self.significance(address) as i32;
+                self.store_partial(address, self.registers.get(ret), lift);
+            }
+            Instruction::StoreWordRight { ret, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                let lift = -(self.significance(address) as i32);
+                self.store_partial(address, self.registers.get(ret), lift);
+            }
+            Instruction::StoreWord { ret, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                self.store(address, 4, self.registers.get(ret));
+            }
+            Instruction::LoadDouble { res, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                if let Some(value) = self.load_double(address) {
+                    self.load = Some((res, value));
+                }
+            }
+            Instruction::StoreDouble { ret, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                self.store_double(address, self.registers.get64(ret));
+            }
+            Instruction::StoreByte { ret, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                self.store(address, 1, self.registers.get(ret));
+            }
+            Instruction::StoreHalf { ret, reg, imm } => {
+                let address = self.effective_address(reg, imm);
+                self.store(address, 2, self.registers.get(ret));
+            }
+            Instruction::MoveFromCoprocessor0 { res, cp0 } => {
+                self.registers.set(res, self.cp0.read(cp0));
+            }
+            Instruction::MoveToCoprocessor0 { ret, cp0 } => {
+                self.cp0.write(cp0, self.registers.get(ret));
+            }
+            Instruction::TlbRead => self.cp0.tlb_read(),
+            Instruction::TlbWriteIndexed => self.cp0.tlb_write_indexed(),
+            Instruction::TlbWriteRandom => self.cp0.tlb_write_random(),
+            Instruction::TlbProbe => self.cp0.tlb_probe(),
+            // Comparing whole registers is right in either mode, as for
+            // `slt`.
+            Instruction::TrapEqual { reg, ret } => {
+                self.trap(self.registers.get64(reg) == self.registers.get64(ret));
+            }
+            Instruction::TrapNotEqual { reg, ret } => {
+                self.trap(self.registers.get64(reg) != self.registers.get64(ret));
+            }
+            Instruction::TrapGreaterEqual { reg, ret } => {
+                self.trap((self.registers.get64(reg) as i64) >= self.registers.get64(ret) as i64);
+            }
+            Instruction::TrapGreaterEqualUnsigned { reg, ret } => {
+                self.trap(self.registers.get64(reg) >= self.registers.get64(ret));
+            }
+            Instruction::TrapLessThan { reg, ret } => {
+                self.trap((self.registers.get64(reg) as i64) < self.registers.get64(ret) as i64);
+            }
+            Instruction::TrapLessThanUnsigned { reg, ret } => {
+                self.trap(self.registers.get64(reg) < self.registers.get64(ret));
+            }
+            Instruction::TrapEqualImmediate { reg, imm } => {
+                self.trap((self.registers.get64(reg) as i64) == imm as i64);
+            }
+            Instruction::TrapNotEqualImmediate { reg, imm } => {
+                self.trap((self.registers.get64(reg) as i64) != imm as i64);
+            }
+            Instruction::TrapGreaterEqualImmediate { reg, imm } => {
+                self.trap((self.registers.get64(reg) as i64) >= imm as i64);
+            }
+            Instruction::TrapGreaterEqualImmediateUnsigned { reg, imm } => {
+                self.trap(self.registers.get64(reg) >= imm as i64 as u64);
+            }
+            Instruction::TrapLessThanImmediate { reg, imm } => {
+                self.trap((self.registers.get64(reg) as i64) < imm as i64);
+            }
+            Instruction::TrapLessThanImmediateUnsigned { reg, imm } => {
+                self.trap(self.registers.get64(reg) < imm as i64 as u64);
+            }
+            Instruction::ExceptionReturn => {
+                self.jump = Some(self.cp0.exception_return());
+            }
+            Instruction::DoubleAdd { res, reg, ret } => {
+                match (self.registers.get64(reg) as i64)
+                    .checked_add(self.registers.get64(ret) as i64)
+                {
+                    Some(value) => self.registers.set64(res, value as u64),
+                    None => self.overflow()?,
+                }
+            }
+            Instruction::DoubleAddUnsigned { res, reg, ret } => {
+                let value = self
+                    .registers
+                    .get64(reg)
+                    .wrapping_add(self.registers.get64(ret));
+                self.registers.set64(res, value);
+            }
+            Instruction::DoubleSubtract { res, reg, ret } => {
+                match (self.registers.get64(reg) as i64)
+                    .checked_sub(self.registers.get64(ret) as i64)
+                {
+                    Some(value) => self.registers.set64(res, value as u64),
+                    None => self.overflow()?,
+                }
+            }
+            Instruction::DoubleSubtractUnsigned { res, reg, ret } => {
+                let value = self
+                    .registers
+                    .get64(reg)
+                    .wrapping_sub(self.registers.get64(ret));
+                self.registers.set64(res, value);
+            }
+            Instruction::DoubleAddImmediate { res, reg, imm } => {
+                match (self.registers.get64(reg) as i64).checked_add(imm as i64) {
+                    Some(value) => self.registers.set64(res, value as u64),
+                    None => self.overflow()?,
+                }
+            }
+            Instruction::DoubleAddImmediateUnsigned { res, reg, imm } => {
+                let value = self.registers.get64(reg).wrapping_add(imm as i64 as u64);
+                self.registers.set64(res, value);
+            }
+            Instruction::DoubleShiftLeftLogical { res, reg, shamt } => {
+                self.registers
+                    .set64(res, self.registers.get64(reg) << shamt);
+            }
+            Instruction::DoubleShiftRightLogical { res, reg, shamt } => {
+                self.registers
+                    .set64(res, self.registers.get64(reg) >> shamt);
+            }
+            Instruction::DoubleShiftRightArithmetic { res, reg, shamt } => {
+                let value = (self.registers.get64(reg) as i64) >> shamt;
+                self.registers.set64(res, value as u64);
+            }
+            Instruction::Custom { plugin, operands } => {
+                if let Some(plugin) = plugin::get(plugin) {
+                    plugin.execute(&operands, self)?;
+                }
+            }
         }
         Ok(())
     }
 
+    /// Leaves a 32-bit load for `advance` to write, sign-extended like every
+    /// 32-bit result.
+    pub(crate) fn load_word(&mut self, res: Register, value: u32) {
+        self.load = Some((res, value as i32 as u64));
+    }
+
+    /// The address a load or store reaches: `imm` bytes past `reg`.
+    pub(crate) fn effective_address(&self, reg: Register, imm: i32) -> Address {
+        Address(self.registers.get(reg)).wrapping_offset(imm)
+    }
+
+    /// Where a branch at the PC goes: `offset` words past the instruction
+    /// after it.
+    fn branch_target(&self, offset: i32) -> Address {
+        self.registers
+            .pc()
+            .wrapping_add(4)
+            .wrapping_add((offset as u32) << 2)
+    }
+
+    /// Where a jump at the PC goes: `target` words into the 256 MB region
+    /// holding the instruction after it.
+    fn jump_target(&self, target: u32) -> Address {
+        let next = self.registers.pc().wrapping_add(4);
+        Address(next.0 & 0xf000_0000 | target << 2)
+    }
+
+    /// Where a call at the PC returns to: past its delay slot, if it has
+    /// one.
+    pub(crate) fn return_address(&self) -> Address {
+        match self.delay_slots.branches {
+            true => self.registers.pc().wrapping_add(8),
+            false => self.registers.pc().wrapping_add(4),
+        }
+    }
+
+    /// Handles an exception raised by the instruction at `address`: the
+    /// instruction's other effects are dropped and execution continues at
+    /// the exception handler, or the run halts if there is none.
+    fn take_exception(
+        &mut self,
+        exception: Exception,
+        address: Address,
+    ) -> Result<(), SimulatorError> {
+        self.jump = None;
+        self.load = None;
+        let handler = self
+            .exception_handler
+            .ok_or(SimulatorError::UnhandledException(exception))?;
+        let in_delay_slot = self.delayed.jump.is_some();
+        let epc = match in_delay_slot {
+            true => address.wrapping_sub(4),
+            false => address,
+        };
+        self.cp0.enter(exception, epc, in_delay_slot);
+        self.delayed = Delayed::default();
+        self.registers.set_pc(handler);
+        Ok(())
+    }
+
     fn get_user_input(&mut self) -> Result<String, SimulatorError> {
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| SimulatorError::IoError(e))?;
-        input = input.trim().to_string();
-        Ok(input)
+        match self.io.read_line() {
+            Ok(input) => Ok(input.trim().to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                Err(SimulatorError::InputPending)
+            }
+            Err(err) => Err(SimulatorError::IoError(err)),
+        }
     }
 
     fn handle_syscall(&mut self) -> Result<(), SimulatorError> {
+        let Some(mut trace) = self.syscall_trace.take() else {
+            return self.run_syscall();
+        };
+        let call = SyscallCall::new(self.steps, &self.registers, |address| {
+            self.memory.read_string(address)
+        });
+        let result = self.run_syscall();
+        // Input syscalls waiting for a line are logged once it arrives.
+        if !matches!(result, Err(SimulatorError::InputPending)) {
+            trace.record(&call, &result, &self.registers);
+        }
+        self.syscall_trace = Some(trace);
+        result
+    }
+
+    fn run_syscall(&mut self) -> Result<(), SimulatorError> {
         let v0 = self.registers.get(Register::V0);
         match v0 {
             1 => {
-                let value = self.registers.get(Register::A0);
-                print!("{}", value);
+                let value = self.registers.get(Register::A0) as i32;
+                self.io.write(&value.to_string())?;
             }
             4 => {
-                let addr = self.registers.get(Register::A0) as usize;
-                let offset: usize = (addr - BASE_DATA_ADDR).into();
-
-                let mut bytes = Vec::new();
-                let mut i = offset;
-                while i < self.memory.len() && self.memory[i] != 0 {
-                    bytes.push(self.memory[i]);
-                    i += 1;
+                let addr = Address(self.registers.get(Register::A0));
+                let bytes = self.memory.read_string(addr);
+                if let Some(check) = &mut self.uninit_check {
+                    // The terminating NUL is read too.
+                    check.load(self.registers.pc(), addr, bytes.len() as u32 + 1);
                 }
-
                 let s = String::from_utf8_lossy(&bytes);
-                print!("{}", s);
+                self.io.write(&s)?;
             }
             5 => {
                 let input = self.get_user_input()?;
                 let value = input
-                    .parse::<u32>()
+                    .parse::<i32>()
                     .map_err(|_| SimulatorError::WrongInputType(input))?;
-                self.registers.set(Register::V0, value);
+                self.registers.set(Register::V0, value as u32);
+            }
+            9 => {
+                let amount = self.registers.get(Register::A0) as i32;
+                let limit = Address(self.registers.get(Register::SP));
+                let address = self
+                    .heap
+                    .sbrk(amount, limit, self.registers.pc(), self.steps)?;
+                self.registers.set(Register::V0, address.0);
             }
             10 => {
                 return Err(SimulatorError::Exit(0));
@@ -130,7 +1403,7 @@ impl Simulator {
             30 => {
                 let duration = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
-                    .map_err(|e| SimulatorError::InvalidSystemTime(e))?;
+                    .map_err(SimulatorError::InvalidSystemTime)?;
 
                 let millis = duration.as_millis() as u64;
 
@@ -147,15 +1420,761 @@ impl Simulator {
         Ok(())
     }
 
+    /// Index of the instruction at the PC; past the end of the text
+    /// segment if the PC is below it.
+    fn text_index(&self) -> usize {
+        self.registers
+            .pc()
+            .word_index(BASE_TEXT_ADDR)
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Why there is no instruction to fetch at the PC.
+    fn fetch_error(&self) -> SimulatorError {
+        match self.registers.pc() == EXIT_ADDRESS {
+            true => SimulatorError::ReturnedFromMain,
+            false => SimulatorError::NoMoreInstructions,
+        }
+    }
+
+    /// The instruction at the PC, if the PC is in the text segment.
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        self.instructions.get(self.text_index()).copied()
+    }
+
+    /// Executes one instruction.
     pub fn step(&mut self) -> Result<(), SimulatorError> {
-        let instruction = self
+        self.run_for(1)
+    }
+
+    /// Moves the PC past an instruction that executed, landing its load and
+    /// taking its jump now or, in delay-slot mode, after the next
+    /// instruction. Returns where the instruction itself jumps to.
+    fn advance(&mut self) -> Option<Address> {
+        let delayed = std::mem::take(&mut self.delayed);
+        let jump = self.jump.take();
+        let load = self.load.take();
+        if let Some((register, value)) = delayed.load {
+            self.registers.set64(register, value);
+        }
+        match self.delay_slots.loads {
+            true => self.delayed.load = load,
+            false => {
+                if let Some((register, value)) = load {
+                    self.registers.set64(register, value);
+                }
+            }
+        }
+        let next = match self.delay_slots.branches {
+            true => {
+                self.delayed.jump = jump;
+                delayed.jump.unwrap_or(self.registers.pc() + 4_u32)
+            }
+            false => jump.unwrap_or(self.registers.pc() + 4_u32),
+        };
+        self.registers.set_pc(next);
+        jump
+    }
+
+    fn execute_next(&mut self) -> Result<(), SimulatorError> {
+        let index = self.text_index();
+        let instruction = *self
             .instructions
-            .get(&self.pc)
-            .ok_or(SimulatorError::NoMoreInstructions)?
-            .clone();
+            .get(index)
+            .ok_or_else(|| self.fetch_error())?;
+
+        let address = self.registers.pc();
+        let sp = self.registers.get(Register::SP);
+        let before = self.trace.is_some().then(|| self.registers.clone());
+        self.cache_access(AccessKind::Fetch, address);
+        let result = self.execute_instruction(instruction);
+        let (result, target) = match (self.exception.take(), result) {
+            (Some(exception), _) => (self.take_exception(exception, address), None),
+            (None, Ok(())) => (Ok(()), self.advance()),
+            (None, Err(err)) => (Err(err), None),
+        };
+        // A linking branch only calls when it is taken.
+        let called = instruction.is_call() && target.is_some();
+        self.called = called;
+        // A syscall waiting for input runs again once it arrives; anything
+        // else, including the exit syscall, is traced and counted as it
+        // happened.
+        if !matches!(result, Err(SimulatorError::InputPending)) {
+            if let (Some(trace), Some(before)) = (&mut self.trace, before) {
+                trace.record(self.steps, address, &instruction, &before, &self.registers);
+            }
+            if let Some(profile) = &mut self.profile {
+                profile.record(index);
+            }
+            if let Some(timing) = &mut self.timing {
+                timing.record(instruction.class(), target.is_some());
+            }
+            if let Some(pipeline) = &mut self.pipeline {
+                pipeline.retire(address, instruction, self.registers.pc());
+            }
+            self.steps += 1;
+        }
+        result?;
+        self.keyboard_display.tick();
+        if let Some(predictor) = &mut self.predictor
+            && instruction.class() == InstructionClass::Branch
+        {
+            predictor.record(address, target.is_some());
+        }
+        if let Some(call_stack) = &mut self.call_stack {
+            if called {
+                // A call returns past its delay slot, if it has one.
+                let return_address = match self.delay_slots.branches {
+                    true => address + 8_u32,
+                    false => address + 4_u32,
+                };
+                call_stack.call(Frame {
+                    call_site: address,
+                    entry: target.unwrap_or(self.registers.pc()),
+                    return_address,
+                    sp,
+                });
+            } else if instruction.is_return() {
+                call_stack.ret(target.unwrap_or(self.registers.pc()));
+            }
+        }
+        if let Some(check) = &mut self.stack_check {
+            if called {
+                check.call(sp);
+            } else if instruction.is_return() {
+                check.ret(address, self.registers.get(Register::SP));
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes up to `count` instructions with the selected backend,
+    /// journaling them when [`Simulator::set_journal_depth`] asked for it.
+    pub fn run_for(&mut self, count: u64) -> Result<(), SimulatorError> {
+        if self.journal.depth() == 0 {
+            return self.execute_for(count);
+        }
+        for _ in 0..count {
+            self.step_journaled()?;
+        }
+        Ok(())
+    }
+
+    fn execute_for(&mut self, count: u64) -> Result<(), SimulatorError> {
+        let Some(code) = self.threaded.clone().filter(|_| !self.observed()) else {
+            for _ in 0..count {
+                self.execute_next()?;
+            }
+            return Ok(());
+        };
 
-        self.execute_instruction(instruction)?;
-        self.pc += 4;
+        for _ in 0..count {
+            let index = self.text_index();
+            let handler = code.get(index).ok_or_else(|| self.fetch_error())?;
+            let address = self.registers.pc();
+            let result = handler(self);
+            // Exceptions are taken, and steps and calls counted, exactly as
+            // `execute_next` does.
+            self.called = false;
+            let result = match (self.exception.take(), result) {
+                (Some(exception), _) => self.take_exception(exception, address),
+                (None, result) => result.map(|()| {
+                    let target = self.advance();
+                    self.called = target.is_some() && self.instructions[index].is_call();
+                }),
+            };
+            if !matches!(result, Err(SimulatorError::InputPending)) {
+                self.steps += 1;
+            }
+            result?;
+            self.keyboard_display.tick();
+        }
         Ok(())
     }
+
+    /// Makes a run stop before executing the instruction at `address`.
+    /// Returns false if there already was a breakpoint there, which loses
+    /// its condition.
+    pub fn add_breakpoint(&mut self, address: Address) -> bool {
+        self.breakpoints.insert(address, None).is_none()
+    }
+
+    /// Like [`Simulator::add_breakpoint`], but the run only stops when
+    /// `condition` evaluates to non-zero, or cannot be evaluated. Labels in
+    /// it must already be resolved.
+    pub fn add_conditional_breakpoint(&mut self, address: Address, condition: Expression) -> bool {
+        self.breakpoints.insert(address, Some(condition)).is_none()
+    }
+
+    /// Returns false if there was no breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: Address) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    /// Breakpoint addresses in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = Address> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    pub fn breakpoint_condition(&self, address: Address) -> Option<&Expression> {
+        self.breakpoints.get(&address)?.as_ref()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Makes a run stop after any instruction whose loads or stores touch
+    /// the watched range.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Returns false if there was no such watchpoint.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|existing| existing != watchpoint);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Takes the watchpoint hit by the last instruction, if any.
+    pub(crate) fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Carries `old`'s breakpoints and watchpoints over, e.g. when
+    /// restarting the program.
+    pub(crate) fn take_stop_points(&mut self, old: Simulator) {
+        self.breakpoints = old.breakpoints;
+        self.watchpoints = old.watchpoints;
+    }
+
+    /// Lets the next run execute the instruction at the PC even if it has a
+    /// breakpoint, e.g. when resuming after pausing there by hand. Runs
+    /// already do this after stopping at a breakpoint.
+    pub fn skip_breakpoint(&mut self) {
+        self.resume_at = Some(self.registers.pc());
+    }
+
+    /// Whether a run should stop before the instruction at the PC, which it
+    /// does at a breakpoint unless it is resuming from that one.
+    pub(crate) fn check_breakpoint(&mut self) -> Option<Address> {
+        let resuming = self.resume_at.take() == Some(self.registers.pc());
+        let condition = match self.breakpoints.get(&self.registers.pc()) {
+            Some(condition) if !resuming => condition,
+            _ => return None,
+        };
+        // A condition that fails to evaluate, e.g. reading unmapped memory,
+        // stops the run so the problem is noticed.
+        if condition
+            .as_ref()
+            .is_some_and(|condition| condition.evaluate(self).is_ok_and(|value| value == 0))
+        {
+            return None;
+        }
+        self.resume_at = Some(self.registers.pc());
+        Some(self.registers.pc())
+    }
+
+    /// Executes up to `count` instructions like [`Simulator::run_for`], but
+    /// stops at breakpoints and watchpoints.
+    pub fn continue_for(&mut self, count: u64) -> Result<(), Stopped> {
+        if self.breakpoints.is_empty() && self.watchpoints.is_empty() {
+            return Ok(self.run_for(count)?);
+        }
+        for _ in 0..count {
+            if let Some(address) = self.check_breakpoint() {
+                return Err(Stopped::Breakpoint(address));
+            }
+            self.step_watched()?;
+        }
+        Ok(())
+    }
+
+    /// Executes one instruction, stopping if it hit a watchpoint.
+    fn step_watched(&mut self) -> Result<(), Stopped> {
+        self.watch_hit = None;
+        let result = self.run_for(1);
+        if let Some(hit) = self.take_watch_hit() {
+            return Err(Stopped::Watchpoint(hit));
+        }
+        Ok(result?)
+    }
+
+    /// Whether the last instruction executed called a subroutine: a `jal`
+    /// or `jalr`, or a `bltzal` or `bgezal` that branched.
+    pub fn called(&self) -> bool {
+        self.called
+    }
+
+    /// Executes one instruction, and if it called a subroutine, runs until
+    /// that returns. Stops early at breakpoints and watchpoints.
+    pub fn step_over(&mut self) -> Result<(), Stopped> {
+        self.step_watched()?;
+        match self.called {
+            // Breakpoints in the subroutine, even at its entry, still stop.
+            true => self
+                .run_until(&mut UntilReturn::step_out(), u64::MAX)
+                .map(drop),
+            false => Ok(()),
+        }
+    }
+
+    /// Runs until the current subroutine returns, stopping early at
+    /// breakpoints and watchpoints.
+    pub fn step_out(&mut self) -> Result<(), Stopped> {
+        self.skip_breakpoint();
+        self.run_until(&mut UntilReturn::step_out(), u64::MAX)
+            .map(drop)
+    }
+
+    /// Runs up to `count` instructions towards the return `until` waits
+    /// for, stopping early at breakpoints and watchpoints. Returns whether
+    /// the return, and its delay slot, have executed; calling again with
+    /// the same `until` carries on.
+    pub fn run_until(&mut self, until: &mut UntilReturn, count: u64) -> Result<bool, Stopped> {
+        for _ in 0..count {
+            if let Some(address) = self.check_breakpoint() {
+                return Err(Stopped::Breakpoint(address));
+            }
+            let instruction = self.current_instruction();
+            self.step_watched()?;
+            let done =
+                instruction.is_some_and(|instruction| until.observe(&instruction, self.called));
+            if done {
+                // The return's delay slot still belongs to the subroutine.
+                if self.delayed.jump.is_some() {
+                    self.step_watched()?;
+                }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Runs until the program stops or reaches a breakpoint or watchpoint.
+    /// Calling it again afterwards continues from there.
+    pub fn run(&mut self) -> Stopped {
+        loop {
+            if let Err(stopped) = self.continue_for(u64::MAX) {
+                return stopped;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RuntimeArgs, assembler::Assembler, cp0, syscall_io::SharedIo};
+
+    fn assemble(source: &str) -> Program {
+        assemble_with(source, &RuntimeArgs::default())
+    }
+
+    fn assemble_with(source: &str, args: &RuntimeArgs) -> Program {
+        let mut assembler = Assembler::new();
+        assembler
+            .assemble_source(source, args)
+            .expect("test program assembles");
+        assembler.finish()
+    }
+
+    /// Runs `body`, followed by an exit, on both backends and checks the
+    /// whole 64-bit value of each register in `expected` afterwards.
+    fn check(data: &str, body: &str, mips64: bool, expected: &[(Register, u64)]) {
+        let source = format!(
+            ".data\n{}\n.text\nmain:\n{}\n  li $v0, 10\n  syscall\n",
+            data, body
+        );
+        let args = RuntimeArgs {
+            mips64,
+            ..RuntimeArgs::default()
+        };
+        let program = assemble_with(&source, &args);
+        for backend in [Backend::Interpreter, Backend::Threaded] {
+            let mut simulator = Simulator::new(program.clone());
+            simulator.set_mips64(mips64);
+            simulator.set_backend(backend);
+            let stop = simulator.run();
+            assert!(
+                matches!(stop, Stopped::Halted(SimulatorError::Exit(0))),
+                "{:?} on {:?}",
+                stop,
+                backend
+            );
+            for &(register, value) in expected {
+                assert_eq!(
+                    simulator.registers.get64(register),
+                    value,
+                    "{} on {:?}",
+                    register.name(),
+                    backend
+                );
+            }
+        }
+    }
+
+    /// A 32-bit result as a register holds it, sign-extended.
+    fn word(value: u32) -> u64 {
+        value as i32 as i64 as u64
+    }
+
+    /// Runs `program` to its exit on `backend`, with exceptions vectored to
+    /// its `handler` label.
+    fn run_with_handler(program: Program, backend: Backend) -> Simulator {
+        let handler = program.debug_info().label("handler");
+        let mut simulator = Simulator::new(program);
+        simulator.set_backend(backend);
+        simulator.set_exception_handler(handler);
+        let stop = simulator.run();
+        assert!(
+            matches!(stop, Stopped::Halted(SimulatorError::Exit(0))),
+            "{:?}",
+            stop
+        );
+        simulator
+    }
+
+    #[test]
+    fn traps_vector_with_cause_and_epc_on_both_backends() {
+        // Each trap with operands that make its condition hold, then fail,
+        // given $t0 = -1 and $t1 = 1.
+        let traps = [
+            ("teq $t0, $t0", "teq $t0, $t1"),
+            ("tne $t0, $t1", "tne $t0, $t0"),
+            ("tge $t1, $t0", "tge $t0, $t1"),
+            ("tgeu $t0, $t1", "tgeu $t1, $t0"),
+            ("tlt $t0, $t1", "tlt $t1, $t0"),
+            ("tltu $t1, $t0", "tltu $t0, $t1"),
+            ("teqi $t1, 1", "teqi $t1, 2"),
+            ("tnei $t1, 2", "tnei $t1, 1"),
+            ("tgei $t0, -1", "tgei $t0, 0"),
+            ("tgeiu $t0, 1", "tgeiu $t1, -1"),
+            ("tlti $t0, 0", "tlti $t1, 0"),
+            ("tltiu $t1, -1", "tltiu $t0, 1"),
+        ];
+        for backend in [Backend::Interpreter, Backend::Threaded] {
+            for (taken, untaken) in traps {
+                for (trap, holds) in [(taken, true), (untaken, false)] {
+                    let source = format!(
+                        ".text\nmain:\n  li $t0, -1\n  li $t1, 1\ntrap:\n  {}\n  li $v0, 10\n  \
+                         syscall\nhandler:\n  li $v0, 10\n  syscall\n",
+                        trap
+                    );
+                    let program = assemble(&source);
+                    let at = program.debug_info().label("trap").unwrap();
+                    let simulator = run_with_handler(program, backend);
+                    let cause = simulator.cp0().read(cp0::CAUSE) >> 2 & 0x1f;
+                    let epc = simulator.cp0().read(cp0::EPC);
+                    let context = format!("{} on {:?}", trap, backend);
+                    match holds {
+                        true => {
+                            assert_eq!(cause, ExceptionCode::Trap as u32, "{}", context);
+                            assert_eq!(epc, at.0, "{}", context);
+                        }
+                        false => assert_eq!(cause, 0, "{}", context),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn overflow_vectors_to_the_handler_or_halts_without_one() {
+        for backend in [Backend::Interpreter, Backend::Threaded] {
+            for add in ["addi $t1, $t0, 1", "add $t1, $t0, $t0", "sub $t1, $t2, $t0"] {
+                let source = format!(
+                    ".text\nmain:\n  li $t0, 0x7fffffff\n  li $t1, 9\n  li $t2, -2\nover:\n  {}\n  \
+                     li $v0, 10\n  syscall\nhandler:\n  li $v0, 10\n  syscall\n",
+                    add
+                );
+                let program = assemble(&source);
+                let at = program.debug_info().label("over").unwrap();
+                let simulator = run_with_handler(program.clone(), backend);
+                let cause = simulator.cp0().read(cp0::CAUSE) >> 2 & 0x1f;
+                let context = format!("{} on {:?}", add, backend);
+                assert_eq!(cause, ExceptionCode::Overflow as u32, "{}", context);
+                assert_eq!(simulator.cp0().read(cp0::EPC), at.0, "{}", context);
+                assert_eq!(simulator.registers.get(Register::T1), 9, "{}", context);
+
+                let mut simulator = Simulator::new(program);
+                simulator.set_backend(backend);
+                let stop = simulator.run();
+                assert!(
+                    matches!(stop, Stopped::Halted(SimulatorError::ArithmeticOverflow)),
+                    "{}: {:?}",
+                    context,
+                    stop
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_and_logic() {
+        check(
+            "",
+            "  li $t0, 7
+  li $t1, -3
+  addu $s0, $t0, $t1
+  subu $s1, $t0, $t1
+  and $s2, $t0, $t1
+  or $s3, $t0, $t1
+  xor $s4, $t0, $t1
+  nor $s5, $t0, $zero
+  slt $s6, $t1, $t0
+  sltu $s7, $t1, $t0
+  andi $t2, $t1, 0xff
+  xori $t3, $t0, 0xf
+  lui $t4, 0x1234
+  ori $t4, $t4, 0x5678
+  addi $t5, $t0, -10
+  add $t6, $t0, $t1
+  sub $t7, $t1, $t0",
+            false,
+            &[
+                (Register::S0, 4),
+                (Register::S1, 10),
+                (Register::S2, 5),
+                (Register::S3, word(0xffff_ffff)),
+                (Register::S4, word(0xffff_fffa)),
+                (Register::S5, word(0xffff_fff8)),
+                (Register::S6, 1),
+                (Register::S7, 0),
+                (Register::T2, 0xfd),
+                (Register::T3, 8),
+                (Register::T4, 0x1234_5678),
+                (Register::T5, word(-3_i32 as u32)),
+                (Register::T6, 4),
+                (Register::T7, word(-10_i32 as u32)),
+            ],
+        );
+    }
+
+    #[test]
+    fn shifts() {
+        check(
+            "",
+            "  li $t0, -16
+  li $t1, 36
+  sll $s0, $t0, 4
+  srl $s1, $t0, 4
+  sra $s2, $t0, 4
+  sllv $s3, $t0, $t1
+  srlv $s4, $t0, $t1
+  srav $s5, $t0, $t1",
+            false,
+            &[
+                (Register::S0, word(0xffff_ff00)),
+                (Register::S1, 0x0fff_ffff),
+                (Register::S2, word(0xffff_ffff)),
+                (Register::S3, word(0xffff_ff00)),
+                (Register::S4, 0x0fff_ffff),
+                (Register::S5, word(0xffff_ffff)),
+            ],
+        );
+    }
+
+    #[test]
+    fn multiply_and_divide() {
+        check(
+            "",
+            "  li $t0, -7
+  li $t1, 2
+  mult $t0, $t1
+  mflo $s0
+  mfhi $s1
+  multu $t0, $t1
+  mfhi $s2
+  div $t0, $t1
+  mflo $s3
+  mfhi $s4
+  divu $t0, $t1
+  mflo $s5
+  mthi $t1
+  mtlo $t1
+  div $t0, $zero
+  mfhi $s6
+  mflo $s7",
+            false,
+            &[
+                (Register::S0, word(-14_i32 as u32)),
+                (Register::S1, word(0xffff_ffff)),
+                (Register::S2, 1),
+                (Register::S3, word(-3_i32 as u32)),
+                (Register::S4, word(-1_i32 as u32)),
+                (Register::S5, 0x7fff_fffc),
+                // Dividing by zero leaves HI and LO alone.
+                (Register::S6, 2),
+                (Register::S7, 2),
+            ],
+        );
+    }
+
+    #[test]
+    fn branches() {
+        // Every taken branch skips an add of 100; each untaken one falls
+        // into an add of 1.
+        check(
+            "",
+            "  li $t0, -1
+  beq $t0, $t0, a
+  addiu $s0, $s0, 100
+a:
+  bne $t0, $zero, b
+  addiu $s0, $s0, 100
+b:
+  bltz $t0, c
+  addiu $s0, $s0, 100
+c:
+  bgez $t0, d
+  addiu $s0, $s0, 1
+d:
+  blez $t0, e
+  addiu $s0, $s0, 100
+e:
+  bgtz $t0, f
+  addiu $s0, $s0, 1
+f:
+  beq $t0, $zero, g
+  addiu $s0, $s0, 1
+g:",
+            false,
+            &[(Register::S0, 3)],
+        );
+    }
+
+    #[test]
+    fn jumps_link_the_return_address() {
+        let source = ".text\nmain:\n  jal f\nafter:\n  j done\n  addiu $s2, $s2, 1\ndone:\n  \
+                      li $v0, 10\n  syscall\nf:\n  move $s0, $ra\nlink:\n  jalr $s1, $ra\n";
+        let program = assemble(source);
+        let label = |name| u64::from(program.debug_info().label(name).unwrap().0);
+        let (after, link) = (label("after"), label("link"));
+        for backend in [Backend::Interpreter, Backend::Threaded] {
+            let simulator = run_with_handler(program.clone(), backend);
+            assert_eq!(simulator.registers.get64(Register::S0), after);
+            assert_eq!(simulator.registers.get64(Register::S1), link + 4);
+            assert_eq!(simulator.registers.get64(Register::S2), 0);
+        }
+    }
+
+    #[test]
+    fn loads_and_stores() {
+        check(
+            "value: .word 0x80ff7f01
+bytes: .byte 1, 2, 3, 4, 5, 6, 7, 8
+buffer: .space 16",
+            "  la $t0, value
+  lb $s0, 3($t0)
+  lbu $s1, 3($t0)
+  lh $s2, 2($t0)
+  lhu $s3, 2($t0)
+  lw $s4, 0($t0)
+  la $t1, buffer
+  sb $s0, 0($t1)
+  sh $s2, 2($t1)
+  lw $s5, 0($t1)
+  la $t2, bytes
+  lwr $s6, 1($t2)
+  lwl $s6, 4($t2)
+  swr $s6, 5($t1)
+  swl $s6, 8($t1)
+  lwr $s7, 5($t1)
+  lwl $s7, 8($t1)",
+            false,
+            &[
+                (Register::S0, word(0xffff_ff80)),
+                (Register::S1, 0x80),
+                (Register::S2, word(0xffff_80ff)),
+                (Register::S3, 0x80ff),
+                (Register::S4, word(0x80ff_7f01)),
+                (Register::S5, word(0x80ff_0080)),
+                (Register::S6, 0x0504_0302),
+                (Register::S7, 0x0504_0302),
+            ],
+        );
+    }
+
+    #[test]
+    fn doubleword_instructions() {
+        check(
+            // First in the data segment, so 8-byte aligned.
+            "buffer: .space 8",
+            "  li $t0, 1
+  dsll32 $t1, $t0, 0
+  daddu $t2, $t1, $t0
+  dsrl32 $t3, $t2, 0
+  dsub $t4, $t0, $t1
+  dsra32 $t5, $t4, 0
+  la $t6, buffer
+  sd $t2, 0($t6)
+  ld $t7, 0($t6)
+  lw $s0, 0($t6)",
+            true,
+            &[
+                (Register::T1, 1 << 32),
+                (Register::T2, (1 << 32) + 1),
+                (Register::T3, 1),
+                (Register::T4, 1_u64.wrapping_sub(1 << 32)),
+                (Register::T5, u64::MAX),
+                (Register::T7, (1 << 32) + 1),
+                (Register::S0, 1),
+            ],
+        );
+    }
+
+    #[test]
+    fn syscalls_write_to_the_console() {
+        let source = ".data\nmessage: .asciiz \" items\"\n.text\nmain:\n  li $a0, 42\n  li $v0, 1\n  \
+                      syscall\n  la $a0, message\n  li $v0, 4\n  syscall\n  li $v0, 10\n  syscall\n";
+        let program = assemble(source);
+        for backend in [Backend::Interpreter, Backend::Threaded] {
+            let io = SharedIo::new();
+            let mut simulator = Simulator::new(program.clone());
+            simulator.set_backend(backend);
+            simulator.set_io(Box::new(io.clone()));
+            assert!(matches!(
+                simulator.run(),
+                Stopped::Halted(SimulatorError::Exit(0))
+            ));
+            assert_eq!(io.lock().output, "42 items");
+        }
+    }
+
+    #[test]
+    fn step_back_undoes_registers_and_memory() {
+        let source = ".data\nslot: .word 1\n.text\nmain:\n  li $t0, 5\n  sw $t0, slot\n  \
+                      addiu $t0, $t0, 1\n  li $v0, 10\n  syscall\n";
+        let program = assemble(source);
+        let slot = program.debug_info().label("slot").unwrap();
+        let mut simulator = Simulator::new(program);
+        simulator.set_journal_depth(8);
+        let start = simulator.registers().clone();
+        // `li`, the two instructions `sw` to a label expands to, `addiu`.
+        for _ in 0..4 {
+            simulator.step().unwrap();
+        }
+        assert_eq!(simulator.registers().get(Register::T0), 6);
+        assert_eq!(simulator.memory().read_word(slot), Some(5));
+
+        assert!(simulator.step_back());
+        assert_eq!(simulator.registers().get(Register::T0), 5);
+        assert!(simulator.step_back());
+        assert!(simulator.step_back());
+        assert_eq!(simulator.memory().read_word(slot), Some(1));
+        assert!(simulator.step_back());
+        assert_eq!(simulator.registers(), &start);
+        assert_eq!(simulator.steps(), 0);
+        assert!(!simulator.step_back());
+    }
 }
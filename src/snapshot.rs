@@ -0,0 +1,295 @@
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+
+use crate::{
+    address::Address,
+    registers::{Register, RegisterId},
+    simulator::Simulator,
+};
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Could not read {0}: {1}")]
+    Io(String, io::Error),
+    #[error("{0} is not a snapshot: {1}")]
+    Invalid(String, String),
+}
+
+fn hex(value: u32) -> String {
+    format!("0x{:08x}", value)
+}
+
+/// Reads a `0x`-prefixed hex word.
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+/// The registers and every non-zero word of memory at one moment, saved
+/// as JSON in the format golden files use, so either can be compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// By name, e.g. `$t0`, `hi` or `pc`.
+    pub registers: BTreeMap<String, u32>,
+    /// Word values by address; words that are missing are zero.
+    pub memory: BTreeMap<u32, u32>,
+}
+
+impl Snapshot {
+    pub fn capture(simulator: &Simulator) -> Self {
+        let registers = simulator.registers();
+        let mut values: BTreeMap<String, u32> = Register::ALL
+            .iter()
+            .map(|&register| (register.name().to_string(), registers.get(register)))
+            .collect();
+        values.insert("hi".to_string(), registers.hi());
+        values.insert("lo".to_string(), registers.lo());
+        values.insert("pc".to_string(), simulator.pc().0);
+        let memory = simulator
+            .memory()
+            .nonzero_words()
+            .into_iter()
+            .map(|(address, value)| (address.0, value))
+            .collect();
+        Self {
+            registers: values,
+            memory,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let registers: Map<String, Value> = self
+            .registers
+            .iter()
+            .map(|(name, &value)| (name.clone(), hex(value).into()))
+            .collect();
+        let memory: Map<String, Value> = self
+            .memory
+            .iter()
+            .map(|(&address, &value)| (hex(address), hex(value).into()))
+            .collect();
+        json!({ "registers": registers, "memory": memory })
+    }
+
+    /// Reads the `registers` and `memory` of a snapshot or golden file,
+    /// ignoring anything else in it.
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let section = |key: &str| -> Result<Vec<(String, u32)>, String> {
+            let Some(entries) = value.get(key) else {
+                return Ok(Vec::new());
+            };
+            let entries = entries
+                .as_object()
+                .ok_or_else(|| format!("'{}' is not an object", key))?;
+            entries
+                .iter()
+                .map(|(name, value)| {
+                    value
+                        .as_str()
+                        .and_then(parse_hex)
+                        .map(|value| (name.clone(), value))
+                        .ok_or_else(|| format!("'{}.{}' is not a hex word", key, name))
+                })
+                .collect()
+        };
+        let registers = section("registers")?.into_iter().collect();
+        let memory = section("memory")?
+            .into_iter()
+            .map(|(address, value)| {
+                parse_hex(&address)
+                    .map(|address| (address, value))
+                    .ok_or_else(|| format!("'{}' is not an address", address))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { registers, memory })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(&self.to_json())? + "\n")
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let name = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|err| SnapshotError::Io(name.clone(), err))?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|err| SnapshotError::Invalid(name.clone(), err.to_string()))?;
+        Self::from_json(&value).map_err(|err| SnapshotError::Invalid(name, err))
+    }
+}
+
+/// A register whose value differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterDifference {
+    pub name: String,
+    pub before: u32,
+    pub after: u32,
+}
+
+/// A run of consecutive words that all differ between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryDifference {
+    pub start: Address,
+    pub before: Vec<u32>,
+    pub after: Vec<u32>,
+}
+
+impl MemoryDifference {
+    /// The address just past the last differing word.
+    pub fn end(&self) -> Address {
+        self.start.wrapping_add(self.before.len() as u32 * 4)
+    }
+}
+
+/// What changed between two snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// In register number order, then HI, LO and the PC.
+    pub registers: Vec<RegisterDifference>,
+    /// In address order.
+    pub memory: Vec<MemoryDifference>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let registers: Vec<Value> = self
+            .registers
+            .iter()
+            .map(|difference| {
+                json!({
+                    "register": difference.name,
+                    "before": hex(difference.before),
+                    "after": hex(difference.after),
+                })
+            })
+            .collect();
+        let words = |words: &[u32]| words.iter().map(|&word| hex(word)).collect::<Vec<_>>();
+        let memory: Vec<Value> = self
+            .memory
+            .iter()
+            .map(|difference| {
+                json!({
+                    "start": hex(difference.start.0),
+                    "end": hex(difference.end().0),
+                    "before": words(&difference.before),
+                    "after": words(&difference.after),
+                })
+            })
+            .collect();
+        json!({ "registers": registers, "memory": memory })
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Snapshots are identical");
+        }
+        if !self.registers.is_empty() {
+            writeln!(f, "Registers:")?;
+            for difference in &self.registers {
+                writeln!(
+                    f,
+                    "  {:<5} 0x{:08x} -> 0x{:08x}  ({} -> {})",
+                    difference.name,
+                    difference.before,
+                    difference.after,
+                    difference.before as i32,
+                    difference.after as i32
+                )?;
+            }
+        }
+        if !self.memory.is_empty() {
+            writeln!(f, "Memory:")?;
+            for difference in &self.memory {
+                writeln!(
+                    f,
+                    "  0x{:08x}..0x{:08x} ({} words)",
+                    difference.start.0,
+                    difference.end().0,
+                    difference.before.len()
+                )?;
+                for (i, (before, after)) in
+                    difference.before.iter().zip(&difference.after).enumerate()
+                {
+                    writeln!(
+                        f,
+                        "    0x{:08x}: 0x{:08x} -> 0x{:08x}",
+                        difference.start.0 + i as u32 * 4,
+                        before,
+                        after
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where `name` sorts among the registers: by number, then HI, LO and PC.
+fn register_rank(name: &str) -> usize {
+    match name.parse::<RegisterId>() {
+        Ok(RegisterId::General(register)) => register as usize,
+        Ok(RegisterId::Hi) => 32,
+        Ok(RegisterId::Lo) => 33,
+        Ok(RegisterId::Pc) => 34,
+        Err(_) => 35,
+    }
+}
+
+/// What changed from `before` to `after`. A register missing from one
+/// snapshot counts as zero there, as memory does.
+pub fn diff(before: &Snapshot, after: &Snapshot) -> SnapshotDiff {
+    let mut names: Vec<&String> = before
+        .registers
+        .keys()
+        .chain(after.registers.keys())
+        .collect();
+    names.sort_by_key(|name| (register_rank(name), *name));
+    names.dedup();
+    let registers = names
+        .into_iter()
+        .filter_map(|name| {
+            let old = before.registers.get(name).copied().unwrap_or(0);
+            let new = after.registers.get(name).copied().unwrap_or(0);
+            (old != new).then(|| RegisterDifference {
+                name: name.clone(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect();
+
+    let mut addresses: Vec<u32> = before
+        .memory
+        .keys()
+        .chain(after.memory.keys())
+        .copied()
+        .collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+    let mut memory: Vec<MemoryDifference> = Vec::new();
+    for address in addresses {
+        let old = before.memory.get(&address).copied().unwrap_or(0);
+        let new = after.memory.get(&address).copied().unwrap_or(0);
+        if old == new {
+            continue;
+        }
+        match memory.last_mut() {
+            Some(run) if run.end().0 == address => {
+                run.before.push(old);
+                run.after.push(new);
+            }
+            _ => memory.push(MemoryDifference {
+                start: Address(address),
+                before: vec![old],
+                after: vec![new],
+            }),
+        }
+    }
+    SnapshotDiff { registers, memory }
+}
@@ -0,0 +1,153 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{address::Address, debug_info::DebugInfo, memory::STACK_TOP};
+
+/// Bytes at the bottom of the caller's frame a callee may store its
+/// register arguments in, as the O32 calling convention allows.
+pub const ARGUMENT_HOME: u32 = 16;
+
+/// A kind of stack misuse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ViolationKind {
+    /// A load from unallocated stack below `$sp`, which an interrupt or
+    /// call may already have overwritten.
+    ReadBelowSp,
+    /// A subroutine stored into its caller's frame.
+    WriteAboveFrame,
+    /// A subroutine returned with `$sp` not where it was at the call, from
+    /// unbalanced pushes and pops.
+    UnbalancedReturn,
+}
+
+/// One occurrence of a [`ViolationKind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub pc: Address,
+    /// The address accessed, or for a return, `$sp` when it returned.
+    pub address: u32,
+    /// `$sp` at the access, or for a return, `$sp` at the call.
+    pub sp: u32,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ViolationKind::ReadBelowSp => write!(
+                f,
+                "read of 0x{:08x}, below $sp 0x{:08x}",
+                self.address, self.sp
+            ),
+            ViolationKind::WriteAboveFrame => write!(
+                f,
+                "write to 0x{:08x} in the caller's frame, which starts at 0x{:08x}",
+                self.address, self.sp
+            ),
+            ViolationKind::UnbalancedReturn => write!(
+                f,
+                "returned with $sp 0x{:08x} but was called with 0x{:08x} ({:+} bytes)",
+                self.address,
+                self.sp,
+                self.address.wrapping_sub(self.sp) as i32
+            ),
+        }
+    }
+}
+
+/// Watches loads, stores, calls and returns for the stack bugs students
+/// make most often. Each kind of violation is kept once per instruction,
+/// with how often it happened.
+#[derive(Debug, Clone, Default)]
+pub struct StackChecker {
+    /// `$sp` at each call not yet returned from, innermost last.
+    frames: Vec<u32>,
+    violations: BTreeMap<(Address, ViolationKind), (Violation, u64)>,
+}
+
+impl StackChecker {
+    fn report(&mut self, violation: Violation) {
+        self.violations
+            .entry((violation.pc, violation.kind))
+            .or_insert((violation, 0))
+            .1 += 1;
+    }
+
+    /// Checks a load at `pc` from `address`. Anything between the heap's
+    /// break and `$sp` is unallocated stack.
+    pub(crate) fn load(&mut self, pc: Address, address: Address, sp: u32, brk: Address) {
+        if (brk.0..sp).contains(&address.0) {
+            self.report(Violation {
+                kind: ViolationKind::ReadBelowSp,
+                pc,
+                address: address.0,
+                sp,
+            });
+        }
+    }
+
+    /// Checks a store at `pc` to `address` against the caller's frame.
+    pub(crate) fn store(&mut self, pc: Address, address: Address) {
+        let Some(&frame) = self.frames.last() else {
+            return;
+        };
+        if (frame.saturating_add(ARGUMENT_HOME)..STACK_TOP.0).contains(&address.0) {
+            self.report(Violation {
+                kind: ViolationKind::WriteAboveFrame,
+                pc,
+                address: address.0,
+                sp: frame,
+            });
+        }
+    }
+
+    /// Records a call made with `$sp` at `sp`.
+    pub(crate) fn call(&mut self, sp: u32) {
+        self.frames.push(sp);
+    }
+
+    /// Checks a return at `pc` leaving `$sp` at `sp`.
+    pub(crate) fn ret(&mut self, pc: Address, sp: u32) {
+        let Some(frame) = self.frames.pop() else {
+            return;
+        };
+        if sp != frame {
+            self.report(Violation {
+                kind: ViolationKind::UnbalancedReturn,
+                pc,
+                address: sp,
+                sp: frame,
+            });
+        }
+    }
+
+    /// The first violation of each kind at each instruction, with how many
+    /// times it happened, by address.
+    pub fn violations(&self) -> impl Iterator<Item = (&Violation, u64)> {
+        self.violations
+            .values()
+            .map(|(violation, count)| (violation, *count))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Prints every violation with its source line.
+    pub fn print_report(&self, debug_info: &DebugInfo) {
+        if self.is_empty() {
+            println!("Stack check: no problems found");
+            return;
+        }
+        println!("Stack check: {} problem(s)", self.violations.len());
+        for (violation, count) in self.violations() {
+            let line = debug_info
+                .line_of(violation.pc)
+                .map_or_else(String::new, |line| format!(" (line {})", line));
+            let times = match count {
+                1 => String::new(),
+                count => format!(" ({} times)", count),
+            };
+            println!("  0x{:08x}{}: {}{}", violation.pc.0, line, violation, times);
+        }
+    }
+}
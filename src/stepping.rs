@@ -0,0 +1,30 @@
+use crate::assembler::Instruction;
+
+/// Counts calls and returns while running until a subroutine returns: the
+/// one called by the instruction at the PC for step-over, or the one
+/// currently executing for step-out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UntilReturn {
+    /// Returns still to execute before stopping.
+    pending: u32,
+}
+
+impl UntilReturn {
+    /// Runs to the end of the current subroutine.
+    pub fn step_out() -> Self {
+        Self { pending: 1 }
+    }
+
+    /// Call after executing `instruction`, with whether it `called` a
+    /// subroutine. Returns whether the run should stop.
+    pub fn observe(&mut self, instruction: &Instruction, called: bool) -> bool {
+        if called {
+            self.pending += 1;
+        } else if instruction.is_return() {
+            // A return past the frame stepping started in also ends it.
+            self.pending = self.pending.saturating_sub(1);
+            return self.pending == 0;
+        }
+        false
+    }
+}
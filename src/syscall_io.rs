@@ -0,0 +1,147 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// Where console syscalls send output and read input from.
+pub trait SyscallIo: Send {
+    fn write(&mut self, text: &str) -> io::Result<()>;
+
+    /// Reads one line of input without its line terminator. Implementations
+    /// that cannot block return [`io::ErrorKind::WouldBlock`] when no input is
+    /// available yet; the simulator then leaves the syscall to be retried.
+    fn read_line(&mut self) -> io::Result<String>;
+}
+
+/// The launching terminal's stdin and stdout.
+#[derive(Debug, Default)]
+pub struct StdIo;
+
+impl SyscallIo for StdIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut input = String::new();
+        io::stdin().lock().read_line(&mut input)?;
+        Ok(input.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+/// Passes console I/O through to another implementation, saving every line
+/// read to a replay file as it arrives.
+pub struct RecordingIo {
+    inner: Box<dyn SyscallIo>,
+    file: File,
+}
+
+impl RecordingIo {
+    /// Records the input read from `inner` to `path`, replacing the file.
+    pub fn create(path: &Path, inner: Box<dyn SyscallIo>) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            file: File::create(path)?,
+        })
+    }
+}
+
+impl SyscallIo for RecordingIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write(text)
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let line = self.inner.read_line()?;
+        // Flushed per line, so a run that crashes still leaves its input.
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()?;
+        Ok(line)
+    }
+}
+
+/// Feeds input lines from a replay file, then reads from another
+/// implementation once they run out. Output goes to the other one.
+pub struct ReplayIo {
+    lines: VecDeque<String>,
+    inner: Box<dyn SyscallIo>,
+}
+
+impl ReplayIo {
+    pub fn load(path: &Path, inner: Box<dyn SyscallIo>) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self {
+            lines: text.lines().map(str::to_string).collect(),
+            inner,
+        })
+    }
+}
+
+impl SyscallIo for ReplayIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write(text)
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        match self.lines.pop_front() {
+            Some(line) => {
+                // Echo the input, as a terminal would have.
+                self.inner.write(&line)?;
+                self.inner.write("\n")?;
+                Ok(line)
+            }
+            None => self.inner.read_line(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ConsoleBuffers {
+    pub output: String,
+    pub input: VecDeque<String>,
+    /// Set while the program is blocked in an input syscall.
+    pub waiting_for_input: bool,
+}
+
+/// In-memory console shared between the simulator and a front-end, which
+/// reads `output` and queues lines into `input`.
+#[derive(Debug, Clone, Default)]
+pub struct SharedIo(Arc<Mutex<ConsoleBuffers>>);
+
+impl SharedIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, ConsoleBuffers> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl SyscallIo for SharedIo {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.lock().output.push_str(text);
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut buffers = self.lock();
+        match buffers.input.pop_front() {
+            Some(line) => {
+                buffers.waiting_for_input = false;
+                Ok(line)
+            }
+            None => {
+                buffers.waiting_for_input = true;
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+use crate::{
+    address::Address,
+    registers::{Register, RegisterFile},
+    simulator::SimulatorError,
+};
+
+/// The longest string argument shown before it is cut off.
+const MAX_STRING: usize = 60;
+
+/// What the registers said a syscall was, read before it ran.
+#[derive(Debug, Clone)]
+pub struct SyscallCall {
+    pub step: u64,
+    pub pc: Address,
+    pub number: u32,
+    /// The decoded arguments, e.g. `$a0=0x10010000 "hello"`.
+    pub arguments: String,
+}
+
+/// The conventional name of syscall `number`.
+pub fn name(number: u32) -> Option<&'static str> {
+    match number {
+        1 => Some("print_int"),
+        4 => Some("print_string"),
+        5 => Some("read_int"),
+        9 => Some("sbrk"),
+        10 => Some("exit"),
+        17 => Some("exit2"),
+        30 => Some("time"),
+        _ => None,
+    }
+}
+
+/// Writes `bytes` as a quoted string with escapes, cut off after
+/// [`MAX_STRING`] characters.
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in bytes.iter().take(MAX_STRING) {
+        match byte {
+            b'\n' => quoted.push_str("\\n"),
+            b'\t' => quoted.push_str("\\t"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => {
+                let _ = write!(quoted, "\\x{:02x}", byte);
+            }
+        }
+    }
+    quoted.push('"');
+    if bytes.len() > MAX_STRING {
+        quoted.push_str("...");
+    }
+    quoted
+}
+
+impl SyscallCall {
+    /// Decodes the syscall the registers describe. `string` reads the
+    /// NUL-terminated string at an address.
+    pub(crate) fn new(
+        step: u64,
+        registers: &RegisterFile,
+        string: impl FnOnce(Address) -> Vec<u8>,
+    ) -> Self {
+        let number = registers.get(Register::V0);
+        let a0 = registers.get(Register::A0);
+        let arguments = match number {
+            1 => format!("$a0={}", a0 as i32),
+            4 => format!("$a0=0x{:08x} {}", a0, quote(&string(Address(a0)))),
+            9 => format!("$a0={}", a0 as i32),
+            17 => format!("$a0={}", a0),
+            _ => String::new(),
+        };
+        Self {
+            step,
+            pc: registers.pc(),
+            number,
+            arguments,
+        }
+    }
+}
+
+/// Logs every syscall a program makes, with its arguments and what it
+/// returned. Install one with
+/// [`crate::simulator::Simulator::set_syscall_trace`].
+pub struct SyscallTracer {
+    out: Box<dyn Write + Send>,
+}
+
+impl SyscallTracer {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Self { out }
+    }
+
+    /// Logs to stderr, keeping the program's own output on stdout clean.
+    pub fn stderr() -> Self {
+        Self::new(Box::new(io::stderr()))
+    }
+
+    /// Writes the line for `call`, which has finished with `result`,
+    /// leaving the registers as `after`.
+    pub(crate) fn record(
+        &mut self,
+        call: &SyscallCall,
+        result: &Result<(), SimulatorError>,
+        after: &RegisterFile,
+    ) {
+        let outcome = match (call.number, result) {
+            (_, Err(SimulatorError::Exit(code))) => format!("exit {}", code),
+            (_, Err(err)) => format!("error: {}", err),
+            (5 | 9, Ok(())) => {
+                let v0 = after.get(Register::V0);
+                format!("$v0={} (0x{:08x})", v0 as i32, v0)
+            }
+            (30, Ok(())) => format!(
+                "$a0=0x{:08x} $a1=0x{:08x}",
+                after.get(Register::A0),
+                after.get(Register::A1)
+            ),
+            (_, Ok(())) => "ok".to_string(),
+        };
+        let name = name(call.number).unwrap_or("unknown");
+        let arguments = match call.arguments.is_empty() {
+            true => String::new(),
+            false => format!(" {}", call.arguments),
+        };
+        // A closed stderr is no reason to stop the program.
+        let _ = writeln!(
+            self.out,
+            "[syscall] step {} at 0x{:08x}: {} ({}){} -> {}",
+            call.step, call.pc.0, name, call.number, arguments, outcome
+        );
+    }
+}
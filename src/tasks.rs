@@ -0,0 +1,128 @@
+use crate::{
+    address::Address,
+    simulator::{Simulator, SimulatorError},
+};
+
+/// Instructions a task runs before the timer switches away from it, unless
+/// configured otherwise.
+pub const DEFAULT_QUANTUM: u64 = 100;
+
+/// Whether a task can still be scheduled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskStatus {
+    Ready,
+    /// The program exited, or ran off the end of its text, with this code.
+    Exited(u32),
+    /// The program stopped with an error.
+    Failed(String),
+}
+
+/// One program with its own context: registers, PC and address space.
+pub struct Task {
+    pub name: String,
+    pub simulator: Simulator,
+    pub status: TaskStatus,
+    /// Time slices the task has been given.
+    pub slices: u64,
+}
+
+/// What happened during one time slice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slice {
+    pub task: usize,
+    pub executed: u64,
+    /// Where the task resumes next time, if it was preempted.
+    pub preempted_at: Option<Address>,
+}
+
+/// Runs several programs on one simulated CPU, switching to the next ready
+/// one whenever the current one has run for a quantum, as a timer
+/// interrupt would. Each program keeps its own machine, so switching
+/// saves and restores the whole context and address spaces never overlap.
+pub struct RoundRobin {
+    tasks: Vec<Task>,
+    quantum: u64,
+    current: usize,
+}
+
+impl RoundRobin {
+    pub fn new(quantum: u64) -> Self {
+        Self {
+            tasks: Vec::new(),
+            quantum: quantum.max(1),
+            current: 0,
+        }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, simulator: Simulator) {
+        self.tasks.push(Task {
+            name: name.into(),
+            simulator,
+            status: TaskStatus::Ready,
+            slices: 0,
+        });
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn quantum(&self) -> u64 {
+        self.quantum
+    }
+
+    /// Gives the next ready task, in turn, one time slice. Returns None
+    /// once every task has finished.
+    pub fn run_slice(&mut self) -> Option<Slice> {
+        let count = self.tasks.len();
+        let index = (0..count)
+            .map(|offset| (self.current + offset) % count)
+            .find(|&index| self.tasks[index].status == TaskStatus::Ready)?;
+        self.current = (index + 1) % count;
+
+        let quantum = self.quantum;
+        let task = &mut self.tasks[index];
+        task.slices += 1;
+        let start = task.simulator.steps();
+        let result = task.simulator.run_for(quantum);
+        task.status = match result {
+            Ok(()) => TaskStatus::Ready,
+            Err(SimulatorError::Exit(code)) => TaskStatus::Exited(code),
+            Err(SimulatorError::NoMoreInstructions | SimulatorError::ReturnedFromMain) => {
+                TaskStatus::Exited(0)
+            }
+            Err(err) => TaskStatus::Failed(err.to_string()),
+        };
+        Some(Slice {
+            task: index,
+            executed: task.simulator.steps() - start,
+            preempted_at: (task.status == TaskStatus::Ready).then(|| task.simulator.pc()),
+        })
+    }
+
+    /// Runs every task to completion, calling `on_slice` after each slice.
+    pub fn run(&mut self, mut on_slice: impl FnMut(&Self, &Slice)) {
+        while let Some(slice) = self.run_slice() {
+            on_slice(self, &slice);
+        }
+    }
+
+    /// Prints how each task ended and how much it ran.
+    pub fn print_summary(&self) {
+        println!("Tasks (quantum {} instructions):", self.quantum);
+        for task in &self.tasks {
+            let status = match &task.status {
+                TaskStatus::Ready => "still ready".to_string(),
+                TaskStatus::Exited(code) => format!("exited with code {}", code),
+                TaskStatus::Failed(err) => format!("failed: {}", err),
+            };
+            println!(
+                "  {:<20} {:>10} instructions {:>6} slices  {}",
+                task.name,
+                task.simulator.steps(),
+                task.slices,
+                status
+            );
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{fs, path::Path};
+
+use crate::assembler::InstructionClass;
+
+/// Cycles each kind of instruction costs in [`CpiModel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpiConfig {
+    pub alu: u64,
+    pub load: u64,
+    pub store: u64,
+    pub branch_taken: u64,
+    pub branch_not_taken: u64,
+    pub jump: u64,
+    pub syscall: u64,
+}
+
+impl Default for CpiConfig {
+    fn default() -> Self {
+        Self {
+            alu: 1,
+            load: 2,
+            store: 1,
+            branch_taken: 3,
+            branch_not_taken: 1,
+            jump: 2,
+            syscall: 10,
+        }
+    }
+}
+
+/// Names of the costs, in report order.
+const COSTS: [&str; 7] = [
+    "alu",
+    "load",
+    "store",
+    "branch-taken",
+    "branch-not-taken",
+    "jump",
+    "syscall",
+];
+
+impl CpiConfig {
+    fn cost_mut(&mut self, name: &str) -> Option<&mut u64> {
+        match name {
+            "alu" => Some(&mut self.alu),
+            "load" => Some(&mut self.load),
+            "store" => Some(&mut self.store),
+            "branch-taken" => Some(&mut self.branch_taken),
+            "branch-not-taken" => Some(&mut self.branch_not_taken),
+            "jump" => Some(&mut self.jump),
+            "syscall" => Some(&mut self.syscall),
+            _ => None,
+        }
+    }
+
+    /// Overrides the costs given as `<name>=<cycles>`, separated by commas
+    /// or new lines. `#` starts a comment.
+    fn apply(&mut self, text: &str) -> Result<(), String> {
+        let settings = text
+            .lines()
+            .flat_map(|line| line.split('#').next().unwrap_or_default().split(','))
+            .map(str::trim)
+            .filter(|setting| !setting.is_empty());
+        for setting in settings {
+            let (name, cycles) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid cost '{}', expected <name>=<cycles>", setting))?;
+            let (name, cycles) = (name.trim(), cycles.trim());
+            let cost = self.cost_mut(name).ok_or_else(|| {
+                format!(
+                    "Unknown instruction class '{}', expected one of {}",
+                    name,
+                    COSTS.join(", ")
+                )
+            })?;
+            *cost = cycles
+                .parse()
+                .map_err(|_| format!("Invalid cycle count '{}' for {}", cycles, name))?;
+        }
+        Ok(())
+    }
+
+    /// Reads costs from a file of `<name> = <cycles>` lines, starting from
+    /// the defaults.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read CPI config '{}': {}", path.display(), err))?;
+        text.parse()
+    }
+
+    /// The cycles an instruction of `class` costs.
+    pub fn cycles(&self, class: InstructionClass, taken: bool) -> u64 {
+        match class {
+            InstructionClass::Alu => self.alu,
+            InstructionClass::Load => self.load,
+            InstructionClass::Store => self.store,
+            InstructionClass::Branch if taken => self.branch_taken,
+            InstructionClass::Branch => self.branch_not_taken,
+            InstructionClass::Jump => self.jump,
+            InstructionClass::Syscall => self.syscall,
+        }
+    }
+}
+
+impl std::str::FromStr for CpiConfig {
+    type Err = String;
+
+    /// Reads `<name>=<cycles>` pairs separated by commas, e.g.
+    /// `load=3,syscall=50`; costs not given keep their defaults.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        config.apply(s)?;
+        Ok(config)
+    }
+}
+
+/// A simple timing model with a fixed cost per instruction class, for
+/// comparing programs without modelling the pipeline.
+#[derive(Debug, Clone)]
+pub struct CpiModel {
+    config: CpiConfig,
+    /// Instructions and cycles for each cost, in [`COSTS`] order.
+    counts: [(u64, u64); 7],
+}
+
+impl CpiModel {
+    pub fn new(config: CpiConfig) -> Self {
+        Self {
+            config,
+            counts: [(0, 0); 7],
+        }
+    }
+
+    pub fn config(&self) -> &CpiConfig {
+        &self.config
+    }
+
+    /// Charges an executed instruction of `class`; `taken` says whether a
+    /// branch was taken.
+    pub fn record(&mut self, class: InstructionClass, taken: bool) {
+        let slot = match class {
+            InstructionClass::Alu => 0,
+            InstructionClass::Load => 1,
+            InstructionClass::Store => 2,
+            InstructionClass::Branch if taken => 3,
+            InstructionClass::Branch => 4,
+            InstructionClass::Jump => 5,
+            InstructionClass::Syscall => 6,
+        };
+        let (count, cycles) = &mut self.counts[slot];
+        *count += 1;
+        *cycles += self.config.cycles(class, taken);
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.counts.iter().map(|(count, _)| count).sum()
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.counts.iter().map(|(_, cycles)| cycles).sum()
+    }
+
+    /// Average cycles per instruction, or 0 before any has executed.
+    pub fn cpi(&self) -> f64 {
+        match self.instructions() {
+            0 => 0.0,
+            instructions => self.cycles() as f64 / instructions as f64,
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "Timing: {} cycles, {} instructions, CPI {:.3}",
+            self.cycles(),
+            self.instructions(),
+            self.cpi()
+        );
+        for (name, (count, cycles)) in COSTS.iter().zip(self.counts) {
+            if count > 0 {
+                println!(
+                    "  {:<17} {:>10} x {:>3} = {:>12} cycles",
+                    name,
+                    count,
+                    cycles / count,
+                    cycles
+                );
+            }
+        }
+    }
+}
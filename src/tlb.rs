@@ -0,0 +1,123 @@
+use crate::address::Address;
+
+/// Entries in the TLB, as on the R3000.
+pub const TLB_ENTRIES: usize = 64;
+/// Entries below this index are never chosen by `tlbwr`, so a kernel can
+/// keep mappings it must not lose there.
+pub const WIRED_ENTRIES: usize = 8;
+
+const PAGE_MASK: u32 = 0xffff_f000;
+const ASID_MASK: u32 = 0x0000_0fc0;
+const NON_CACHEABLE: u32 = 1 << 11;
+const DIRTY: u32 = 1 << 10;
+const VALID: u32 = 1 << 9;
+const GLOBAL: u32 = 1 << 8;
+
+/// One mapping of a 4 KiB virtual page, in the EntryHi/EntryLo layout the
+/// `tlbr` and `tlbwi` instructions move through coprocessor 0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TlbEntry {
+    /// Virtual page number in bits 31..12, ASID in bits 11..6.
+    pub entry_hi: u32,
+    /// Physical frame number in bits 31..12, then the N, D, V and G flags
+    /// in bits 11..8.
+    pub entry_lo: u32,
+}
+
+impl TlbEntry {
+    pub fn vpn(&self) -> u32 {
+        self.entry_hi >> 12
+    }
+
+    pub fn asid(&self) -> u32 {
+        (self.entry_hi & ASID_MASK) >> 6
+    }
+
+    pub fn pfn(&self) -> u32 {
+        self.entry_lo >> 12
+    }
+
+    pub fn non_cacheable(&self) -> bool {
+        self.entry_lo & NON_CACHEABLE != 0
+    }
+
+    /// Whether the page may be written.
+    pub fn dirty(&self) -> bool {
+        self.entry_lo & DIRTY != 0
+    }
+
+    pub fn valid(&self) -> bool {
+        self.entry_lo & VALID != 0
+    }
+
+    /// Whether the entry matches every ASID.
+    pub fn global(&self) -> bool {
+        self.entry_lo & GLOBAL != 0
+    }
+
+    /// Whether the entry maps the page and ASID of `entry_hi`.
+    fn matches(&self, entry_hi: u32) -> bool {
+        self.entry_hi & PAGE_MASK == entry_hi & PAGE_MASK
+            && (self.global() || self.entry_hi & ASID_MASK == entry_hi & ASID_MASK)
+    }
+}
+
+/// Why a virtual address could not be translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlbFault {
+    /// No entry maps the page, or the one that does is not valid.
+    Miss,
+    /// A store to a page whose entry is not dirty.
+    Modified,
+}
+
+/// A fully associative, software-managed TLB. The hardware only looks
+/// entries up; filling them on a miss is left to the program's exception
+/// handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tlb {
+    entries: [TlbEntry; TLB_ENTRIES],
+}
+
+impl Default for Tlb {
+    fn default() -> Self {
+        Self {
+            entries: [TlbEntry::default(); TLB_ENTRIES],
+        }
+    }
+}
+
+impl Tlb {
+    pub fn entries(&self) -> &[TlbEntry; TLB_ENTRIES] {
+        &self.entries
+    }
+
+    /// The entry at `index`, which wraps around the TLB's size.
+    pub fn get(&self, index: usize) -> TlbEntry {
+        self.entries[index % TLB_ENTRIES]
+    }
+
+    pub fn set(&mut self, index: usize, entry: TlbEntry) {
+        self.entries[index % TLB_ENTRIES] = entry;
+    }
+
+    /// The index of the entry mapping the page and ASID of `entry_hi`.
+    pub fn probe(&self, entry_hi: u32) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.matches(entry_hi))
+    }
+
+    /// The physical address `address` maps to for the address space `asid`.
+    pub fn translate(&self, address: Address, asid: u32, write: bool) -> Result<Address, TlbFault> {
+        let entry_hi = address.0 & PAGE_MASK | (asid << 6) & ASID_MASK;
+        let entry = match self.probe(entry_hi) {
+            Some(index) if self.entries[index].valid() => self.entries[index],
+            _ => return Err(TlbFault::Miss),
+        };
+        if write && !entry.dirty() {
+            return Err(TlbFault::Modified);
+        }
+        Ok(Address(entry.entry_lo & PAGE_MASK | address.0 & !PAGE_MASK))
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    address::Address,
+    assembler::Instruction,
+    registers::{RegisterFile, RegisterId},
+};
+
+/// How trace records are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One human-readable line per instruction.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl TraceFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            TraceFormat::Text => "text",
+            TraceFormat::Jsonl => "jsonl",
+        }
+    }
+
+    /// JSON Lines for a `.jsonl` file, text otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .is_some_and(|extension| extension == "jsonl")
+        {
+            true => TraceFormat::Jsonl,
+            false => TraceFormat::Text,
+        }
+    }
+}
+
+impl std::str::FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "jsonl" | "json" => Ok(TraceFormat::Jsonl),
+            other => Err(format!(
+                "Unknown trace format '{}', expected text or jsonl",
+                other
+            )),
+        }
+    }
+}
+
+/// A load or store made by a traced instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: Address,
+    pub size: u32,
+    pub write: bool,
+    /// The value loaded or stored.
+    pub value: u32,
+}
+
+/// A register an instruction changed, with its old and new value.
+type Delta = (&'static str, u32, u32);
+
+/// Every register but the PC, which has a column of its own.
+fn deltas(before: &RegisterFile, after: &RegisterFile) -> Vec<Delta> {
+    after
+        .diff(before)
+        .into_iter()
+        .filter(|change| change.register != RegisterId::Pc)
+        .map(|change| (change.register.name(), change.old, change.new))
+        .collect()
+}
+
+/// Quotes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes a record per executed instruction: its cycle, PC, disassembly,
+/// the registers it changed and the memory it accessed. Install one with
+/// [`crate::simulator::Simulator::set_trace`].
+///
+/// Write errors do not stop the program. The first one ends the trace and
+/// is returned by [`Tracer::finish`].
+pub struct Tracer {
+    out: BufWriter<Box<dyn Write + Send>>,
+    format: TraceFormat,
+    /// Accesses made by the instruction being executed.
+    accesses: Vec<MemoryAccess>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(out: impl Write + Send + 'static, format: TraceFormat) -> Self {
+        Self {
+            out: BufWriter::new(Box::new(out)),
+            format,
+            accesses: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Traces to a new file at `path`, replacing any existing one.
+    pub fn create(path: &Path, format: TraceFormat) -> io::Result<Self> {
+        Ok(Self::new(File::create(path)?, format))
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+
+    pub(crate) fn access(&mut self, access: MemoryAccess) {
+        self.accesses.push(access);
+    }
+
+    /// Writes the record for the instruction at `pc`, which has just
+    /// executed as instruction number `cycle`.
+    pub(crate) fn record(
+        &mut self,
+        cycle: u64,
+        pc: Address,
+        instruction: &Instruction,
+        before: &RegisterFile,
+        after: &RegisterFile,
+    ) {
+        let deltas = deltas(before, after);
+        let accesses = std::mem::take(&mut self.accesses);
+        if self.error.is_some() {
+            return;
+        }
+        let line = match self.format {
+            TraceFormat::Text => text_record(cycle, pc, instruction, &deltas, &accesses),
+            TraceFormat::Jsonl => json_record(cycle, pc, instruction, &deltas, &accesses),
+        };
+        if let Err(err) = writeln!(self.out, "{}", line) {
+            self.error = Some(err);
+        }
+    }
+
+    /// Flushes the trace, reporting the first write error if there was one.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        }
+    }
+}
+
+fn text_record(
+    cycle: u64,
+    pc: Address,
+    instruction: &Instruction,
+    deltas: &[Delta],
+    accesses: &[MemoryAccess],
+) -> String {
+    let mut line = format!(
+        "{:>8} 0x{:08x}  {:<24}",
+        cycle,
+        pc.0,
+        instruction.to_string()
+    );
+    for &(name, old, new) in deltas {
+        let _ = write!(line, " {}: 0x{:08x} -> 0x{:08x};", name, old, new);
+    }
+    for access in accesses {
+        let _ = write!(
+            line,
+            " {} 0x{:08x} [{}] 0x{:08x};",
+            if access.write { "store" } else { "load" },
+            access.address.0,
+            access.size,
+            access.value
+        );
+    }
+    line.trim_end_matches(';').trim_end().to_string()
+}
+
+fn json_record(
+    cycle: u64,
+    pc: Address,
+    instruction: &Instruction,
+    deltas: &[Delta],
+    accesses: &[MemoryAccess],
+) -> String {
+    let registers: Vec<_> = deltas
+        .iter()
+        .map(|&(name, old, new)| {
+            format!(
+                "{{\"name\":{},\"old\":{},\"new\":{}}}",
+                json_string(name),
+                old,
+                new
+            )
+        })
+        .collect();
+    let memory: Vec<_> = accesses
+        .iter()
+        .map(|access| {
+            format!(
+                "{{\"kind\":\"{}\",\"address\":{},\"size\":{},\"value\":{}}}",
+                if access.write { "store" } else { "load" },
+                access.address.0,
+                access.size,
+                access.value
+            )
+        })
+        .collect();
+    format!(
+        "{{\"cycle\":{},\"pc\":{},\"instruction\":{},\"registers\":[{}],\"memory\":[{}]}}",
+        cycle,
+        pc.0,
+        json_string(&instruction.to_string()),
+        registers.join(","),
+        memory.join(",")
+    )
+}
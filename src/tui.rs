@@ -0,0 +1,527 @@
+use std::{io, time::Duration};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+};
+
+use crate::{
+    address::Address,
+    assembler::BASE_DATA_ADDR,
+    debug_info::DebugInfo,
+    heap::HEAP_BASE,
+    journal,
+    registers::{Register, RegisterFile, RegisterId},
+    simulator::{Simulator, SimulatorError, Stopped},
+    syscall_io::SharedIo,
+};
+
+/// Instructions run between redraws while the program is running.
+const RUN_SLICE: u64 = 20_000;
+/// Words shown per memory row.
+const ROW_WORDS: u32 = 4;
+
+const KEYS: &str = "F5/c run  F6/p pause  F11/s step  F10/n over  Shift+F11/o out  \
+u back  b breakpoint  r reset  m memory  [/] scroll  q quit";
+
+/// Which part of memory the memory pane follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Data,
+    Stack,
+    Heap,
+}
+
+impl Region {
+    fn name(self) -> &'static str {
+        match self {
+            Region::Data => "data",
+            Region::Stack => "stack",
+            Region::Heap => "heap",
+        }
+    }
+
+    fn next(self) -> Region {
+        match self {
+            Region::Data => Region::Stack,
+            Region::Stack => Region::Heap,
+            Region::Heap => Region::Data,
+        }
+    }
+}
+
+/// The terminal debugger: the source with breakpoints and the current
+/// line, the registers, a memory dump and the program's console.
+struct Tui<B: Fn() -> Simulator> {
+    build: B,
+    simulator: Simulator,
+    debug_info: DebugInfo,
+    io: SharedIo,
+    source: Vec<String>,
+    /// Line the cursor is on, 1-based.
+    cursor: usize,
+    /// First source line shown.
+    top: usize,
+    region: Region,
+    /// Rows the memory pane is scrolled past the region's start.
+    memory_rows: i64,
+    /// Registers before the last step or run, to highlight what changed.
+    before: Option<RegisterFile>,
+    running: bool,
+    finished: bool,
+    /// A line of console input being typed.
+    input: String,
+    status: String,
+    quit: bool,
+}
+
+/// Runs the terminal debugger until the user quits. `build` makes a fresh
+/// simulator for the program, at the start and on every reset; its console
+/// is replaced by the TUI's own.
+pub fn run(
+    build: impl Fn() -> Simulator,
+    debug_info: DebugInfo,
+    source: &str,
+    file: &str,
+) -> io::Result<()> {
+    let io = SharedIo::new();
+    let mut tui = Tui {
+        simulator: prepare(build(), &io),
+        build,
+        debug_info,
+        io,
+        source: source.lines().map(str::to_string).collect(),
+        cursor: 1,
+        top: 1,
+        region: Region::Data,
+        memory_rows: 0,
+        before: None,
+        running: false,
+        finished: false,
+        input: String::new(),
+        status: format!("{} loaded", file),
+        quit: false,
+    };
+    tui.follow_pc();
+
+    let mut terminal = ratatui::init();
+    let result = tui.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+fn prepare(mut simulator: Simulator, io: &SharedIo) -> Simulator {
+    simulator.set_io(Box::new(io.clone()));
+    simulator.set_journal_depth(journal::DEFAULT_DEPTH);
+    simulator.set_call_tracking(true);
+    simulator
+}
+
+impl<B: Fn() -> Simulator> Tui<B> {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            let timeout = match self.running && !self.waiting_for_input() {
+                true => Duration::ZERO,
+                false => Duration::from_millis(100),
+            };
+            if event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key);
+            }
+            if self.running && !self.waiting_for_input() {
+                let result = self.simulator.continue_for(RUN_SLICE);
+                self.stopped(result);
+            }
+        }
+        Ok(())
+    }
+
+    fn waiting_for_input(&self) -> bool {
+        self.io.lock().waiting_for_input
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if self.waiting_for_input() {
+            match key.code {
+                KeyCode::Enter => {
+                    let line = std::mem::take(&mut self.input);
+                    let mut buffers = self.io.lock();
+                    buffers.output.push_str(&line);
+                    buffers.output.push('\n');
+                    buffers.input.push_back(line);
+                    buffers.waiting_for_input = false;
+                    return;
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                    return;
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.input.push(c);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::F(5) | KeyCode::Char('c') => self.start(),
+            KeyCode::F(6) | KeyCode::Char('p') => {
+                self.running = false;
+                self.status = "Paused".to_string();
+                self.follow_pc();
+            }
+            KeyCode::F(11) if shift => self.execute(Simulator::step_out),
+            KeyCode::Char('o') => self.execute(Simulator::step_out),
+            KeyCode::F(11) | KeyCode::Char('s') => self.execute(|simulator| {
+                let result = simulator.step();
+                match simulator.take_watch_hit() {
+                    Some(hit) => Err(Stopped::Watchpoint(hit)),
+                    None => result.map_err(Stopped::Halted),
+                }
+            }),
+            KeyCode::F(10) | KeyCode::Char('n') => self.execute(Simulator::step_over),
+            KeyCode::Char('u') => self.step_back(),
+            KeyCode::Char('b') => self.toggle_breakpoint(),
+            KeyCode::Char('r') => self.reset(),
+            KeyCode::Char('m') => {
+                self.region = self.region.next();
+                self.memory_rows = 0;
+            }
+            KeyCode::Char('[') => self.memory_rows -= 1,
+            KeyCode::Char(']') => self.memory_rows += 1,
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1).max(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(self.source.len().max(1)),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(10).max(1),
+            KeyCode::PageDown => self.cursor = (self.cursor + 10).min(self.source.len().max(1)),
+            _ => {}
+        }
+    }
+
+    /// Runs or continues the program until something stops it.
+    fn start(&mut self) {
+        if self.finished {
+            self.status = "The program has finished; reset to run it again".to_string();
+            return;
+        }
+        self.before = Some(self.simulator.registers().clone());
+        self.simulator.skip_breakpoint();
+        self.running = true;
+        self.status = "Running".to_string();
+    }
+
+    /// Executes one debugger command while paused.
+    fn execute(&mut self, command: impl FnOnce(&mut Simulator) -> Result<(), Stopped>) {
+        if self.finished {
+            self.status = "The program has finished; step back or reset".to_string();
+            return;
+        }
+        if self.running {
+            return;
+        }
+        if self.waiting_for_input() {
+            self.status = "Waiting for console input".to_string();
+            return;
+        }
+        self.before = Some(self.simulator.registers().clone());
+        self.simulator.skip_breakpoint();
+        let result = command(&mut self.simulator);
+        self.status = "Paused".to_string();
+        self.stopped(result);
+    }
+
+    /// Reports how a run or step ended.
+    fn stopped(&mut self, result: Result<(), Stopped>) {
+        let stopped = match result {
+            Ok(()) => {
+                if !self.running {
+                    self.follow_pc();
+                }
+                return;
+            }
+            Err(stopped) => stopped,
+        };
+        match stopped {
+            Stopped::Halted(SimulatorError::InputPending) => return,
+            Stopped::Breakpoint(address) => {
+                self.status = format!("Breakpoint at {}", address);
+            }
+            Stopped::Watchpoint(hit) => {
+                self.status = format!("Watchpoint {}: {}", hit.watchpoint, hit);
+            }
+            Stopped::Halted(err) => {
+                let message = match err {
+                    SimulatorError::Exit(code) => {
+                        format!("-- program is finished running (exit code {}) --", code)
+                    }
+                    SimulatorError::NoMoreInstructions => {
+                        "-- program is finished running (dropped off bottom) --".to_string()
+                    }
+                    SimulatorError::ReturnedFromMain => {
+                        "-- program is finished running (returned from main) --".to_string()
+                    }
+                    err => format!("Simulator Error: {}", err),
+                };
+                self.io.lock().output.push_str(&format!("\n{}\n", message));
+                self.status = message;
+                self.finished = true;
+            }
+        }
+        self.running = false;
+        self.follow_pc();
+    }
+
+    fn step_back(&mut self) {
+        if self.running {
+            return;
+        }
+        self.before = Some(self.simulator.registers().clone());
+        match self.simulator.step_back() {
+            true => {
+                self.finished = false;
+                self.status = "Stepped back".to_string();
+            }
+            false => self.status = "Nothing to step back to".to_string(),
+        }
+        self.follow_pc();
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        let Some(address) = self.debug_info.address_of(self.cursor) else {
+            self.status = format!("Line {} has no instruction", self.cursor);
+            return;
+        };
+        if self.simulator.remove_breakpoint(address) {
+            self.status = format!("Removed the breakpoint at {}", address);
+        } else {
+            self.simulator.add_breakpoint(address);
+            self.status = format!("Breakpoint at {}", address);
+        }
+    }
+
+    /// Starts the program over, keeping breakpoints and watchpoints.
+    fn reset(&mut self) {
+        let old = std::mem::replace(&mut self.simulator, prepare((self.build)(), &self.io));
+        self.simulator.take_stop_points(old);
+        let mut buffers = self.io.lock();
+        buffers.input.clear();
+        buffers.waiting_for_input = false;
+        buffers.output.push_str("\n-- machine reset --\n");
+        drop(buffers);
+        self.input.clear();
+        self.before = None;
+        self.running = false;
+        self.finished = false;
+        self.status = "Reset".to_string();
+        self.follow_pc();
+    }
+
+    /// Moves the cursor to the line of the next instruction.
+    fn follow_pc(&mut self) {
+        if let Some(line) = self.debug_info.line_of(self.simulator.pc()) {
+            self.cursor = line;
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, console, status] = Layout::vertical([
+            Constraint::Min(10),
+            Constraint::Length(8),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [source, side] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(main);
+        let [registers, memory] =
+            Layout::vertical([Constraint::Length(21), Constraint::Min(4)]).areas(side);
+
+        self.draw_source(frame, source);
+        self.draw_registers(frame, registers);
+        self.draw_memory(frame, memory);
+        self.draw_console(frame, console);
+        let state = match (self.running, self.finished) {
+            (true, _) if self.waiting_for_input() => "waiting for input",
+            (true, _) => "running",
+            (false, true) => "finished",
+            (false, false) => "paused",
+        };
+        let lines = vec![
+            Line::from(vec![
+                Span::styled(
+                    format!("[{}] ", state),
+                    Style::new().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(self.status.as_str()),
+            ]),
+            Line::from(Span::styled(KEYS, Style::new().fg(Color::DarkGray))),
+        ];
+        frame.render_widget(Paragraph::new(lines), status);
+    }
+
+    fn draw_source(&mut self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if height > 0 && self.cursor >= self.top + height {
+            self.top = self.cursor + 1 - height;
+        }
+        let current = match self.running {
+            true => None,
+            false => self.debug_info.line_of(self.simulator.pc()),
+        };
+        let breakpoints: Vec<usize> = self
+            .simulator
+            .breakpoints()
+            .filter_map(|address| self.debug_info.line_of(address))
+            .collect();
+
+        let lines: Vec<Line> = self
+            .source
+            .iter()
+            .enumerate()
+            .skip(self.top - 1)
+            .take(height)
+            .map(|(index, text)| {
+                let number = index + 1;
+                let marker = match (breakpoints.contains(&number), current == Some(number)) {
+                    (true, true) => "●▶",
+                    (true, false) => "● ",
+                    (false, true) => " ▶",
+                    (false, false) => "  ",
+                };
+                let mut style = Style::new();
+                if current == Some(number) {
+                    style = style.bg(Color::Rgb(60, 60, 20));
+                }
+                if number == self.cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Line::from(vec![
+                    Span::styled(marker, Style::new().fg(Color::Red)),
+                    Span::styled(format!("{:>4} ", number), Style::new().fg(Color::DarkGray)),
+                    Span::styled(text.replace('\t', "    "), style),
+                ])
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Source ")),
+            area,
+        );
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect) {
+        let registers = self.simulator.registers();
+        let changed: Vec<RegisterId> = match (&self.before, self.running) {
+            (Some(before), false) => registers
+                .diff(before)
+                .into_iter()
+                .map(|change| change.register)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let cell = |id: RegisterId, value: u32| {
+            let style = match changed.contains(&id) {
+                true => Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                false => Style::new(),
+            };
+            Span::styled(format!("{:<5} 0x{:08x}  ", id.name(), value), style)
+        };
+        let general: Vec<(Register, u32)> = registers.iter().collect();
+        let mut lines: Vec<Line> = general[..16]
+            .iter()
+            .zip(&general[16..])
+            .map(|(&(left, a), &(right, b))| {
+                Line::from(vec![cell(left.into(), a), cell(right.into(), b)])
+            })
+            .collect();
+        lines.push(Line::from(
+            [RegisterId::Pc, RegisterId::Hi, RegisterId::Lo]
+                .map(|id| cell(id, registers.read(id)))
+                .to_vec(),
+        ));
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Registers ")),
+            area,
+        );
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let start = match self.region {
+            Region::Data => BASE_DATA_ADDR,
+            Region::Stack => Address(self.simulator.registers().get(Register::SP) & !0xf),
+            Region::Heap => HEAP_BASE,
+        };
+        let row_bytes = ROW_WORDS * 4;
+        let start = start.wrapping_offset((self.memory_rows * row_bytes as i64) as i32);
+        let memory = self.simulator.memory();
+        let lines: Vec<Line> = (0..area.height.saturating_sub(2) as u32)
+            .map(|row| {
+                let address = start.wrapping_add(row * row_bytes);
+                let mut text = format!("{} ", address);
+                let mut ascii = String::new();
+                for word in 0..ROW_WORDS {
+                    let word_address = address.wrapping_add(word * 4);
+                    match memory.read_word(word_address) {
+                        Some(value) => {
+                            text.push_str(&format!(" {:08x}", value));
+                            ascii.extend(value.to_le_bytes().map(|byte| match byte {
+                                0x20..=0x7e => byte as char,
+                                _ => '.',
+                            }));
+                        }
+                        None => {
+                            text.push_str(" --------");
+                            ascii.push_str("    ");
+                        }
+                    }
+                }
+                Line::from(vec![
+                    Span::raw(text),
+                    Span::styled(format!("  {}", ascii), Style::new().fg(Color::DarkGray)),
+                ])
+            })
+            .collect();
+        let title = format!(" Memory ({}) ", self.region.name());
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_console(&self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        let buffers = self.io.lock();
+        let mut lines: Vec<Line> = buffers
+            .output
+            .split('\n')
+            .map(|line| Line::raw(line.to_string()))
+            .collect();
+        if buffers.waiting_for_input {
+            // Input goes on the line the program's prompt left off.
+            let prompt = lines.pop().unwrap_or_default();
+            let mut spans = prompt.spans;
+            spans.push(Span::styled(
+                format!("{}_", self.input),
+                Style::new().fg(Color::Cyan),
+            ));
+            lines.push(Line::from(spans));
+        }
+        let skip = lines.len().saturating_sub(height);
+        let lines: Vec<Line> = lines.into_iter().skip(skip).collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Console ")),
+            area,
+        );
+    }
+}
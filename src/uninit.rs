@@ -0,0 +1,117 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::Range,
+};
+
+use crate::{address::Address, debug_info::DebugInfo};
+
+const PAGE_SIZE: u32 = 4096;
+
+/// A read of memory the program never wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRead {
+    pub pc: Address,
+    /// The first never-written byte read.
+    pub address: Address,
+}
+
+impl fmt::Display for UninitRead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "read of {}, which was never written", self.address)
+    }
+}
+
+/// Tracks which bytes outside the assembled data segment the program has
+/// written, and reports loads of the rest: the stack and heap start out
+/// uninitialized rather than zero. Each instruction's first bad read is
+/// kept, with how often it read uninitialized memory.
+#[derive(Debug, Clone)]
+pub struct UninitChecker {
+    /// The assembled data segment, which starts out initialized.
+    data: Range<u32>,
+    /// One bit per byte written, by page.
+    written: HashMap<u32, Box<[u64; PAGE_SIZE as usize / 64]>>,
+    reads: BTreeMap<Address, (UninitRead, u64)>,
+}
+
+impl UninitChecker {
+    pub fn new(data: Range<u32>) -> Self {
+        Self {
+            data,
+            written: HashMap::new(),
+            reads: BTreeMap::new(),
+        }
+    }
+
+    fn is_written(&self, address: u32) -> bool {
+        if self.data.contains(&address) {
+            return true;
+        }
+        let offset = address % PAGE_SIZE;
+        self.written
+            .get(&(address / PAGE_SIZE))
+            .is_some_and(|bits| bits[offset as usize / 64] & (1 << (offset % 64)) != 0)
+    }
+
+    /// Records a store of `size` bytes at `address`.
+    pub(crate) fn store(&mut self, address: Address, size: u32) {
+        for address in (0..size).map(|i| address.wrapping_add(i).0) {
+            let offset = address % PAGE_SIZE;
+            let bits = self
+                .written
+                .entry(address / PAGE_SIZE)
+                .or_insert_with(|| Box::new([0; PAGE_SIZE as usize / 64]));
+            bits[offset as usize / 64] |= 1 << (offset % 64);
+        }
+    }
+
+    /// Checks a load at `pc` of `size` bytes at `address`.
+    pub(crate) fn load(&mut self, pc: Address, address: Address, size: u32) {
+        let Some(unwritten) = (0..size)
+            .map(|i| address.wrapping_add(i).0)
+            .find(|&address| !self.is_written(address))
+        else {
+            return;
+        };
+        self.reads
+            .entry(pc)
+            .or_insert((
+                UninitRead {
+                    pc,
+                    address: Address(unwritten),
+                },
+                0,
+            ))
+            .1 += 1;
+    }
+
+    /// The first uninitialized read at each instruction, with how many
+    /// times it happened, by address.
+    pub fn reads(&self) -> impl Iterator<Item = (&UninitRead, u64)> {
+        self.reads.values().map(|(read, count)| (read, *count))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty()
+    }
+
+    /// Prints every uninitialized read with its source line.
+    pub fn print_report(&self, debug_info: &DebugInfo) {
+        if self.is_empty() {
+            println!("Uninitialized reads: none found");
+            return;
+        }
+        println!("Uninitialized reads: {} instruction(s)", self.reads.len());
+        for (read, count) in self.reads() {
+            let line = debug_info
+                .line_of(read.pc)
+                .map_or_else(String::new, |line| format!(" (line {})", line));
+            let times = match count {
+                1 => String::new(),
+                count => format!(" ({} times)", count),
+            };
+            println!("  0x{:08x}{}: {}{}", read.pc.0, line, read, times);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use crate::address::Address;
+
+/// Which accesses a watchpoint stops on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Reads and writes.
+    Access,
+}
+
+impl WatchKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        }
+    }
+
+    fn matches(self, write: bool) -> bool {
+        match self {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+impl std::str::FromStr for WatchKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r" | "read" => Ok(WatchKind::Read),
+            "w" | "write" => Ok(WatchKind::Write),
+            "rw" | "access" => Ok(WatchKind::Access),
+            other => Err(format!(
+                "Unknown watchpoint kind '{}', expected r, w or rw",
+                other
+            )),
+        }
+    }
+}
+
+/// Stops a run when the program loads or stores any byte of a range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: Address,
+    /// Bytes watched, at least one.
+    pub len: u32,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Whether an access of `size` bytes at `address` touches the range.
+    pub fn overlaps(&self, address: Address, size: u32) -> bool {
+        let (start, end) = (self.start.0 as u64, self.start.0 as u64 + self.len as u64);
+        let (access_start, access_end) = (address.0 as u64, address.0 as u64 + size as u64);
+        access_start < end && start < access_end
+    }
+
+    pub(crate) fn triggered_by(&self, address: Address, size: u32, write: bool) -> bool {
+        self.kind.matches(write) && self.overlaps(address, size)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind.name(), self.start)?;
+        if self.len > 1 {
+            write!(f, "..{}", self.start.wrapping_add(self.len - 1))?;
+        }
+        Ok(())
+    }
+}
+
+/// A load or store that triggered a watchpoint. The instruction has
+/// completed; for reads `old` and `new` are both the value loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    /// The instruction that made the access.
+    pub pc: Address,
+    pub address: Address,
+    pub write: bool,
+    pub old: u32,
+    pub new: u32,
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.write {
+            true => write!(
+                f,
+                "write to 0x{:08x} at pc 0x{:08x}: 0x{:08x} -> 0x{:08x}",
+                self.address.0, self.pc.0, self.old, self.new
+            ),
+            false => write!(
+                f,
+                "read of 0x{:08x} at pc 0x{:08x}: 0x{:08x}",
+                self.address.0, self.pc.0, self.new
+            ),
+        }
+    }
+}